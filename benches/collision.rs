@@ -0,0 +1,111 @@
+//! Footprint collision checks for a large vehicle at 32 headings, where
+//! footprints span up to two dozen rows: the row mask test behind
+//! `Grid::is_pose_blocked` against testing every cell, and the AVX2 overlap
+//! kernel against the scalar loop on the same rows.
+//!
+//! Run with `cargo bench --bench collision --features simd`. Without `simd`
+//! (or on a CPU without AVX2) `any_overlap` is the scalar loop.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use notan::math::{IVec2, Vec2};
+use vehicle_pathfinding::agent::Agent;
+use vehicle_pathfinding::cell::Cell;
+use vehicle_pathfinding::collision::{any_overlap, any_overlap_scalar};
+use vehicle_pathfinding::grid::Grid;
+
+const MAX_INCREMENTS: u16 = 32;
+const SIZE: i32 = 256;
+/// How long each case runs for.
+const RUN_TIME: Duration = Duration::from_secs(2);
+
+/// Runs `f` over and over for [`RUN_TIME`] and prints the time per call,
+/// with the number of blocked poses it found.
+fn bench(name: &str, mut f: impl FnMut() -> usize) {
+    let start = Instant::now();
+    let mut calls = 0u32;
+    let mut blocked = 0;
+    while start.elapsed() < RUN_TIME {
+        blocked = black_box(f());
+        calls += 1;
+    }
+    let per_call = start.elapsed() / calls;
+    println!("{name:<24} {per_call:>12?} per call, {blocked} blocked");
+}
+
+/// A map with a scattering of single blocked cells, so most poses are clear
+/// and every row of their footprints gets tested.
+fn grid() -> Grid {
+    let mut grid = Grid::new(1.0, SIZE, SIZE);
+    let mut state = 0x2545_f491_u32;
+    for _ in 0..SIZE * SIZE / 200 {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let cell = state as i32 & (SIZE * SIZE - 1);
+        grid.set_cell(cell % SIZE, cell / SIZE, true);
+    }
+    grid
+}
+
+fn main() {
+    let grid = grid();
+    // A truck-sized vehicle: 20 cells long, 8 wide.
+    let agent = Agent::new(IVec2::ZERO, Vec2::new(20.0, 8.0), 0, MAX_INCREMENTS);
+    let poses: Vec<Cell> = (16..SIZE - 16)
+        .step_by(7)
+        .flat_map(|y| (16..SIZE - 16).step_by(7).map(move |x| IVec2::new(x, y)))
+        .enumerate()
+        .map(|(i, position)| Cell::new((i % MAX_INCREMENTS as usize) as i16, position))
+        .collect();
+    let rows: usize = (0..MAX_INCREMENTS as i16)
+        .map(|rotation| agent.row_masks(rotation).unwrap().masks.len())
+        .max()
+        .unwrap();
+    println!("{} poses, footprints up to {rows} rows", poses.len());
+
+    bench("is_pose_blocked", || {
+        poses
+            .iter()
+            .filter(|pose| grid.is_pose_blocked(&agent, pose))
+            .count()
+    });
+    bench("cell by cell", || {
+        poses
+            .iter()
+            .filter(|pose| {
+                agent.rotation_footprint(pose.rotation).iter().any(|cell| {
+                    grid.is_cell_blocked(cell.x + pose.position.x, cell.y + pose.position.y)
+                })
+            })
+            .count()
+    });
+
+    // The grid rows under every footprint, fetched once, so only the
+    // overlap kernels are timed.
+    let gathered: Vec<(&[u64], Vec<u64>)> = poses
+        .iter()
+        .map(|pose| {
+            let masks = agent.row_masks(pose.rotation).unwrap();
+            let words = masks
+                .origins
+                .iter()
+                .map(|origin| grid.row_bits(origin.x + pose.position.x, origin.y + pose.position.y))
+                .collect();
+            (masks.masks.as_slice(), words)
+        })
+        .collect();
+    bench("any_overlap", || {
+        gathered
+            .iter()
+            .filter(|(masks, words)| any_overlap(masks, words))
+            .count()
+    });
+    bench("any_overlap_scalar", || {
+        gathered
+            .iter()
+            .filter(|(masks, words)| any_overlap_scalar(masks, words))
+            .count()
+    });
+}
@@ -0,0 +1,140 @@
+//! Open lists behind the planner's A*: the std binary heap it uses by
+//! default, the `binary_heap_plus` heap, and bucket queues of a few widths.
+//! Each case prints the time per search and the cost it found, so the
+//! price of wider buckets shows next to their speed.
+//!
+//! Run with `cargo bench --bench open_list`.
+
+use std::cell::RefCell;
+use std::collections::BinaryHeap;
+use std::hint::black_box;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use notan::math::{IVec2, Vec2};
+use vehicle_pathfinding::agent::Agent;
+use vehicle_pathfinding::cell::{Cell, NeighborCache};
+use vehicle_pathfinding::grid::Grid;
+use vehicle_pathfinding::pathfind::{explored_astar_with, AStarNode, BucketQueue, OpenList};
+use vehicle_pathfinding::planner::{self, OpenListKind, PlannerConfig};
+
+const SIZE: i32 = 160;
+const MAX_INCREMENTS: u16 = 16;
+/// How long each case runs for.
+const RUN_TIME: Duration = Duration::from_secs(2);
+
+/// Runs `f` over and over for [`RUN_TIME`] and prints the time per call,
+/// with the cost it found.
+fn bench(name: &str, mut f: impl FnMut() -> u32) {
+    let start = Instant::now();
+    let mut calls = 0u32;
+    let mut cost = 0;
+    while start.elapsed() < RUN_TIME {
+        cost = black_box(f());
+        calls += 1;
+    }
+    let per_call = start.elapsed() / calls;
+    println!("{name:<28} {per_call:>12?} per search, cost {cost}");
+}
+
+/// The `binary_heap_plus` heap, as an open list.
+struct PlusHeap(binary_heap_plus::BinaryHeap<AStarNode<usize>>);
+
+impl OpenList<usize> for PlusHeap {
+    fn push(&mut self, node: AStarNode<usize>) {
+        self.0.push(node);
+    }
+
+    fn pop(&mut self) -> Option<AStarNode<usize>> {
+        self.0.pop()
+    }
+}
+
+fn xorshift(state: &mut u32) -> u32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state
+}
+
+/// Searches a 4-connected grid with random entry costs from 1 to 9 on
+/// `open_list`, corner to corner.
+fn grid_search(costs: &[u32], open_list: impl OpenList<usize>) -> u32 {
+    let neighbors = |&(x, y): &(i32, i32)| {
+        [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .into_iter()
+            .map(move |(dx, dy)| (x + dx, y + dy))
+            .filter(|&(x, y)| (0..SIZE).contains(&x) && (0..SIZE).contains(&y))
+            .map(|(x, y)| ((x, y), costs[(y * SIZE + x) as usize]))
+    };
+    let goal = (SIZE - 1, SIZE - 1);
+    let heuristic = |&(x, y): &(i32, i32)| ((goal.0 - x) + (goal.1 - y)) as u32;
+    let states = (SIZE * SIZE) as usize;
+    explored_astar_with(open_list, (0, 0), states, neighbors, heuristic, |state| {
+        *state == goal
+    })
+    .map_or(u32::MAX, |(_, cost)| cost)
+}
+
+fn main() {
+    let mut state = 0x2545_f491_u32;
+    let costs: Vec<u32> = (0..SIZE * SIZE)
+        .map(|_| 1 + xorshift(&mut state) % 9)
+        .collect();
+    println!("{SIZE}x{SIZE} grid, random entry costs");
+    bench("std BinaryHeap", || grid_search(&costs, BinaryHeap::new()));
+    bench("binary_heap_plus", || {
+        grid_search(&costs, PlusHeap(binary_heap_plus::BinaryHeap::new()))
+    });
+    for width in [1, 4, 16] {
+        bench(&format!("BucketQueue width {width}"), || {
+            grid_search(&costs, BucketQueue::new(width))
+        });
+    }
+
+    // The planner itself, on a map with scattered walls.
+    let mut grid = Grid::new(1.0, SIZE, SIZE);
+    for _ in 0..SIZE * SIZE / 40 {
+        let cell = xorshift(&mut state) as i32 & i32::MAX;
+        let (x, y) = (cell % SIZE, cell / SIZE % SIZE);
+        for dx in 0..3 {
+            grid.set_cell(x + dx, y, true);
+        }
+    }
+    let start = Cell::new(0, IVec2::new(2, 2));
+    let goal = IVec2::new(SIZE - 3, SIZE - 3);
+    // Keeps the ends clear of walls.
+    for end in [start.position, goal] {
+        for dy in -2..=2 {
+            for dx in -2..=2 {
+                grid.set_cell(end.x + dx, end.y + dy, false);
+            }
+        }
+    }
+    let agent = Agent::new(start.position, Vec2::new(0.01, 0.01), 0, MAX_INCREMENTS);
+    let cache = Rc::new(RefCell::new(NeighborCache::new_precomputed(
+        MAX_INCREMENTS,
+        1,
+    )));
+    let mut config = PlannerConfig::new(1, MAX_INCREMENTS, (SIZE * SIZE) as usize * 16);
+    config.heuristic_weight = Some(1.0);
+    println!("planner, {MAX_INCREMENTS} headings");
+    for (name, open_list) in [
+        ("std BinaryHeap", OpenListKind::BinaryHeap),
+        ("BucketQueue width 1", OpenListKind::Buckets { width: 1 }),
+        (
+            "BucketQueue width 100",
+            OpenListKind::Buckets { width: 100 },
+        ),
+        (
+            "BucketQueue width 1000",
+            OpenListKind::Buckets { width: 1000 },
+        ),
+    ] {
+        config.open_list = open_list;
+        bench(name, || {
+            planner::plan(&grid, &agent, &cache, start.clone(), goal, &config)
+                .map_or(u32::MAX, |result| result.cost)
+        });
+    }
+}
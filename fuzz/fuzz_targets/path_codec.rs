@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vehicle_pathfinding::path_codec::decode_path;
+
+fuzz_target!(|data: &[u8]| {
+    // Any input decodes or fails cleanly, never panics.
+    let _ = decode_path(data);
+});
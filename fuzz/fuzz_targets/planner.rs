@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vehicle_pathfinding::fuzzing::{check, Case};
+
+fuzz_target!(|data: &[u8]| {
+    let case = Case::from_bytes(data);
+    if let Err(violation) = check(&case) {
+        panic!("{violation}\n{case:#?}");
+    }
+});
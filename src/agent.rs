@@ -1,9 +1,17 @@
+use geo::{LineString, Polygon};
 use notan::{
     app::Color,
     draw::{Draw, DrawShapes, DrawTransform},
     math::{Affine2, IVec2, Mat3, Vec2},
 };
 
+use crate::cell::Cell;
+use crate::collision::RowMasks;
+use crate::grid::Grid;
+
+/// Whether the unit cell at `aabb_x, aabb_y` and the rotated rectangle
+/// overlap or come within `epsilon` of each other. Touching counts.
+#[allow(clippy::too_many_arguments)]
 fn aabb_rect_collision(
     aabb_x: f32,
     aabb_y: f32, // AABB upper-left corner
@@ -12,6 +20,7 @@ fn aabb_rect_collision(
     rect_half_extents_x: f32,
     rect_half_extents_y: f32,
     rect_angle: f32,
+    epsilon: f32,
 ) -> bool {
     // Helper functions
     fn dot(ax: f32, ay: f32, bx: f32, by: f32) -> f32 {
@@ -76,7 +85,7 @@ fn aabb_rect_collision(
     for &(axis_x, axis_y) in rect_axes.iter().chain(aabb_axes.iter()) {
         let (rect_min, rect_max) = project_onto_axis(&rect_vertices, axis_x, axis_y);
         let (aabb_min, aabb_max) = project_onto_axis(&aabb_vertices, axis_x, axis_y);
-        if rect_max < aabb_min || aabb_max < rect_min {
+        if rect_max + epsilon < aabb_min || aabb_max + epsilon < rect_min {
             return false;
         }
     }
@@ -84,87 +93,370 @@ fn aabb_rect_collision(
     true
 }
 
+/// How the vehicle is able to move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MotionModel {
+    /// Moves along its heading, steering through the neighbor cache.
+    Car,
+    /// Translates in any direction regardless of heading (mecanum or omni
+    /// wheels). Every increment of misalignment between heading and motion,
+    /// and every increment rotated in place, costs `heading_weight`.
+    Holonomic { heading_weight: u32 },
+}
+
+/// Relative speeds when moving along, across, and against the heading.
+/// Costs of translating moves are divided by the speed, interpolated over
+/// the angle between heading and motion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedProfile {
+    pub forward: f32,
+    pub sideways: f32,
+    pub backward: f32,
+}
+
+impl SpeedProfile {
+    pub fn cost_factor(&self, heading: f32, motion: Vec2) -> f32 {
+        let angle = Vec2::from_angle(heading).angle_between(motion).abs();
+        let half_pi = std::f32::consts::FRAC_PI_2;
+        let speed = if angle <= half_pi {
+            self.forward + (self.sideways - self.forward) * angle / half_pi
+        } else {
+            self.sideways + (self.backward - self.sideways) * (angle - half_pi) / half_pi
+        };
+        1.0 / speed
+    }
+}
+
+/// Acceleration limits of a car-like vehicle, in cells and seconds. Moves
+/// that force the vehicle below cruising speed, like tight arcs or
+/// switching into reverse, are charged the time lost braking and speeding
+/// back up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dynamics {
+    pub cruise_speed: f32,
+    pub reverse_speed: f32,
+    pub acceleration: f32,
+    pub deceleration: f32,
+    /// Sideways acceleration the vehicle can hold through a turn.
+    pub lateral_acceleration: f32,
+}
+
+impl Dynamics {
+    /// Time lost, in seconds, slowing from cruising speed for a move of
+    /// `distance` cells turning `turn` radians, and speeding back up.
+    ///
+    /// The search state holds no speed, so every move is charged as if
+    /// entered and left at cruising speed. A run of reverse moves is
+    /// charged a direction switch on each move, which favors fewer and
+    /// shorter reverse segments.
+    pub fn time_lost(&self, distance: f32, turn: f32, reverse: bool) -> f32 {
+        let mut speed = if reverse {
+            self.reverse_speed
+        } else {
+            self.cruise_speed
+        };
+        if turn > 0.0 {
+            let radius = distance / turn;
+            speed = speed.min((self.lateral_acceleration * radius).sqrt());
+        }
+        // Reversing means going through a stop, so the velocity change is
+        // the sum of both speeds.
+        let change = if reverse {
+            self.cruise_speed + speed
+        } else {
+            self.cruise_speed - speed
+        };
+        change * change / (2.0 * self.cruise_speed)
+            * (1.0 / self.deceleration + 1.0 / self.acceleration)
+    }
+}
+
+/// Slack [`Agent::rasterize_footprints`] gives the rectangle, in cells, so
+/// rounding in the rotation can't drop a cell the rectangle touches.
+pub const FOOTPRINT_EPSILON: f32 = 1e-4;
+
+/// Name of the footprint set an agent starts out with.
+pub const DEFAULT_FOOTPRINT: &str = "default";
+
+/// Footprints rasterized for one outline of the vehicle, e.g. with or
+/// without a load, kept around so switching doesn't rasterize again.
+#[derive(Clone)]
+struct FootprintSet {
+    name: String,
+    size: Vec2,
+    footprints: Vec<Vec<IVec2>>,
+    row_masks: Vec<Option<RowMasks>>,
+}
+
+#[derive(Clone)]
 pub struct Agent {
     pub position: IVec2,
     pub size: Vec2,
     /// Rotation in increments.
     pub rotation: i16,
     pub max_increments: u16,
+    /// Maximum heading change, in increments, per cell traveled.
+    /// `None` leaves turning limited only by the neighbor arc.
+    pub max_rotation_rate: Option<f32>,
+    /// Cost of rotating one increment without moving, for vehicles that
+    /// can spin in place. `None` means every rotation needs a move.
+    pub turn_in_place_cost: Option<u32>,
+    pub motion: MotionModel,
+    pub speed_profile: Option<SpeedProfile>,
+    /// Acceleration limits charged on top of move costs. Only used by
+    /// [`MotionModel::Car`].
+    pub dynamics: Option<Dynamics>,
+    /// Steepest climb per cell the vehicle can take. `None` means any.
+    pub max_grade: Option<f32>,
+    /// Extra cost per unit of height climbed.
+    pub climb_cost: u32,
+
+    /// Center of the rectangle relative to the pose, in cells along and
+    /// across the heading. Set with [`Agent::set_pivot_offset`].
+    pivot_offset: Vec2,
 
     footprints_cache: Vec<Vec<IVec2>>,
+    /// Footprint plus the pose cell, as row masks per rotation.
+    row_masks_cache: Vec<Option<RowMasks>>,
+    /// Name of the footprint set in the caches above.
+    footprint_name: String,
+    /// Footprint sets not currently in use.
+    footprint_sets: Vec<FootprintSet>,
 }
 
 impl Agent {
     pub fn new(position: IVec2, size: Vec2, rotation: i16, max_increments: u16) -> Self {
-        let mut footprints_cache = Vec::with_capacity(max_increments as usize);
+        let footprints = Self::rasterize_footprints(size, max_increments);
+        Self::from_footprints(position, size, rotation, max_increments, footprints)
+    }
+
+    /// Cells covered by a `size` rectangle at each of `max_increments`
+    /// rotations, relative to its center.
+    pub fn rasterize_footprints(size: Vec2, max_increments: u16) -> Vec<Vec<IVec2>> {
+        Self::rasterize_footprints_with_epsilon(size, max_increments, FOOTPRINT_EPSILON)
+    }
+
+    /// Like [`Agent::rasterize_footprints`], counting every cell that
+    /// overlaps, touches or comes within `epsilon` cells of the rectangle,
+    /// so the footprint never leaves a gap the vehicle could clip an
+    /// obstacle through.
+    pub fn rasterize_footprints_with_epsilon(
+        size: Vec2,
+        max_increments: u16,
+        epsilon: f32,
+    ) -> Vec<Vec<IVec2>> {
+        Self::rasterize_offset_footprints(size, Vec2::ZERO, max_increments, epsilon)
+    }
+
+    /// Like [`Agent::rasterize_footprints_with_epsilon`] for a rectangle
+    /// whose center sits `offset` cells from the pose, in the vehicle's
+    /// frame (x forward), e.g. a long vehicle turning about its rear axle.
+    /// The offset is rotated with each footprint, so it can land anywhere
+    /// within a cell rather than snapping to cell centers.
+    pub fn rasterize_offset_footprints(
+        size: Vec2,
+        offset: Vec2,
+        max_increments: u16,
+        epsilon: f32,
+    ) -> Vec<Vec<IVec2>> {
+        (0..max_increments)
+            .map(|increment| {
+                let angle =
+                    2.0 * std::f32::consts::PI * (increment as f32) / (max_increments as f32);
+                let center = Vec2::splat(0.5) + Vec2::from_angle(angle).rotate(offset);
+                Self::rasterize_rectangle(size, center, angle, epsilon)
+            })
+            .collect()
+    }
+
+    /// Cells a `size` rectangle centered on `center` and turned `angle`
+    /// radians overlaps, touches or comes within `epsilon` of, where cell
+    /// `(x, y)` spans `x..x + 1` and `y..y + 1`.
+    fn rasterize_rectangle(size: Vec2, center: Vec2, angle: f32, epsilon: f32) -> Vec<IVec2> {
         let half_width = size.x / 2.0;
         let half_height = size.y / 2.0;
+        let transform = Affine2::from_translation(center) * Affine2::from_angle(angle);
 
-        for increment in 0..max_increments {
-            let angle = 2.0 * std::f32::consts::PI * (increment as f32) / (max_increments as f32);
-            let transform = Affine2::from_angle(angle);
+        // Define the corners of the rectangle
+        let corners = [
+            Vec2::new(-half_width, -half_height),
+            Vec2::new(half_width, -half_height),
+            Vec2::new(half_width, half_height),
+            Vec2::new(-half_width, half_height),
+        ];
 
-            // Define the corners of the rectangle
-            let corners = [
-                Vec2::new(-half_width, -half_height),
-                Vec2::new(half_width, -half_height),
-                Vec2::new(half_width, half_height),
-                Vec2::new(-half_width, half_height),
-            ];
+        // Rotate the corners and move them onto the center
+        let transformed_corners: Vec<Vec2> = corners
+            .iter()
+            .map(|&corner| transform.transform_point2(corner))
+            .collect();
 
-            // Add 0.5 to the corners to center them
-            let transformed_corners: Vec<Vec2> = corners
-                .iter()
-                .map(|&corner| transform.transform_point2(corner))
-                .collect();
-
-            // Calculate bounding box of the transformed rectangle
-            let (min_x, max_x) = transformed_corners.iter().fold(
-                (f32::INFINITY, f32::NEG_INFINITY),
-                |(min_x, max_x), corner| (min_x.min(corner.x), max_x.max(corner.x)),
-            );
-            let (min_y, max_y) = transformed_corners.iter().fold(
-                (f32::INFINITY, f32::NEG_INFINITY),
-                |(min_y, max_y), corner| (min_y.min(corner.y), max_y.max(corner.y)),
-            );
-
-            // Create a list of all the points inside the bounding box
-            let mut footprint = Vec::new();
-            for x in min_x.round() as i32..=max_x.round() as i32 {
-                for y in min_y.round() as i32..=max_y.round() as i32 {
-                    // test if the cell collides with the agent
-                    if !aabb_rect_collision(
-                        x as f32,
-                        y as f32,
-                        0.5,
-                        0.5,
-                        half_width,
-                        half_height,
-                        angle,
-                    ) {
-                        continue;
-                    }
+        // Calculate bounding box of the transformed rectangle
+        let (min_x, max_x) = transformed_corners.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min_x, max_x), corner| (min_x.min(corner.x), max_x.max(corner.x)),
+        );
+        let (min_y, max_y) = transformed_corners.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min_y, max_y), corner| (min_y.min(corner.y), max_y.max(corner.y)),
+        );
 
-                    let point = IVec2::new(x, y);
-                    footprint.push(point);
+        // Every cell the bounding box, grown by epsilon, touches
+        let first = |min: f32| (min - epsilon - 1.0).ceil() as i32;
+        let last = |max: f32| (max + epsilon).floor() as i32;
+        let mut footprint = Vec::new();
+        for x in first(min_x)..=last(max_x) {
+            for y in first(min_y)..=last(max_y) {
+                // test if the cell collides with the agent
+                if aabb_rect_collision(
+                    x as f32,
+                    y as f32,
+                    center.x,
+                    center.y,
+                    half_width,
+                    half_height,
+                    angle,
+                    epsilon,
+                ) {
+                    footprint.push(IVec2::new(x, y));
                 }
             }
-
-            footprints_cache.push(footprint);
         }
+        footprint
+    }
+
+    /// Builds an agent from footprints rasterized earlier, e.g. loaded from
+    /// disk by [`crate::persist`].
+    pub fn from_footprints(
+        position: IVec2,
+        size: Vec2,
+        rotation: i16,
+        max_increments: u16,
+        footprints_cache: Vec<Vec<IVec2>>,
+    ) -> Self {
+        let row_masks_cache = Self::row_masks_for(&footprints_cache);
 
         Self {
             position,
             size,
             rotation,
             max_increments,
+            max_rotation_rate: None,
+            turn_in_place_cost: None,
+            motion: MotionModel::Car,
+            speed_profile: None,
+            dynamics: None,
+            max_grade: None,
+            climb_cost: 1000,
+            pivot_offset: Vec2::ZERO,
             footprints_cache,
+            row_masks_cache,
+            footprint_name: DEFAULT_FOOTPRINT.to_string(),
+            footprint_sets: Vec::new(),
+        }
+    }
+
+    fn row_masks_for(footprints: &[Vec<IVec2>]) -> Vec<Option<RowMasks>> {
+        footprints
+            .iter()
+            .map(|footprint| {
+                let mut cells = footprint.clone();
+                cells.push(IVec2::ZERO);
+                RowMasks::from_cells(&cells)
+            })
+            .collect()
+    }
+
+    /// Rasterizes a `size` outline under `name` for [`Agent::set_footprint`],
+    /// replacing any set of that name. Adding the active set's name updates
+    /// it in place.
+    pub fn add_footprint_set(&mut self, name: &str, size: Vec2) {
+        let footprints = Self::rasterize_offset_footprints(
+            size,
+            self.pivot_offset,
+            self.max_increments,
+            FOOTPRINT_EPSILON,
+        );
+        let row_masks = Self::row_masks_for(&footprints);
+        if name == self.footprint_name {
+            self.size = size;
+            self.footprints_cache = footprints;
+            self.row_masks_cache = row_masks;
+            return;
+        }
+        self.footprint_sets.retain(|set| set.name != name);
+        self.footprint_sets.push(FootprintSet {
+            name: name.to_string(),
+            size,
+            footprints,
+            row_masks,
+        });
+    }
+
+    /// Switches to the footprint set `name`, returning `false` if there is
+    /// none. The set that was active stays available under its name.
+    pub fn set_footprint(&mut self, name: &str) -> bool {
+        if name == self.footprint_name {
+            return true;
+        }
+        let Some(set) = self.footprint_sets.iter_mut().find(|set| set.name == name) else {
+            return false;
+        };
+        std::mem::swap(&mut set.name, &mut self.footprint_name);
+        std::mem::swap(&mut set.size, &mut self.size);
+        std::mem::swap(&mut set.footprints, &mut self.footprints_cache);
+        std::mem::swap(&mut set.row_masks, &mut self.row_masks_cache);
+        true
+    }
+
+    pub fn pivot_offset(&self) -> Vec2 {
+        self.pivot_offset
+    }
+
+    /// Moves the rectangle's center `offset` cells from the pose, forward
+    /// and to the side, and rasterizes every footprint set again around it.
+    pub fn set_pivot_offset(&mut self, offset: Vec2) {
+        self.pivot_offset = offset;
+        let names: Vec<String> = self.footprint_names().map(str::to_string).collect();
+        let sizes: Vec<Vec2> = std::iter::once(self.size)
+            .chain(self.footprint_sets.iter().map(|set| set.size))
+            .collect();
+        for (name, size) in names.iter().zip(sizes) {
+            self.add_footprint_set(name, size);
         }
     }
 
+    pub fn footprint_name(&self) -> &str {
+        &self.footprint_name
+    }
+
+    /// Names of every footprint set, the active one first.
+    pub fn footprint_names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.footprint_name.as_str())
+            .chain(self.footprint_sets.iter().map(|set| set.name.as_str()))
+    }
+
+    /// Checks a heading change of `rotation_change` increments over
+    /// `distance` cells against `max_rotation_rate`.
+    pub fn can_rotate(&self, rotation_change: i16, distance: f32) -> bool {
+        match self.max_rotation_rate {
+            Some(rate) => rotation_change as f32 <= rate * distance + f32::EPSILON,
+            None => true,
+        }
+    }
+
+    /// Footprints for every rotation, as from [`Agent::rasterize_footprints`].
+    pub fn footprints(&self) -> &[Vec<IVec2>] {
+        &self.footprints_cache
+    }
     pub fn rotation_footprint(&self, rotation: i16) -> &Vec<IVec2> {
         &self.footprints_cache[rotation as usize]
     }
+    /// Row masks of the footprint and pose cell, `None` for footprints
+    /// wider than 64 cells.
+    pub fn row_masks(&self, rotation: i16) -> Option<&RowMasks> {
+        self.row_masks_cache[rotation as usize].as_ref()
+    }
     pub fn footprint(&self, position: IVec2, rotation: i16) -> Vec<IVec2> {
         let footprint = &self.footprints_cache[rotation as usize];
         footprint
@@ -175,6 +467,108 @@ impl Agent {
     pub fn current_footprint(&self) -> Vec<IVec2> {
         self.footprint(self.position, self.rotation)
     }
+    /// Cells covered at a pose between cells and increments, such as along
+    /// a curve to a point goal. `position` is in cells, with cell centers at
+    /// `.5`, and `heading` in radians.
+    pub fn footprint_at_point(&self, position: Vec2, heading: f32) -> Vec<IVec2> {
+        let center = position + Vec2::from_angle(heading).rotate(self.pivot_offset);
+        Self::rasterize_rectangle(self.size, center, heading, FOOTPRINT_EPSILON)
+    }
+
+    /// Whether the footprint collides at `position` and `rotation`, with
+    /// exactly the planner's semantics: blocked cells, cells off the grid,
+    /// heading restrictions and geofences all count. Rotations wrap around.
+    /// Only the geofence check allocates, when the grid has fences, so it's
+    /// cheap enough for physics steps and hover checks.
+    pub fn collides_at(&self, grid: &Grid, position: IVec2, rotation: i16) -> bool {
+        let rotation = rotation.rem_euclid(self.max_increments as i16);
+        let pose = Cell::new(rotation, position);
+        grid.is_pose_blocked(self, &pose) || grid.violates_geofence(self, &pose)
+    }
+
+    /// The cell that makes [`Agent::collides_at`] fail, or `None` if the
+    /// pose is free. Cells forbidding the heading come first, then blocked
+    /// or off-grid cells row by row from the top left; a pose only outside
+    /// the geofences gives its own cell.
+    pub fn first_colliding_cell(
+        &self,
+        grid: &Grid,
+        position: IVec2,
+        rotation: i16,
+    ) -> Option<IVec2> {
+        let rotation = rotation.rem_euclid(self.max_increments as i16);
+        let footprint = self.rotation_footprint(rotation);
+        let cells =
+            || std::iter::once(position).chain(footprint.iter().map(move |cell| *cell + position));
+        if let Some(cell) = self.heading_restricted_cell(grid, position, rotation) {
+            return Some(cell);
+        }
+        let blocked = match self.row_masks(rotation) {
+            Some(rows) => rows
+                .origins
+                .iter()
+                .zip(&rows.masks)
+                .find_map(|(origin, mask)| {
+                    let start = *origin + position;
+                    let hits = grid.row_bits(start.x, start.y) & mask;
+                    (hits != 0).then(|| start + IVec2::new(hits.trailing_zeros() as i32, 0))
+                }),
+            None => cells()
+                .filter(|cell| grid.is_cell_blocked(cell.x, cell.y))
+                .min_by_key(|cell| (cell.y, cell.x)),
+        };
+        blocked.or_else(|| {
+            grid.violates_geofence(self, &Cell::new(rotation, position))
+                .then_some(position)
+        })
+    }
+    /// The first cell, pose cell first, that the footprint covers at
+    /// `position` and `rotation` but that forbids the heading. Rotations are
+    /// in the agent's increments and wrap.
+    pub fn heading_restricted_cell(
+        &self,
+        grid: &Grid,
+        position: IVec2,
+        rotation: i16,
+    ) -> Option<IVec2> {
+        grid.heading_ranges.as_ref()?;
+        let rotation = rotation.rem_euclid(self.max_increments as i16);
+        let angle = Cell::increment_to_heading(rotation, self.max_increments);
+        std::iter::once(position)
+            .chain(
+                self.rotation_footprint(rotation)
+                    .iter()
+                    .map(|cell| *cell + position),
+            )
+            .find(|cell| !grid.is_heading_allowed(cell.x, cell.y, angle))
+    }
+    /// World-space outline of the agent's rectangle at the given pose.
+    pub fn footprint_polygon(
+        &self,
+        position: IVec2,
+        rotation: i16,
+        cell_size: f32,
+    ) -> Polygon<f64> {
+        let angle = 2.0 * std::f32::consts::PI * rotation as f32 / self.max_increments as f32;
+        let offset = Vec2::from_angle(angle).rotate(self.pivot_offset);
+        let center = (position.as_vec2() + Vec2::splat(0.5) + offset) * cell_size;
+        let half = self.size * cell_size / 2.0;
+        let transform = Affine2::from_translation(center) * Affine2::from_angle(angle);
+        let corners = [
+            Vec2::new(-half.x, -half.y),
+            Vec2::new(half.x, -half.y),
+            Vec2::new(half.x, half.y),
+            Vec2::new(-half.x, half.y),
+        ];
+        let exterior = corners
+            .iter()
+            .map(|corner| {
+                let corner = transform.transform_point2(*corner);
+                (corner.x as f64, corner.y as f64)
+            })
+            .collect::<Vec<_>>();
+        Polygon::new(LineString::from(exterior), vec![])
+    }
 
     pub fn draw(&mut self, draw: &mut Draw, color: Color, cell_size: f32) {
         let (x_grid, y_grid) = (
@@ -190,6 +584,7 @@ impl Agent {
         let rotation = self.rotation as f32 * increment_size;
         let transform = Affine2::from_translation(Vec2::new(x_grid, y_grid))
             * Affine2::from_angle(rotation)
+            * Affine2::from_translation(self.pivot_offset * cell_size)
             * Affine2::from_translation(-Vec2::new(half_width, half_height));
         draw.rect((0.0, 0.0), (width_grid, height_grid))
             .color(color)
@@ -204,7 +599,20 @@ impl Agent {
         .transform(transform.into());
     }
     pub fn draw_current_footprint(&mut self, draw: &mut Draw, color: Color, cell_size: f32) {
-        for footprint in self.current_footprint() {
+        self.draw_footprint_at(draw, self.position, self.rotation, color, 1.0, cell_size);
+    }
+    /// Draws the footprint cells at another pose, e.g. as a placement
+    /// preview.
+    pub fn draw_footprint_at(
+        &self,
+        draw: &mut Draw,
+        position: IVec2,
+        rotation: i16,
+        color: Color,
+        alpha: f32,
+        cell_size: f32,
+    ) {
+        for footprint in self.footprint(position, rotation) {
             draw.rect(
                 (
                     footprint.x as f32 * cell_size,
@@ -212,7 +620,130 @@ impl Agent {
                 ),
                 (cell_size, cell_size),
             )
-            .color(color);
+            .color(color)
+            .alpha(alpha);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const INCREMENTS: [u16; 6] = [4, 8, 12, 16, 32, 64];
+
+    fn sizes() -> impl Iterator<Item = Vec2> {
+        (1..=16).flat_map(|w| (1..=16).map(move |h| Vec2::new(w as f32, h as f32) / 4.0))
+    }
+
+    #[test]
+    fn test_footprints_cover_rectangle() {
+        let offsets = [Vec2::ZERO, Vec2::new(0.75, -0.25)];
+        for (max_increments, offset) in INCREMENTS.into_iter().zip(offsets.into_iter().cycle()) {
+            for size in sizes() {
+                let footprints = Agent::rasterize_offset_footprints(
+                    size,
+                    offset,
+                    max_increments,
+                    FOOTPRINT_EPSILON,
+                );
+                for (increment, footprint) in footprints.iter().enumerate() {
+                    let angle =
+                        2.0 * std::f32::consts::PI * increment as f32 / max_increments as f32;
+                    let rotation = Vec2::from_angle(angle);
+                    let center = Vec2::splat(0.5) + rotation.rotate(offset);
+                    let cells: HashSet<IVec2> = footprint.iter().copied().collect();
+                    assert_eq!(cells.len(), footprint.len());
+
+                    // Points all over the rectangle, edges included, land in
+                    // footprint cells.
+                    for u in -8..=8 {
+                        for v in -8..=8 {
+                            let local = Vec2::new(u as f32, v as f32) / 8.0 * size / 2.0;
+                            let point = center + rotation.rotate(local);
+                            let cell = point.floor().as_ivec2();
+                            assert!(
+                                cells.contains(&cell),
+                                "{size} at {increment}/{max_increments} misses {cell}"
+                            );
+                        }
+                    }
+                    // And no cell is further out than touching.
+                    let reach = size.length() / 2.0 + std::f32::consts::SQRT_2 / 2.0 + 1e-3;
+                    for cell in footprint {
+                        let cell_center = cell.as_vec2() + Vec2::splat(0.5);
+                        assert!((cell_center - center).length() <= reach);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_footprints_are_symmetric() {
+        // Turning the rectangle half way round mirrors its cells through the
+        // pose cell's center, and a quarter turn rotates them, however the
+        // rotation rounds.
+        let sorted = |cells: Vec<IVec2>| {
+            let mut cells = cells;
+            cells.sort_by_key(|cell| (cell.y, cell.x));
+            cells
+        };
+        for max_increments in INCREMENTS {
+            let quarter = max_increments as usize / 4;
+            for size in sizes() {
+                let footprints = Agent::rasterize_footprints(size, max_increments);
+                for increment in 0..max_increments as usize {
+                    let footprint = &footprints[increment];
+                    let half_turn = &footprints[(increment + 2 * quarter) % footprints.len()];
+                    let quarter_turn = &footprints[(increment + quarter) % footprints.len()];
+                    assert_eq!(
+                        sorted(footprint.iter().map(|c| IVec2::new(-c.x, -c.y)).collect()),
+                        sorted(half_turn.clone()),
+                        "{size} at {increment}/{max_increments}"
+                    );
+                    assert_eq!(
+                        sorted(footprint.iter().map(|c| IVec2::new(-c.y, c.x)).collect()),
+                        sorted(quarter_turn.clone()),
+                        "{size} at {increment}/{max_increments}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pivot_offset() {
+        let sorted = |cells: &[IVec2]| {
+            let mut cells = cells.to_vec();
+            cells.sort_by_key(|cell| (cell.y, cell.x));
+            cells
+        };
+        let range = |xs: std::ops::RangeInclusive<i32>, ys: std::ops::RangeInclusive<i32>| {
+            ys.flat_map(|y| xs.clone().map(move |x| IVec2::new(x, y)))
+                .collect::<Vec<_>>()
+        };
+        let mut agent = Agent::new(IVec2::new(5, 5), Vec2::new(3.0, 0.5), 0, 8);
+        agent.add_footprint_set("loaded", Vec2::new(4.0, 0.5));
+        assert_eq!(sorted(agent.rotation_footprint(0)), range(-2..=2, 0..=0));
+
+        // Half a cell forward, the rectangle spans x -0.5..2.5 facing +x,
+        // and -1.5..1.5 turned round, neither of which a whole cell shift
+        // of the centered footprint matches.
+        agent.set_pivot_offset(Vec2::new(0.5, 0.0));
+        assert_eq!(agent.pivot_offset(), Vec2::new(0.5, 0.0));
+        assert_eq!(sorted(agent.rotation_footprint(0)), range(-1..=2, 0..=0));
+        assert_eq!(sorted(agent.rotation_footprint(4)), range(-2..=1, 0..=0));
+        assert_eq!(sorted(agent.rotation_footprint(2)), range(0..=0, -1..=2));
+
+        let polygon = agent.footprint_polygon(IVec2::new(5, 5), 0, 1.0);
+        let xs = polygon.exterior().points().map(|point| point.x());
+        assert_eq!(xs.fold(f64::INFINITY, f64::min), 4.5);
+
+        // Stored footprint sets are rasterized around the offset too.
+        assert!(agent.set_footprint("loaded"));
+        assert_eq!(sorted(agent.rotation_footprint(0)), range(-2..=3, 0..=0));
+    }
+}
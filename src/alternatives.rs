@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlanResult, PlannerConfig};
+
+/// Settings for [`plan_alternatives`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlternativesConfig {
+    /// Number of paths to return at most.
+    pub count: usize,
+    /// Cost added to a cell for every earlier path through it.
+    pub penalty: u32,
+    /// Largest share of cells a path may have in common with an accepted
+    /// one and still count as distinct.
+    pub max_overlap: f32,
+    /// Replans allowed per requested path before giving up.
+    pub attempts_per_path: usize,
+}
+
+impl Default for AlternativesConfig {
+    fn default() -> Self {
+        Self {
+            count: 3,
+            penalty: 5000,
+            max_overlap: 0.7,
+            attempts_per_path: 3,
+        }
+    }
+}
+
+/// Share of the shorter path's cells that the other path also visits.
+pub fn path_overlap(a: &[Cell], b: &[Cell]) -> f32 {
+    let a: HashSet<IVec2> = a.iter().map(|cell| cell.position).collect();
+    let b: HashSet<IVec2> = b.iter().map(|cell| cell.position).collect();
+    let shared = a.intersection(&b).count();
+    let smallest = a.len().min(b.len());
+    if smallest == 0 {
+        return 0.0;
+    }
+    shared as f32 / smallest as f32
+}
+
+/// Finds up to `alternatives.count` sufficiently distinct paths, best first,
+/// by penalizing the cells of every path found so far and replanning. The
+/// reported costs leave the penalties out.
+pub fn plan_alternatives(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+    alternatives: &AlternativesConfig,
+) -> Vec<PlanResult> {
+    let goal = goal.into();
+    let mut penalties: HashMap<IVec2, u32> = HashMap::new();
+    let mut results: Vec<PlanResult> = Vec::new();
+
+    for _ in 0..alternatives.count * alternatives.attempts_per_path {
+        if results.len() == alternatives.count {
+            break;
+        }
+        let extra_cost = |cell: &Cell| penalties.get(&cell.position).copied().unwrap_or(0);
+        let Ok(mut result) = planner::plan_with_extra_cost(
+            grid,
+            agent,
+            neighbor_cache,
+            start.clone(),
+            goal.clone(),
+            config,
+            extra_cost,
+        ) else {
+            break;
+        };
+        let charged: u32 = result.path.iter().skip(1).map(extra_cost).sum();
+        result.cost -= charged;
+
+        for cell in &result.path {
+            *penalties.entry(cell.position).or_insert(0) += alternatives.penalty;
+        }
+        let distinct = results
+            .iter()
+            .all(|other| path_overlap(&other.path, &result.path) <= alternatives.max_overlap);
+        if distinct {
+            results.push(result);
+        }
+    }
+
+    results.sort_by_key(|result| result.cost);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_routes_around_pillar() {
+        // A pillar in the middle leaves a route above and one below.
+        let mut grid = Grid::new(1.0, 10, 10);
+        for y in 2..=7 {
+            for x in 4..=5 {
+                grid.set_cell(x, y, true);
+            }
+        }
+        let (agent, cache, config) = Fixture::new(10, 10).holonomic(1).build();
+        let alternatives = AlternativesConfig {
+            count: 2,
+            ..AlternativesConfig::default()
+        };
+        let start = Cell::new(0, IVec2::new(1, 5));
+        let results = plan_alternatives(
+            &grid,
+            &agent,
+            &cache,
+            start,
+            IVec2::new(8, 5),
+            &config,
+            &alternatives,
+        );
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].cost <= results[1].cost);
+        let above = |result: &PlanResult| result.path.iter().any(|cell| cell.position.y < 2);
+        assert_ne!(above(&results[0]), above(&results[1]));
+        assert!(path_overlap(&results[0].path, &results[1].path) <= 0.7);
+    }
+}
@@ -0,0 +1,244 @@
+use std::io::{self, Read, Write};
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::Cell;
+use crate::encoding::{read_ivec2, read_str, read_u32, write_ivec2, write_str, write_u32};
+use crate::goal::Goal;
+
+/// What a named place on the map is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationKind {
+    Label,
+    Spawn,
+    Dock,
+    Charger,
+    /// A pull-in spot off a narrow aisle where a vehicle can wait for
+    /// another to pass, see [`crate::fleet::plan_fleet`].
+    PassingBay,
+}
+
+impl AnnotationKind {
+    fn to_u32(self) -> u32 {
+        match self {
+            AnnotationKind::Label => 0,
+            AnnotationKind::Spawn => 1,
+            AnnotationKind::Dock => 2,
+            AnnotationKind::Charger => 3,
+            AnnotationKind::PassingBay => 4,
+        }
+    }
+
+    fn from_u32(value: u32) -> io::Result<Self> {
+        Ok(match value {
+            0 => AnnotationKind::Label,
+            1 => AnnotationKind::Spawn,
+            2 => AnnotationKind::Dock,
+            3 => AnnotationKind::Charger,
+            4 => AnnotationKind::PassingBay,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unknown annotation kind",
+                ))
+            }
+        })
+    }
+}
+
+/// A named point on the map, or a pose if it has a heading.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub name: String,
+    pub kind: AnnotationKind,
+    pub position: IVec2,
+    /// Heading increment the vehicle must arrive at, like facing into a
+    /// dock. Any heading will do if `None`.
+    pub heading: Option<i16>,
+}
+
+impl Annotation {
+    /// The goal to plan to, exact about the heading if there is one.
+    pub fn goal(&self) -> Goal {
+        match self.heading {
+            Some(heading) => Goal::Oriented {
+                goal: Box::new(Goal::Cell(self.position)),
+                heading,
+                tolerance: 0,
+            },
+            None => Goal::Cell(self.position),
+        }
+    }
+
+    /// The pose at the annotation, facing heading 0 if it has none.
+    pub fn pose(&self) -> Cell {
+        Cell::new(self.heading.unwrap_or(0), self.position)
+    }
+}
+
+/// Named places on a map, so scripts can plan to `dock_3` rather than to
+/// raw coordinates. Names are unique.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Annotations {
+    pub entries: Vec<Annotation>,
+}
+
+impl Annotations {
+    /// Adds `annotation`, replacing any with the same name.
+    pub fn insert(&mut self, annotation: Annotation) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.name == annotation.name)
+        {
+            Some(entry) => *entry = annotation,
+            None => self.entries.push(annotation),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Annotation> {
+        let index = self.entries.iter().position(|entry| entry.name == name)?;
+        Some(self.entries.remove(index))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Annotation> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn goal(&self, name: &str) -> Option<Goal> {
+        self.get(name).map(Annotation::goal)
+    }
+
+    pub fn pose(&self, name: &str) -> Option<Cell> {
+        self.get(name).map(Annotation::pose)
+    }
+
+    pub fn of_kind(&self, kind: AnnotationKind) -> impl Iterator<Item = &Annotation> {
+        self.entries.iter().filter(move |entry| entry.kind == kind)
+    }
+
+    /// The annotation of `kind` nearest to `position`, by straight-line
+    /// distance, like the closest charger.
+    pub fn nearest(&self, kind: AnnotationKind, position: IVec2) -> Option<&Annotation> {
+        self.of_kind(kind)
+            .min_by_key(|entry| (entry.position - position).length_squared())
+    }
+
+    /// Places `agent` at the spawn point `name`.
+    pub fn spawn(&self, agent: &mut Agent, name: &str) -> bool {
+        let Some(pose) = self
+            .get(name)
+            .filter(|entry| entry.kind == AnnotationKind::Spawn)
+            .map(Annotation::pose)
+        else {
+            return false;
+        };
+        agent.position = pose.position;
+        agent.rotation = pose.rotation;
+        true
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_u32(writer, self.entries.len() as u32)?;
+        for entry in &self.entries {
+            write_str(writer, &entry.name)?;
+            write_u32(writer, entry.kind.to_u32())?;
+            write_ivec2(writer, entry.position)?;
+            // Headings are small and never negative, so u32::MAX is free to
+            // mean none.
+            write_u32(
+                writer,
+                entry.heading.map_or(u32::MAX, |heading| heading as u32),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let count = read_u32(reader)?;
+        let entries = (0..count)
+            .map(|_| {
+                Ok(Annotation {
+                    name: read_str(reader)?,
+                    kind: AnnotationKind::from_u32(read_u32(reader)?)?,
+                    position: read_ivec2(reader)?,
+                    heading: match read_u32(reader)? {
+                        u32::MAX => None,
+                        heading => Some(heading as i16),
+                    },
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_lookup_and_round_trip() {
+        let mut annotations = Annotations::default();
+        annotations.insert(Annotation {
+            name: "dock_3".to_string(),
+            kind: AnnotationKind::Dock,
+            position: IVec2::new(12, 4),
+            heading: Some(2),
+        });
+        annotations.insert(Annotation {
+            name: "start".to_string(),
+            kind: AnnotationKind::Spawn,
+            position: IVec2::new(1, 1),
+            heading: None,
+        });
+        annotations.insert(Annotation {
+            name: "charger_a".to_string(),
+            kind: AnnotationKind::Charger,
+            position: IVec2::new(20, 20),
+            heading: None,
+        });
+        annotations.insert(Annotation {
+            name: "charger_b".to_string(),
+            kind: AnnotationKind::Charger,
+            position: IVec2::new(3, 18),
+            heading: None,
+        });
+
+        let Some(Goal::Oriented { goal, heading, .. }) = annotations.goal("dock_3") else {
+            panic!("docks with a heading are oriented goals");
+        };
+        assert_eq!(*goal, Goal::Cell(IVec2::new(12, 4)));
+        assert_eq!(heading, 2);
+        assert_eq!(
+            annotations.goal("start"),
+            Some(Goal::Cell(IVec2::new(1, 1)))
+        );
+        assert!(annotations.goal("dock_4").is_none());
+        let nearest = annotations.nearest(AnnotationKind::Charger, IVec2::new(0, 16));
+        assert_eq!(nearest.unwrap().name, "charger_b");
+
+        let mut agent = Agent::new(IVec2::new(9, 9), Vec2::new(0.01, 0.01), 5, 8);
+        assert!(!annotations.spawn(&mut agent, "dock_3"));
+        assert!(annotations.spawn(&mut agent, "start"));
+        assert_eq!((agent.position, agent.rotation), (IVec2::new(1, 1), 0));
+
+        // Inserting a taken name replaces the old entry.
+        annotations.insert(Annotation {
+            name: "start".to_string(),
+            kind: AnnotationKind::Spawn,
+            position: IVec2::new(2, 2),
+            heading: Some(4),
+        });
+        assert_eq!(annotations.entries.len(), 4);
+
+        let mut bytes = Vec::new();
+        annotations.write_to(&mut bytes).unwrap();
+        let read = Annotations::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, annotations);
+        assert!(Annotations::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+
+use crate::agent::Agent;
+use crate::annotations::Annotations;
+use crate::cell::{Cell, NeighborCache};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlanResult, PlannerConfig};
+
+/// One plan of a [`plan_many`] batch.
+#[derive(Clone)]
+pub struct PlanRequest {
+    pub agent: Agent,
+    pub start: Cell,
+    pub goal: Goal,
+}
+
+impl PlanRequest {
+    /// A request to the annotation `name`, like `dock_3`, or `None` if the
+    /// map has no such annotation.
+    pub fn to_annotation(
+        agent: Agent,
+        start: Cell,
+        annotations: &Annotations,
+        name: &str,
+    ) -> Option<Self> {
+        Some(Self {
+            goal: annotations.goal(name)?,
+            agent,
+            start,
+        })
+    }
+}
+
+/// Plans every request on up to `workers` threads, returning the results in
+/// request order. Each search runs on its own copy of the neighbor cache
+/// and shares nothing mutable with the others, so the results are the same
+/// as planning the requests one by one, whatever the worker count or
+/// scheduling.
+pub fn plan_many(
+    grid: &Grid,
+    neighbor_cache: &NeighborCache,
+    requests: &[PlanRequest],
+    config: &PlannerConfig,
+    workers: usize,
+) -> Vec<Option<PlanResult>> {
+    let workers = workers.clamp(1, requests.len().max(1));
+    let plan_stripe = |worker: usize| {
+        let cache = Rc::new(RefCell::new(neighbor_cache.clone()));
+        requests
+            .iter()
+            .enumerate()
+            .skip(worker)
+            .step_by(workers)
+            .map(|(index, request)| {
+                let result = planner::plan(
+                    grid,
+                    &request.agent,
+                    &cache,
+                    request.start.clone(),
+                    request.goal.clone(),
+                    config,
+                )
+                .ok();
+                (index, result)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut results = vec![None; requests.len()];
+    let stripes: Vec<_> = if workers == 1 {
+        vec![plan_stripe(0)]
+    } else {
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|worker| scope.spawn(move || plan_stripe(worker)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("planner worker panicked"))
+                .collect()
+        })
+    };
+    for (index, result) in stripes.into_iter().flatten() {
+        results[index] = result;
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use notan::math::IVec2;
+
+    use super::*;
+    use crate::agent::MotionModel;
+    use crate::field::goal_distance;
+    use crate::test_support::{point_agent, Fixture, MAX_INCREMENTS};
+
+    /// Mixed agents and goals on a map with a few walls, one of them
+    /// unreachable.
+    fn scenarios() -> (Grid, Vec<PlanRequest>) {
+        let mut grid = Grid::new(1.0, 24, 24);
+        for y in 4..20 {
+            grid.set_cell(8, y, true);
+        }
+        for x in 12..22 {
+            grid.set_cell(x, 12, true);
+        }
+        for y in 0..24 {
+            grid.set_cell(22, y, true);
+        }
+        let car = point_agent(IVec2::new(0, 0));
+        let mut holonomic = car.clone();
+        holonomic.motion = MotionModel::Holonomic { heading_weight: 50 };
+        let mut requests = Vec::new();
+        for i in 0..12 {
+            let agent = if i % 3 == 0 { &holonomic } else { &car };
+            let start = Cell::new((i % 8) as i16, IVec2::new(2 + i % 4, 2 + i));
+            let goal = match i % 4 {
+                0 => Goal::Cell(IVec2::new(16, 4 + i)),
+                1 => Goal::Rect {
+                    min: IVec2::new(14, 15),
+                    max: IVec2::new(17, 18),
+                },
+                2 => Goal::Oriented {
+                    goal: Box::new(Goal::Cell(IVec2::new(18, 20))),
+                    heading: 4,
+                    tolerance: 1,
+                },
+                _ => Goal::Cell(IVec2::new(23, 5)),
+            };
+            requests.push(PlanRequest {
+                agent: agent.clone(),
+                start,
+                goal,
+            });
+        }
+        (grid, requests)
+    }
+
+    fn same(a: &Option<PlanResult>, b: &Option<PlanResult>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.path == b.path && a.cost == b.cost,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_results_independent_of_workers() {
+        let (grid, requests) = scenarios();
+        let cache = NeighborCache::new_precomputed(MAX_INCREMENTS, 1);
+        let mut config = Fixture::new(24, 24).config();
+
+        let shared = Rc::new(RefCell::new(cache.clone()));
+        let sequential: Vec<_> = requests
+            .iter()
+            .map(|request| {
+                planner::plan(
+                    &grid,
+                    &request.agent,
+                    &shared,
+                    request.start.clone(),
+                    request.goal.clone(),
+                    &config,
+                )
+                .ok()
+            })
+            .collect();
+        assert!(sequential.iter().any(|result| result.is_some()));
+        assert!(sequential.iter().any(|result| result.is_none()));
+
+        for workers in [1, 2, 3, 8, 64] {
+            let batch = plan_many(&grid, &cache, &requests, &config, workers);
+            assert_eq!(batch.len(), sequential.len());
+            assert!(batch.iter().zip(&sequential).all(|(a, b)| same(a, b)));
+        }
+
+        // Shared read-only heuristic data doesn't change that either.
+        config.heuristic_field = Some(Arc::new(goal_distance(
+            &grid,
+            &Goal::Cell(IVec2::new(16, 4)),
+        )));
+        let one = plan_many(&grid, &cache, &requests, &config, 1);
+        let many = plan_many(&grid, &cache, &requests, &config, 5);
+        assert!(one.iter().zip(&many).all(|(a, b)| same(a, b)));
+    }
+}
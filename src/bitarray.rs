@@ -1,3 +1,6 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
 #[derive(Debug)]
 pub struct BitArray {
     bits: Vec<u32>,
@@ -67,6 +70,21 @@ impl BitArray {
         self.is_bit_set(position)
     }
 
+    // Get the 64 bits starting at `position`, with bits past the end clear
+    pub fn bits_from(&self, position: usize) -> u64 {
+        let element = position / 32;
+        let bit = position % 32;
+        let word = |i: usize| self.bits.get(element + i).copied().unwrap_or(0) as u128;
+        let bits = word(0) | word(1) << 32 | word(2) << 64;
+        let bits = (bits >> bit) as u64;
+        let remaining = self.num_bits.saturating_sub(position);
+        if remaining >= 64 {
+            bits
+        } else {
+            bits & ((1u64 << remaining) - 1)
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.num_bits
     }
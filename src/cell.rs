@@ -1,29 +1,81 @@
-use notan::draw::*;
-use notan::math::{IVec2, Vec2};
-use notan::prelude::*;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::f32::consts::PI;
-use std::hash::Hash;
-use std::rc::Rc;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::f32::consts::PI;
+use core::hash::Hash;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
 
-use crate::draw_arrow;
+use glam::IVec2;
+
+#[cfg(feature = "std")]
+use crate::encoding;
+use crate::fixed;
+use crate::neighbor_rules::{default_rules, NeighborCandidate, NeighborRule, RuleContext};
+
+#[cfg(feature = "std")]
+fn round(value: f32) -> f32 {
+    value.round()
+}
+#[cfg(not(feature = "std"))]
+fn round(value: f32) -> f32 {
+    libm::roundf(value)
+}
+#[cfg(feature = "std")]
+fn atan2(y: f32, x: f32) -> f32 {
+    y.atan2(x)
+}
+#[cfg(not(feature = "std"))]
+fn atan2(y: f32, x: f32) -> f32 {
+    libm::atan2f(y, x)
+}
 
 // ===============================
 // NEIGHBOR CACHE
 // ===============================
 pub type NeighborCacheRef = Rc<RefCell<NeighborCache>>;
+
+/// One precomputed move out of a heading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CachedMove {
+    pub offset: IVec2,
+    /// Heading the move ends at.
+    pub rotation: i16,
+    /// Heading the move drives along, opposite `rotation` for reverse
+    /// moves.
+    pub heading: i16,
+    /// Whether the move backs up, worked out once here so every cost and
+    /// filter agrees on it.
+    pub reverse: bool,
+}
+
+impl CachedMove {
+    /// Move from heading `from` by `offset`, ending at `rotation` after
+    /// driving along `heading`.
+    pub fn new(from: i16, offset: IVec2, rotation: i16, heading: i16, max_increments: u16) -> Self {
+        let start = Cell::new(from, IVec2::ZERO);
+        Self {
+            offset,
+            rotation,
+            heading,
+            reverse: Cell::new(rotation, offset).is_reverse_to(&start, max_increments as i16),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct NeighborCache {
-    cache: Vec<Vec<(IVec2, i16)>>,
-    neighbor_xy_to_increment: HashMap<IVec2, i16>,
+    cache: Vec<Vec<CachedMove>>,
+    neighbor_xy_to_increment: Vec<(IVec2, i16)>,
 }
 
 impl NeighborCache {
     pub fn new(max_increments: u16, arc: u16) -> Self {
         NeighborCache {
             cache: Vec::with_capacity(max_increments as usize),
-            neighbor_xy_to_increment: HashMap::new(),
+            neighbor_xy_to_increment: Vec::new(),
         }
     }
     pub fn new_precomputed(max_increments: u16, arc: u16) -> Self {
@@ -31,15 +83,43 @@ impl NeighborCache {
         cache.precompute(max_increments, arc);
         cache
     }
+    /// Same as [`NeighborCache::new_precomputed`], with its own arc width for
+    /// reverse moves.
+    pub fn new_precomputed_with_reverse_arc(
+        max_increments: u16,
+        arc: u16,
+        reverse_arc: u16,
+    ) -> Self {
+        let mut cache = Self::new(max_increments, arc);
+        cache.precompute_with_reverse_arc(max_increments, arc, reverse_arc);
+        cache
+    }
 
-    pub fn get(&self, rotation: i16) -> Option<&Vec<(IVec2, i16)>> {
+    pub fn get(&self, rotation: i16) -> Option<&Vec<CachedMove>> {
         self.cache.get(rotation as usize)
     }
 
+    /// Precomputes forward moves within `arc` increments of the heading and
+    /// reverse moves within twice that.
     pub fn precompute(&mut self, max_increments: u16, arc: u16) {
+        self.precompute_with_reverse_arc(max_increments, arc, arc * 2);
+    }
+
+    pub fn precompute_with_reverse_arc(&mut self, max_increments: u16, arc: u16, reverse_arc: u16) {
+        self.precompute_with_rules(max_increments, arc, reverse_arc, &default_rules());
+    }
+
+    /// Precomputes every move within the forward and reverse arcs and keeps
+    /// those all of `rules` allow.
+    pub fn precompute_with_rules(
+        &mut self,
+        max_increments: u16,
+        arc: u16,
+        reverse_arc: u16,
+        rules: &[Box<dyn NeighborRule>],
+    ) {
         // Precompute increments pointing in "cardinal" directions.
         // Those are the increments that go most "straight" to that neighbor.
-        let increment_size = std::f32::consts::PI * 2.0 / max_increments as f32;
         let cardinal_directions = vec![
             IVec2::new(0, 1),
             IVec2::new(1, 0),
@@ -54,79 +134,340 @@ impl NeighborCache {
         // now use dot product to find the closest increment to each direction
         for direction in cardinal_directions {
             let mut closest_increment = 0;
-            let mut closest_dot = -1.0;
+            let mut closest_dot = i32::MIN;
             for increment in 0..max_increments {
-                let angle = increment as f32 * increment_size;
-                let rotation_vector = Vec2::from_angle(angle);
-                let direction_vector = Vec2::new(direction.x as f32, direction.y as f32);
-                let dot = rotation_vector.dot(direction_vector);
+                let dot = fixed::direction(increment as i16, max_increments).dot(direction);
                 if dot > closest_dot {
                     closest_dot = dot;
                     closest_increment = increment;
                 }
             }
             self.neighbor_xy_to_increment
-                .insert(direction, closest_increment as i16);
+                .push((direction, closest_increment as i16));
         }
         // now print them pretty
+        #[cfg(feature = "std")]
         for (direction, increment) in self.neighbor_xy_to_increment.iter() {
             println!("Direction: {:?} -> Increment: {}", direction, increment);
         }
 
+        let context = RuleContext {
+            max_increments,
+            aligned: self
+                .neighbor_xy_to_increment
+                .iter()
+                .map(|(_, increment)| *increment)
+                .collect(),
+        };
+
         // Precompute the neighbors for each rotation.
         for rotation in 0..max_increments as i16 {
             let arc = arc as i16;
-            let mut neighbors = Vec::with_capacity((arc * 2 + 1) as usize);
+            let mut candidates = Vec::with_capacity((arc * 2 + 1) as usize);
 
             for i in -arc..=arc {
                 let new_rotation = Cell::clamp_rotation(rotation + i, max_increments as i16);
-                let cell =
-                    Cell::precompute_neighbor(new_rotation, increment_size, false, max_increments);
-                neighbors.push((cell.position, cell.rotation));
+                let cell = Cell::precompute_neighbor(new_rotation, false, max_increments);
+                candidates.push((cell, new_rotation, false));
             }
 
-            let reverse_arc = arc * 2;
-            let opposite_rotation =
-                Cell::clamp_rotation(rotation + max_increments as i16 / 2, max_increments as i16);
+            let reverse_arc = reverse_arc as i16;
+            let opposite_rotation = Cell::opposite_rotation(rotation, max_increments as i16);
             for i in -reverse_arc..=reverse_arc {
                 let new_rotation =
                     Cell::clamp_rotation(opposite_rotation + i, max_increments as i16);
-                let cell =
-                    Cell::precompute_neighbor(new_rotation, increment_size, true, max_increments);
-                neighbors.push((cell.position, cell.rotation));
+                let cell = Cell::precompute_neighbor(new_rotation, true, max_increments);
+                candidates.push((cell, new_rotation, true));
             }
 
-            // filter out the neighbors which don't follow one of the
-            // following rules:
-            // 1. rotation changed, there was turning
-            // 2. rotation didn't change, but going in a "cardinal" direction
-            // 3. going in reverse
-            neighbors = neighbors
+            let neighbors = candidates
                 .into_iter()
-                .filter(|(pos, rot)| {
-                    let rotation_changed = *rot != rotation;
-                    let cardinal = self
-                        .neighbor_xy_to_increment
-                        .values()
-                        .any(|&inc| inc == *rot);
-                    rotation_changed || cardinal
+                .map(|(cell, heading, reverse)| {
+                    let candidate = NeighborCandidate {
+                        from_rotation: rotation,
+                        offset: cell.position,
+                        rotation: cell.rotation,
+                        reverse,
+                    };
+                    (candidate, heading)
+                })
+                .filter(|(candidate, _)| rules.iter().all(|rule| rule.allows(candidate, &context)))
+                .map(|(candidate, heading)| {
+                    CachedMove::new(
+                        rotation,
+                        candidate.offset,
+                        candidate.rotation,
+                        heading,
+                        max_increments,
+                    )
                 })
                 .collect();
 
             self.cache.push(neighbors);
         }
     }
+
+    /// Writes the precomputed neighbors, for [`NeighborCache::read_from`].
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        encoding::write_u32(writer, self.neighbor_xy_to_increment.len() as u32)?;
+        for (direction, increment) in &self.neighbor_xy_to_increment {
+            encoding::write_ivec2(writer, *direction)?;
+            encoding::write_u32(writer, *increment as u32)?;
+        }
+        encoding::write_u32(writer, self.cache.len() as u32)?;
+        for neighbors in &self.cache {
+            encoding::write_u32(writer, neighbors.len() as u32)?;
+            for neighbor in neighbors {
+                encoding::write_ivec2(writer, neighbor.offset)?;
+                encoding::write_u32(writer, neighbor.rotation as u32)?;
+                encoding::write_u32(writer, neighbor.heading as u32)?;
+                encoding::write_u32(writer, neighbor.reverse as u32)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut neighbor_xy_to_increment = Vec::new();
+        for _ in 0..encoding::read_u32(reader)? {
+            let direction = encoding::read_ivec2(reader)?;
+            neighbor_xy_to_increment.push((direction, encoding::read_u32(reader)? as i16));
+        }
+        let rotations = encoding::read_u32(reader)?;
+        let mut cache = Vec::with_capacity(rotations as usize);
+        for _ in 0..rotations {
+            let len = encoding::read_u32(reader)?;
+            let mut neighbors = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                neighbors.push(CachedMove {
+                    offset: encoding::read_ivec2(reader)?,
+                    rotation: encoding::read_u32(reader)? as i16,
+                    heading: encoding::read_u32(reader)? as i16,
+                    reverse: encoding::read_u32(reader)? != 0,
+                });
+            }
+            cache.push(neighbors);
+        }
+        Ok(Self {
+            cache,
+            neighbor_xy_to_increment,
+        })
+    }
+
+    /// Returns a copy that keeps only forward and/or reverse neighbors.
+    pub fn filtered(&self, allow_forward: bool, allow_reverse: bool) -> Self {
+        let mut filtered = self.clone();
+        for neighbors in filtered.cache.iter_mut() {
+            neighbors.retain(|neighbor| {
+                if neighbor.reverse {
+                    allow_reverse
+                } else {
+                    allow_forward
+                }
+            });
+        }
+        filtered
+    }
+
+    /// Returns a copy with the reverse moves of every heading widened out to
+    /// `reverse_arc` increments either side. Moves already cached are kept
+    /// as they are, and straight ones stay up to the rules the cache was
+    /// built with.
+    pub fn with_reverse_arc(&self, reverse_arc: u16) -> Self {
+        let max_increments = self.cache.len() as u16;
+        let reverse_arc = reverse_arc.min(max_increments / 2) as i16;
+        let mut widened = self.clone();
+        for (rotation, neighbors) in widened.cache.iter_mut().enumerate() {
+            let opposite = Cell::opposite_rotation(rotation as i16, max_increments as i16);
+            for i in (-reverse_arc..=reverse_arc).filter(|i| *i != 0) {
+                let new_rotation = Cell::clamp_rotation(opposite + i, max_increments as i16);
+                let cell = Cell::precompute_neighbor(new_rotation, true, max_increments);
+                let neighbor = CachedMove::new(
+                    rotation as i16,
+                    cell.position,
+                    cell.rotation,
+                    new_rotation,
+                    max_increments,
+                );
+                if !neighbors.iter().any(|cached| {
+                    (cached.offset, cached.rotation) == (cell.position, cell.rotation)
+                }) {
+                    neighbors.push(neighbor);
+                }
+            }
+        }
+        widened
+    }
+
+    /// Extends every straight neighbor with primitives that advance
+    /// 2..=`max_length` cells in the same direction.
+    pub fn add_straight_primitives(&mut self, max_length: i32) {
+        for (rotation, neighbors) in self.cache.iter_mut().enumerate() {
+            let straight: Vec<CachedMove> = neighbors
+                .iter()
+                .filter(|neighbor| neighbor.rotation as usize == rotation)
+                .cloned()
+                .collect();
+            for length in 2..=max_length {
+                for neighbor in &straight {
+                    neighbors.push(CachedMove {
+                        offset: neighbor.offset * length,
+                        ..*neighbor
+                    });
+                }
+            }
+        }
+    }
 }
 
 // ===============================
 // COST CACHE
 // ===============================
 pub type CostCacheRef = Rc<RefCell<CostCache>>;
+/// Cost of every move in a [`NeighborCache`], in the same order, so
+/// expanding a pose looks costs up instead of recomputing them.
 #[derive(Clone, Debug)]
 pub struct CostCache {
     cache: Vec<Vec<u32>>,
 }
 
+impl CostCache {
+    pub fn new(
+        neighbors: &NeighborCache,
+        arc: u16,
+        max_increments: u16,
+        reverse_factor: u32,
+    ) -> Self {
+        Self::from_fn(neighbors, |from, to, _| {
+            to.cost_with_reverse_factor(Some(from.clone()), arc, max_increments, reverse_factor)
+        })
+    }
+
+    /// Costs every move with `cost(from, to, move)` instead of
+    /// [`Cell::cost`].
+    pub fn from_fn(
+        neighbors: &NeighborCache,
+        cost: impl Fn(&Cell, &Cell, &CachedMove) -> u32,
+    ) -> Self {
+        let cache = neighbors
+            .cache
+            .iter()
+            .enumerate()
+            .map(|(rotation, moves)| {
+                let from = Cell::new(rotation as i16, IVec2::ZERO);
+                moves
+                    .iter()
+                    .map(|neighbor| {
+                        cost(
+                            &from,
+                            &Cell::new(neighbor.rotation, neighbor.offset),
+                            neighbor,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { cache }
+    }
+
+    /// Costs of the moves [`NeighborCache::get`] returns for `rotation`.
+    pub fn get(&self, rotation: i16) -> Option<&Vec<u32>> {
+        self.cache.get(rotation as usize)
+    }
+}
+
+/// Cheapest total turning cost from every heading to every other one,
+/// counting only the angle part of each move's cost. Never more than what
+/// reaching a heading really costs, so it can be added to the heuristic.
+#[derive(Clone, Debug)]
+pub struct HeadingCache {
+    max_increments: u16,
+    table: Vec<u32>,
+}
+
+impl HeadingCache {
+    /// Turning costs using the moves in `neighbors`, plus one increment
+    /// turns in place for `turn_in_place_cost` if the agent can.
+    pub fn new(
+        neighbors: &NeighborCache,
+        arc: u16,
+        max_increments: u16,
+        turn_in_place_cost: Option<u32>,
+    ) -> Self {
+        let count = max_increments as usize;
+        let mut edges = vec![u32::MAX; count * count];
+        for (from, moves) in neighbors.cache.iter().enumerate() {
+            let start = Cell::new(from as i16, IVec2::ZERO);
+            for neighbor in moves {
+                let turn = start.rotation_to(neighbor.rotation, max_increments as i16);
+                // A zero arc allows no turning moves, the same as the planner.
+                if turn != 0 && arc == 0 {
+                    continue;
+                }
+                // Integer math, so fixed point searches get the same table.
+                let cost = turn as u32 * 1000 / arc.max(1) as u32;
+                let edge = &mut edges[from * count + neighbor.rotation as usize];
+                *edge = (*edge).min(cost);
+            }
+            if let Some(turn_cost) = turn_in_place_cost {
+                for delta in [-1, 1] {
+                    let to = Cell::clamp_rotation(from as i16 + delta, max_increments as i16);
+                    let edge = &mut edges[from * count + to as usize];
+                    *edge = (*edge).min(turn_cost);
+                }
+            }
+        }
+        Self::from_edges(max_increments, &edges)
+    }
+
+    /// Turning costs of an agent paying `weight` per increment.
+    pub fn uniform(max_increments: u16, weight: u32) -> Self {
+        let count = max_increments as usize;
+        let table = (0..count * count)
+            .map(|index| {
+                let from = Cell::new((index / count) as i16, IVec2::ZERO);
+                from.rotation_to((index % count) as i16, max_increments as i16) as u32 * weight
+            })
+            .collect();
+        Self {
+            max_increments,
+            table,
+        }
+    }
+
+    /// All-pairs cheapest paths over the direct turn costs in `edges`.
+    fn from_edges(max_increments: u16, edges: &[u32]) -> Self {
+        let count = max_increments as usize;
+        let mut table = edges.to_vec();
+        for heading in 0..count {
+            table[heading * count + heading] = 0;
+        }
+        for via in 0..count {
+            for from in 0..count {
+                for to in 0..count {
+                    let through = table[from * count + via].saturating_add(table[via * count + to]);
+                    if through < table[from * count + to] {
+                        table[from * count + to] = through;
+                    }
+                }
+            }
+        }
+        Self {
+            max_increments,
+            table,
+        }
+    }
+
+    /// Cheapest turning cost from `from` to `to`, `u32::MAX` if the moves
+    /// never get there.
+    pub fn get(&self, from: i16, to: i16) -> u32 {
+        let count = self.max_increments as usize;
+        self.table[from as usize * count + to as usize]
+    }
+}
+
 // ===============================
 // CELL
 // ===============================
@@ -134,26 +475,29 @@ pub struct CostCache {
 pub struct Cell {
     pub rotation: i16,
     pub position: IVec2,
+    /// Index of the map layer the cell is on, 0 for single-layer grids.
+    pub layer: u8,
 }
 impl Cell {
     pub fn new(rotation: i16, start: IVec2) -> Self {
         Self {
             rotation,
             position: start,
+            layer: 0,
         }
     }
-    pub fn precompute_neighbor(
-        rotation: i16,
-        increment_size: f32,
-        reverse: bool,
-        max_increments: u16,
-    ) -> Self {
-        let angle = rotation as f32 * increment_size;
-        let rotation_vector = Vec2::from_angle(angle);
-        let x = rotation_vector.x.round() as i32;
-        let y = rotation_vector.y.round() as i32;
-        let direction_vector = Vec2::new(x.clamp(-1, 1) as f32, y.clamp(-1, 1) as f32);
-        let new_position = IVec2::new(direction_vector.x as i32, direction_vector.y as i32);
+    pub fn with_layer(mut self, layer: u8) -> Self {
+        self.layer = layer;
+        self
+    }
+    /// Single-cell step along heading `rotation`, ending facing the other
+    /// way if `reverse`. Rounded from [`fixed::direction`], so every target
+    /// gets the same steps.
+    pub fn precompute_neighbor(rotation: i16, reverse: bool, max_increments: u16) -> Self {
+        // Rounds each component of the unit vector half away from zero.
+        let vector = fixed::direction(rotation, max_increments);
+        let round = |value: i32| (value * 2 + value.signum() * fixed::UNIT) / (fixed::UNIT * 2);
+        let new_position = IVec2::new(round(vector.x), round(vector.y));
 
         let adjusted_rotation = if reverse {
             Self::opposite_rotation(rotation, max_increments as i16)
@@ -163,144 +507,154 @@ impl Cell {
         Self {
             position: new_position,
             rotation: adjusted_rotation,
+            layer: 0,
         }
     }
-    pub fn neighbors(&self, cache: &NeighborCacheRef, arc: u16, max_increments: u16) -> Vec<Self> {
-        let mut neighbors = Vec::new();
+    /// Calls `f` with every cached neighbor of this pose, reading the
+    /// precomputed moves in place.
+    pub fn for_each_neighbor(&self, cache: &NeighborCacheRef, mut f: impl FnMut(Self)) {
+        self.for_each_move(cache, |neighbor, _| f(neighbor));
+    }
+    /// Same as [`Cell::for_each_neighbor`], along with the cached move that
+    /// reaches each neighbor.
+    pub fn for_each_move(&self, cache: &NeighborCacheRef, mut f: impl FnMut(Self, &CachedMove)) {
         if let Some(cached) = cache.borrow().get(self.rotation) {
-            neighbors = Vec::with_capacity(cached.len());
-            for (position, rotation) in cached {
-                let new_position = self.position + *position;
-                let new_rotation = *rotation;
-                neighbors.push(Self {
-                    position: new_position,
-                    rotation: new_rotation,
-                });
+            for neighbor in cached {
+                let cell = Self {
+                    position: self.position + neighbor.offset,
+                    rotation: neighbor.rotation,
+                    layer: self.layer,
+                };
+                f(cell, neighbor);
             }
         }
-        neighbors
     }
+    /// The rotation half a turn from `rotation`, wrapped into
+    /// `0..max_increments` like [`Cell::clamp_rotation`].
     pub fn opposite_rotation(rotation: i16, max_increments: i16) -> i16 {
-        let current_rotation = rotation as i32;
         let max_rotation = max_increments as i32;
-        let opposite_rotation = current_rotation + max_rotation / 2;
-        Self::clamp_rotation(opposite_rotation as i16, max_increments)
+        (rotation as i32 + max_rotation / 2).rem_euclid(max_rotation) as i16
     }
+    /// Wraps any `rotation` into `0..max_increments`, however many turns
+    /// out of range it is.
     pub fn clamp_rotation(rotation: i16, max_increments: i16) -> i16 {
-        let max_rotation = max_increments as i16;
-        if rotation < 0 {
-            max_rotation + rotation
-        } else if rotation >= max_rotation {
-            rotation - max_rotation
-        } else {
-            rotation
-        }
+        (rotation as i32).rem_euclid(max_increments as i32) as i16
+    }
+    /// Heading increment closest to `angle` (radians, counter-clockwise from
+    /// +x) out of `max_increments`.
+    pub fn heading_to_increment(angle: f32, max_increments: u16) -> i16 {
+        let increment_size = 2.0 * PI / max_increments as f32;
+        let increment = round(angle / increment_size) as i32;
+        increment.rem_euclid(max_increments as i32) as i16
     }
+    /// Angle in radians of heading increment `rotation`.
+    pub fn increment_to_heading(rotation: i16, max_increments: u16) -> f32 {
+        rotation as f32 * 2.0 * PI / max_increments as f32
+    }
+    /// Increments to turn from this rotation to `to` the shorter way round.
+    /// Either rotation may be out of range.
     pub fn rotation_to(&self, to: i16, max_increments: i16) -> i16 {
-        let angle1 = self.rotation as i16;
-        let angle2 = to as i16;
-        // Calculate the clockwise difference
-        let diff_clockwise = (angle2 - angle1 + max_increments) % max_increments;
-
-        // Calculate the counterclockwise difference
-        let diff_counterclockwise = (angle1 - angle2 + max_increments) % max_increments;
-
-        // Return the smaller difference
-        if diff_clockwise < diff_counterclockwise {
-            diff_clockwise
-        } else {
-            diff_counterclockwise
-        }
+        // Widened so deltas between far out of range rotations can't overflow
+        let max_increments = max_increments as i32;
+        let diff_clockwise = (to as i32 - self.rotation as i32).rem_euclid(max_increments);
+        let diff_counterclockwise = max_increments - diff_clockwise;
+        diff_clockwise.min(diff_counterclockwise) as i16
     }
+    /// Whether getting here from `other` moves against `other`'s heading.
+    /// Integer math, so sideways moves are never reverse on any target.
     pub fn is_reverse_to(&self, other: &Self, max_increments: i16) -> bool {
-        let from_other_to_self = self.position.as_vec2() - other.position.as_vec2();
-        let increment_size = 2.0 * PI / max_increments as f32;
-        let angle = other.rotation as f32 * increment_size;
-        let rotation_vector = Vec2::from_angle(angle);
-        let dot = rotation_vector.dot(from_other_to_self.normalize());
-        dot < 0.0
+        let from_other_to_self = (self.position - other.position).as_i64vec2();
+        let heading = fixed::direction(other.rotation, max_increments as u16).as_i64vec2();
+        heading.dot(from_other_to_self) < 0
     }
     pub fn cost(&self, from: Option<Cell>, arc: u16, max_increments: u16) -> u32 {
+        self.cost_with_reverse_factor(from, arc, max_increments, 10)
+    }
+    /// Same as [`Cell::cost`], with reverse moves multiplied by
+    /// `reverse_factor` instead of the default 10.
+    pub fn cost_with_reverse_factor(
+        &self,
+        from: Option<Cell>,
+        arc: u16,
+        max_increments: u16,
+        reverse_factor: u32,
+    ) -> u32 {
         if let Some(from) = from {
             let rotation = self.rotation_to(from.rotation, max_increments as i16);
             let reverse = self.is_reverse_to(&from, max_increments as i16);
-            let reverse_cost = if reverse { 10 } else { 1 };
+            let reverse_cost = if reverse { reverse_factor } else { 1 };
 
-            let arc_fraction = rotation as f32 / arc as f32;
+            // A zero arc allows no turning at all.
+            if rotation != 0 && arc == 0 {
+                return u32::MAX;
+            }
+            let arc_fraction = rotation as f32 / arc.max(1) as f32;
             let angle_cost = (arc_fraction * 1000.0) as u32;
 
+            // Multi-cell primitives cost the same as taking their single
+            // steps one by one.
+            let steps = from.steps_to(self);
             let distance = self
                 .position
                 .as_vec2()
-                .distance_squared(from.position.as_vec2());
-            let distance_cost = (distance * 1000.0) as u32;
+                .distance_squared(from.position.as_vec2())
+                / (steps * steps) as f32;
+            let distance_cost = (distance * 1000.0) as u32 * steps as u32;
 
             (angle_cost + distance_cost) * reverse_cost
         } else {
             0
         }
     }
+    /// Cost of an omnidirectional move: the distance traveled plus
+    /// `heading_weight` for every increment the heading is off from the
+    /// direction of motion.
+    pub fn holonomic_cost(&self, from: &Cell, max_increments: u16, heading_weight: u32) -> u32 {
+        let offset = (self.position - from.position).as_vec2();
+        let distance_cost = (offset.length() * 1000.0) as u32;
+        let increment_size = 2.0 * PI / max_increments as f32;
+        let motion_rotation = round(atan2(offset.y, offset.x) / increment_size) as i16;
+        let motion_rotation = motion_rotation.rem_euclid(max_increments as i16);
+        let misalignment = from.rotation_to(motion_rotation, max_increments as i16);
+        distance_cost + misalignment as u32 * heading_weight
+    }
+    /// Slot of this pose among every pose of a `width` by `height` grid with
+    /// `max_increments` headings, `None` off the grid. Layers aren't
+    /// included.
+    pub fn dense_index(&self, width: i32, height: i32, max_increments: u16) -> Option<usize> {
+        if self.position.x < 0
+            || self.position.x >= width
+            || self.position.y < 0
+            || self.position.y >= height
+        {
+            return None;
+        }
+        let cell = (self.position.y * width + self.position.x) as usize;
+        Some(cell * max_increments as usize + self.rotation as usize)
+    }
+    /// Number of single-cell steps a move from `self` to `to` is made of.
+    pub fn steps_to(&self, to: &Self) -> i32 {
+        let offset = to.position - self.position;
+        offset.x.abs().max(offset.y.abs()).max(1)
+    }
     pub fn heuristic(&self, to: IVec2, max_increments: u16) -> u32 {
         let distance = self.position.as_vec2().distance_squared(to.as_vec2());
         (distance * 10.0) as u32
     }
-
-    pub fn draw(
-        &self,
-        from: Option<&Cell>,
-        draw: &mut Draw,
-        font: &Font,
-        cell_size: f32,
-        max_increments: u16,
-    ) {
-        // Define the color based on the reverse flag
-        let reverse = if let Some(from) = from {
-            self.is_reverse_to(&from, max_increments as i16)
-        } else {
-            false
-        };
-        let color = if reverse { Color::RED } else { Color::BLUE };
-
-        // Calculate the center of the current cell as the starting point
-        let center = Vec2::new(
-            self.position.x as f32 * cell_size + cell_size / 2.0,
-            self.position.y as f32 * cell_size + cell_size / 2.0,
-        );
-
-        // Determine the rotation angle, adjusting for reverse if necessary
-        let rotation_angle =
-            self.rotation as f32 * 2.0 * std::f32::consts::PI / max_increments as f32;
-
-        // Calculate the end point of the arrow based on the rotation angle
-        // Ensuring it remains visually centered within the cell
-        let arrow_length = cell_size / 2.0; // Adjust this value to change the arrow's length
-        let end = center + Vec2::from_angle(rotation_angle).normalize() * arrow_length;
-
-        // Draw the arrow from center to the calculated end point
-        draw_arrow(draw, center, end, color);
-
-        // Write the data (rotation) below the arrow
-        let text = format!("R: {}", self.rotation);
-        let text_position = Vec2::new(
-            self.position.x as f32 * cell_size,
-            (self.position.y as f32 + 1.0) * cell_size - 20.0, // Adjust this to position the text below the cell
-        );
-        draw.text(&font, &text)
-            .translate(text_position.x, text_position.y)
-            .size(15.0)
-            .color(Color::WHITE);
-    }
 }
 impl PartialEq for Cell {
     fn eq(&self, other: &Self) -> bool {
-        self.position == other.position && self.rotation == other.rotation
+        self.position == other.position
+            && self.rotation == other.rotation
+            && self.layer == other.layer
     }
 }
 impl Eq for Cell {}
 impl Hash for Cell {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         self.position.hash(state);
         self.rotation.hash(state);
+        self.layer.hash(state);
         // self.reverse.hash(state);
     }
 }
@@ -309,6 +663,244 @@ impl Hash for Cell {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_with_reverse_arc() {
+        let cache = NeighborCache::new_precomputed(32, 1);
+        let widened = cache.with_reverse_arc(3);
+        for rotation in 0..32 {
+            let from = Cell::new(rotation, IVec2::ZERO);
+            let widest_reverse = |cache: &NeighborCache| {
+                cache
+                    .get(rotation)
+                    .unwrap()
+                    .iter()
+                    .filter(|neighbor| neighbor.reverse)
+                    .map(|neighbor| from.rotation_to(neighbor.rotation, 32))
+                    .max()
+            };
+            assert_eq!(widest_reverse(&cache), Some(2));
+            assert_eq!(widest_reverse(&widened), Some(3));
+            // Everything cached before is still there.
+            let before = cache.get(rotation).unwrap();
+            let after = widened.get(rotation).unwrap();
+            assert_eq!(after[..before.len()], before[..]);
+            assert_eq!(after.len(), before.len() + 2);
+        }
+        // Straight moves the rules dropped stay dropped.
+        let cache = NeighborCache::new_precomputed(16, 1);
+        let widened = cache.with_reverse_arc(3);
+        assert_eq!(
+            widened.get(1).unwrap().len(),
+            cache.get(1).unwrap().len() + 2
+        );
+        // Narrower arcs add nothing.
+        assert_eq!(cache.with_reverse_arc(1).get(3), cache.get(3));
+    }
+
+    #[test]
+    fn test_rotation_arithmetic() {
+        for max_increments in [4, 8, 16, 64] {
+            for rotation in [
+                i16::MIN,
+                -1000,
+                -65,
+                -17,
+                -8,
+                -1,
+                0,
+                1,
+                7,
+                8,
+                63,
+                1000,
+                i16::MAX,
+            ] {
+                let clamped = Cell::clamp_rotation(rotation, max_increments);
+                assert!((0..max_increments).contains(&clamped));
+                assert_eq!(
+                    clamped as i32,
+                    (rotation as i32).rem_euclid(max_increments as i32)
+                );
+                let opposite = Cell::opposite_rotation(clamped, max_increments);
+                assert_eq!(
+                    Cell::new(clamped, IVec2::ZERO).rotation_to(opposite, max_increments),
+                    max_increments / 2
+                );
+            }
+        }
+        assert_eq!(Cell::clamp_rotation(-1, 8), 7);
+        assert_eq!(Cell::clamp_rotation(-9, 8), 7);
+        assert_eq!(Cell::clamp_rotation(-32768, 8), 0);
+        assert_eq!(Cell::clamp_rotation(17, 8), 1);
+        assert_eq!(Cell::opposite_rotation(i16::MAX, 8), 3);
+        assert_eq!(Cell::opposite_rotation(i16::MAX, 12), 1);
+        assert_eq!(Cell::opposite_rotation(i16::MIN, 8), 4);
+        assert_eq!(Cell::opposite_rotation(-1, 8), 3);
+
+        let pose = Cell::new(1, IVec2::ZERO);
+        assert_eq!(pose.rotation_to(7, 8), 2);
+        assert_eq!(pose.rotation_to(-3, 8), 4);
+        assert_eq!(pose.rotation_to(i16::MAX, 8), 2);
+        assert_eq!(Cell::new(i16::MIN, IVec2::ZERO).rotation_to(i16::MAX, 8), 1);
+        assert_eq!(Cell::new(-1004, IVec2::ZERO).rotation_to(1000, 16), 4);
+
+        assert_eq!(Cell::heading_to_increment(-PI / 2.0, 8), 6);
+        assert_eq!(Cell::heading_to_increment(5.0 * PI, 8), 4);
+    }
+
+    #[test]
+    fn test_filtered_directions() {
+        let max_increments = 8;
+        let cache = NeighborCache::new_precomputed(max_increments, 1);
+        let from = Cell::new(0, IVec2::ZERO);
+        let is_reverse = |neighbor: &CachedMove| {
+            Cell::new(neighbor.rotation, neighbor.offset)
+                .is_reverse_to(&from, max_increments as i16)
+        };
+
+        let forward = cache.filtered(true, false);
+        assert!(!forward.get(0).unwrap().is_empty());
+        assert!(forward.get(0).unwrap().iter().all(|n| !is_reverse(n)));
+
+        let reverse = cache.filtered(false, true);
+        assert!(!reverse.get(0).unwrap().is_empty());
+        assert!(reverse.get(0).unwrap().iter().all(is_reverse));
+    }
+
+    #[test]
+    fn test_for_each_neighbor() {
+        let cache = Rc::new(RefCell::new(NeighborCache::new_precomputed(8, 1)));
+        let from = Cell::new(2, IVec2::new(5, 5)).with_layer(1);
+        let mut neighbors = Vec::new();
+        from.for_each_neighbor(&cache, |cell| neighbors.push(cell));
+        let cached = cache.borrow().get(2).unwrap().clone();
+        assert_eq!(neighbors.len(), cached.len());
+        for (cell, neighbor) in neighbors.iter().zip(cached) {
+            assert_eq!(cell.position, from.position + neighbor.offset);
+            assert_eq!(cell.rotation, neighbor.rotation);
+            assert_eq!(cell.layer, 1);
+        }
+    }
+
+    #[test]
+    fn test_heading_cache() {
+        let neighbors = NeighborCache::new_precomputed(8, 1);
+        let headings = HeadingCache::new(&neighbors, 1, 8, None);
+        assert_eq!(headings.get(0, 0), 0);
+        assert_eq!(headings.get(3, 2), 1000);
+        // Turning around takes four one-increment moves either way.
+        assert_eq!(headings.get(0, 4), 4000);
+        assert_eq!(headings.get(7, 1), 2000);
+
+        let turning = HeadingCache::new(&neighbors, 1, 8, Some(500));
+        assert_eq!(turning.get(0, 4), 2000);
+        assert_eq!(HeadingCache::uniform(8, 3).get(1, 7), 6);
+
+        // Without an arc only turning in place changes heading.
+        let straight = NeighborCache::new_precomputed_with_reverse_arc(8, 0, 1);
+        let stuck = HeadingCache::new(&straight, 0, 8, None);
+        assert_eq!(stuck.get(0, 0), 0);
+        assert_eq!(stuck.get(0, 2), u32::MAX);
+        let spinning = HeadingCache::new(&straight, 0, 8, Some(500));
+        assert_eq!(spinning.get(0, 2), 1000);
+    }
+
+    #[test]
+    fn test_cost_cache() {
+        let mut neighbors = NeighborCache::new_precomputed(16, 2);
+        neighbors.add_straight_primitives(3);
+        let costs = CostCache::new(&neighbors, 2, 16, 5);
+        for rotation in 0..16 {
+            let from = Cell::new(rotation, IVec2::new(4, -2));
+            let moves = neighbors.get(rotation).unwrap();
+            let cached = costs.get(rotation).unwrap();
+            assert_eq!(moves.len(), cached.len());
+            for (neighbor, cost) in moves.iter().zip(cached) {
+                let to = Cell::new(neighbor.rotation, from.position + neighbor.offset);
+                assert_eq!(
+                    *cost,
+                    to.cost_with_reverse_factor(Some(from.clone()), 2, 16, 5)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_sideways_is_not_reverse() {
+        for max_increments in [4, 8, 16, 32] {
+            let quarter = max_increments / 4;
+            let step = Cell::new(0, IVec2::new(1, 0));
+            for from in [quarter, quarter * 3] {
+                let from = Cell::new(from, IVec2::ZERO);
+                assert!(!step.is_reverse_to(&from, max_increments));
+            }
+            assert!(!step.is_reverse_to(&Cell::new(0, IVec2::ZERO), max_increments));
+            let back = Cell::new(0, IVec2::new(-1, 0));
+            assert!(back.is_reverse_to(&Cell::new(0, IVec2::ZERO), max_increments));
+        }
+        // Cached flags agree with the check, whatever the rules kept.
+        let cache = NeighborCache::new_precomputed(8, 2);
+        for rotation in 0..8 {
+            let from = Cell::new(rotation, IVec2::ZERO);
+            for neighbor in cache.get(rotation).unwrap() {
+                let to = Cell::new(neighbor.rotation, neighbor.offset);
+                assert_eq!(neighbor.reverse, to.is_reverse_to(&from, 8));
+            }
+        }
+    }
+
+    #[test]
+    fn test_reverse_arc() {
+        let max_increments = 16;
+        let from = Cell::new(0, IVec2::ZERO);
+        let reverse_turns = |cache: &NeighborCache| {
+            let mut turns: Vec<i16> = cache
+                .get(0)
+                .unwrap()
+                .iter()
+                .filter(|neighbor| neighbor.reverse)
+                .map(|neighbor| from.rotation_to(neighbor.rotation, max_increments))
+                .collect();
+            turns.sort();
+            turns
+        };
+
+        let narrow = NeighborCache::new_precomputed_with_reverse_arc(max_increments as u16, 2, 1);
+        assert_eq!(reverse_turns(&narrow), vec![0, 1, 1]);
+        assert_eq!(narrow.get(0).unwrap().len(), 5 + 3);
+
+        // The default keeps twice the forward arc.
+        let default = NeighborCache::new_precomputed(max_increments as u16, 1);
+        assert_eq!(reverse_turns(&default), vec![0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_straight_primitives() {
+        let max_increments = 8;
+        let mut cache = NeighborCache::new_precomputed(max_increments, 1);
+        let before = cache.get(0).unwrap().len();
+        cache.add_straight_primitives(3);
+        let neighbors = cache.get(0).unwrap();
+        // one forward and one reverse straight move, two extra lengths each
+        assert_eq!(neighbors.len(), before + 4);
+        let has = |offset: IVec2, reverse: bool| {
+            neighbors
+                .iter()
+                .any(|n| (n.offset, n.rotation, n.reverse) == (offset, 0, reverse))
+        };
+        assert!(has(IVec2::new(3, 0), false));
+        assert!(has(IVec2::new(-3, 0), true));
+
+        let start = Cell::new(0, IVec2::new(0, 0));
+        let one = Cell::new(0, IVec2::new(1, 0));
+        let three = Cell::new(0, IVec2::new(3, 0));
+        assert_eq!(start.steps_to(&three), 3);
+        assert_eq!(
+            three.cost(Some(start.clone()), 1, max_increments),
+            one.cost(Some(start), 1, max_increments) * 3
+        );
+    }
+
     #[test]
     fn test_is_opposite() {
         let max_increments = 8;
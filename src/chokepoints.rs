@@ -0,0 +1,145 @@
+use notan::math::{IVec2, Vec2};
+
+use crate::grid::Grid;
+
+/// A connected stretch of passage too narrow for some vehicle to pass
+/// another in, or to drive comfortably, like a doorway or a gap between
+/// racks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chokepoint {
+    /// `choke_0`, `choke_1`, ... in the order found, top row first.
+    pub name: String,
+    pub cells: Vec<IVec2>,
+    /// Width of the narrowest cross-section, in cells.
+    pub min_clearance: f32,
+}
+
+impl Chokepoint {
+    pub fn contains(&self, cell: IVec2) -> bool {
+        self.cells.contains(&cell)
+    }
+
+    /// Mean position of the cells, in cells.
+    pub fn center(&self) -> Vec2 {
+        let sum: Vec2 = self.cells.iter().map(|cell| cell.as_vec2()).sum();
+        sum / self.cells.len().max(1) as f32
+    }
+}
+
+/// Free cells in a straight line through `cell` along `step`, counting the
+/// cell itself.
+fn free_run(grid: &Grid, cell: IVec2, step: IVec2) -> i32 {
+    let mut run = 1;
+    for direction in [step, -step] {
+        let mut next = cell + direction;
+        while grid.in_bounds(next.x, next.y) && !grid.is_cell_blocked(next.x, next.y) {
+            run += 1;
+            next += direction;
+        }
+    }
+    run
+}
+
+/// Free width of the passage at a cell, in cells: the shorter of its free
+/// run along the row and along the column. Passages running diagonally
+/// read up to √2 wider than they are. Zero for blocked cells.
+pub fn passage_width(grid: &Grid, x: i32, y: i32) -> f32 {
+    if !grid.in_bounds(x, y) || grid.is_cell_blocked(x, y) {
+        return 0.0;
+    }
+    let cell = IVec2::new(x, y);
+    free_run(grid, cell, IVec2::X).min(free_run(grid, cell, IVec2::Y)) as f32
+}
+
+/// Finds every passage narrower than `swept_width` cells, such as
+/// `agent.size.y` for a vehicle driving straight, grouping touching narrow
+/// cells into one chokepoint each. Planners can keep out of them with soft
+/// costs, or reserve a whole chokepoint at once so only one vehicle is
+/// ever inside.
+pub fn find_chokepoints(grid: &Grid, swept_width: f32) -> Vec<Chokepoint> {
+    let (width, height) = grid.size;
+    let widths: Vec<f32> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| passage_width(grid, x, y))
+        .collect();
+    let is_narrow = |cell: IVec2| {
+        grid.in_bounds(cell.x, cell.y) && {
+            let width = widths[grid.index(cell.x, cell.y)];
+            width > 0.0 && width < swept_width
+        }
+    };
+
+    let mut seen = vec![false; widths.len()];
+    let mut chokepoints = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let seed = IVec2::new(x, y);
+            if seen[grid.index(x, y)] || !is_narrow(seed) {
+                continue;
+            }
+            seen[grid.index(x, y)] = true;
+            let mut cells = Vec::new();
+            let mut open = vec![seed];
+            while let Some(cell) = open.pop() {
+                cells.push(cell);
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let next = cell + IVec2::new(dx, dy);
+                        if is_narrow(next) && !seen[grid.index(next.x, next.y)] {
+                            seen[grid.index(next.x, next.y)] = true;
+                            open.push(next);
+                        }
+                    }
+                }
+            }
+            cells.sort_by_key(|cell| (cell.y, cell.x));
+            let min_clearance = cells
+                .iter()
+                .map(|cell| widths[grid.index(cell.x, cell.y)])
+                .fold(f32::INFINITY, f32::min);
+            chokepoints.push(Chokepoint {
+                name: format!("choke_{}", chokepoints.len()),
+                cells,
+                min_clearance,
+            });
+        }
+    }
+    chokepoints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_doorway_and_aisle() {
+        // Two rooms split by a wall at x = 10 with a two-cell doorway, and
+        // a three-cell aisle along the bottom of the right room, entered
+        // through a two-cell gap at its end.
+        let mut grid = Grid::new(1.0, 20, 16);
+        for y in 0..16 {
+            grid.set_cell(10, y, !(6..8).contains(&y));
+        }
+        for x in 11..20 {
+            grid.set_cell(x, 12, x < 18);
+        }
+        assert_eq!(passage_width(&grid, 10, 6), 2.0);
+        assert_eq!(passage_width(&grid, 10, 0), 0.0);
+        assert_eq!(passage_width(&grid, 3, 3), 10.0);
+
+        let chokepoints = find_chokepoints(&grid, 4.0);
+        assert_eq!(chokepoints.len(), 2);
+        let doorway = &chokepoints[0];
+        assert_eq!(doorway.name, "choke_0");
+        assert_eq!(doorway.cells, vec![IVec2::new(10, 6), IVec2::new(10, 7)]);
+        assert_eq!(doorway.min_clearance, 2.0);
+        assert_eq!(doorway.center(), Vec2::new(10.0, 6.5));
+        let aisle = &chokepoints[1];
+        assert!(aisle.contains(IVec2::new(14, 14)) && !aisle.contains(IVec2::new(14, 11)));
+        assert!(aisle.contains(IVec2::new(18, 12)));
+        assert_eq!(aisle.min_clearance, 2.0);
+
+        // A narrow vehicle fits through both.
+        assert!(find_chokepoints(&grid, 2.0).is_empty());
+    }
+}
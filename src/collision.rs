@@ -0,0 +1,109 @@
+use notan::math::IVec2;
+
+/// A footprint as one bitmask per row, so it can be tested against the grid
+/// a row at a time instead of a cell at a time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RowMasks {
+    /// Leftmost cell of each row, relative to the pose.
+    pub origins: Vec<IVec2>,
+    /// Bit `i` of a mask covers the cell `i` to the right of its origin.
+    pub masks: Vec<u64>,
+}
+
+impl RowMasks {
+    /// Packs `cells` into row masks, or `None` if a row spans more than 64
+    /// cells.
+    pub fn from_cells(cells: &[IVec2]) -> Option<Self> {
+        let (Some(min_y), Some(max_y)) = (
+            cells.iter().map(|cell| cell.y).min(),
+            cells.iter().map(|cell| cell.y).max(),
+        ) else {
+            return Some(Self {
+                origins: Vec::new(),
+                masks: Vec::new(),
+            });
+        };
+        let mut rows = Self {
+            origins: Vec::new(),
+            masks: Vec::new(),
+        };
+        for y in min_y..=max_y {
+            let row = cells.iter().filter(|cell| cell.y == y);
+            let Some(min_x) = row.clone().map(|cell| cell.x).min() else {
+                continue;
+            };
+            let mut mask = 0u64;
+            for cell in row {
+                let bit = cell.x - min_x;
+                if bit >= 64 {
+                    return None;
+                }
+                mask |= 1 << bit;
+            }
+            rows.origins.push(IVec2::new(min_x, y));
+            rows.masks.push(mask);
+        }
+        Some(rows)
+    }
+}
+
+/// Whether any `masks[i] & words[i]` is non-zero. Uses AVX2 when the `simd`
+/// feature is on and the CPU supports it.
+pub fn any_overlap(masks: &[u64], words: &[u64]) -> bool {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 support was just checked.
+        return unsafe { any_overlap_avx2(masks, words) };
+    }
+    any_overlap_scalar(masks, words)
+}
+
+/// The portable loop behind [`any_overlap`], public so benchmarks can
+/// compare the two.
+pub fn any_overlap_scalar(masks: &[u64], words: &[u64]) -> bool {
+    masks.iter().zip(words).any(|(mask, word)| mask & word != 0)
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn any_overlap_avx2(masks: &[u64], words: &[u64]) -> bool {
+    use std::arch::x86_64::{__m256i, _mm256_loadu_si256, _mm256_testz_si256};
+
+    let len = masks.len().min(words.len());
+    let chunks = len / 4;
+    for chunk in 0..chunks {
+        let a = _mm256_loadu_si256(masks.as_ptr().add(chunk * 4) as *const __m256i);
+        let b = _mm256_loadu_si256(words.as_ptr().add(chunk * 4) as *const __m256i);
+        if _mm256_testz_si256(a, b) == 0 {
+            return true;
+        }
+    }
+    any_overlap_scalar(&masks[chunks * 4..len], &words[chunks * 4..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_masks() {
+        let cells = [
+            IVec2::new(-1, 0),
+            IVec2::new(0, 0),
+            IVec2::new(1, 0),
+            IVec2::new(1, 2),
+        ];
+        let rows = RowMasks::from_cells(&cells).unwrap();
+        assert_eq!(rows.origins, vec![IVec2::new(-1, 0), IVec2::new(1, 2)]);
+        assert_eq!(rows.masks, vec![0b111, 0b1]);
+        assert!(RowMasks::from_cells(&[IVec2::new(0, 0), IVec2::new(64, 0)]).is_none());
+    }
+
+    #[test]
+    fn test_any_overlap() {
+        let masks = [0b1010, 0, 0, 0, 0, 0b1];
+        assert!(!any_overlap(&masks, &[0b0101, 1, 1, 1, 1, 0b10]));
+        assert!(any_overlap(&masks, &[0, 0, 0, 0, 0, 0b11]));
+        assert!(any_overlap(&masks, &[0b1000, 0, 0, 0, 0, 0]));
+    }
+}
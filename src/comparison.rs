@@ -0,0 +1,134 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlanResult, PlannerConfig};
+
+/// One planner configuration run on a query.
+#[derive(Clone, Debug)]
+pub struct Trial {
+    pub label: String,
+    pub result: Option<PlanResult>,
+    /// Poses expanded, whether or not a path was found.
+    pub expanded: usize,
+    pub elapsed: Duration,
+}
+
+/// Runs every labelled configuration on the same query, one after the
+/// other, for comparing them side by side.
+pub fn compare(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: &Cell,
+    goal: &Goal,
+    variants: &[(&str, PlannerConfig)],
+) -> Vec<Trial> {
+    variants
+        .iter()
+        .map(|(label, config)| {
+            let started = Instant::now();
+            let result = planner::search(
+                grid,
+                agent,
+                neighbor_cache,
+                start.clone(),
+                goal.clone(),
+                config,
+                |_, _| 0,
+            );
+            let elapsed = started.elapsed();
+            let (result, expanded) = match result {
+                Ok(result) => {
+                    let expanded = result.expanded;
+                    (Some(result), expanded)
+                }
+                Err((_, explored)) => (None, explored.len()),
+            };
+            Trial {
+                label: label.to_string(),
+                result,
+                expanded,
+                elapsed,
+            }
+        })
+        .collect()
+}
+
+/// The trials as a plain text table of cost, time and expansions, one row
+/// per trial.
+pub fn table(trials: &[Trial]) -> String {
+    let width = trials
+        .iter()
+        .map(|trial| trial.label.len())
+        .max()
+        .unwrap_or(0)
+        .max("config".len());
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:width$}  {:>10}  {:>10}  {:>9}",
+        "config", "cost", "time", "expanded"
+    );
+    for trial in trials {
+        let cost = match &trial.result {
+            Some(result) => result.cost.to_string(),
+            None => "no path".to_string(),
+        };
+        let time = format!("{:.2?}", trial.elapsed);
+        let _ = writeln!(
+            out,
+            "{:width$}  {:>10}  {:>10}  {:>9}",
+            trial.label, cost, time, trial.expanded
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+    use crate::test_support::{neighbor_cache, point_agent, Fixture};
+
+    #[test]
+    fn test_compare_configs() {
+        let mut grid = Grid::new(1.0, 16, 16);
+        for y in 0..12 {
+            grid.set_cell(8, y, true);
+        }
+        let agent = point_agent(IVec2::new(2, 2));
+        let cache = neighbor_cache();
+        let greedy = Fixture::new(16, 16).config();
+        let mut admissible = greedy.clone();
+        admissible.heuristic_weight = Some(1.0);
+        let start = Cell::new(0, agent.position);
+        let goal = Goal::Cell(IVec2::new(13, 2));
+
+        let trials = compare(
+            &grid,
+            &agent,
+            &cache,
+            &start,
+            &goal,
+            &[("greedy", greedy), ("admissible", admissible)],
+        );
+        let [greedy, admissible] = &trials[..] else {
+            panic!("one trial per config");
+        };
+        let optimal = admissible.result.as_ref().unwrap().cost;
+        assert!(greedy.result.as_ref().unwrap().cost >= optimal);
+        assert!(admissible.expanded > 0);
+
+        let table = table(&trials);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("config"));
+        assert!(lines[2].starts_with("admissible"));
+        assert!(lines[2].contains(&optimal.to_string()));
+    }
+}
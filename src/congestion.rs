@@ -0,0 +1,96 @@
+use notan::math::IVec2;
+
+use crate::cell::Cell;
+
+/// Decaying per-cell traffic counts from executed paths, charged back to
+/// the planner so repeated trips spread over alternate routes.
+#[derive(Clone, Debug)]
+pub struct CongestionMap {
+    pub size: (i32, i32),
+    values: Vec<f32>,
+    /// Factor every count is multiplied by on [`CongestionMap::decay`].
+    pub decay: f32,
+    /// Extra cost of entering a cell per unit of traffic.
+    pub weight: u32,
+}
+
+impl CongestionMap {
+    pub fn new(size: (i32, i32), decay: f32, weight: u32) -> Self {
+        Self {
+            size,
+            values: vec![0.0; (size.0 * size.1) as usize],
+            decay,
+            weight,
+        }
+    }
+
+    fn index(&self, position: IVec2) -> Option<usize> {
+        let in_bounds = position.x >= 0
+            && position.x < self.size.0
+            && position.y >= 0
+            && position.y < self.size.1;
+        in_bounds.then(|| (position.y * self.size.0 + position.x) as usize)
+    }
+
+    /// Counts one traversal of every distinct cell along `path`.
+    pub fn record_path(&mut self, path: &[Cell]) {
+        let mut previous = None;
+        for cell in path {
+            if previous == Some(cell.position) {
+                continue;
+            }
+            previous = Some(cell.position);
+            if let Some(index) = self.index(cell.position) {
+                self.values[index] += 1.0;
+            }
+        }
+    }
+
+    pub fn decay(&mut self) {
+        for value in &mut self.values {
+            *value *= self.decay;
+        }
+    }
+
+    pub fn value_at(&self, position: IVec2) -> f32 {
+        self.index(position).map_or(0.0, |index| self.values[index])
+    }
+
+    pub fn cost_at(&self, position: IVec2) -> u32 {
+        (self.value_at(position) * self.weight as f32) as u32
+    }
+
+    /// Raw counts in row-major order, e.g. for heatmap rendering.
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn max_value(&self) -> f32 {
+        self.values.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_decay() {
+        let mut map = CongestionMap::new((4, 4), 0.5, 1000);
+        let path = vec![
+            Cell::new(0, IVec2::new(0, 0)),
+            Cell::new(1, IVec2::new(0, 0)),
+            Cell::new(1, IVec2::new(1, 1)),
+        ];
+        map.record_path(&path);
+        map.record_path(&path);
+        assert_eq!(map.value_at(IVec2::new(0, 0)), 2.0);
+        assert_eq!(map.cost_at(IVec2::new(1, 1)), 2000);
+        assert_eq!(map.value_at(IVec2::new(5, 5)), 0.0);
+
+        map.decay();
+        assert_eq!(map.value_at(IVec2::new(1, 1)), 1.0);
+        assert_eq!(map.max_value(), 1.0);
+        assert_eq!(map.values().len(), 16);
+    }
+}
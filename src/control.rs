@@ -0,0 +1,549 @@
+use std::f32::consts::PI;
+
+use notan::math::Vec2;
+
+use crate::cell::Cell;
+use crate::maneuver;
+
+/// Kinematic bicycle model of a car-like vehicle, in cells and seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BicycleModel {
+    /// Distance between the axles.
+    pub wheelbase: f32,
+    /// Largest front wheel angle either way, in radians.
+    pub max_steering: f32,
+}
+
+/// Where the vehicle is, measured at the rear axle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VehicleState {
+    pub position: Vec2,
+    /// In radians.
+    pub heading: f32,
+    pub speed: f32,
+    /// Front wheel angle the vehicle was last steered to.
+    pub steering: f32,
+}
+
+impl BicycleModel {
+    /// The state after driving `dt` seconds with the wheels at `steering`,
+    /// clamped to the model's limit.
+    pub fn step(&self, state: &VehicleState, steering: f32, dt: f32) -> VehicleState {
+        let steering = steering.clamp(-self.max_steering, self.max_steering);
+        VehicleState {
+            position: state.position + Vec2::from_angle(state.heading) * state.speed * dt,
+            heading: state.heading + state.speed / self.wheelbase * steering.tan() * dt,
+            speed: state.speed,
+            steering,
+        }
+    }
+
+    /// Curvature of the tightest turn, one over its radius.
+    pub fn max_curvature(&self) -> f32 {
+        self.max_steering.tan() / self.wheelbase
+    }
+
+    pub fn front_axle(&self, state: &VehicleState) -> Vec2 {
+        state.position + Vec2::from_angle(state.heading) * self.wheelbase
+    }
+}
+
+/// Path tracking control laws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Controller {
+    /// Steers the rear axle onto the arc through the path point
+    /// `lookahead` cells ahead.
+    PurePursuit { lookahead: f32 },
+    /// Steers the front axle against its heading error plus its offset
+    /// from the path, weighted by `gain` and eased off with speed.
+    Stanley { gain: f32 },
+}
+
+fn angle_of(vector: Vec2) -> f32 {
+    vector.y.atan2(vector.x)
+}
+
+/// Wrap an angle to `-PI..=PI`.
+fn wrap_angle(angle: f32) -> f32 {
+    Vec2::X.angle_between(Vec2::from_angle(angle))
+}
+
+/// Index of the path point nearest `position`, searching a few cells of
+/// path on from `from` so loops in the path aren't skipped ahead.
+pub fn nearest_index(path: &[Vec2], position: Vec2, from: usize) -> usize {
+    const WINDOW: f32 = 4.0;
+    let mut best = from.min(path.len().saturating_sub(1));
+    let mut travelled = 0.0;
+    for index in from..path.len() {
+        if index > from {
+            travelled += path[index].distance(path[index - 1]);
+            if travelled > WINDOW {
+                break;
+            }
+        }
+        if path[index].distance_squared(position) < path[best].distance_squared(position) {
+            best = index;
+        }
+    }
+    best
+}
+
+/// Signed distance of `position` from the path near point `index`,
+/// positive on the side headings turn towards as they grow, which is the
+/// right of the direction of travel with y pointing down.
+pub fn cross_track_error(path: &[Vec2], index: usize, position: Vec2) -> f32 {
+    let (a, b) = match index {
+        _ if path.len() < 2 => return path.first().map_or(0.0, |p| p.distance(position)),
+        0 => (path[0], path[1]),
+        _ => (path[index - 1], path[index]),
+    };
+    let direction = (b - a).normalize_or_zero();
+    direction.perp_dot(position - a)
+}
+
+impl Controller {
+    /// Front wheel angle to follow `path` from `state`, with `progress` the
+    /// index of the path point nearest the vehicle.
+    pub fn steering(
+        &self,
+        model: &BicycleModel,
+        state: &VehicleState,
+        path: &[Vec2],
+        progress: usize,
+    ) -> f32 {
+        if path.is_empty() {
+            return 0.0;
+        }
+        match *self {
+            Controller::PurePursuit { lookahead } => {
+                let target = path[progress..]
+                    .iter()
+                    .find(|point| point.distance(state.position) >= lookahead)
+                    .or(path.last())
+                    .copied()
+                    .unwrap_or(state.position);
+                let to_target = target - state.position;
+                let alpha = Vec2::from_angle(state.heading).angle_between(to_target);
+                let distance = to_target.length().max(f32::EPSILON);
+                (2.0 * model.wheelbase * alpha.sin() / distance).atan()
+            }
+            Controller::Stanley { gain } => {
+                let front = model.front_axle(state);
+                let index = nearest_index(path, front, progress);
+                let next = (index + 1).min(path.len() - 1);
+                let tangent = if next > index {
+                    path[next] - path[index]
+                } else if index > 0 {
+                    path[index] - path[index - 1]
+                } else {
+                    Vec2::from_angle(state.heading)
+                };
+                let heading_error = wrap_angle(angle_of(tangent) - state.heading);
+                // A positive offset needs steering back the other way.
+                let offset = cross_track_error(path, next, front);
+                heading_error - (gain * offset / (state.speed.abs() + 1.0)).atan()
+            }
+        }
+    }
+}
+
+/// How closely a [`Tracker`] followed its path, for judging whether the
+/// planner's output can be driven.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackingReport {
+    /// Cross-track error of every traced state, with its time.
+    pub errors: Vec<(f32, f32)>,
+    /// Largest cross-track error either side, in cells.
+    pub max_error: f32,
+    pub mean_error: f32,
+    /// Tightest curvature steered, in 1 / cells.
+    pub max_curvature: f32,
+    /// Switches between driving forward and in reverse in the planned path
+    /// and in the trace.
+    pub planned_switches: usize,
+    pub executed_switches: usize,
+    pub duration: f32,
+    pub arrived: bool,
+}
+
+/// Stretches of `path` driven one way, and whether that way is reverse.
+/// Consecutive stretches share the pose where the vehicle switches, and
+/// point turns are left out.
+pub fn direction_runs(path: &[Cell], max_increments: u16) -> Vec<(&[Cell], bool)> {
+    let mut runs: Vec<(usize, usize, bool)> = Vec::new();
+    for maneuver in maneuver::segment_path(path, max_increments)
+        .iter()
+        .filter(|maneuver| maneuver.length > 0.0)
+    {
+        match runs.last_mut() {
+            Some(run) if run.2 == maneuver.reverse => run.1 = maneuver.end,
+            _ => runs.push((maneuver.start, maneuver.end, maneuver.reverse)),
+        }
+    }
+    runs.into_iter()
+        .map(|(start, end, reverse)| (&path[start..=end], reverse))
+        .collect()
+}
+
+/// Switches between forward and reverse in `path`, ignoring point turns.
+pub fn direction_switches(path: &[Cell], max_increments: u16) -> usize {
+    direction_runs(path, max_increments).len().saturating_sub(1)
+}
+
+/// Drives a simulated vehicle along a smoothed path, recording every
+/// state, to check that the planned path can actually be followed.
+#[derive(Clone, Debug)]
+pub struct Tracker {
+    pub model: BicycleModel,
+    pub controller: Controller,
+    /// Path points in cells, like [`crate::smoothing::smooth_path`] with a
+    /// cell size of 1.
+    pub path: Vec<Vec2>,
+    /// Where each stretch of `path` driven one way ends, exclusive, and
+    /// whether it's driven in reverse.
+    pub segments: Vec<(usize, bool)>,
+    /// Index into `segments` of the stretch being driven.
+    pub segment: usize,
+    pub state: VehicleState,
+    /// Index of the path point nearest the vehicle.
+    pub progress: usize,
+    /// Every state so far with its time, starting with the initial one.
+    pub trace: Vec<(f32, VehicleState)>,
+    /// Distance from the end of the path counted as arrived.
+    pub tolerance: f32,
+}
+
+impl Tracker {
+    /// Starts at the first path point facing the second, at `speed`.
+    pub fn new(model: BicycleModel, controller: Controller, path: Vec<Vec2>, speed: f32) -> Self {
+        Self::with_segments(model, controller, vec![(path, false)], speed)
+    }
+
+    /// Drives each of `segments` in turn, forward or in reverse as marked,
+    /// like the stretches [`direction_runs`] splits a planned path into.
+    /// Starts at the first point with the first stretch ahead, or behind in
+    /// reverse, at `speed`.
+    pub fn with_segments(
+        model: BicycleModel,
+        controller: Controller,
+        segments: Vec<(Vec<Vec2>, bool)>,
+        speed: f32,
+    ) -> Self {
+        let mut path = Vec::new();
+        let mut ends = Vec::new();
+        for (points, reverse) in segments {
+            path.extend(points);
+            ends.push((path.len(), reverse));
+        }
+        let reverse = ends.first().is_some_and(|(_, reverse)| *reverse);
+        let position = path.first().copied().unwrap_or_default();
+        let travel = path
+            .iter()
+            .find(|point| **point != position)
+            .map_or(0.0, |point| angle_of(*point - position));
+        let state = VehicleState {
+            position,
+            heading: if reverse { travel + PI } else { travel },
+            speed: if reverse { -speed.abs() } else { speed.abs() },
+            steering: 0.0,
+        };
+        Self {
+            model,
+            controller,
+            path,
+            segments: ends,
+            segment: 0,
+            state,
+            progress: 0,
+            trace: vec![(0.0, state)],
+            tolerance: 0.5,
+        }
+    }
+
+    /// Range of `path` in the stretch being driven.
+    fn segment_range(&self) -> (usize, usize) {
+        let start = match self.segment {
+            0 => 0,
+            segment => self.segments[segment - 1].0,
+        };
+        let end = self
+            .segments
+            .get(self.segment)
+            .map_or(self.path.len(), |(end, _)| *end);
+        (start, end)
+    }
+
+    /// Whether the vehicle is at the end of the stretch it's driving.
+    fn at_segment_end(&self) -> bool {
+        let (start, end) = self.segment_range();
+        end > start
+            && self.progress + 1 >= end
+            && self.state.position.distance(self.path[end - 1]) <= self.tolerance
+    }
+
+    /// Moves on to the next stretch, driving whichever way it goes.
+    fn next_segment(&mut self) {
+        self.segment += 1;
+        self.progress = self.segment_range().0;
+        if let Some((_, reverse)) = self.segments.get(self.segment) {
+            let speed = self.state.speed.abs();
+            self.state.speed = if *reverse { -speed } else { speed };
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.path.is_empty() || (self.segment + 1 >= self.segments.len() && self.at_segment_end())
+    }
+
+    /// Steering for `path`. In reverse the controller steers a mirrored
+    /// vehicle facing the way it travels, which turns the other way for
+    /// the same wheel angle.
+    fn steering(&self, path: &[Vec2], progress: usize) -> f32 {
+        if self.state.speed >= 0.0 {
+            return self
+                .controller
+                .steering(&self.model, &self.state, path, progress);
+        }
+        let mirrored = VehicleState {
+            heading: self.state.heading + PI,
+            speed: -self.state.speed,
+            ..self.state
+        };
+        -self
+            .controller
+            .steering(&self.model, &mirrored, path, progress)
+    }
+
+    /// Steers and drives for `dt` seconds. Returns whether the vehicle
+    /// still has path left to follow.
+    pub fn step(&mut self, dt: f32) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        if self.at_segment_end() {
+            self.next_segment();
+        }
+        let (start, end) = self.segment_range();
+        let steering = self.steering(&self.path[start..end], self.progress - start);
+        self.state = self.model.step(&self.state, steering, dt);
+        self.progress = start
+            + nearest_index(
+                &self.path[start..end],
+                self.state.position,
+                self.progress - start,
+            );
+        let time = self.trace.last().map_or(0.0, |(time, _)| *time) + dt;
+        self.trace.push((time, self.state));
+        // Past the end of a stretch, the vehicle only gets farther away.
+        if self.progress + 1 >= end
+            && self.state.position.distance(self.path[self.progress]) > self.tolerance
+        {
+            let target = self.path[self.progress];
+            let travel = Vec2::from_angle(self.state.heading) * self.state.speed.signum();
+            if travel.dot(target - self.state.position) < 0.0 {
+                if self.segment + 1 >= self.segments.len() {
+                    self.state.speed = 0.0;
+                    return false;
+                }
+                self.next_segment();
+            }
+        }
+        !self.is_finished()
+    }
+
+    /// Compares the trace so far with the path and with `planned`, the grid
+    /// path it was smoothed from.
+    pub fn report(&self, planned: &[Cell], max_increments: u16) -> TrackingReport {
+        let mut progress = 0;
+        let errors: Vec<(f32, f32)> = self
+            .trace
+            .iter()
+            .map(|(time, state)| {
+                progress = nearest_index(&self.path, state.position, progress);
+                (
+                    *time,
+                    cross_track_error(&self.path, progress, state.position),
+                )
+            })
+            .collect();
+        let max_error = errors
+            .iter()
+            .map(|(_, error)| error.abs())
+            .fold(0.0, f32::max);
+        let mean_error =
+            errors.iter().map(|(_, error)| error.abs()).sum::<f32>() / errors.len().max(1) as f32;
+        let max_curvature = self
+            .trace
+            .iter()
+            .map(|(_, state)| state.steering.tan().abs() / self.model.wheelbase)
+            .fold(0.0, f32::max);
+        let executed_switches = self
+            .trace
+            .windows(2)
+            .filter(|pair| pair[0].1.speed * pair[1].1.speed < 0.0)
+            .count();
+        TrackingReport {
+            errors,
+            max_error,
+            mean_error,
+            max_curvature,
+            planned_switches: direction_switches(planned, max_increments),
+            executed_switches,
+            duration: self.trace.last().map_or(0.0, |(time, _)| *time),
+            arrived: self.is_finished(),
+        }
+    }
+
+    /// Steps until arrival or `max_time` seconds, whichever comes first.
+    pub fn run(&mut self, dt: f32, max_time: f32) {
+        let steps = (max_time / dt).ceil() as usize;
+        for _ in 0..steps {
+            if !self.step(dt) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+
+    /// Straight along x, then a quarter turn of radius 8 down to y.
+    fn lane_change() -> Vec<Vec2> {
+        let mut path: Vec<Vec2> = (0..100).map(|i| Vec2::new(i as f32 * 0.1, 0.0)).collect();
+        for i in 0..=50 {
+            let angle = i as f32 / 50.0 * std::f32::consts::FRAC_PI_2;
+            path.push(Vec2::new(10.0 + 8.0 * angle.sin(), 8.0 - 8.0 * angle.cos()));
+        }
+        path.extend((1..60).map(|i| Vec2::new(18.0, 8.0 + i as f32 * 0.1)));
+        path
+    }
+
+    #[test]
+    fn test_controllers_track_turn() {
+        let model = BicycleModel {
+            wheelbase: 2.0,
+            max_steering: 0.6,
+        };
+        for controller in [
+            Controller::PurePursuit { lookahead: 2.0 },
+            Controller::Stanley { gain: 2.0 },
+        ] {
+            let mut tracker = Tracker::new(model, controller, lane_change(), 3.0);
+            tracker.run(0.02, 30.0);
+            assert!(tracker.is_finished(), "{controller:?} didn't arrive");
+            let worst = tracker
+                .trace
+                .iter()
+                .map(|(_, state)| {
+                    tracker
+                        .path
+                        .iter()
+                        .map(|point| point.distance(state.position))
+                        .fold(f32::MAX, f32::min)
+                })
+                .fold(0.0, f32::max);
+            assert!(worst < 0.6, "{controller:?} strayed {worst} cells");
+        }
+    }
+
+    /// Out four cells along x, then back two in reverse.
+    fn out_and_back() -> Vec<Cell> {
+        [0, 1, 2, 3, 4, 3, 2]
+            .iter()
+            .map(|x| Cell::new(0, IVec2::new(*x, 0)))
+            .collect()
+    }
+
+    /// Grid poses along the start of [`lane_change`], all forward.
+    fn lane_change_cells() -> Vec<Cell> {
+        (0..10).map(|x| Cell::new(0, IVec2::new(x, 0))).collect()
+    }
+
+    fn tracker_for(planned: &[Cell], controller: Controller) -> Tracker {
+        let model = BicycleModel {
+            wheelbase: 2.0,
+            max_steering: 0.6,
+        };
+        let segments = direction_runs(planned, 8)
+            .into_iter()
+            .map(|(cells, reverse)| {
+                let points = cells.iter().map(|cell| cell.position.as_vec2()).collect();
+                (points, reverse)
+            })
+            .collect();
+        Tracker::with_segments(model, controller, segments, 3.0)
+    }
+
+    #[test]
+    fn test_drives_reverse_segments() {
+        let planned = out_and_back();
+        let runs = direction_runs(&planned, 8);
+        assert_eq!(
+            runs.iter().map(|(_, reverse)| *reverse).collect::<Vec<_>>(),
+            [false, true]
+        );
+        assert_eq!((runs[0].0.len(), runs[1].0.len()), (5, 3));
+        for controller in [
+            Controller::PurePursuit { lookahead: 2.0 },
+            Controller::Stanley { gain: 2.0 },
+        ] {
+            let mut tracker = tracker_for(&planned, controller);
+            tracker.run(0.02, 30.0);
+            assert!(tracker.is_finished(), "{controller:?} didn't arrive");
+            assert!((tracker.state.position - Vec2::new(2.0, 0.0)).length() <= 0.5);
+            // Backed up facing the way it drove out.
+            assert!(tracker.state.speed < 0.0);
+            assert!(Vec2::from_angle(tracker.state.heading).x > 0.99);
+            let farthest = tracker
+                .trace
+                .iter()
+                .map(|(_, state)| state.position.x)
+                .fold(f32::MIN, f32::max);
+            assert!(farthest > 3.5);
+        }
+        // Nothing to follow steers straight.
+        let stanley = Controller::Stanley { gain: 2.0 };
+        let tracker = tracker_for(&planned, stanley);
+        assert_eq!(
+            stanley.steering(&tracker.model, &tracker.state, &[], 0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_tracking_report() {
+        let model = BicycleModel {
+            wheelbase: 2.0,
+            max_steering: 0.6,
+        };
+        let mut tracker = Tracker::new(
+            model,
+            Controller::PurePursuit { lookahead: 2.0 },
+            lane_change(),
+            3.0,
+        );
+        tracker.run(0.02, 30.0);
+        let report = tracker.report(&lane_change_cells(), 8);
+        assert!(report.arrived);
+        assert_eq!(report.errors.len(), tracker.trace.len());
+        assert!(report.max_error < 0.6 && report.mean_error <= report.max_error);
+        // The radius 8 turn needs about 1 / 8, within the steering limit.
+        assert!(report.max_curvature > 0.1);
+        assert!(report.max_curvature <= 0.6f32.tan() / 2.0 + f32::EPSILON);
+        assert_eq!(report.planned_switches, 0);
+        assert_eq!(report.executed_switches, 0);
+
+        // The trace switches into reverse wherever the plan does.
+        let planned = out_and_back();
+        let mut tracker = tracker_for(&planned, Controller::PurePursuit { lookahead: 2.0 });
+        tracker.run(0.02, 30.0);
+        let report = tracker.report(&planned, 8);
+        assert!(report.arrived);
+        assert_eq!(report.planned_switches, 1);
+        assert_eq!(report.executed_switches, report.planned_switches);
+    }
+}
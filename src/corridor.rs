@@ -0,0 +1,320 @@
+use std::collections::{HashSet, VecDeque};
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::Cell;
+use crate::grid::Grid;
+
+/// An axis-aligned block of free cells, bounds inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CorridorRect {
+    pub min: IVec2,
+    pub max: IVec2,
+}
+
+impl CorridorRect {
+    pub fn contains(&self, position: IVec2) -> bool {
+        position.x >= self.min.x
+            && position.x <= self.max.x
+            && position.y >= self.min.y
+            && position.y <= self.max.y
+    }
+
+    pub fn overlaps(&self, other: &CorridorRect) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    pub fn size(&self) -> IVec2 {
+        self.max - self.min + IVec2::ONE
+    }
+
+    /// The smallest rectangle covering both `a` and `b`.
+    fn spanning(a: IVec2, b: IVec2) -> Self {
+        CorridorRect {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    fn is_free(&self, grid: &Grid) -> bool {
+        (self.min.y..=self.max.y)
+            .all(|y| (self.min.x..=self.max.x).all(|x| !grid.is_cell_blocked(x, y)))
+    }
+}
+
+/// Grows the largest free rectangle around `seed`, extending each side in
+/// turn while the newly covered row or column is free and no side is more
+/// than `max_extent` cells away from the seed.
+pub fn grow_rect(grid: &Grid, seed: IVec2, max_extent: i32) -> Option<CorridorRect> {
+    grow_rect_around(grid, CorridorRect::spanning(seed, seed), max_extent)
+}
+
+/// Same as [`grow_rect`], starting from a whole rectangle.
+pub fn grow_rect_around(grid: &Grid, seed: CorridorRect, max_extent: i32) -> Option<CorridorRect> {
+    let mut rect = seed;
+    if !rect.is_free(grid) {
+        return None;
+    }
+
+    let directions = [
+        IVec2::new(-1, 0),
+        IVec2::new(1, 0),
+        IVec2::new(0, -1),
+        IVec2::new(0, 1),
+    ];
+    let mut grown = [true; 4];
+    while grown.iter().any(|grown| *grown) {
+        for (i, direction) in directions.iter().enumerate() {
+            if !grown[i] {
+                continue;
+            }
+            // Only the freshly covered strip needs checking.
+            let (strip, candidate) = if direction.x + direction.y < 0 {
+                let min = rect.min + *direction;
+                let strip = CorridorRect {
+                    min,
+                    max: if direction.x != 0 {
+                        IVec2::new(min.x, rect.max.y)
+                    } else {
+                        IVec2::new(rect.max.x, min.y)
+                    },
+                };
+                (strip, CorridorRect { min, ..rect })
+            } else {
+                let max = rect.max + *direction;
+                let strip = CorridorRect {
+                    min: if direction.x != 0 {
+                        IVec2::new(max.x, rect.min.y)
+                    } else {
+                        IVec2::new(rect.min.x, max.y)
+                    },
+                    max,
+                };
+                (strip, CorridorRect { max, ..rect })
+            };
+
+            let within_extent = (seed.min - candidate.min).max_element() <= max_extent
+                && (candidate.max - seed.max).max_element() <= max_extent;
+            if within_extent && strip.is_free(grid) {
+                rect = candidate;
+            } else {
+                grown[i] = false;
+            }
+        }
+    }
+
+    Some(rect)
+}
+
+/// The free rectangles a step from `from` to `to` passes through: the one
+/// spanning both, or for a step cutting past a blocked corner, one leg to
+/// the free corner and one from it. Empty if the step has no free way.
+fn step_legs(grid: &Grid, from: IVec2, to: IVec2) -> Vec<CorridorRect> {
+    let direct = CorridorRect::spanning(from, to);
+    if direct.is_free(grid) {
+        return vec![direct];
+    }
+    [IVec2::new(to.x, from.y), IVec2::new(from.x, to.y)]
+        .into_iter()
+        .map(|corner| {
+            [
+                CorridorRect::spanning(from, corner),
+                CorridorRect::spanning(corner, to),
+            ]
+        })
+        .find(|legs| legs.iter().all(|leg| leg.is_free(grid)))
+        .map(Vec::from)
+        .unwrap_or_default()
+}
+
+/// Computes a safety corridor along `path`: maximal free rectangles, each
+/// grown from the step into the first pose the last one misses, so
+/// consecutive rectangles overlap wherever the path steps through free
+/// cells. Poses on blocked cells are skipped.
+pub fn extract_corridor(grid: &Grid, path: &[Cell], max_extent: i32) -> Vec<CorridorRect> {
+    let mut corridor: Vec<CorridorRect> = Vec::new();
+    let mut previous: Option<IVec2> = None;
+    for position in path.iter().map(|cell| cell.position) {
+        let step = previous.replace(position);
+        // Poses already covered by the last rectangle don't add anything.
+        if corridor.last().is_some_and(|last| last.contains(position)) {
+            continue;
+        }
+        let legs = step.map_or_else(Vec::new, |from| step_legs(grid, from, position));
+        if legs.is_empty() {
+            corridor.extend(grow_rect(grid, position, max_extent));
+        }
+        for leg in legs {
+            let covered = corridor
+                .last()
+                .is_some_and(|last| last.contains(leg.min) && last.contains(leg.max));
+            if !covered {
+                corridor.extend(grow_rect_around(grid, leg, max_extent));
+            }
+        }
+    }
+    corridor
+}
+
+/// Every cell `agent`'s footprint covers driving along `path`, the lane
+/// another vehicle must leave clear for it to pass.
+pub fn swept_cells(agent: &Agent, path: &[Cell]) -> HashSet<IVec2> {
+    path.iter()
+        .flat_map(|pose| {
+            let mut cells = agent.footprint(pose.position, pose.rotation);
+            cells.push(pose.position);
+            cells
+        })
+        .collect()
+}
+
+/// The pose nearest to `from` where `agent` fits without covering any
+/// cell `passing` sweeps along `path`, for pulling over to let it by in a
+/// narrow aisle. Cells are searched outward from `from` through free cells,
+/// up to `max_distance` steps, and each cell tries the headings closest to
+/// `from`'s first, so the vehicle turns as little as it can.
+pub fn pull_over_pose(
+    grid: &Grid,
+    agent: &Agent,
+    from: &Cell,
+    passing: &Agent,
+    path: &[Cell],
+    max_distance: u32,
+) -> Option<Cell> {
+    let corridor = swept_cells(passing, path);
+    let max_increments = agent.max_increments as i16;
+    let fits = |position: IVec2| {
+        (0..max_increments)
+            .flat_map(|turn| [turn, -turn])
+            .map(|turn| Cell::new((from.rotation + turn).rem_euclid(max_increments), position))
+            .find(|pose| {
+                !grid.is_pose_blocked(agent, pose)
+                    && !corridor.contains(&position)
+                    && agent
+                        .footprint(position, pose.rotation)
+                        .iter()
+                        .all(|cell| !corridor.contains(cell))
+            })
+    };
+
+    let mut visited = HashSet::from([from.position]);
+    let mut open = VecDeque::from([(from.position, 0)]);
+    while let Some((position, distance)) = open.pop_front() {
+        if let Some(pose) = fits(position) {
+            return Some(pose);
+        }
+        if distance == max_distance {
+            continue;
+        }
+        for step in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let next = position + step;
+            if grid.in_bounds(next.x, next.y)
+                && !grid.is_cell_blocked(next.x, next.y)
+                && visited.insert(next)
+            {
+                open.push_back((next, distance + 1));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::Vec2;
+
+    use super::*;
+    use crate::test_support::point_agent;
+
+    #[test]
+    fn test_grow_rect_open() {
+        let grid = Grid::new(1.0, 20, 20);
+        let rect = grow_rect(&grid, IVec2::new(10, 10), 3).unwrap();
+        assert_eq!(rect.min, IVec2::new(7, 7));
+        assert_eq!(rect.max, IVec2::new(13, 13));
+    }
+
+    #[test]
+    fn test_grow_rect_walls() {
+        let mut grid = Grid::new(1.0, 20, 20);
+        for x in 0..20 {
+            grid.toggle_cell(x, 8);
+            grid.toggle_cell(x, 12);
+        }
+        let rect = grow_rect(&grid, IVec2::new(10, 10), 3).unwrap();
+        assert_eq!(rect.min, IVec2::new(7, 9));
+        assert_eq!(rect.max, IVec2::new(13, 11));
+
+        assert!(grow_rect(&grid, IVec2::new(10, 8), 3).is_none());
+    }
+
+    #[test]
+    fn test_extract_corridor_covers_path() {
+        let mut grid = Grid::new(1.0, 20, 20);
+        grid.toggle_cell(5, 6);
+        let path: Vec<Cell> = (0..10).map(|x| Cell::new(0, IVec2::new(x, 5))).collect();
+        let corridor = extract_corridor(&grid, &path, 2);
+        assert!(corridor.len() > 1);
+        for cell in &path {
+            assert!(corridor.iter().any(|rect| rect.contains(cell.position)));
+        }
+        for rect in &corridor {
+            assert!(!rect.contains(IVec2::new(5, 6)));
+        }
+    }
+
+    #[test]
+    fn test_corridor_is_connected() {
+        // A staircase whose diagonal steps each cut past a post.
+        let mut grid = Grid::new(1.0, 20, 20);
+        for k in 0..8 {
+            grid.set_cell(2 + 2 * k, 2 * k, true);
+        }
+        let mut path = vec![Cell::new(0, IVec2::new(0, 0))];
+        for step in 0..24 {
+            let last = path[path.len() - 1].position;
+            let offset = [IVec2::new(1, 0), IVec2::new(1, 1), IVec2::new(0, 1)][step % 3];
+            path.push(Cell::new(0, last + offset));
+        }
+        let corridor = extract_corridor(&grid, &path, 1);
+        assert!(corridor.len() > 2);
+        for cell in &path {
+            assert!(corridor.iter().any(|rect| rect.contains(cell.position)));
+        }
+        for pair in corridor.windows(2) {
+            assert!(
+                pair[0].overlaps(&pair[1]),
+                "{:?} and {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn test_pull_over_pose() {
+        // A five-cell-wide aisle with a notch in its top wall at x = 12.
+        let mut grid = Grid::new(1.0, 20, 10);
+        for x in 0..20 {
+            for y in [0, 1, 2, 8] {
+                grid.set_cell(x, y, !(x == 12 && (y == 1 || y == 2)));
+            }
+        }
+        let oncoming = Agent::new(IVec2::new(19, 5), Vec2::new(1.0, 3.0), 0, 8);
+        let path: Vec<Cell> = (0..20)
+            .rev()
+            .map(|x| Cell::new(4, IVec2::new(x, 5)))
+            .collect();
+        let corridor = swept_cells(&oncoming, &path);
+        assert!((3..=7).all(|y| corridor.contains(&IVec2::new(10, y))));
+
+        // Nowhere in the aisle is out of the way, so the vehicle pulls into
+        // the notch.
+        let agent = point_agent(IVec2::new(9, 5));
+        let from = Cell::new(0, IVec2::new(9, 5));
+        let pose = pull_over_pose(&grid, &agent, &from, &oncoming, &path, 10).unwrap();
+        assert_eq!(pose, Cell::new(0, IVec2::new(12, 2)));
+        assert!(pull_over_pose(&grid, &agent, &from, &oncoming, &path, 5).is_none());
+    }
+}
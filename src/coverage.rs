@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+
+/// A straight run along one lane, driven at a single heading.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sweep {
+    pub start: Cell,
+    pub end: Cell,
+}
+
+impl Sweep {
+    pub fn poses(&self) -> Vec<Cell> {
+        let step = (self.end.position.x - self.start.position.x).signum();
+        let length = (self.end.position.x - self.start.position.x).abs();
+        (0..=length)
+            .map(|i| {
+                Cell::new(
+                    self.start.rotation,
+                    self.start.position + IVec2::new(step * i, 0),
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CoveragePlan {
+    pub path: Vec<Cell>,
+    /// Free cells under the footprint somewhere along `path`.
+    pub covered: usize,
+    /// All free cells of the grid.
+    pub free: usize,
+    /// Sweeps that no headland turn could reach.
+    pub skipped: Vec<Sweep>,
+}
+
+/// Lane spacing that lets neighboring lanes' footprints touch without gaps.
+pub fn footprint_spacing(agent: &Agent) -> i32 {
+    let footprint = agent.rotation_footprint(0);
+    let min = footprint
+        .iter()
+        .map(|cell| cell.y)
+        .min()
+        .unwrap_or(0)
+        .min(0);
+    let max = footprint
+        .iter()
+        .map(|cell| cell.y)
+        .max()
+        .unwrap_or(0)
+        .max(0);
+    max - min + 1
+}
+
+/// Splits the grid into horizontal lanes `spacing` rows apart and returns
+/// the free runs of each, alternating direction from lane to lane
+/// (boustrophedon order).
+pub fn sweeps(grid: &Grid, agent: &Agent, spacing: i32) -> Vec<Sweep> {
+    let east = 0;
+    let west = agent.max_increments as i16 / 2;
+    let mut result = Vec::new();
+    for (lane, y) in (0..grid.size.1)
+        .step_by(spacing.max(1) as usize)
+        .enumerate()
+    {
+        let rotation = if lane % 2 == 0 { east } else { west };
+        let mut runs = Vec::new();
+        let mut run_start = None;
+        for x in 0..=grid.size.0 {
+            let free = x < grid.size.0
+                && !grid.is_pose_blocked(agent, &Cell::new(rotation, IVec2::new(x, y)));
+            match (free, run_start) {
+                (true, None) => run_start = Some(x),
+                (false, Some(start)) => {
+                    runs.push((start, x - 1));
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+        if rotation == west {
+            runs.reverse();
+        }
+        for (from, to) in runs {
+            let (from, to) = if rotation == west {
+                (to, from)
+            } else {
+                (from, to)
+            };
+            result.push(Sweep {
+                start: Cell::new(rotation, IVec2::new(from, y)),
+                end: Cell::new(rotation, IVec2::new(to, y)),
+            });
+        }
+    }
+    result
+}
+
+/// Plans a lawnmower path over every free cell: sweeps along lanes joined
+/// by headland turns from the regular planner, so turns respect the
+/// vehicle's turning radius. `spacing` defaults to the footprint width.
+pub fn plan_coverage(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    config: &PlannerConfig,
+    spacing: Option<i32>,
+) -> CoveragePlan {
+    let spacing = spacing.unwrap_or_else(|| footprint_spacing(agent));
+    let mut path = vec![start];
+    let mut skipped = Vec::new();
+
+    for sweep in sweeps(grid, agent, spacing) {
+        let current = path.last().unwrap().clone();
+        if current != sweep.start {
+            let goal = Goal::Oriented {
+                goal: Box::new(Goal::Cell(sweep.start.position)),
+                heading: sweep.start.rotation,
+                tolerance: 0,
+            };
+            match planner::plan(grid, agent, neighbor_cache, current, goal, config) {
+                Ok(turn) => path.extend(turn.path.into_iter().skip(1)),
+                Err(_) => {
+                    skipped.push(sweep);
+                    continue;
+                }
+            }
+        }
+        path.extend(sweep.poses().into_iter().skip(1));
+    }
+
+    let mut covered = HashSet::new();
+    for pose in &path {
+        covered.insert(pose.position);
+        for cell in agent.rotation_footprint(pose.rotation) {
+            covered.insert(*cell + pose.position);
+        }
+    }
+    let covered = covered
+        .into_iter()
+        .filter(|cell| !grid.is_cell_blocked(cell.x, cell.y))
+        .count();
+    let free = (0..grid.size.1)
+        .flat_map(|y| (0..grid.size.0).map(move |x| (x, y)))
+        .filter(|&(x, y)| !grid.is_cell_blocked(x, y))
+        .count();
+
+    CoveragePlan {
+        path,
+        covered,
+        free,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{point_agent, Fixture};
+
+    #[test]
+    fn test_sweeps_alternate_around_obstacle() {
+        let mut grid = Grid::new(1.0, 6, 3);
+        grid.set_cell(2, 1, true);
+        let agent = point_agent(IVec2::new(0, 0));
+        let sweeps = sweeps(&grid, &agent, 1);
+        let ends: Vec<(i32, i32, i32)> = sweeps
+            .iter()
+            .map(|sweep| {
+                (
+                    sweep.start.position.x,
+                    sweep.end.position.x,
+                    sweep.start.position.y,
+                )
+            })
+            .collect();
+        assert_eq!(ends, vec![(0, 5, 0), (5, 3, 1), (1, 0, 1), (0, 5, 2)]);
+        assert_eq!(sweeps[1].start.rotation, 4);
+        assert_eq!(sweeps[1].poses().len(), 3);
+    }
+
+    #[test]
+    fn test_covers_open_field() {
+        let grid = Grid::new(1.0, 10, 10);
+        let (agent, cache, config) = Fixture::new(10, 10).build();
+        let plan = plan_coverage(
+            &grid,
+            &agent,
+            &cache,
+            Cell::new(0, IVec2::new(0, 0)),
+            &config,
+            Some(2),
+        );
+        assert!(plan.skipped.is_empty());
+        assert_eq!(plan.free, 100);
+        // Every other lane is swept, so at least half the field is covered.
+        assert!(plan.covered >= 50);
+        for y in (0..10).step_by(2) {
+            assert!((0..10).all(|x| plan
+                .path
+                .iter()
+                .any(|pose| pose.position == IVec2::new(x, y))));
+        }
+    }
+}
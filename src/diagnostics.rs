@@ -0,0 +1,356 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, EscapeMode, PlanError, PlanResult, PlannerConfig, SearchAlgorithm};
+use crate::reachability::Components;
+
+/// Why no path could be found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureCause {
+    /// [`PlannerConfig::fixed_point`] is set, but the agent or grid has
+    /// [`planner::float_cost_terms`], so the search refused to start.
+    FloatCostTerms,
+    /// The footprint collides at the start, and the escape mode doesn't
+    /// get it out.
+    StartBlocked,
+    /// The footprint collides at every pose the goal accepts.
+    GoalBlocked,
+    /// No chain of free cells connects start and goal.
+    Blockage,
+    /// Free cells connect start and goal, but the footprint doesn't fit
+    /// through somewhere along the way at any heading.
+    Clearance,
+    /// The footprint fits all the way, but not with the allowed turns.
+    Kinematic,
+    /// The footprint fits all the way, but an
+    /// [`IterativeDeepening`](planner::SearchAlgorithm::IterativeDeepening)
+    /// search spent its `max_states` expansions before finding the turns.
+    /// A* has no expansion budget, `max_states` only sizes its tables, so
+    /// it never fails this way.
+    BudgetExhausted,
+}
+
+/// What a failed search got to, for explaining the failure.
+#[derive(Clone, Debug)]
+pub struct PlanFailure {
+    /// Distinct poses the search expanded.
+    pub explored: usize,
+    /// Expanded positions next to a free cell the search never expanded,
+    /// i.e. where the frontier died.
+    pub boundary: Vec<IVec2>,
+    /// Expanded poses closest to the goal.
+    pub closest: Vec<Cell>,
+    pub cause: FailureCause,
+}
+
+fn goal_cells(goal: &Goal) -> Vec<IVec2> {
+    let (min, max) = goal.bounds();
+    (min.y..=max.y)
+        .flat_map(|y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+        .filter(|cell| goal.contains(*cell))
+        .collect()
+}
+
+/// Positions 8-connected to `start` where the footprint fits at some
+/// heading.
+fn footprint_reachable(grid: &Grid, agent: &Agent, start: IVec2) -> HashSet<IVec2> {
+    let fits = |position: IVec2| {
+        grid.in_bounds(position.x, position.y)
+            && (0..agent.max_increments as i16)
+                .any(|rotation| !grid.is_pose_blocked(agent, &Cell::new(rotation, position)))
+    };
+    let mut reached = HashSet::new();
+    if !fits(start) {
+        return reached;
+    }
+    let mut stack = vec![start];
+    reached.insert(start);
+    while let Some(cell) = stack.pop() {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let next = cell + IVec2::new(dx, dy);
+                if !reached.contains(&next) && fits(next) {
+                    reached.insert(next);
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    reached
+}
+
+/// Classifies why `start` can't reach `goal` under `config`, from cheapest
+/// check to most expensive, starting with the ones the planner refuses to
+/// search for.
+pub fn failure_cause(
+    grid: &Grid,
+    agent: &Agent,
+    start: &Cell,
+    goal: &Goal,
+    config: &PlannerConfig,
+) -> FailureCause {
+    if config.fixed_point && planner::float_cost_terms(grid, agent) {
+        return FailureCause::FloatCostTerms;
+    }
+    if !goal.accepts(agent, start)
+        && !planner::goal_has_free_pose(grid, agent, goal, config.door_cost.is_some())
+    {
+        return FailureCause::GoalBlocked;
+    }
+    // Where the search really starts from, as the planner picks it.
+    let start = match config.escape {
+        _ if !grid.is_pose_blocked(agent, start) => start.position,
+        EscapeMode::Disabled => return FailureCause::StartBlocked,
+        EscapeMode::Penalized { .. } => start.position,
+        EscapeMode::Reroot { max_radius } => {
+            match planner::nearest_free_pose(grid, agent, start, max_radius, config.max_increments)
+            {
+                Some(root) => root.position,
+                None => return FailureCause::StartBlocked,
+            }
+        }
+    };
+    let cells = goal_cells(goal);
+    let components = Components::compute(grid);
+    if !cells
+        .iter()
+        .any(|cell| components.is_reachable(start, *cell))
+    {
+        return FailureCause::Blockage;
+    }
+    let reached = footprint_reachable(grid, agent, start);
+    if !cells.iter().any(|cell| reached.contains(cell)) {
+        return FailureCause::Clearance;
+    }
+    FailureCause::Kinematic
+}
+
+/// Plans like [`planner::plan`], but explains a failure: where the search
+/// frontier died, how close it got to the goal, and whether the goal is
+/// walled off or the vehicle just doesn't fit.
+pub fn plan_diagnosed(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+) -> Result<PlanResult, PlanFailure> {
+    let goal = goal.into();
+    let (error, explored) = match planner::search(
+        grid,
+        agent,
+        neighbor_cache,
+        start.clone(),
+        goal.clone(),
+        config,
+        |_, _| 0,
+    ) {
+        Ok(result) => return Ok(result),
+        Err(failure) => failure,
+    };
+
+    let exhausted = config.algorithm == SearchAlgorithm::IterativeDeepening
+        && explored.len() >= config.max_states;
+    let poses: HashSet<Cell> = explored.into_iter().collect();
+    let positions: HashSet<IVec2> = poses.iter().map(|pose| pose.position).collect();
+    let mut boundary: Vec<IVec2> = positions
+        .iter()
+        .filter(|cell| {
+            (-1..=1).any(|dy| {
+                (-1..=1).any(|dx| {
+                    let next = **cell + IVec2::new(dx, dy);
+                    !positions.contains(&next) && !grid.is_cell_blocked(next.x, next.y)
+                })
+            })
+        })
+        .copied()
+        .collect();
+    boundary.sort_by_key(|cell| (cell.y, cell.x));
+
+    let distance =
+        |pose: &Cell| (goal.nearest_point(pose.position) - pose.position).length_squared();
+    let nearest = poses.iter().map(distance).min();
+    let mut closest: Vec<Cell> = poses
+        .iter()
+        .filter(|pose| Some(distance(pose)) == nearest)
+        .cloned()
+        .collect();
+    closest.sort_by_key(|pose| (pose.position.y, pose.position.x, pose.rotation));
+
+    Err(PlanFailure {
+        explored: poses.len(),
+        boundary,
+        closest,
+        cause: match error {
+            PlanError::FloatCostTerms => FailureCause::FloatCostTerms,
+            PlanError::StartBlocked => FailureCause::StartBlocked,
+            PlanError::GoalBlocked => FailureCause::GoalBlocked,
+            PlanError::NoPath => match failure_cause(grid, agent, &start, &goal, config) {
+                FailureCause::Kinematic if exhausted => FailureCause::BudgetExhausted,
+                cause => cause,
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::Vec2;
+
+    use super::*;
+    use crate::door::Door;
+    use crate::test_support::Fixture;
+
+    fn setup(size: Vec2) -> (Agent, NeighborCacheRef, PlannerConfig) {
+        Fixture::new(12, 12).size(size).holonomic(1).build()
+    }
+
+    /// A full-height wall at x = 6, with an optional one-cell gap.
+    fn walled(gap: Option<i32>) -> Grid {
+        let mut grid = Grid::new(1.0, 12, 12);
+        for y in 0..12 {
+            if Some(y) != gap {
+                grid.set_cell(6, y, true);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_blockage_reports_frontier() {
+        let grid = walled(None);
+        let (agent, cache, config) = setup(Vec2::new(0.01, 0.01));
+        let start = Cell::new(0, IVec2::new(2, 5));
+        let failure =
+            plan_diagnosed(&grid, &agent, &cache, start, IVec2::new(9, 5), &config).unwrap_err();
+        assert_eq!(failure.cause, FailureCause::Blockage);
+        assert!(failure.explored > 0);
+        assert!(failure.closest.iter().all(|pose| pose.position.x == 5));
+        assert!(failure.closest.iter().any(|pose| pose.position.y == 5));
+        // The whole left side gets explored, so nothing is left to expand.
+        assert!(failure.boundary.is_empty());
+    }
+
+    #[test]
+    fn test_clearance_and_goal_blocked() {
+        let grid = walled(Some(5));
+        let (agent, cache, config) = setup(Vec2::new(3.0, 3.0));
+        let start = Cell::new(0, IVec2::new(2, 5));
+        let failure = plan_diagnosed(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(9, 5),
+            &config,
+        )
+        .unwrap_err();
+        assert_eq!(failure.cause, FailureCause::Clearance);
+
+        let (small, _, _) = setup(Vec2::new(0.01, 0.01));
+        assert!(plan_diagnosed(
+            &grid,
+            &small,
+            &cache,
+            start.clone(),
+            IVec2::new(9, 5),
+            &config
+        )
+        .is_ok());
+        // The same trip is possible, just not within the expansion budget.
+        let mut budget = config.clone();
+        budget.algorithm = SearchAlgorithm::IterativeDeepening;
+        budget.max_states = 50;
+        let failure =
+            plan_diagnosed(&grid, &small, &cache, start, IVec2::new(9, 5), &budget).unwrap_err();
+        assert_eq!(failure.cause, FailureCause::BudgetExhausted);
+        assert!(failure.explored > 0);
+        assert_eq!(
+            failure_cause(
+                &grid,
+                &small,
+                &Cell::new(0, IVec2::new(2, 5)),
+                &Goal::Cell(IVec2::new(6, 3)),
+                &config
+            ),
+            FailureCause::GoalBlocked
+        );
+    }
+
+    #[test]
+    fn test_goal_footprint_blocked() {
+        let grid = walled(Some(5));
+        let (agent, cache, config) = setup(Vec2::new(3.0, 3.0));
+        // The goal cell is free, but right next to the wall the footprint
+        // overlaps it at every heading.
+        let goal = IVec2::new(7, 2);
+        assert!(!grid.is_cell_blocked(goal.x, goal.y));
+        let failure = plan_diagnosed(
+            &grid,
+            &agent,
+            &cache,
+            Cell::new(0, IVec2::new(9, 9)),
+            goal,
+            &config,
+        )
+        .unwrap_err();
+        assert_eq!(failure.cause, FailureCause::GoalBlocked);
+        // Found out before searching at all.
+        assert_eq!(failure.explored, 0);
+    }
+
+    #[test]
+    fn test_refused_searches() {
+        let mut grid = walled(None);
+        let (agent, cache, mut config) = setup(Vec2::new(0.01, 0.01));
+        let goal = Goal::Cell(IVec2::new(9, 5));
+        let cause = |grid: &Grid, start: IVec2, config: &PlannerConfig| {
+            let start = Cell::new(0, start);
+            plan_diagnosed(grid, &agent, &cache, start, goal.clone(), config)
+                .unwrap_err()
+                .cause
+        };
+
+        // A closed door only blocks the goal when doors can't be passed.
+        grid.add_door(Door::new("gate", vec![IVec2::new(9, 5)]));
+        assert_eq!(
+            cause(&grid, IVec2::new(2, 5), &config),
+            FailureCause::GoalBlocked
+        );
+        config.door_cost = Some(500);
+        assert_eq!(
+            cause(&grid, IVec2::new(2, 5), &config),
+            FailureCause::Blockage
+        );
+
+        // Starting inside the wall.
+        assert_eq!(
+            cause(&grid, IVec2::new(6, 5), &config),
+            FailureCause::StartBlocked
+        );
+        config.escape = EscapeMode::Reroot { max_radius: 0 };
+        assert_eq!(
+            cause(&grid, IVec2::new(6, 5), &config),
+            FailureCause::StartBlocked
+        );
+        config.escape = EscapeMode::Reroot { max_radius: 1 };
+        assert_ne!(
+            cause(&grid, IVec2::new(6, 5), &config),
+            FailureCause::StartBlocked
+        );
+
+        config.escape = EscapeMode::Disabled;
+        config.fixed_point = true;
+        grid.set_flow(4, 6, Vec2::new(0.5, 0.0));
+        assert_eq!(
+            cause(&grid, IVec2::new(2, 5), &config),
+            FailureCause::FloatCostTerms
+        );
+    }
+}
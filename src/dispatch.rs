@@ -0,0 +1,289 @@
+use crate::agent::Agent;
+use crate::batch::PlanRequest;
+use crate::cell::Cell;
+use crate::eta::{EtaEstimator, EtaProfile};
+use crate::goal::Goal;
+use crate::grid::Grid;
+
+/// A load to move from `pickup` to `dropoff`.
+#[derive(Clone, Debug)]
+pub struct TransportTask {
+    pub name: String,
+    pub pickup: Goal,
+    pub dropoff: Goal,
+}
+
+/// How [`assign_tasks`] matches vehicles to tasks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocationMethod {
+    /// Repeatedly gives the quickest remaining vehicle and task pair to
+    /// each other. Fast and predictable, but an early greedy pick can
+    /// leave another vehicle a long way to go.
+    Auction,
+    /// Minimizes the summed time to reach the pickups (Hungarian method).
+    Hungarian,
+}
+
+/// A task given to a vehicle, with the plan request that drives it to the
+/// pickup.
+#[derive(Clone)]
+pub struct Assignment {
+    pub vehicle: usize,
+    pub task: usize,
+    /// Estimated seconds to the pickup.
+    pub eta: f32,
+    pub request: PlanRequest,
+}
+
+/// Estimated seconds from each vehicle to each task's pickup, indexed
+/// `[vehicle][task]`, `None` where the pickup can't be reached. Builds one
+/// [`EtaEstimator`] per task.
+pub fn eta_matrix(
+    grid: &Grid,
+    vehicles: &[(Agent, Cell)],
+    tasks: &[TransportTask],
+    profile: EtaProfile,
+) -> Vec<Vec<Option<f32>>> {
+    let max_increments = vehicles
+        .first()
+        .map_or(1, |(agent, _)| agent.max_increments);
+    let estimators: Vec<EtaEstimator> = tasks
+        .iter()
+        .map(|task| EtaEstimator::new(grid, &task.pickup, profile, max_increments))
+        .collect();
+    vehicles
+        .iter()
+        .map(|(_, start)| {
+            estimators
+                .iter()
+                .map(|estimator| estimator.estimate(start))
+                .collect()
+        })
+        .collect()
+}
+
+/// Gives each task to at most one vehicle and each vehicle at most one
+/// task, using the ETA estimates, and returns a plan request to the pickup
+/// per assignment, in vehicle order. With more tasks than vehicles some
+/// tasks wait for the next round; pickups no vehicle can reach are never
+/// assigned.
+pub fn assign_tasks(
+    grid: &Grid,
+    vehicles: &[(Agent, Cell)],
+    tasks: &[TransportTask],
+    profile: EtaProfile,
+    method: AllocationMethod,
+) -> Vec<Assignment> {
+    let etas = eta_matrix(grid, vehicles, tasks, profile);
+    let pairs = match method {
+        AllocationMethod::Auction => auction(&etas),
+        AllocationMethod::Hungarian => hungarian(&etas),
+    };
+    let mut assignments: Vec<Assignment> = pairs
+        .into_iter()
+        .filter_map(|(vehicle, task)| {
+            let eta = etas[vehicle][task]?;
+            let (agent, start) = &vehicles[vehicle];
+            Some(Assignment {
+                vehicle,
+                task,
+                eta,
+                request: PlanRequest {
+                    agent: agent.clone(),
+                    start: start.clone(),
+                    goal: tasks[task].pickup.clone(),
+                },
+            })
+        })
+        .collect();
+    assignments.sort_by_key(|assignment| assignment.vehicle);
+    assignments
+}
+
+fn auction(etas: &[Vec<Option<f32>>]) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(f32, usize, usize)> = etas
+        .iter()
+        .enumerate()
+        .flat_map(|(vehicle, row)| {
+            row.iter()
+                .enumerate()
+                .filter_map(move |(task, eta)| Some(((*eta)?, vehicle, task)))
+        })
+        .collect();
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut vehicle_taken = vec![false; etas.len()];
+    let mut task_taken = vec![false; etas.first().map_or(0, Vec::len)];
+    let mut assigned = Vec::new();
+    for (_, vehicle, task) in pairs {
+        if !vehicle_taken[vehicle] && !task_taken[task] {
+            vehicle_taken[vehicle] = true;
+            task_taken[task] = true;
+            assigned.push((vehicle, task));
+        }
+    }
+    assigned
+}
+
+/// Minimum-cost matching of rows to columns with the Hungarian method, in
+/// O(rows² · columns). Unreachable pairs cost far more than any real ETA,
+/// so they're only matched when nothing else is left, and dropped by the
+/// caller.
+fn hungarian(etas: &[Vec<Option<f32>>]) -> Vec<(usize, usize)> {
+    const UNREACHABLE: f64 = 1e9;
+    let vehicles = etas.len();
+    let tasks = etas.first().map_or(0, Vec::len);
+    // The method needs no more rows than columns.
+    let transposed = vehicles > tasks;
+    let (rows, columns) = if transposed {
+        (tasks, vehicles)
+    } else {
+        (vehicles, tasks)
+    };
+    let cost = |row: usize, column: usize| {
+        let (vehicle, task) = if transposed {
+            (column, row)
+        } else {
+            (row, column)
+        };
+        etas[vehicle][task].map_or(UNREACHABLE, |eta| eta as f64)
+    };
+
+    // Potentials and matches are 1-based, with column 0 as the scratch
+    // column every augmenting path starts from.
+    let mut u = vec![0.0; rows + 1];
+    let mut v = vec![0.0; columns + 1];
+    let mut matched = vec![0; columns + 1];
+    let mut way = vec![0; columns + 1];
+    for row in 1..=rows {
+        matched[0] = row;
+        let mut column = 0;
+        let mut min = vec![f64::INFINITY; columns + 1];
+        let mut used = vec![false; columns + 1];
+        loop {
+            used[column] = true;
+            let current = matched[column];
+            let mut delta = f64::INFINITY;
+            let mut next = 0;
+            for candidate in 1..=columns {
+                if used[candidate] {
+                    continue;
+                }
+                let reduced = cost(current - 1, candidate - 1) - u[current] - v[candidate];
+                if reduced < min[candidate] {
+                    min[candidate] = reduced;
+                    way[candidate] = column;
+                }
+                if min[candidate] < delta {
+                    delta = min[candidate];
+                    next = candidate;
+                }
+            }
+            for candidate in 0..=columns {
+                if used[candidate] {
+                    u[matched[candidate]] += delta;
+                    v[candidate] -= delta;
+                } else {
+                    min[candidate] -= delta;
+                }
+            }
+            column = next;
+            if matched[column] == 0 {
+                break;
+            }
+        }
+        while column != 0 {
+            let previous = way[column];
+            matched[column] = matched[previous];
+            column = previous;
+        }
+    }
+
+    (1..=columns)
+        .filter(|column| matched[*column] != 0)
+        .map(|column| {
+            let (row, column) = (matched[column] - 1, column - 1);
+            if transposed {
+                (column, row)
+            } else {
+                (row, column)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use notan::math::IVec2;
+
+    use super::*;
+    use crate::test_support::point_agent;
+
+    fn task(name: &str, x: i32) -> TransportTask {
+        TransportTask {
+            name: name.to_string(),
+            pickup: Goal::Cell(IVec2::new(x, 1)),
+            dropoff: Goal::Cell(IVec2::new(0, 1)),
+        }
+    }
+
+    #[test]
+    fn test_assigns_fleet() {
+        let mut grid = Grid::new(1.0, 24, 6);
+        // A third vehicle walled in at the bottom can't reach anything.
+        for x in 0..24 {
+            grid.set_cell(x, 3, true);
+        }
+        let vehicles: Vec<(Agent, Cell)> = [IVec2::new(0, 1), IVec2::new(10, 1), IVec2::new(5, 5)]
+            .into_iter()
+            .map(|position| {
+                let agent = point_agent(position);
+                (agent, Cell::new(0, position))
+            })
+            .collect();
+        let tasks = vec![task("near", 9), task("far", 20)];
+        let profile = EtaProfile {
+            average_speed: 1.0,
+            turn_rate: 1.0,
+        };
+
+        // The auction gives "near" to the vehicle right next to it, leaving
+        // the other one the whole aisle to drive, and turns around to do it.
+        let greedy = assign_tasks(&grid, &vehicles, &tasks, profile, AllocationMethod::Auction);
+        let pairs: Vec<(usize, usize)> = greedy.iter().map(|a| (a.vehicle, a.task)).collect();
+        assert_eq!(pairs, vec![(0, 1), (1, 0)]);
+        assert!((greedy.iter().map(|a| a.eta).sum::<f32>() - (21.0 + PI)).abs() < 1e-4);
+
+        let optimal = assign_tasks(
+            &grid,
+            &vehicles,
+            &tasks,
+            profile,
+            AllocationMethod::Hungarian,
+        );
+        let pairs: Vec<(usize, usize)> = optimal.iter().map(|a| (a.vehicle, a.task)).collect();
+        assert_eq!(pairs, vec![(0, 0), (1, 1)]);
+        assert_eq!(optimal.iter().map(|a| a.eta).sum::<f32>(), 19.0);
+        assert_eq!(optimal[1].request.start.position, IVec2::new(10, 1));
+        assert!(optimal[1].request.goal.contains(IVec2::new(20, 1)));
+
+        // More vehicles than tasks, and one task nobody can reach.
+        let tasks = vec![
+            task("near", 9),
+            TransportTask {
+                pickup: Goal::Cell(IVec2::new(23, 3)),
+                ..task("walled", 0)
+            },
+        ];
+        let optimal = assign_tasks(
+            &grid,
+            &vehicles,
+            &tasks,
+            profile,
+            AllocationMethod::Hungarian,
+        );
+        let pairs: Vec<(usize, usize)> = optimal.iter().map(|a| (a.vehicle, a.task)).collect();
+        assert_eq!(pairs, vec![(1, 0)]);
+    }
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCache, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+
+/// Settings for the fine stage of [`plan_docking`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DockingConfig {
+    /// Coarse cells from the dock where the fine stage takes over.
+    pub radius: i32,
+    /// Rotation increments of the fine stage.
+    pub increments: u16,
+    /// Fine cells per coarse cell along each axis.
+    pub subdivision: i32,
+    /// Arc of the fine stage, in fine increments.
+    pub arc: u16,
+    /// Allowed heading error at the dock, in fine increments.
+    pub tolerance: i16,
+}
+
+impl Default for DockingConfig {
+    fn default() -> Self {
+        Self {
+            radius: 3,
+            increments: 128,
+            subdivision: 2,
+            arc: 4,
+            tolerance: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DockingPlan {
+    /// Coarse path up to and including the handover pose.
+    pub coarse: Vec<Cell>,
+    /// Fine path from the handover pose to the dock, in fine cells and
+    /// fine increments.
+    pub fine: Vec<Cell>,
+    pub cost: u32,
+    pub subdivision: i32,
+    pub increments: u16,
+    pub coarse_increments: u16,
+}
+
+impl DockingPlan {
+    /// Both stages as positions in coarse cells and headings in radians.
+    pub fn stitched(&self) -> Vec<(Vec2, f32)> {
+        let coarse_step = 2.0 * std::f32::consts::PI / self.coarse_increments as f32;
+        let fine_step = 2.0 * std::f32::consts::PI / self.increments as f32;
+        let scale = self.subdivision as f32;
+        let coarse = self
+            .coarse
+            .iter()
+            .map(|pose| (pose.position.as_vec2(), pose.rotation as f32 * coarse_step));
+        let fine = self.fine.iter().skip(1).map(|pose| {
+            let center = (pose.position.as_vec2() + Vec2::splat(0.5)) / scale - Vec2::splat(0.5);
+            (center, pose.rotation as f32 * fine_step)
+        });
+        coarse.chain(fine).collect()
+    }
+}
+
+/// Blocks every fine cell whose coarse cell is blocked.
+pub fn subdivide_grid(grid: &Grid, subdivision: i32) -> Grid {
+    let mut fine = Grid::new(1.0, grid.size.0 * subdivision, grid.size.1 * subdivision);
+    fine.cell_size = grid.cell_size / subdivision as f32;
+    fine.resolution = grid.resolution / subdivision as f32;
+    for y in 0..fine.size.1 {
+        for x in 0..fine.size.0 {
+            if grid.is_cell_blocked(x / subdivision, y / subdivision) {
+                fine.set_cell(x, y, true);
+            }
+            // Heading ranges are angles, so they hold at the finer
+            // increments too.
+            let ranges = grid.allowed_headings(x / subdivision, y / subdivision);
+            if !ranges.is_empty() {
+                fine.set_allowed_headings(x, y, ranges);
+            }
+        }
+    }
+    // So are geofences, which are in world space.
+    fine.keep_out = grid.keep_out.clone();
+    fine.keep_in = grid.keep_in.clone();
+    fine
+}
+
+/// Plans to `dock` in two stages: a coarse plan to within `radius` cells of
+/// the dock, then a fine plan at higher angular and spatial resolution from
+/// there, ending at `heading` (in fine increments).
+#[allow(clippy::too_many_arguments)]
+pub fn plan_docking(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    dock: IVec2,
+    heading: i16,
+    config: &PlannerConfig,
+    docking: &DockingConfig,
+) -> Option<DockingPlan> {
+    let approach = planner::plan(grid, agent, neighbor_cache, start, dock, config).ok()?;
+    let handover = approach
+        .path
+        .iter()
+        .position(|pose| {
+            let offset = (pose.position - dock).abs();
+            offset.x.max(offset.y) <= docking.radius
+        })
+        .unwrap_or(approach.path.len() - 1);
+    let coarse = approach.path[..=handover].to_vec();
+    let handover = &coarse[handover];
+
+    let sub = docking.subdivision;
+    let ratio = docking.increments as f32 / config.max_increments as f32;
+    let fine_grid = subdivide_grid(grid, sub);
+    let mut fine_agent = Agent::new(
+        handover.position * sub,
+        agent.size * sub as f32,
+        (handover.rotation as f32 * ratio).round() as i16,
+        docking.increments,
+    );
+    fine_agent.motion = agent.motion;
+    fine_agent.turn_in_place_cost = agent.turn_in_place_cost;
+    let fine_cache = Rc::new(RefCell::new(NeighborCache::new_precomputed(
+        docking.increments,
+        docking.arc,
+    )));
+    let mut fine_config = config.clone();
+    fine_config.arc = docking.arc;
+    fine_config.reverse_arc = docking.arc * 2;
+    fine_config.max_increments = docking.increments;
+    fine_config.max_states = (fine_grid.size.0 * fine_grid.size.1) as usize;
+
+    let fine_start = Cell::new(fine_agent.rotation, fine_agent.position);
+    let fine_goal = Goal::Oriented {
+        goal: Box::new(Goal::Rect {
+            min: dock * sub,
+            max: dock * sub + IVec2::splat(sub - 1),
+        }),
+        heading,
+        tolerance: docking.tolerance,
+    };
+    let fine = planner::plan(
+        &fine_grid,
+        &fine_agent,
+        &fine_cache,
+        fine_start,
+        fine_goal,
+        &fine_config,
+    )
+    .ok()?;
+
+    let coarse_cost = coarse
+        .windows(2)
+        .map(|pair| {
+            pair[1].cost_with_reverse_factor(
+                Some(pair[0].clone()),
+                config.arc,
+                config.max_increments,
+                config.reverse_factor,
+            )
+        })
+        .sum::<u32>();
+    Some(DockingPlan {
+        coarse,
+        fine: fine.path,
+        cost: coarse_cost + fine.cost,
+        subdivision: sub,
+        increments: docking.increments,
+        coarse_increments: config.max_increments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::polygon;
+
+    use super::*;
+    use crate::grid::HeadingRange;
+    use crate::test_support::{point_agent, Fixture};
+
+    #[test]
+    fn test_subdivide_grid() {
+        let mut grid = Grid::new(1.0, 4, 4);
+        grid.set_cell(1, 2, true);
+        let aligned = [HeadingRange::new(0.0, 0.1)];
+        grid.set_allowed_headings(3, 0, &aligned);
+        let fine = subdivide_grid(&grid, 2);
+        assert_eq!(fine.size, (8, 8));
+        assert!(fine.is_cell_blocked(2, 4));
+        assert!(fine.is_cell_blocked(3, 5));
+        assert!(!fine.is_cell_blocked(4, 4));
+        assert_eq!(fine.allowed_headings(7, 1), &aligned);
+        assert!(fine.allowed_headings(5, 2).is_empty());
+
+        grid.keep_in.push(polygon![
+            (x: 0.0, y: 0.0),
+            (x: 2.0, y: 0.0),
+            (x: 2.0, y: 4.0),
+            (x: 0.0, y: 4.0),
+        ]);
+        let fine = subdivide_grid(&grid, 2);
+        assert_eq!(fine.keep_in, grid.keep_in);
+        let agent = point_agent(IVec2::ZERO);
+        assert!(!fine.violates_geofence(&agent, &Cell::new(0, IVec2::new(1, 6))));
+        assert!(fine.violates_geofence(&agent, &Cell::new(0, IVec2::new(6, 6))));
+    }
+
+    #[test]
+    fn test_docks_at_fine_heading() {
+        let grid = Grid::new(1.0, 12, 12);
+        let (agent, cache, config) = Fixture::new(12, 12).build();
+        let docking = DockingConfig {
+            tolerance: 2,
+            ..DockingConfig::default()
+        };
+        // Halfway between two coarse headings, which the coarse planner
+        // can't represent.
+        let heading = 8;
+        let plan = plan_docking(
+            &grid,
+            &agent,
+            &cache,
+            Cell::new(0, IVec2::new(1, 1)),
+            IVec2::new(9, 6),
+            heading,
+            &config,
+            &docking,
+        )
+        .expect("dock should be reachable");
+
+        let handover = plan.coarse.last().unwrap();
+        let offset = (handover.position - IVec2::new(9, 6)).abs();
+        assert!(offset.x.max(offset.y) <= docking.radius);
+        let last = plan.fine.last().unwrap();
+        assert!(last.position.cmpge(IVec2::new(18, 12)).all());
+        assert!(last.position.cmple(IVec2::new(19, 13)).all());
+        assert!(last.rotation_to(heading, docking.increments as i16) <= 2);
+        assert_eq!(
+            plan.stitched().len(),
+            plan.coarse.len() + plan.fine.len() - 1
+        );
+    }
+}
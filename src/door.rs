@@ -0,0 +1,66 @@
+use notan::math::IVec2;
+
+/// Opens a door for `open_for` ticks out of every `period`, starting at
+/// `offset`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DoorSchedule {
+    pub period: u32,
+    pub open_for: u32,
+    pub offset: u32,
+}
+
+impl DoorSchedule {
+    pub fn is_open_at(&self, time: u32) -> bool {
+        if self.period == 0 {
+            return false;
+        }
+        (time + self.period - self.offset % self.period) % self.period < self.open_for
+    }
+}
+
+/// A named group of cells that block the grid while closed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Door {
+    pub name: String,
+    pub cells: Vec<IVec2>,
+    pub open: bool,
+    pub schedule: Option<DoorSchedule>,
+}
+
+impl Door {
+    pub fn new(name: impl Into<String>, cells: Vec<IVec2>) -> Self {
+        Self {
+            name: name.into(),
+            cells,
+            open: false,
+            schedule: None,
+        }
+    }
+
+    pub fn contains(&self, position: IVec2) -> bool {
+        self.cells.contains(&position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let schedule = DoorSchedule {
+            period: 10,
+            open_for: 3,
+            offset: 2,
+        };
+        let open: Vec<u32> = (0..20).filter(|&t| schedule.is_open_at(t)).collect();
+        assert_eq!(open, vec![2, 3, 4, 12, 13, 14]);
+
+        let never = DoorSchedule {
+            period: 0,
+            open_for: 3,
+            offset: 0,
+        };
+        assert!(!never.is_open_at(0));
+    }
+}
@@ -0,0 +1,38 @@
+use std::io::{self, Read, Write};
+
+use glam::IVec2;
+
+/// Little-endian helpers shared by the precompute cache files.
+pub fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn write_ivec2(writer: &mut impl Write, value: IVec2) -> io::Result<()> {
+    write_u32(writer, value.x as u32)?;
+    write_u32(writer, value.y as u32)
+}
+
+pub fn read_ivec2(reader: &mut impl Read) -> io::Result<IVec2> {
+    Ok(IVec2::new(
+        read_u32(reader)? as i32,
+        read_u32(reader)? as i32,
+    ))
+}
+
+/// Length-prefixed UTF-8.
+pub fn write_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(writer, value.len() as u32)?;
+    writer.write_all(value.as_bytes())
+}
+
+pub fn read_str(reader: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(reader)? as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
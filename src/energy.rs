@@ -0,0 +1,287 @@
+use crate::agent::Agent;
+use crate::annotations::AnnotationKind;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlanResult, PlannerConfig};
+
+/// Energy drawn by the moves of a path, in whatever unit the battery uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnergyModel {
+    pub per_meter: f32,
+    /// Drawn per heading increment turned.
+    pub per_turn: f32,
+    /// Drawn per meter on top of `per_meter` when driving in reverse.
+    pub per_reverse: f32,
+}
+
+impl EnergyModel {
+    pub fn move_energy(&self, grid: &Grid, from: &Cell, to: &Cell, max_increments: u16) -> f32 {
+        let meters = grid.to_meters((to.position - from.position).as_vec2().length());
+        let turn = to.rotation_to(from.rotation, max_increments as i16) as f32;
+        let mut energy = meters * self.per_meter + turn * self.per_turn;
+        if meters > 0.0 && to.is_reverse_to(from, max_increments as i16) {
+            energy += meters * self.per_reverse;
+        }
+        energy
+    }
+
+    pub fn path_energy(&self, grid: &Grid, path: &[Cell], max_increments: u16) -> f32 {
+        path.windows(2)
+            .map(|pair| self.move_energy(grid, &pair[0], &pair[1], max_increments))
+            .sum()
+    }
+
+    /// Energy of a move in search cost units: a thousand per unit drawn,
+    /// the same as a straight cell.
+    pub fn move_cost(&self, grid: &Grid, from: &Cell, to: &Cell, max_increments: u16) -> u32 {
+        (self.move_energy(grid, from, to, max_increments) * 1000.0) as u32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Battery {
+    pub remaining: f32,
+    /// Charge after a stop at a charger.
+    pub capacity: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnergyPlan {
+    pub path: Vec<Cell>,
+    /// Search cost of the path, the energy charged to it included.
+    pub cost: u32,
+    /// Energy drawn over the whole path, charging stop included.
+    pub energy: f32,
+    /// Name of the charger annotation visited on the way, if the goal was
+    /// out of range.
+    pub charger: Option<String>,
+    /// Charge left at the goal.
+    pub remaining: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnergyError {
+    NoPath,
+    /// The paths found, direct and through each charger, all draw more
+    /// than the battery holds. Each is the cheapest by cost and energy
+    /// together, so a costlier one drawing a little less may still exist.
+    CheapestPathsOutOfRange {
+        /// Energy the cheapest direct path would draw, if there is one.
+        direct_energy: Option<f32>,
+    },
+}
+
+/// Plans to `goal` charging each move's energy on top of its cost, then
+/// checks the plan against the battery, stopping at one of the map's
+/// charger annotations when the direct path draws more than is left.
+///
+/// Energy steers the search but doesn't cap it: each leg is the cheapest
+/// path by cost and energy together, checked against the battery after
+/// the fact (see [`EnergyError::CheapestPathsOutOfRange`]). Of the chargers
+/// that work, the cheapest route is taken.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_then_check_range(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+    model: &EnergyModel,
+    battery: Battery,
+) -> Result<EnergyPlan, EnergyError> {
+    let goal = goal.into();
+    let max_increments = config.max_increments;
+    let energy_of = |result: &PlanResult| model.path_energy(grid, &result.path, max_increments);
+    let plan = |start: Cell, goal: Goal| {
+        planner::plan_with_move_cost(
+            grid,
+            agent,
+            neighbor_cache,
+            start,
+            goal,
+            config,
+            |from, to| model.move_cost(grid, from, to, max_increments),
+        )
+    };
+
+    let direct = plan(start.clone(), goal.clone());
+    let mut any_path = direct.is_ok();
+    let direct_energy = direct.as_ref().ok().map(energy_of);
+    if let Ok(direct) = direct {
+        let energy = energy_of(&direct);
+        if energy <= battery.remaining {
+            return Ok(EnergyPlan {
+                path: direct.path,
+                cost: direct.cost,
+                energy,
+                charger: None,
+                remaining: battery.remaining - energy,
+            });
+        }
+    }
+
+    let mut best: Option<EnergyPlan> = None;
+    for charger in grid.annotations.of_kind(AnnotationKind::Charger) {
+        let Ok(to_charger) = plan(start.clone(), charger.goal()) else {
+            continue;
+        };
+        let arrival = to_charger
+            .path
+            .last()
+            .expect("plans are never empty")
+            .clone();
+        let Ok(from_charger) = plan(arrival, goal.clone()) else {
+            continue;
+        };
+        any_path = true;
+        let (first, second) = (energy_of(&to_charger), energy_of(&from_charger));
+        if first > battery.remaining || second > battery.capacity {
+            continue;
+        }
+        let cost = to_charger.cost + from_charger.cost;
+        if best.as_ref().is_some_and(|best| best.cost <= cost) {
+            continue;
+        }
+        let mut path = to_charger.path;
+        path.extend(from_charger.path.into_iter().skip(1));
+        best = Some(EnergyPlan {
+            path,
+            cost,
+            energy: first + second,
+            charger: Some(charger.name.clone()),
+            remaining: battery.capacity - second,
+        });
+    }
+
+    match best {
+        Some(plan) => Ok(plan),
+        None if any_path => Err(EnergyError::CheapestPathsOutOfRange { direct_energy }),
+        None => Err(EnergyError::NoPath),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+    use crate::annotations::Annotation;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_move_energy() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.resolution = 0.5;
+        let model = EnergyModel {
+            per_meter: 2.0,
+            per_turn: 0.5,
+            per_reverse: 1.0,
+        };
+        let from = Cell::new(0, IVec2::new(2, 2));
+        assert_eq!(
+            model.move_energy(&grid, &from, &Cell::new(0, IVec2::new(3, 2)), 8),
+            1.0
+        );
+        assert_eq!(
+            model.move_energy(&grid, &from, &Cell::new(0, IVec2::new(1, 2)), 8),
+            1.5
+        );
+        assert_eq!(
+            model.move_energy(&grid, &from, &Cell::new(2, IVec2::new(2, 2)), 8),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_detours_to_charger() {
+        let plain = Grid::new(1.0, 20, 20);
+        let mut grid = Grid::new(1.0, 20, 20);
+        for (name, position) in [("far", IVec2::new(0, 0)), ("near", IVec2::new(5, 10))] {
+            grid.annotations.insert(Annotation {
+                name: name.to_string(),
+                kind: AnnotationKind::Charger,
+                position,
+                heading: None,
+            });
+        }
+        let (agent, cache, config) = Fixture::new(20, 20)
+            .at(IVec2::new(0, 10))
+            .holonomic(1)
+            .build();
+        let model = EnergyModel {
+            per_meter: 1.0,
+            per_turn: 0.0,
+            per_reverse: 0.0,
+        };
+        let start = Cell::new(0, agent.position);
+        let goal = IVec2::new(10, 10);
+        let plan = |grid: &Grid, battery| {
+            plan_then_check_range(
+                grid,
+                &agent,
+                &cache,
+                start.clone(),
+                goal,
+                &config,
+                &model,
+                battery,
+            )
+        };
+
+        let full = Battery {
+            remaining: 20.0,
+            capacity: 20.0,
+        };
+        let direct = plan(&grid, full).unwrap();
+        assert_eq!(direct.charger, None);
+        assert_eq!(direct.remaining, 10.0);
+
+        let low = Battery {
+            remaining: 6.0,
+            capacity: 20.0,
+        };
+        assert_eq!(
+            plan(&plain, low),
+            Err(EnergyError::CheapestPathsOutOfRange {
+                direct_energy: Some(10.0)
+            })
+        );
+        let charged = plan(&grid, low).unwrap();
+        assert_eq!(charged.charger.as_deref(), Some("near"));
+        assert_eq!(charged.path.last().unwrap().position, goal);
+        assert_eq!(charged.remaining, 15.0);
+    }
+
+    #[test]
+    fn test_energy_steers_search() {
+        let grid = Grid::new(1.0, 20, 20);
+        let (agent, cache, mut config) = Fixture::new(20, 20).at(IVec2::new(10, 10)).build();
+        // Backing up is as cheap as driving, but draws far more.
+        config.reverse_factor = 1;
+        let model = EnergyModel {
+            per_meter: 1.0,
+            per_turn: 0.0,
+            per_reverse: 10.0,
+        };
+        let start = Cell::new(0, agent.position);
+        let goal = IVec2::new(7, 10);
+        let by_cost = planner::plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert!(model.path_energy(&grid, &by_cost.path, 8) > 30.0);
+
+        let battery = Battery {
+            remaining: 25.0,
+            capacity: 25.0,
+        };
+        let plan =
+            plan_then_check_range(&grid, &agent, &cache, start, goal, &config, &model, battery)
+                .unwrap();
+        assert_eq!(plan.charger, None);
+        assert!(plan.energy <= 25.0);
+        assert!(plan
+            .path
+            .windows(2)
+            .all(|pair| !pair[1].is_reverse_to(&pair[0], 8)));
+    }
+}
@@ -0,0 +1,136 @@
+use notan::math::{IVec2, Vec2};
+
+use crate::cell::Cell;
+use crate::field::{goal_distance, DistanceField};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::units::COST_PER_CELL;
+
+/// Average driving figures for quick travel time estimates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EtaProfile {
+    /// Typical speed over a whole trip, slowing for turns and traffic
+    /// included, in meters per second.
+    pub average_speed: f32,
+    /// Turn rate for swinging onto the way to the goal, in radians per
+    /// second.
+    pub turn_rate: f32,
+}
+
+/// Estimates travel times to one goal from anywhere, without planning.
+/// Building it runs one grid-wide Dijkstra; every estimate after that is a
+/// lookup, so a task allocator can price thousands of vehicle and task
+/// pairs. The distance ignores the footprint, the motion model and closed
+/// doors, so it underestimates plans that squeeze through tight spots,
+/// need maneuvering or wait at a door.
+#[derive(Clone, Debug)]
+pub struct EtaEstimator {
+    pub field: DistanceField,
+    pub profile: EtaProfile,
+    pub max_increments: u16,
+    /// Meters per cell of the grid the field was built on.
+    pub resolution: f32,
+}
+
+impl EtaEstimator {
+    pub fn new(grid: &Grid, goal: &Goal, profile: EtaProfile, max_increments: u16) -> Self {
+        Self {
+            field: goal_distance(grid, goal),
+            profile,
+            max_increments,
+            resolution: grid.resolution,
+        }
+    }
+
+    /// Seconds from `start` to the goal, or `None` if the goal can't be
+    /// reached from there.
+    pub fn estimate(&self, start: &Cell) -> Option<f32> {
+        let cost = self.field.at(start.position);
+        if cost == u32::MAX {
+            return None;
+        }
+        let meters = cost as f32 / COST_PER_CELL * self.resolution;
+        let mut seconds = meters / self.profile.average_speed;
+        if let Some(direction) = self.first_step(start.position) {
+            let heading = Cell::increment_to_heading(start.rotation, self.max_increments);
+            let turn = Vec2::from_angle(heading).angle_between(direction).abs();
+            seconds += turn / self.profile.turn_rate;
+        }
+        Some(seconds)
+    }
+
+    /// Direction of the steepest descent of the field, toward the goal.
+    fn first_step(&self, position: IVec2) -> Option<Vec2> {
+        let here = self.field.at(position);
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| IVec2::new(dx, dy)))
+            .filter(|step| *step != IVec2::ZERO)
+            .map(|step| (step, self.field.at(position + step)))
+            .filter(|(_, value)| *value < here)
+            .min_by_key(|(_, value)| *value)
+            .map(|(step, _)| step.as_vec2())
+    }
+}
+
+/// One-off travel time estimate from `start` to `goal`. Build an
+/// [`EtaEstimator`] instead when estimating many starts for the same goal.
+pub fn estimate_eta(
+    grid: &Grid,
+    start: &Cell,
+    goal: &Goal,
+    profile: EtaProfile,
+    max_increments: u16,
+) -> Option<f32> {
+    EtaEstimator::new(grid, goal, profile, max_increments).estimate(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::planner;
+    use crate::test_support::{Fixture, MAX_INCREMENTS};
+    use crate::units::{to_metric, VelocityLimits};
+
+    #[test]
+    fn test_estimates_close_to_plans() {
+        let mut grid = Grid::new(1.0, 20, 12);
+        grid.resolution = 0.5;
+        for y in 0..9 {
+            grid.set_cell(10, y, true);
+        }
+        let goal = Goal::Cell(IVec2::new(16, 2));
+        let profile = EtaProfile {
+            average_speed: 1.0,
+            turn_rate: PI / 2.0,
+        };
+        let estimator = EtaEstimator::new(&grid, &goal, profile, MAX_INCREMENTS);
+        assert_eq!(
+            estimator.estimate(&Cell::new(0, IVec2::new(16, 2))),
+            Some(0.0)
+        );
+        assert_eq!(estimator.estimate(&Cell::new(0, IVec2::new(10, 2))), None);
+        assert_eq!(
+            estimate_eta(&grid, &Cell::new(0, IVec2::new(12, 2)), &goal, profile, 8),
+            Some(2.0)
+        );
+        // Facing away costs the time to turn around.
+        assert_eq!(
+            estimator.estimate(&Cell::new(4, IVec2::new(12, 2))),
+            Some(4.0)
+        );
+
+        let start = Cell::new(0, IVec2::new(3, 2));
+        let estimate = estimator.estimate(&start).unwrap();
+        let (agent, cache, config) = Fixture::new(20, 12).at(start.position).build();
+        let result =
+            planner::plan(&grid, &agent, &cache, start.clone(), goal.clone(), &config).unwrap();
+        let limits = VelocityLimits {
+            max_speed: 1.0,
+            max_turn_rate: PI / 2.0,
+        };
+        let planned = to_metric(&grid, &agent, &result, limits).duration;
+        assert!((estimate - planned).abs() < planned * 0.1);
+    }
+}
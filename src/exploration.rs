@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+use crate::sensor::{self, Discovery, Lidar};
+
+/// How much of the hidden map has been seen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CoverageStats {
+    pub known: usize,
+    pub total: usize,
+    /// Known cells that turned out to be free.
+    pub known_free: usize,
+}
+
+impl CoverageStats {
+    pub fn ratio(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.known as f32 / self.total as f32
+    }
+}
+
+pub fn coverage(discovery: &Discovery) -> CoverageStats {
+    let (width, height) = discovery.truth.size;
+    let mut stats = CoverageStats {
+        known: 0,
+        total: (width * height) as usize,
+        known_free: 0,
+    };
+    for y in 0..height {
+        for x in 0..width {
+            if discovery.is_known(x, y) {
+                stats.known += 1;
+                if !discovery.truth.is_cell_blocked(x, y) {
+                    stats.known_free += 1;
+                }
+            }
+        }
+    }
+    stats
+}
+
+/// Known free cells with at least one unknown 4-neighbor.
+pub fn frontier_cells(discovery: &Discovery) -> Vec<IVec2> {
+    let (width, height) = discovery.truth.size;
+    let mut frontier = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if !discovery.is_known(x, y) || discovery.truth.is_cell_blocked(x, y) {
+                continue;
+            }
+            let borders_unknown = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|(dx, dy)| {
+                discovery.truth.in_bounds(x + dx, y + dy) && !discovery.is_known(x + dx, y + dy)
+            });
+            if borders_unknown {
+                frontier.push(IVec2::new(x, y));
+            }
+        }
+    }
+    frontier
+}
+
+#[derive(Clone, Debug)]
+pub struct ExplorationReport {
+    /// Every pose driven, in order.
+    pub path: Vec<Cell>,
+    /// Frontier goals that were reached.
+    pub goals_reached: usize,
+    /// Frontier goals given up on because no path led there.
+    pub goals_failed: usize,
+    pub stats: CoverageStats,
+}
+
+/// Drives `agent` to the nearest frontier over and over, scanning at every
+/// pose and replanning as soon as a scan blocks the current path, until no
+/// reachable frontier is left or `max_goals` goals were tried. Planning
+/// happens on `discovered`, which the scans fill in.
+pub fn explore(
+    discovery: &mut Discovery,
+    discovered: &mut Grid,
+    agent: &mut Agent,
+    neighbor_cache: &NeighborCacheRef,
+    lidar: &Lidar,
+    config: &PlannerConfig,
+    max_goals: usize,
+) -> ExplorationReport {
+    let mut report = ExplorationReport {
+        path: vec![Cell::new(agent.rotation, agent.position)],
+        goals_reached: 0,
+        goals_failed: 0,
+        stats: coverage(discovery),
+    };
+    let mut failed: HashSet<IVec2> = HashSet::new();
+    discovery.scan(discovered, agent.position, lidar);
+
+    for _ in 0..max_goals {
+        let Some(goal) = frontier_cells(discovery)
+            .into_iter()
+            .filter(|cell| !failed.contains(cell))
+            .min_by_key(|cell| (*cell - agent.position).length_squared())
+        else {
+            break;
+        };
+
+        let start = Cell::new(agent.rotation, agent.position);
+        let Ok(result) = planner::plan(discovered, agent, neighbor_cache, start, goal, config)
+        else {
+            failed.insert(goal);
+            report.goals_failed += 1;
+            continue;
+        };
+
+        let mut reached = true;
+        for pose in result.path.iter().skip(1) {
+            if discovered.is_pose_blocked(agent, pose) {
+                reached = false;
+                break;
+            }
+            agent.position = pose.position;
+            agent.rotation = pose.rotation;
+            report.path.push(pose.clone());
+            let found = discovery.scan(discovered, agent.position, lidar);
+            if !found.is_empty() && sensor::is_path_invalidated(discovered, agent, &result.path) {
+                reached = pose.position == goal;
+                break;
+            }
+        }
+        if reached {
+            report.goals_reached += 1;
+        }
+    }
+
+    report.stats = coverage(discovery);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_explores_behind_wall() {
+        // A wall splits the map, with a gap at the bottom.
+        let mut truth = Grid::new(1.0, 12, 12);
+        for y in 0..10 {
+            truth.set_cell(6, y, true);
+        }
+        let mut discovery = Discovery::new(truth);
+        let mut discovered = discovery.blank_grid();
+        let (mut agent, cache, config) = Fixture::new(12, 12)
+            .at(IVec2::new(1, 1))
+            .holonomic(1)
+            .build();
+        let lidar = Lidar {
+            range: 4.0,
+            rays: 64,
+        };
+
+        let report = explore(
+            &mut discovery,
+            &mut discovered,
+            &mut agent,
+            &cache,
+            &lidar,
+            &config,
+            100,
+        );
+
+        assert!(frontier_cells(&discovery).is_empty());
+        assert_eq!(report.stats.known_free, 12 * 12 - 10);
+        assert!(report.goals_reached > 0);
+        assert!(report.path.iter().any(|pose| pose.position.x > 6));
+    }
+}
@@ -0,0 +1,346 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::rc::Rc;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, Candidates, PlannerConfig};
+
+/// Cost of a straight and a diagonal step, in the planner's cost units.
+pub(crate) const STRAIGHT_COST: u32 = 1000;
+pub(crate) const DIAGONAL_COST: u32 = 1414;
+
+/// A value per grid cell, `u32::MAX` where the source can't be reached.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistanceField {
+    pub size: (i32, i32),
+    pub values: Vec<u32>,
+}
+
+impl DistanceField {
+    /// Value at `position`, `u32::MAX` outside the field.
+    pub fn at(&self, position: IVec2) -> u32 {
+        if position.x < 0
+            || position.x >= self.size.0
+            || position.y < 0
+            || position.y >= self.size.1
+        {
+            return u32::MAX;
+        }
+        self.values[(position.y * self.size.0 + position.x) as usize]
+    }
+
+    /// Whether the source can be reached from `position`.
+    pub fn reaches(&self, position: IVec2) -> bool {
+        self.at(position) != u32::MAX
+    }
+}
+
+/// A value per pose, i.e. per cell and heading, `u32::MAX` where the source
+/// can't be reached.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoseField {
+    pub size: (i32, i32),
+    pub max_increments: u16,
+    /// Indexed by [`Cell::dense_index`].
+    pub values: Vec<u32>,
+}
+
+impl PoseField {
+    /// Value at `pose`, `u32::MAX` outside the field.
+    pub fn at(&self, pose: &Cell) -> u32 {
+        pose.dense_index(self.size.0, self.size.1, self.max_increments)
+            .map_or(u32::MAX, |index| self.values[index])
+    }
+
+    /// The cheapest heading at every cell.
+    pub fn collapse(&self) -> DistanceField {
+        DistanceField {
+            size: self.size,
+            values: self
+                .values
+                .chunks(self.max_increments as usize)
+                .map(|headings| headings.iter().copied().min().unwrap_or(u32::MAX))
+                .collect(),
+        }
+    }
+
+    /// Number of poses reached.
+    pub fn reached(&self) -> usize {
+        self.values
+            .iter()
+            .filter(|value| **value != u32::MAX)
+            .count()
+    }
+}
+
+/// Cost of the cheapest plan from `start` to every pose, following the
+/// planner's motion model, collision checks and move costs. Use
+/// [`PoseField::collapse`] for the cost to every cell at any heading.
+pub fn dijkstra_from(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: &Cell,
+    config: &PlannerConfig,
+) -> PoseField {
+    let max_increments = config.max_increments;
+    let mut field = PoseField {
+        size: grid.size,
+        max_increments,
+        values: vec![u32::MAX; (grid.size.0 * grid.size.1) as usize * max_increments as usize],
+    };
+    let Some(start_index) = start.dense_index(grid.size.0, grid.size.1, max_increments) else {
+        return field;
+    };
+    if grid.is_pose_blocked(agent, start) {
+        return field;
+    }
+    let filtered_cache;
+    let neighbor_cache = if config.allow_forward && config.allow_reverse {
+        neighbor_cache
+    } else {
+        filtered_cache = Rc::new(RefCell::new(
+            neighbor_cache
+                .borrow()
+                .filtered(config.allow_forward, config.allow_reverse),
+        ));
+        &filtered_cache
+    };
+    let costs = planner::cost_cache(neighbor_cache, config);
+
+    let pose_at = |index: usize| {
+        let cell = (index / max_increments as usize) as i32;
+        let position = IVec2::new(cell % grid.size.0, cell / grid.size.0);
+        Cell::new((index % max_increments as usize) as i16, position)
+    };
+    let mut candidates = Candidates::new();
+    let mut open = BinaryHeap::new();
+    field.values[start_index] = 0;
+    open.push(Reverse((0, start_index)));
+    while let Some(Reverse((distance, index))) = open.pop() {
+        if distance > field.values[index] {
+            continue;
+        }
+        let pose = pose_at(index);
+        candidates.clear();
+        planner::motion_candidates(
+            agent,
+            neighbor_cache,
+            &costs,
+            &pose,
+            config,
+            &mut candidates,
+        );
+        for (next, cost) in candidates.drain(..) {
+            let Some(next_index) = next.dense_index(grid.size.0, grid.size.1, max_increments)
+            else {
+                continue;
+            };
+            let Some(cost) = planner::expand_move(grid, agent, &pose, &next, cost, config) else {
+                continue;
+            };
+            let next_distance = distance.saturating_add(cost);
+            if next_distance < field.values[next_index] {
+                field.values[next_index] = next_distance;
+                open.push(Reverse((next_distance, next_index)));
+            }
+        }
+    }
+    field
+}
+
+/// 8-connected Dijkstra from `sources`, only stepping onto cells for which
+/// `passable` holds.
+fn dijkstra(grid: &Grid, sources: &[IVec2], passable: impl Fn(IVec2) -> bool) -> DistanceField {
+    let mut field = DistanceField {
+        size: grid.size,
+        values: vec![u32::MAX; (grid.size.0 * grid.size.1) as usize],
+    };
+    let mut open = BinaryHeap::new();
+    for source in sources {
+        if grid.in_bounds(source.x, source.y) {
+            field.values[grid.index(source.x, source.y)] = 0;
+            open.push(Reverse((0, source.x, source.y)));
+        }
+    }
+    while let Some(Reverse((distance, x, y))) = open.pop() {
+        if distance > field.values[grid.index(x, y)] {
+            continue;
+        }
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let next = IVec2::new(x + dx, y + dy);
+                if (dx == 0 && dy == 0) || !grid.in_bounds(next.x, next.y) || !passable(next) {
+                    continue;
+                }
+                let step = if dx != 0 && dy != 0 {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                let index = grid.index(next.x, next.y);
+                if distance + step < field.values[index] {
+                    field.values[index] = distance + step;
+                    open.push(Reverse((distance + step, next.x, next.y)));
+                }
+            }
+        }
+    }
+    field
+}
+
+/// Travel cost from every free cell to the nearest cell of `goal`, around
+/// obstacles. Used as the planner heuristic, it steers the search out of
+/// dead ends that straight-line distance walks into. Closed doors count as
+/// free, since a search with a [`PlannerConfig::door_cost`] drives through
+/// them.
+pub fn goal_distance(grid: &Grid, goal: &Goal) -> DistanceField {
+    dijkstra(grid, &goal_sources(grid, goal), |cell| passable(grid, cell))
+}
+
+/// Distance from every cell to the nearest blocked one (the obstacle
+/// distance transform), for clearance-based cost layers.
+pub fn obstacle_distance(grid: &Grid) -> DistanceField {
+    dijkstra(grid, &obstacle_sources(grid), |_| true)
+}
+
+/// Whether [`goal_distance`] steps onto `cell`: it's free or a closed door.
+pub(crate) fn passable(grid: &Grid, cell: IVec2) -> bool {
+    !grid.is_cell_blocked(cell.x, cell.y) || grid.is_closed_door(cell.x, cell.y)
+}
+
+/// The passable cells of `goal`, where [`goal_distance`] is zero.
+pub(crate) fn goal_sources(grid: &Grid, goal: &Goal) -> Vec<IVec2> {
+    let (min, max) = goal.bounds();
+    (min.y..=max.y)
+        .flat_map(|y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+        .filter(|cell| goal.contains(*cell) && passable(grid, *cell))
+        .collect()
+}
+
+/// The blocked cells, where [`obstacle_distance`] is zero.
+pub(crate) fn obstacle_sources(grid: &Grid) -> Vec<IVec2> {
+    (0..grid.size.1)
+        .flat_map(|y| (0..grid.size.0).map(move |x| IVec2::new(x, y)))
+        .filter(|cell| grid.is_cell_blocked(cell.x, cell.y))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::cell::Cell;
+    use crate::door::Door;
+    use crate::planner::{self, EscapeMode};
+    use crate::test_support::{neighbor_cache, point_agent, Fixture};
+
+    /// A cup open to the left, with the goal behind its bottom.
+    fn cup() -> Grid {
+        let mut grid = Grid::new(1.0, 16, 16);
+        for y in 3..=12 {
+            grid.set_cell(10, y, true);
+        }
+        for x in 4..=10 {
+            grid.set_cell(x, 3, true);
+            grid.set_cell(x, 12, true);
+        }
+        grid
+    }
+
+    #[test]
+    fn test_fields() {
+        let grid = cup();
+        let goal = goal_distance(&grid, &Goal::Cell(IVec2::new(13, 7)));
+        assert_eq!(goal.at(IVec2::new(13, 7)), 0);
+        assert_eq!(goal.at(IVec2::new(11, 7)), 2 * STRAIGHT_COST);
+        assert_eq!(goal.at(IVec2::new(10, 7)), u32::MAX);
+        // Inside the cup the way out is longer than the straight line.
+        assert!(goal.at(IVec2::new(8, 7)) > 5 * STRAIGHT_COST);
+
+        let obstacles = obstacle_distance(&grid);
+        assert_eq!(obstacles.at(IVec2::new(10, 7)), 0);
+        assert_eq!(obstacles.at(IVec2::new(8, 7)), 2 * STRAIGHT_COST);
+    }
+
+    #[test]
+    fn test_dijkstra_matches_optimal_plans() {
+        let grid = cup();
+        let (agent, cache, mut config) = Fixture::new(16, 16).build();
+        config.heuristic_weight = Some(1.0);
+        let start = Cell::new(0, IVec2::new(7, 7));
+
+        let field = dijkstra_from(&grid, &agent, &cache, &start, &config);
+        assert_eq!(field.at(&start), 0);
+        assert_eq!(field.at(&Cell::new(0, IVec2::new(10, 7))), u32::MAX);
+        let cells = field.collapse();
+        for goal in [IVec2::new(13, 7), IVec2::new(2, 2), IVec2::new(8, 10)] {
+            let optimal = planner::plan(&grid, &agent, &cache, start.clone(), goal, &config)
+                .unwrap()
+                .cost;
+            assert_eq!(cells.at(goal), optimal, "{goal}");
+        }
+    }
+
+    #[test]
+    fn test_plans_with_field_heuristic() {
+        let grid = cup();
+        let agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let goal = IVec2::new(13, 7);
+        let mut config = Fixture::new(16, 16).config();
+        config.heuristic_field = Some(Arc::new(goal_distance(&grid, &Goal::Cell(goal))));
+        let start = Cell::new(0, IVec2::new(7, 7));
+        let result = planner::plan(&grid, &agent, &cache, start, goal, &config).unwrap();
+        assert_eq!(result.path.last().unwrap().position, goal);
+        assert!(result
+            .path
+            .iter()
+            .all(|pose| !grid.is_pose_blocked(&agent, pose)));
+    }
+
+    #[test]
+    fn test_field_heuristic_keeps_searched_moves() {
+        // A wall across the grid with a closed door in it.
+        let mut grid = Grid::new(1.0, 16, 16);
+        for x in 0..16 {
+            grid.set_cell(x, 8, true);
+        }
+        grid.add_door(Door::new("gate", vec![IVec2::new(3, 8)]));
+        let agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let goal = IVec2::new(3, 12);
+        let field = goal_distance(&grid, &Goal::Cell(goal));
+        assert!(field.reaches(IVec2::new(3, 4)));
+        let mut config = Fixture::new(16, 16).config();
+        config.heuristic_field = Some(Arc::new(field));
+        config.door_cost = Some(20_000);
+        let start = Cell::new(0, IVec2::new(3, 4));
+        let result = planner::plan(&grid, &agent, &cache, start, goal, &config).unwrap();
+        assert!(result
+            .path
+            .iter()
+            .any(|pose| pose.position == IVec2::new(3, 8)));
+
+        // Escaping a blocked start crosses cells the field never reached.
+        let mut grid = cup();
+        for x in 1..=3 {
+            grid.set_cell(x, 7, true);
+        }
+        let goal = IVec2::new(13, 7);
+        let field = goal_distance(&grid, &Goal::Cell(goal));
+        assert!(!field.reaches(IVec2::new(2, 7)));
+        config.heuristic_field = Some(Arc::new(field));
+        config.escape = EscapeMode::Penalized { penalty: 1000 };
+        let start = Cell::new(0, IVec2::new(2, 7));
+        let result = planner::plan(&grid, &agent, &cache, start, goal, &config).unwrap();
+        assert_eq!(result.path.last().unwrap().position, goal);
+    }
+}
@@ -0,0 +1,200 @@
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlanResult, PlannerConfig};
+
+/// Samples per cell of curve checked by [`Finish::is_clear`].
+const SAMPLES_PER_CELL: f32 = 8.0;
+
+/// The cell a point goal lies in. Points are in cells, cell `(x, y)`
+/// spanning `x..x + 1` and `y..y + 1`.
+pub fn point_cell(point: Vec2) -> IVec2 {
+    point.floor().as_ivec2()
+}
+
+/// A quadratic Bezier from the center of a plan's last pose to a point
+/// goal between cell centers, leaving along the pose's heading, or against
+/// it when the point is behind. Points are in cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Finish {
+    pub from: Vec2,
+    pub control: Vec2,
+    pub to: Vec2,
+    pub reverse: bool,
+}
+
+impl Finish {
+    pub fn new(pose: &Cell, point: Vec2, max_increments: u16) -> Self {
+        let from = pose.position.as_vec2() + Vec2::splat(0.5);
+        let heading = Vec2::from_angle(Self::pose_heading(pose, max_increments));
+        let along = (point - from).dot(heading);
+        Self {
+            from,
+            control: from + heading * along,
+            to: point,
+            reverse: along < 0.0,
+        }
+    }
+
+    fn pose_heading(pose: &Cell, max_increments: u16) -> f32 {
+        2.0 * std::f32::consts::PI * pose.rotation as f32 / max_increments as f32
+    }
+
+    pub fn sample(&self, t: f32) -> Vec2 {
+        let u = 1.0 - t;
+        self.from * u * u + self.control * 2.0 * u * t + self.to * t * t
+    }
+
+    /// Heading of the vehicle at `t`, in radians, which points against the
+    /// direction of travel when reversing. `None` where the curve has no
+    /// direction, as when the point is the pose's center.
+    pub fn heading_at(&self, t: f32) -> Option<f32> {
+        let derivative =
+            (self.control - self.from) * 2.0 * (1.0 - t) + (self.to - self.control) * 2.0 * t;
+        if derivative.length_squared() < 1e-12 {
+            return None;
+        }
+        let direction = if self.reverse {
+            -derivative
+        } else {
+            derivative
+        };
+        Some(direction.y.atan2(direction.x))
+    }
+
+    /// Length of the curve, in cells.
+    pub fn length(&self) -> f32 {
+        self.points(self.samples())
+            .windows(2)
+            .map(|pair| pair[0].distance(pair[1]))
+            .sum()
+    }
+
+    /// `samples + 1` evenly spaced points, both ends included.
+    pub fn points(&self, samples: usize) -> Vec<Vec2> {
+        (0..=samples)
+            .map(|i| self.sample(i as f32 / samples.max(1) as f32))
+            .collect()
+    }
+
+    fn samples(&self) -> usize {
+        let hull = self.from.distance(self.control) + self.control.distance(self.to);
+        ((hull * SAMPLES_PER_CELL).ceil() as usize).max(1)
+    }
+
+    /// Whether the footprint stays clear of blocked cells and the grid's
+    /// edges all along the curve. Where the curve has no direction the pose's
+    /// heading is kept.
+    pub fn is_clear(&self, grid: &Grid, agent: &Agent, pose: &Cell) -> bool {
+        let samples = self.samples();
+        let mut heading = Self::pose_heading(pose, agent.max_increments);
+        (0..=samples).all(|i| {
+            let t = i as f32 / samples as f32;
+            heading = self.heading_at(t).unwrap_or(heading);
+            agent
+                .footprint_at_point(self.sample(t), heading)
+                .iter()
+                .all(|cell| !grid.is_cell_blocked(cell.x, cell.y))
+        })
+    }
+}
+
+/// A plan to a point goal: the grid path to the cell holding the point,
+/// then the curve to the point itself.
+pub struct PointPlan {
+    pub result: PlanResult,
+    /// `None` when the curve would hit something, leaving the plan at the
+    /// cell's center.
+    pub finish: Option<Finish>,
+}
+
+/// Plans to the cell holding `point`, then connects the last pose to the
+/// exact point with a [`Finish`], so the vehicle stops where it was sent
+/// rather than at the nearest cell center.
+pub fn plan_to_point(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    point: Vec2,
+    config: &PlannerConfig,
+) -> Option<PointPlan> {
+    let result = planner::plan(
+        grid,
+        agent,
+        neighbor_cache,
+        start,
+        Goal::Cell(point_cell(point)),
+        config,
+    )
+    .ok()?;
+    let finish = finish_path(grid, agent, &result.path, point);
+    Some(PointPlan { result, finish })
+}
+
+/// The curve from the end of `path` to `point`, if the path ends in the
+/// point's cell and the curve is clear.
+pub fn finish_path(grid: &Grid, agent: &Agent, path: &[Cell], point: Vec2) -> Option<Finish> {
+    let last = path.last()?;
+    if last.position != point_cell(point) {
+        return None;
+    }
+    let finish = Finish::new(last, point, agent.max_increments);
+    finish.is_clear(grid, agent, last).then_some(finish)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_plan_to_point() {
+        let grid = Grid::new(1.0, 16, 10);
+        let (agent, cache, config) = Fixture::new(16, 10).at(IVec2::new(2, 5)).build();
+
+        let point = Vec2::new(10.8, 5.3);
+        let plan = plan_to_point(
+            &grid,
+            &agent,
+            &cache,
+            Cell::new(0, IVec2::new(2, 5)),
+            point,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(plan.result.path.last().unwrap().position, IVec2::new(10, 5));
+        let finish = plan.finish.unwrap();
+        assert_eq!(finish.from, Vec2::new(10.5, 5.5));
+        assert_eq!(finish.sample(1.0), point);
+        assert!(!finish.reverse);
+        // Leaves along the heading, bending toward the point.
+        assert_eq!(finish.heading_at(0.0), Some(0.0));
+        assert!(finish.heading_at(1.0).unwrap() < 0.0);
+        assert!(finish.length() > finish.from.distance(point));
+        assert!(finish.length() < 0.5);
+
+        // Behind the pose, the vehicle backs up to it.
+        let behind = Finish::new(&Cell::new(0, IVec2::new(10, 5)), Vec2::new(10.1, 5.5), 8);
+        assert!(behind.reverse);
+        assert_eq!(behind.heading_at(0.5), Some(0.0));
+    }
+
+    #[test]
+    fn test_finish_checks_footprint() {
+        let mut grid = Grid::new(1.0, 16, 10);
+        let agent = Agent::new(IVec2::new(2, 5), Vec2::new(2.2, 0.5), 0, 8);
+        let path = [Cell::new(0, IVec2::new(10, 5))];
+        // At the cell center the vehicle reaches x = 11.6, clear of the
+        // obstacle, and only driving on to the point takes its nose into it.
+        grid.set_cell(12, 5, true);
+        assert!(!grid.is_pose_blocked(&agent, &path[0]));
+        assert!(finish_path(&grid, &agent, &path, Vec2::new(10.7, 5.5)).is_some());
+        assert!(finish_path(&grid, &agent, &path, Vec2::new(10.9, 5.5)).is_none());
+        // Points outside the path's last cell get no curve.
+        assert!(finish_path(&grid, &agent, &path, Vec2::new(9.9, 5.5)).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+//! Integer twins of the cost and heuristic math in [`crate::cell`], for
+//! lockstep simulations that need every target to produce the same costs,
+//! and so the same paths, from the same inputs. Costs are in the same units
+//! (1000 per straight cell) and agree with the `f32` versions up to
+//! rounding.
+
+use glam::IVec2;
+
+use crate::cell::Cell;
+
+/// Length of the unit vectors [`direction`] returns.
+pub const UNIT: i32 = 1 << 14;
+
+/// π in 2^-30 units.
+const PI_Q30: i64 = 3_373_259_426;
+
+/// Sine and cosine of `quarters` quarter turns, at most half of one, in
+/// 2^-30 units, by their Taylor series.
+fn sin_cos_q30(quarters: i64, of: i64) -> (i64, i64) {
+    let angle = PI_Q30 * quarters / (2 * of);
+    let square = (angle * angle) >> 30;
+    let (mut sin, mut cos) = (0, 0);
+    let (mut sin_term, mut cos_term) = (angle, 1 << 30);
+    for n in 1..8 {
+        sin += sin_term;
+        cos += cos_term;
+        sin_term = -((sin_term * square) >> 30) / ((2 * n) * (2 * n + 1));
+        cos_term = -((cos_term * square) >> 30) / ((2 * n - 1) * (2 * n));
+    }
+    (sin, cos)
+}
+
+/// Unit vector of heading `rotation` out of `max_increments`, `UNIT` long.
+/// Integer math only, and exactly symmetric across the axes and diagonals,
+/// so every target derives the same moves from it.
+pub fn direction(rotation: i16, max_increments: u16) -> IVec2 {
+    let count = max_increments as i64;
+    let rotation = (rotation as i64).rem_euclid(count);
+    // Which quarter turn the heading is in, and how far into it out of
+    // `count`.
+    let quadrant = rotation * 4 / count;
+    let into = rotation * 4 - quadrant * count;
+    let (cos, sin) = if into * 2 <= count {
+        let (sin, cos) = sin_cos_q30(into, count);
+        // Both halves of the diagonal from one series.
+        (if into * 2 == count { sin } else { cos }, sin)
+    } else {
+        let (sin, cos) = sin_cos_q30(count - into, count);
+        (sin, cos)
+    };
+    let to_unit = |value: i64| ((value + (1 << 15)) >> 16) as i32;
+    let (cos, sin) = (to_unit(cos), to_unit(sin));
+    match quadrant {
+        0 => IVec2::new(cos, sin),
+        1 => IVec2::new(-sin, cos),
+        2 => IVec2::new(-cos, -sin),
+        _ => IVec2::new(sin, -cos),
+    }
+}
+
+/// Largest integer whose square is at most `value`.
+pub fn isqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+    // Newton's method from above converges to the floor.
+    let mut root = value / 2 + 1;
+    let mut next = (root + value / root) / 2;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+    root
+}
+
+fn distance_squared(from: IVec2, to: IVec2) -> u64 {
+    let offset = (to - from).as_i64vec2();
+    (offset.x * offset.x + offset.y * offset.y) as u64
+}
+
+/// Straight-line distance from `from` to `to` in thousandths of a cell,
+/// rounded down.
+pub fn distance(from: IVec2, to: IVec2) -> u32 {
+    isqrt(distance_squared(from, to).saturating_mul(1_000_000)).min(u32::MAX as u64) as u32
+}
+
+/// Integer [`Cell::cost_with_reverse_factor`], taking whether the move
+/// backs up from [`crate::cell::CachedMove::reverse`]. `u32::MAX` for any
+/// turn when `arc` is zero.
+pub fn move_cost(
+    from: &Cell,
+    to: &Cell,
+    reverse: bool,
+    arc: u16,
+    max_increments: u16,
+    reverse_factor: u32,
+) -> u32 {
+    let rotation = to.rotation_to(from.rotation, max_increments as i16) as u32;
+    let reverse_cost = if reverse { reverse_factor } else { 1 };
+    let angle_cost = match (rotation, arc) {
+        (0, _) => 0,
+        (_, 0) => return u32::MAX,
+        _ => rotation * 1000 / arc as u32,
+    };
+
+    // Multi-cell primitives cost the same as taking their single steps one
+    // by one.
+    let steps = from.steps_to(to) as u64;
+    let step_cost = distance_squared(from.position, to.position) * 1000 / (steps * steps);
+    let distance_cost = (step_cost * steps) as u32;
+
+    (angle_cost + distance_cost) * reverse_cost
+}
+
+/// Integer [`Cell::holonomic_cost`], for single-cell steps.
+pub fn holonomic_cost(from: &Cell, to: &Cell, max_increments: u16, heading_weight: u32) -> u32 {
+    let offset = (to.position - from.position).clamp(IVec2::splat(-1), IVec2::ONE);
+    // Eighths of a turn from the x axis, the way atan2 would measure them.
+    let octant: i32 = match (offset.x, offset.y) {
+        (1, 1) => 1,
+        (0, 1) => 2,
+        (-1, 1) => 3,
+        (-1, 0) => 4,
+        (-1, -1) => -3,
+        (0, -1) => -2,
+        (1, -1) => -1,
+        _ => 0,
+    };
+    // Rounds octant * max_increments / 8 half away from zero.
+    let scaled = octant * max_increments as i32;
+    let rounded = (scaled.abs() * 2 + 8) / 16 * scaled.signum();
+    let motion_rotation = rounded.rem_euclid(max_increments as i32) as i16;
+    let misalignment = from.rotation_to(motion_rotation, max_increments as i16);
+    distance(from.position, to.position) + misalignment as u32 * heading_weight
+}
+
+/// Integer [`Cell::heuristic`].
+pub fn heuristic(from: IVec2, to: IVec2) -> u32 {
+    (distance_squared(from, to) * 10).min(u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::NeighborCache;
+
+    #[test]
+    fn test_direction() {
+        for max_increments in [4u16, 6, 8, 12, 16, 32, 360] {
+            for rotation in 0..max_increments as i16 {
+                let vector = direction(rotation, max_increments);
+                let angle = Cell::increment_to_heading(rotation, max_increments);
+                let float = glam::Vec2::from_angle(angle) * UNIT as f32;
+                assert!((vector.as_vec2() - float).abs().max_element() <= 1.0);
+                let opposite = rotation + max_increments as i16 / 2;
+                if max_increments % 2 == 0 {
+                    assert_eq!(direction(opposite, max_increments), -vector);
+                }
+            }
+        }
+        assert_eq!(direction(2, 8), IVec2::new(0, UNIT));
+        assert_eq!(direction(-1, 8), IVec2::new(11585, -11585));
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for value in 0..2000u64 {
+            let root = isqrt(value);
+            assert!(root * root <= value && (root + 1) * (root + 1) > value);
+        }
+        assert_eq!(isqrt(u64::MAX), u32::MAX as u64);
+        assert_eq!(distance(IVec2::ZERO, IVec2::new(1, 1)), 1414);
+    }
+
+    #[test]
+    fn test_matches_float_costs() {
+        for max_increments in [8, 16, 32] {
+            let cache = NeighborCache::new_precomputed(max_increments, 2);
+            for rotation in 0..max_increments as i16 {
+                let from = Cell::new(rotation, IVec2::new(3, -2));
+                for neighbor in cache.get(rotation).unwrap() {
+                    let to = Cell::new(neighbor.rotation, from.position + neighbor.offset);
+                    assert_eq!(
+                        neighbor.reverse,
+                        to.is_reverse_to(&from, max_increments as i16)
+                    );
+                    assert_eq!(
+                        move_cost(&from, &to, neighbor.reverse, 2, max_increments, 5),
+                        to.cost_with_reverse_factor(Some(from.clone()), 2, max_increments, 5)
+                    );
+                }
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let to = Cell::new(rotation, from.position + IVec2::new(dx, dy));
+                        let float = to.holonomic_cost(&from, max_increments, 7);
+                        assert!(holonomic_cost(&from, &to, max_increments, 7).abs_diff(float) <= 1);
+                    }
+                }
+            }
+        }
+        let from = Cell::new(0, IVec2::ZERO);
+        let turned = Cell::new(1, IVec2::new(1, 0));
+        assert_eq!(move_cost(&from, &turned, false, 0, 8, 5), u32::MAX);
+        let straight = Cell::new(0, IVec2::new(1, 0));
+        assert_eq!(move_cost(&from, &straight, false, 0, 8, 5), 1000);
+        assert_eq!(
+            heuristic(IVec2::new(1, 2), IVec2::new(4, 6)),
+            Cell::new(0, IVec2::new(1, 2)).heuristic(IVec2::new(4, 6), 8)
+        );
+    }
+}
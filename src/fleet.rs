@@ -0,0 +1,486 @@
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::annotations::AnnotationKind;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::corridor::pull_over_pose;
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+use crate::reservation::{plan_reserved, ReservationTable};
+
+/// A vehicle of the fleet and where it has to go.
+#[derive(Clone)]
+pub struct FleetVehicle {
+    pub agent: Agent,
+    pub start: Cell,
+    pub goal: Goal,
+    /// Higher plans first.
+    pub priority: i32,
+}
+
+/// How [`plan_fleet`] got vehicles unstuck.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// `vehicle` was planned ahead of the vehicles it was stuck behind.
+    Reordered { vehicle: usize },
+    /// `vehicle` pulled into the passing bay `bay` and waited there for the
+    /// others to pass.
+    PassingBay { vehicle: usize, bay: String },
+    /// With no passing bay on the map, `vehicle` pulled over at `pose`, off
+    /// the way of the vehicle it was stuck with.
+    PullOver { vehicle: usize, pose: Cell },
+}
+
+/// Plans for every vehicle of a fleet, sharing one timeline.
+#[derive(Clone, Debug)]
+pub struct FleetPlan {
+    /// One pose per tick from tick 0, per vehicle in input order. `None`
+    /// for vehicles no plan was found for.
+    pub paths: Vec<Option<Vec<Cell>>>,
+    /// Groups of vehicles whose individual plans each run through where
+    /// another in the group starts, from [`circular_waits`].
+    pub deadlocks: Vec<Vec<usize>>,
+    pub resolutions: Vec<Resolution>,
+}
+
+fn footprint(agent: &Agent, pose: &Cell) -> Vec<IVec2> {
+    agent
+        .rotation_footprint(pose.rotation)
+        .iter()
+        .map(|cell| *cell + pose.position)
+        .chain(std::iter::once(pose.position))
+        .collect()
+}
+
+/// Which vehicles each vehicle waits for: vehicle `i` waits for `j` if
+/// `j`'s footprint at `poses[j]` overlaps `i`'s footprint anywhere along
+/// `paths[i]`, the path still ahead of it.
+pub fn wait_for_graph(agents: &[Agent], poses: &[Cell], paths: &[Vec<Cell>]) -> Vec<Vec<usize>> {
+    let occupied: Vec<Vec<IVec2>> = agents
+        .iter()
+        .zip(poses)
+        .map(|(agent, pose)| footprint(agent, pose))
+        .collect();
+    (0..agents.len())
+        .map(|i| {
+            let swept: Vec<IVec2> = paths[i]
+                .iter()
+                .flat_map(|pose| footprint(&agents[i], pose))
+                .collect();
+            (0..agents.len())
+                .filter(|j| *j != i && occupied[*j].iter().any(|cell| swept.contains(cell)))
+                .collect()
+        })
+        .collect()
+}
+
+/// Groups of two or more vehicles waiting on each other in a circle (the
+/// strongly connected components of `waits`), each sorted, in order of
+/// their lowest vehicle. None of a group can move until one of them gives
+/// way.
+pub fn circular_waits(waits: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    // Tarjan's algorithm, recursive since fleets are small.
+    struct Search<'a> {
+        waits: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        groups: Vec<Vec<usize>>,
+    }
+
+    impl Search<'_> {
+        fn visit(&mut self, vehicle: usize) {
+            self.index[vehicle] = Some(self.next);
+            self.low[vehicle] = self.next;
+            self.next += 1;
+            self.stack.push(vehicle);
+            self.on_stack[vehicle] = true;
+            for &other in &self.waits[vehicle] {
+                match self.index[other] {
+                    None => {
+                        self.visit(other);
+                        self.low[vehicle] = self.low[vehicle].min(self.low[other]);
+                    }
+                    Some(index) if self.on_stack[other] => {
+                        self.low[vehicle] = self.low[vehicle].min(index);
+                    }
+                    Some(_) => {}
+                }
+            }
+            if Some(self.low[vehicle]) == self.index[vehicle] {
+                let mut group = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    group.push(member);
+                    if member == vehicle {
+                        break;
+                    }
+                }
+                if group.len() > 1 {
+                    group.sort_unstable();
+                    self.groups.push(group);
+                }
+            }
+        }
+    }
+
+    let mut search = Search {
+        waits,
+        index: vec![None; waits.len()],
+        low: vec![0; waits.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; waits.len()],
+        next: 0,
+        groups: Vec::new(),
+    };
+    for vehicle in 0..waits.len() {
+        if search.index[vehicle].is_none() {
+            search.visit(vehicle);
+        }
+    }
+    search.groups.sort();
+    search.groups
+}
+
+/// Plans the vehicles one at a time in `order`, each around the paths
+/// already planned and the starts of those still waiting their turn. If
+/// `bay` is set, that vehicle first drives to the bay, and only leaves it
+/// once everyone else is planned. Fails with the first vehicle that can't
+/// be planned.
+#[allow(clippy::too_many_arguments)]
+fn plan_in_order(
+    grid: &Grid,
+    neighbor_cache: &NeighborCacheRef,
+    vehicles: &[FleetVehicle],
+    order: &[usize],
+    bay: Option<(usize, &Goal)>,
+    horizon: u32,
+    config: &PlannerConfig,
+) -> Result<Vec<Vec<Cell>>, usize> {
+    let mut legs: Vec<(usize, &Goal)> = Vec::new();
+    if let Some((vehicle, bay)) = bay {
+        legs.push((vehicle, bay));
+    }
+    legs.extend(
+        order
+            .iter()
+            .filter(|vehicle| bay.is_none_or(|(parked, _)| parked != **vehicle))
+            .map(|vehicle| (*vehicle, &vehicles[*vehicle].goal)),
+    );
+    if let Some((vehicle, _)) = bay {
+        legs.push((vehicle, &vehicles[vehicle].goal));
+    }
+
+    let mut paths: Vec<Vec<Cell>> = vec![Vec::new(); vehicles.len()];
+    for (vehicle, goal) in legs {
+        let mut table = ReservationTable::new();
+        for (other, path) in paths.iter().enumerate() {
+            if other == vehicle {
+                continue;
+            }
+            let agent = &vehicles[other].agent;
+            if path.is_empty() {
+                table.reserve_path(agent, &[vehicles[other].start.clone()], 0, horizon);
+            } else {
+                table.reserve_path(agent, path, 0, horizon);
+            }
+        }
+        let (start, start_time) = match paths[vehicle].last() {
+            Some(pose) => (pose.clone(), paths[vehicle].len() as u32 - 1),
+            None => (vehicles[vehicle].start.clone(), 0),
+        };
+        let result = plan_reserved(
+            grid,
+            &vehicles[vehicle].agent,
+            neighbor_cache,
+            &table,
+            start,
+            start_time,
+            goal.clone(),
+            horizon,
+            config,
+        )
+        .ok_or(vehicle)?;
+        let skip = if paths[vehicle].is_empty() { 0 } else { 1 };
+        paths[vehicle].extend(result.path.into_iter().skip(skip));
+    }
+    Ok(paths)
+}
+
+/// Plans the whole fleet on one timeline with prioritized planning: by
+/// priority, each vehicle plans around the reservations of those before
+/// it, while the rest wait at their starts. When a vehicle can't be
+/// planned, it's moved to the front and the fleet replanned. If no order
+/// works, the vehicles are checked for circular waits, and for each group
+/// the lowest priority vehicle tries pulling into the nearest passing bay
+/// annotated on the grid to let the others through, or with none
+/// annotated, pulling over wherever it's out of the way of the highest
+/// priority one. Vehicles still stuck after that get no path.
+pub fn plan_fleet(
+    grid: &Grid,
+    neighbor_cache: &NeighborCacheRef,
+    vehicles: &[FleetVehicle],
+    horizon: u32,
+    config: &PlannerConfig,
+) -> FleetPlan {
+    let agents: Vec<Agent> = vehicles
+        .iter()
+        .map(|vehicle| vehicle.agent.clone())
+        .collect();
+    let starts: Vec<Cell> = vehicles
+        .iter()
+        .map(|vehicle| vehicle.start.clone())
+        .collect();
+    let alone: Vec<Vec<Cell>> = vehicles
+        .iter()
+        .map(|vehicle| {
+            planner::plan(
+                grid,
+                &vehicle.agent,
+                neighbor_cache,
+                vehicle.start.clone(),
+                vehicle.goal.clone(),
+                config,
+            )
+            .map_or_else(|_| Vec::new(), |result| result.path)
+        })
+        .collect();
+    let deadlocks = circular_waits(&wait_for_graph(&agents, &starts, &alone));
+
+    let mut plan = FleetPlan {
+        paths: vec![None; vehicles.len()],
+        deadlocks,
+        resolutions: Vec::new(),
+    };
+    let finish = |mut plan: FleetPlan, paths: Vec<Vec<Cell>>| {
+        plan.paths = paths.into_iter().map(Some).collect();
+        plan
+    };
+
+    let mut order: Vec<usize> = (0..vehicles.len()).collect();
+    order.sort_by_key(|vehicle| std::cmp::Reverse(vehicles[*vehicle].priority));
+    let mut tried = vec![order.clone()];
+    let stuck = loop {
+        match plan_in_order(
+            grid,
+            neighbor_cache,
+            vehicles,
+            &order,
+            None,
+            horizon,
+            config,
+        ) {
+            Ok(paths) => return finish(plan, paths),
+            Err(vehicle) => {
+                order.retain(|other| *other != vehicle);
+                order.insert(0, vehicle);
+                if tried.contains(&order) {
+                    break vehicle;
+                }
+                tried.push(order.clone());
+                plan.resolutions.push(Resolution::Reordered { vehicle });
+            }
+        }
+    };
+
+    for group in plan.deadlocks.clone() {
+        let Some(vehicle) = group
+            .iter()
+            .copied()
+            .min_by_key(|vehicle| vehicles[*vehicle].priority)
+        else {
+            continue;
+        };
+        let start = &vehicles[vehicle].start;
+        let (goal, resolution) = match grid
+            .annotations
+            .nearest(AnnotationKind::PassingBay, start.position)
+        {
+            Some(bay) => (
+                bay.goal(),
+                Resolution::PassingBay {
+                    vehicle,
+                    bay: bay.name.clone(),
+                },
+            ),
+            None => {
+                let Some(pose) = group
+                    .iter()
+                    .filter(|other| **other != vehicle)
+                    .max_by_key(|other| vehicles[**other].priority)
+                    .and_then(|other| {
+                        let passing = &vehicles[*other].agent;
+                        pull_over_pose(
+                            grid,
+                            &agents[vehicle],
+                            start,
+                            passing,
+                            &alone[*other],
+                            horizon,
+                        )
+                    })
+                else {
+                    continue;
+                };
+                // Any heading will do; the reservations keep whichever it
+                // arrives at clear of the others.
+                (
+                    Goal::Cell(pose.position),
+                    Resolution::PullOver { vehicle, pose },
+                )
+            }
+        };
+        if let Ok(paths) = plan_in_order(
+            grid,
+            neighbor_cache,
+            vehicles,
+            &order,
+            Some((vehicle, &goal)),
+            horizon,
+            config,
+        ) {
+            plan.resolutions.push(resolution);
+            return finish(plan, paths);
+        }
+    }
+
+    // Plan whoever can still be planned, leaving each vehicle that can't
+    // where it is.
+    let mut stuck = vec![stuck];
+    loop {
+        order.retain(|vehicle| !stuck.contains(vehicle));
+        match plan_in_order(
+            grid,
+            neighbor_cache,
+            vehicles,
+            &order,
+            None,
+            horizon,
+            config,
+        ) {
+            Ok(paths) => {
+                plan.paths = paths
+                    .into_iter()
+                    .map(|path| Some(path).filter(|path| !path.is_empty()))
+                    .collect();
+                return plan;
+            }
+            Err(vehicle) => stuck.push(vehicle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::Annotation;
+    use crate::test_support::{neighbor_cache, point_agent, Fixture, MAX_INCREMENTS};
+
+    #[test]
+    fn test_circular_waits() {
+        // 0 and 1 wait on each other, 2 waits on them, 3 and 4 and 5 form
+        // a ring.
+        let waits = vec![vec![1], vec![0], vec![0], vec![4], vec![5], vec![3]];
+        assert_eq!(circular_waits(&waits), vec![vec![0, 1], vec![3, 4, 5]]);
+        assert!(circular_waits(&[vec![1], vec![2], vec![]]).is_empty());
+    }
+
+    #[test]
+    fn test_head_on_in_corridor_uses_passing_bay() {
+        // A one-cell aisle along row 1 with a bay off it at (5, 0).
+        let mut grid = Grid::new(1.0, 12, 3);
+        for x in 0..12 {
+            grid.set_cell(x, 0, x != 5);
+            grid.set_cell(x, 2, true);
+        }
+        grid.annotations.insert(Annotation {
+            name: "bay_1".to_string(),
+            kind: AnnotationKind::PassingBay,
+            position: IVec2::new(5, 0),
+            heading: None,
+        });
+        let cache = neighbor_cache();
+        let config = Fixture::new(12, 3)
+            .states_per_cell(64 * MAX_INCREMENTS as usize)
+            .config();
+        let vehicle = |x: i32, rotation: i16, goal_x: i32, priority: i32| FleetVehicle {
+            agent: point_agent(IVec2::new(x, 1)),
+            start: Cell::new(rotation, IVec2::new(x, 1)),
+            goal: Goal::Cell(IVec2::new(goal_x, 1)),
+            priority,
+        };
+        let vehicles = vec![vehicle(1, 0, 10, 1), vehicle(10, 4, 1, 0)];
+
+        let plan = plan_fleet(&grid, &cache, &vehicles, 64, &config);
+        assert_eq!(plan.deadlocks, vec![vec![0, 1]]);
+        assert_eq!(
+            plan.resolutions.last(),
+            Some(&Resolution::PassingBay {
+                vehicle: 1,
+                bay: "bay_1".to_string()
+            })
+        );
+        let paths: Vec<Vec<Cell>> = plan.paths.into_iter().map(Option::unwrap).collect();
+        assert_eq!(paths[0].last().unwrap().position, IVec2::new(10, 1));
+        assert_eq!(paths[1].last().unwrap().position, IVec2::new(1, 1));
+        assert!(paths[1]
+            .iter()
+            .any(|pose| pose.position == IVec2::new(5, 0)));
+        // Never in the same cell at the same tick, counting parked vehicles.
+        let at = |path: &Vec<Cell>, tick: usize| path[tick.min(path.len() - 1)].position;
+        let ticks = paths[0].len().max(paths[1].len());
+        assert!((0..ticks).all(|tick| at(&paths[0], tick) != at(&paths[1], tick)));
+
+        // Without the annotation the same spot is found by searching for
+        // somewhere off the other vehicle's path.
+        grid.annotations.remove("bay_1");
+        let vehicles = vec![vehicle(1, 0, 11, 1), vehicle(10, 4, 1, 0)];
+        let plan = plan_fleet(&grid, &cache, &vehicles, 64, &config);
+        match plan.resolutions.last() {
+            Some(Resolution::PullOver { vehicle: 1, pose }) => {
+                assert_eq!(pose.position, IVec2::new(5, 0))
+            }
+            resolution => panic!("{:?}", resolution),
+        }
+        assert!(plan.paths.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn test_stuck_vehicles_leave_others_planned() {
+        // A sealed one-cell aisle along row 1, too short to pull over in,
+        // and a separate open area below it.
+        let mut grid = Grid::new(1.0, 8, 6);
+        for x in 0..8 {
+            grid.set_cell(x, 0, true);
+            grid.set_cell(x, 2, true);
+            grid.set_cell(x, 3, true);
+        }
+        grid.set_cell(0, 1, true);
+        grid.set_cell(7, 1, true);
+        let cache = neighbor_cache();
+        let config = Fixture::new(8, 6)
+            .states_per_cell(64 * MAX_INCREMENTS as usize)
+            .config();
+        let vehicle = |start: IVec2, rotation: i16, goal: IVec2, priority: i32| FleetVehicle {
+            agent: point_agent(start),
+            start: Cell::new(rotation, start),
+            goal: Goal::Cell(goal),
+            priority,
+        };
+        let vehicles = vec![
+            vehicle(IVec2::new(1, 1), 0, IVec2::new(6, 1), 2),
+            vehicle(IVec2::new(6, 1), 4, IVec2::new(1, 1), 1),
+            vehicle(IVec2::new(1, 4), 0, IVec2::new(6, 4), 0),
+        ];
+
+        let plan = plan_fleet(&grid, &cache, &vehicles, 32, &config);
+        assert_eq!(plan.deadlocks, vec![vec![0, 1]]);
+        assert!(plan.paths[0].is_none());
+        assert!(plan.paths[1].is_none());
+        let free = plan.paths[2].as_ref().unwrap();
+        assert_eq!(free.last().unwrap().position, IVec2::new(6, 4));
+    }
+}
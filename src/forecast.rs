@@ -0,0 +1,106 @@
+use notan::math::IVec2;
+
+/// Occupancy above which a forecast cell counts as blocked by default.
+pub const DEFAULT_THRESHOLD: u8 = 128;
+
+/// Predicted occupancy snapshots at evenly spaced future ticks, for
+/// machinery that moves on a known schedule. Between snapshots the
+/// occupancy is interpolated, so obstacles grow and shrink gradually.
+#[derive(Clone, Debug)]
+pub struct Forecast {
+    pub size: (i32, i32),
+    /// Tick of the first snapshot.
+    pub start: u32,
+    /// Ticks between snapshots.
+    pub interval: u32,
+    /// Interpolated occupancy at or above this blocks a cell.
+    pub threshold: u8,
+    /// Occupancy per cell, 0 free to 255 blocked, row by row.
+    snapshots: Vec<Vec<u8>>,
+}
+
+impl Forecast {
+    pub fn new(size: (i32, i32), start: u32, interval: u32) -> Self {
+        Self {
+            size,
+            start,
+            interval: interval.max(1),
+            threshold: DEFAULT_THRESHOLD,
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Appends the snapshot `interval` ticks after the last one.
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        assert_eq!(snapshot.len(), (self.size.0 * self.size.1) as usize);
+        self.snapshots.push(snapshot);
+    }
+
+    /// Appends a snapshot with `cells` certainly blocked and the rest free.
+    pub fn push_cells(&mut self, cells: &[IVec2]) {
+        let mut snapshot = vec![0; (self.size.0 * self.size.1) as usize];
+        for cell in cells {
+            if let Some(index) = self.index(*cell) {
+                snapshot[index] = 255;
+            }
+        }
+        self.push(snapshot);
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let in_bounds = cell.x >= 0 && cell.x < self.size.0 && cell.y >= 0 && cell.y < self.size.1;
+        in_bounds.then(|| (cell.y * self.size.0 + cell.x) as usize)
+    }
+
+    /// Occupancy of `cell` at `time`, interpolated between the surrounding
+    /// snapshots. Before the first and after the last snapshot, the nearest
+    /// one holds.
+    pub fn occupancy_at(&self, cell: IVec2, time: u32) -> f32 {
+        let (Some(index), Some(last)) = (self.index(cell), self.snapshots.len().checked_sub(1))
+        else {
+            return 0.0;
+        };
+        let elapsed = time.saturating_sub(self.start);
+        let slot = (elapsed / self.interval) as usize;
+        if slot >= last {
+            return self.snapshots[last][index] as f32;
+        }
+        let fraction = (elapsed % self.interval) as f32 / self.interval as f32;
+        let before = self.snapshots[slot][index] as f32;
+        let after = self.snapshots[slot + 1][index] as f32;
+        before + (after - before) * fraction
+    }
+
+    pub fn is_blocked(&self, cell: IVec2, time: u32) -> bool {
+        self.occupancy_at(cell, time) >= self.threshold as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolates_between_snapshots() {
+        let crane = IVec2::new(1, 1);
+        let mut forecast = Forecast::new((3, 3), 10, 4);
+        forecast.push_cells(&[crane]);
+        forecast.push_cells(&[]);
+
+        assert_eq!(forecast.occupancy_at(crane, 0), 255.0);
+        assert_eq!(forecast.occupancy_at(crane, 12), 127.5);
+        assert_eq!(forecast.occupancy_at(crane, 20), 0.0);
+        assert!(forecast.is_blocked(crane, 11));
+        assert!(!forecast.is_blocked(crane, 12));
+        assert!(!forecast.is_blocked(IVec2::new(0, 1), 10));
+        assert!(!forecast.is_blocked(IVec2::new(-1, 1), 10));
+    }
+}
@@ -0,0 +1,263 @@
+//! Random maps, vehicle shapes and queries for fuzzing the planner, and the
+//! invariants every plan has to hold. The `planner` target under `fuzz/`
+//! runs [`check`] on generated cases, and the tests here run it again on
+//! every input recorded under `fuzz/regressions/planner`.
+//!
+//! To record a failure, copy the crashing input from `fuzz/artifacts/planner`
+//! into `fuzz/regressions/planner` once it's fixed, so `cargo test` keeps
+//! replaying it.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCache, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+use crate::validation::{validate_path, PathViolation};
+
+/// Largest map side generated.
+pub const MAX_SIZE: i32 = 24;
+/// Heading counts generated, including ones that don't divide the
+/// cardinal directions evenly.
+pub const HEADINGS: [u16; 5] = [4, 6, 8, 12, 16];
+
+/// One generated query. Raw values are folded into range by the accessors,
+/// so every input is a valid case.
+#[derive(Clone, Debug, Default)]
+pub struct Case {
+    pub width: u8,
+    pub height: u8,
+    pub headings: u8,
+    pub arc: u8,
+    /// Vehicle length along its heading and width across it, in cells.
+    pub length: u8,
+    pub breadth: u8,
+    pub start: (u8, u8, u8),
+    pub goal: (u8, u8, u8),
+    /// Whether the goal's heading has to match too.
+    pub oriented: bool,
+    pub obstacles: Vec<(u8, u8)>,
+}
+
+impl Case {
+    /// Reads the fields in order, one byte each, then obstacles from pairs
+    /// of the bytes left. Missing bytes read as zero, so any input is a
+    /// case.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut fields = bytes.iter().copied();
+        let mut next = || fields.next().unwrap_or(0);
+        let case = Self {
+            width: next(),
+            height: next(),
+            headings: next(),
+            arc: next(),
+            length: next(),
+            breadth: next(),
+            start: (next(), next(), next()),
+            goal: (next(), next(), next()),
+            oriented: next() % 2 == 1,
+            obstacles: Vec::new(),
+        };
+        let rest = bytes.get(13..).unwrap_or_default();
+        Self {
+            obstacles: rest
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect(),
+            ..case
+        }
+    }
+
+    pub fn size(&self) -> IVec2 {
+        IVec2::new(
+            2 + self.width as i32 % (MAX_SIZE - 1),
+            2 + self.height as i32 % (MAX_SIZE - 1),
+        )
+    }
+
+    pub fn max_increments(&self) -> u16 {
+        HEADINGS[self.headings as usize % HEADINGS.len()]
+    }
+
+    pub fn arc(&self) -> u16 {
+        1 + self.arc as u16 % 3
+    }
+
+    /// Vehicle length and width, in cells.
+    pub fn vehicle_size(&self) -> Vec2 {
+        Vec2::new(
+            0.5 + (self.length % 4) as f32,
+            0.5 + (self.breadth % 4) as f32,
+        )
+    }
+
+    fn pose(&self, (x, y, rotation): (u8, u8, u8)) -> Cell {
+        let size = self.size();
+        Cell::new(
+            (rotation as u16 % self.max_increments()) as i16,
+            IVec2::new(x as i32 % size.x, y as i32 % size.y),
+        )
+    }
+
+    pub fn start(&self) -> Cell {
+        self.pose(self.start)
+    }
+
+    pub fn goal(&self) -> Goal {
+        let goal = self.pose(self.goal);
+        let cell = Goal::Cell(goal.position);
+        if self.oriented {
+            Goal::Oriented {
+                goal: Box::new(cell),
+                heading: goal.rotation,
+                tolerance: 0,
+            }
+        } else {
+            cell
+        }
+    }
+
+    pub fn grid(&self) -> Grid {
+        let size = self.size();
+        let mut grid = Grid::new(1.0, size.x, size.y);
+        for &(x, y) in &self.obstacles {
+            grid.set_cell(x as i32 % size.x, y as i32 % size.y, true);
+        }
+        grid
+    }
+}
+
+/// An invariant a returned plan broke.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// The path doesn't start at the start pose.
+    WrongStart,
+    /// The last pose isn't one the goal accepts.
+    WrongGoal,
+    /// [`validate_path`] rejected the path.
+    Invalid(PathViolation),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::WrongStart => write!(f, "path doesn't start at the start pose"),
+            Violation::WrongGoal => write!(f, "path doesn't end at the goal"),
+            Violation::Invalid(violation) => write!(f, "invalid path: {violation:?}"),
+        }
+    }
+}
+
+thread_local! {
+    static CACHES: RefCell<HashMap<(u16, u16), NeighborCacheRef>> = RefCell::new(HashMap::new());
+}
+
+/// Neighbor caches are shared between cases with the same headings and arc.
+fn neighbor_cache(max_increments: u16, arc: u16) -> NeighborCacheRef {
+    CACHES.with(|caches| {
+        caches
+            .borrow_mut()
+            .entry((max_increments, arc))
+            .or_insert_with(|| {
+                Rc::new(RefCell::new(NeighborCache::new_precomputed(
+                    max_increments,
+                    arc,
+                )))
+            })
+            .clone()
+    })
+}
+
+/// Plans the case with [`planner::plan`] and checks the result: the path
+/// starts at the start, ends where the goal accepts it, and passes
+/// [`validate_path`], so every move is a primitive of the agent's
+/// footprint that stays clear and the cost is what the moves add up to.
+/// Cases without a plan pass.
+pub fn check(case: &Case) -> Result<(), Violation> {
+    let grid = case.grid();
+    let max_increments = case.max_increments();
+    let start = case.start();
+    let agent = Agent::new(
+        start.position,
+        case.vehicle_size(),
+        start.rotation,
+        max_increments,
+    );
+    let cache = neighbor_cache(max_increments, case.arc());
+    let size = case.size();
+    let max_states = (size.x * size.y) as usize * max_increments as usize;
+    let config = PlannerConfig::new(case.arc(), max_increments, max_states);
+    let goal = case.goal();
+
+    let Ok(result) = planner::plan(&grid, &agent, &cache, start.clone(), goal.clone(), &config)
+    else {
+        return Ok(());
+    };
+    if result.path.first() != Some(&start) {
+        return Err(Violation::WrongStart);
+    }
+    if !result
+        .path
+        .last()
+        .is_some_and(|last| goal.accepts(&agent, last))
+    {
+        return Err(Violation::WrongGoal);
+    }
+    validate_path(&result.path, result.cost, &grid, &agent, &cache, &config)
+        .map_err(Violation::Invalid)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    /// Replays every recorded input through the same checks as the fuzz
+    /// target.
+    #[test]
+    fn test_replay_regressions() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions/planner");
+        let mut replayed = 0;
+        for entry in fs::read_dir(&directory).expect("regressions directory") {
+            let path = entry.unwrap().path();
+            let case = Case::from_bytes(&fs::read(&path).unwrap());
+            if let Err(violation) = check(&case) {
+                panic!("{}: {violation}\n{case:#?}", path.display());
+            }
+            replayed += 1;
+        }
+        assert!(replayed > 0, "no inputs in {}", directory.display());
+    }
+
+    #[test]
+    fn test_checks_planned_cases() {
+        // A wall across a 12x6 map, open at the top.
+        let mut bytes = vec![10, 4, 2, 0, 0, 0, 1, 2, 0, 9, 2, 0, 1];
+        for y in 0..4 {
+            bytes.extend([5, y]);
+        }
+        let case = Case::from_bytes(&bytes);
+        assert_eq!(case.size(), IVec2::new(12, 6));
+        assert_eq!(case.obstacles.len(), 4);
+        assert!(planner::plan(
+            &case.grid(),
+            &Agent::new(IVec2::ZERO, case.vehicle_size(), 0, 8),
+            &neighbor_cache(8, 1),
+            case.start(),
+            case.goal(),
+            &PlannerConfig::new(1, 8, 12 * 6 * 8),
+        )
+        .is_ok());
+        assert_eq!(check(&case), Ok(()));
+        assert_eq!(check(&Case::default()), Ok(()));
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::Cell;
+use crate::fixed;
+
+/// Where a plan is allowed to end.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Goal {
+    /// Reach this exact cell, with any footprint.
+    Cell(IVec2),
+    /// End with the whole footprint inside the rectangle (bounds inclusive).
+    Rect { min: IVec2, max: IVec2 },
+    /// End with the whole footprint inside the circle of cell centers.
+    Circle { center: IVec2, radius: f32 },
+    /// End with the whole footprint inside an arbitrary painted set of cells.
+    Region {
+        cells: HashSet<IVec2>,
+        min: IVec2,
+        max: IVec2,
+    },
+    /// Any other goal, additionally requiring the final heading to be within
+    /// `tolerance` increments of `heading`.
+    Oriented {
+        goal: Box<Goal>,
+        heading: i16,
+        tolerance: i16,
+    },
+}
+
+impl Goal {
+    /// A [`Goal::Region`] over `cells`, or `None` if there are none to end
+    /// in.
+    pub fn region(cells: HashSet<IVec2>) -> Option<Self> {
+        let first = *cells.iter().next()?;
+        let (min, max) = cells.iter().fold((first, first), |(min, max), cell| {
+            (min.min(*cell), max.max(*cell))
+        });
+        Some(Goal::Region { cells, min, max })
+    }
+
+    pub fn contains(&self, position: IVec2) -> bool {
+        match self {
+            Goal::Cell(cell) => *cell == position,
+            Goal::Rect { min, max } => position.cmpge(*min).all() && position.cmple(*max).all(),
+            Goal::Circle { center, radius } => {
+                position.as_vec2().distance(center.as_vec2()) <= *radius
+            }
+            Goal::Region { cells, .. } => cells.contains(&position),
+            Goal::Oriented { goal, .. } => goal.contains(position),
+        }
+    }
+
+    /// Inclusive bounding box of every cell the goal contains.
+    pub fn bounds(&self) -> (IVec2, IVec2) {
+        match self {
+            Goal::Cell(cell) => (*cell, *cell),
+            Goal::Rect { min, max } | Goal::Region { min, max, .. } => (*min, *max),
+            Goal::Circle { center, radius } => {
+                let extent = IVec2::splat(radius.floor() as i32);
+                (*center - extent, *center + extent)
+            }
+            Goal::Oriented { goal, .. } => goal.bounds(),
+        }
+    }
+
+    /// The point of the goal closest to `position`, used as heuristic target.
+    /// Painted regions use their bounding box to keep this cheap.
+    pub fn nearest_point(&self, position: IVec2) -> IVec2 {
+        match self {
+            Goal::Cell(cell) => *cell,
+            Goal::Rect { min, max } | Goal::Region { min, max, .. } => position.clamp(*min, *max),
+            Goal::Circle { center, radius } => {
+                let offset = (position - *center).as_vec2();
+                if offset.length() <= *radius {
+                    position
+                } else {
+                    *center + (offset.normalize() * *radius).round().as_ivec2()
+                }
+            }
+            Goal::Oriented { goal, .. } => goal.nearest_point(position),
+        }
+    }
+
+    /// Straight-line distance from `position` to the goal, in cells. Never
+    /// more than the true distance to any goal cell.
+    pub fn distance_lower_bound(&self, position: IVec2) -> f32 {
+        match self {
+            Goal::Circle { center, radius } => {
+                (position.as_vec2().distance(center.as_vec2()) - radius).max(0.0)
+            }
+            Goal::Oriented { goal, .. } => goal.distance_lower_bound(position),
+            _ => position
+                .as_vec2()
+                .distance(self.nearest_point(position).as_vec2()),
+        }
+    }
+
+    /// Integer [`Goal::distance_lower_bound`], in thousandths of a cell.
+    pub fn fixed_distance_lower_bound(&self, position: IVec2) -> u32 {
+        match self {
+            Goal::Circle { center, radius } => {
+                fixed::distance(position, *center).saturating_sub((radius * 1000.0) as u32)
+            }
+            Goal::Oriented { goal, .. } => goal.fixed_distance_lower_bound(position),
+            _ => fixed::distance(position, self.nearest_point(position)),
+        }
+    }
+
+    /// Whether a plan may end at `pose`.
+    pub fn accepts(&self, agent: &Agent, pose: &Cell) -> bool {
+        match self {
+            Goal::Cell(cell) => pose.position == *cell,
+            Goal::Oriented {
+                goal,
+                heading,
+                tolerance,
+            } => {
+                pose.rotation_to(*heading, agent.max_increments as i16) <= *tolerance
+                    && goal.accepts(agent, pose)
+            }
+            _ => {
+                self.contains(pose.position)
+                    && agent
+                        .rotation_footprint(pose.rotation)
+                        .iter()
+                        .all(|cell| self.contains(pose.position + *cell))
+            }
+        }
+    }
+}
+
+impl From<IVec2> for Goal {
+    fn from(cell: IVec2) -> Self {
+        Goal::Cell(cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_rect_goal() {
+        let goal = Goal::Rect {
+            min: IVec2::new(2, 2),
+            max: IVec2::new(6, 4),
+        };
+        assert!(goal.contains(IVec2::new(6, 4)));
+        assert!(!goal.contains(IVec2::new(7, 4)));
+        assert_eq!(goal.nearest_point(IVec2::new(0, 10)), IVec2::new(2, 4));
+        assert_eq!(goal.nearest_point(IVec2::new(3, 3)), IVec2::new(3, 3));
+
+        let agent = Agent::new(IVec2::ZERO, Vec2::new(2.5, 1.0), 0, 8);
+        assert!(goal.accepts(&agent, &Cell::new(0, IVec2::new(4, 3))));
+        // the footprint pokes out of the rectangle
+        assert!(!goal.accepts(&agent, &Cell::new(0, IVec2::new(6, 3))));
+        assert!(!goal.accepts(&agent, &Cell::new(2, IVec2::new(4, 4))));
+    }
+
+    #[test]
+    fn test_circle_goal() {
+        let goal = Goal::Circle {
+            center: IVec2::new(5, 5),
+            radius: 2.0,
+        };
+        assert!(goal.contains(IVec2::new(7, 5)));
+        assert!(!goal.contains(IVec2::new(7, 7)));
+        assert_eq!(goal.nearest_point(IVec2::new(15, 5)), IVec2::new(7, 5));
+        assert_eq!(goal.bounds(), (IVec2::new(3, 3), IVec2::new(7, 7)));
+    }
+
+    #[test]
+    fn test_oriented_goal() {
+        let goal = Goal::Oriented {
+            goal: Box::new(Goal::Cell(IVec2::new(3, 3))),
+            heading: 0,
+            tolerance: 1,
+        };
+        let agent = Agent::new(IVec2::ZERO, Vec2::new(0.5, 0.5), 0, 8);
+        assert!(goal.accepts(&agent, &Cell::new(7, IVec2::new(3, 3))));
+        assert!(!goal.accepts(&agent, &Cell::new(2, IVec2::new(3, 3))));
+        assert!(!goal.accepts(&agent, &Cell::new(0, IVec2::new(3, 4))));
+    }
+
+    #[test]
+    fn test_region_goal() {
+        let cells: HashSet<IVec2> = [IVec2::new(1, 1), IVec2::new(4, 2)].into_iter().collect();
+        let goal = Goal::region(cells).unwrap();
+        assert!(goal.contains(IVec2::new(4, 2)));
+        assert!(!goal.contains(IVec2::new(2, 2)));
+        assert_eq!(goal.bounds(), (IVec2::new(1, 1), IVec2::new(4, 2)));
+        assert_eq!(Goal::region(HashSet::new()), None);
+    }
+}
@@ -0,0 +1,347 @@
+//! The fields of [`crate::field`] relaxed on the GPU, for maps large enough
+//! that the CPU Dijkstra stalls a frame. notan exposes no compute shaders,
+//! so each pass is a fragment shader drawn over a render texture the size
+//! of the grid: every cell takes the cheapest of its own distance and its
+//! 8 neighbors' plus the step, until a pass changes nothing.
+//!
+//! Distances are kept in `f32`, exact up to 2^24 cost units, i.e. paths of
+//! about 16,000 cells; longer ones round to the nearest representable cost.
+
+use notan::graphics::prelude::*;
+use notan::math::IVec2;
+
+use crate::field::{self, DistanceField};
+use crate::goal::Goal;
+use crate::grid::Grid;
+
+/// Passes drawn between read backs that check for convergence.
+const PASSES_PER_CHECK: usize = 32;
+
+/// Distance of cells not reached yet, read back as `u32::MAX`.
+const UNREACHED: f32 = 1.0e30;
+
+const VERTEX: ShaderSource = ShaderSource {
+    sources: &[
+        (
+            "opengl",
+            b"#version 330
+layout(location = 0) in vec2 a_position;
+void main() {
+    gl_Position = vec4(a_position, 0.0, 1.0);
+}
+",
+        ),
+        (
+            "webgl2",
+            b"#version 300 es
+layout(location = 0) in vec2 a_position;
+void main() {
+    gl_Position = vec4(a_position, 0.0, 1.0);
+}
+",
+        ),
+    ],
+};
+
+/// One relaxation pass; `u_passable` is 0 where cells can't be entered.
+/// Steps cost the same as in [`crate::field`].
+macro_rules! relax_shader {
+    ($version:literal) => {
+        concat!(
+            $version,
+            "
+precision highp float;
+precision highp sampler2D;
+uniform sampler2D u_distance;
+uniform sampler2D u_passable;
+layout(location = 0) out vec4 color;
+void main() {
+    ivec2 cell = ivec2(gl_FragCoord.xy);
+    ivec2 size = textureSize(u_distance, 0);
+    float best = texelFetch(u_distance, cell, 0).r;
+    if (texelFetch(u_passable, cell, 0).r > 0.5) {
+        for (int dy = -1; dy <= 1; dy++) {
+            for (int dx = -1; dx <= 1; dx++) {
+                ivec2 next = cell + ivec2(dx, dy);
+                if ((dx == 0 && dy == 0) || any(lessThan(next, ivec2(0))) || any(greaterThanEqual(next, size))) {
+                    continue;
+                }
+                float step = (dx != 0 && dy != 0) ? 1414.0 : 1000.0;
+                best = min(best, texelFetch(u_distance, next, 0).r + step);
+            }
+        }
+    }
+    color = vec4(best, 0.0, 0.0, 1.0);
+}
+"
+        )
+        .as_bytes()
+    };
+}
+
+const RELAX: ShaderSource = ShaderSource {
+    sources: &[
+        ("opengl", relax_shader!("#version 330")),
+        ("webgl2", relax_shader!("#version 300 es")),
+    ],
+};
+
+/// The pipeline and quad behind [`GpuFieldPass::goal_distance`] and
+/// [`GpuFieldPass::obstacle_distance`]; build it once and reuse it.
+pub struct GpuFieldPass {
+    pipeline: Pipeline,
+    quad: Buffer,
+}
+
+impl GpuFieldPass {
+    pub fn new(gfx: &mut Device) -> Result<Self, String> {
+        let info = VertexInfo::new().attr(0, VertexFormat::Float32x2);
+        let pipeline = gfx
+            .create_pipeline()
+            .from(&VERTEX, &RELAX)
+            .with_vertex_info(&info)
+            .with_texture_location(0, "u_distance")
+            .with_texture_location(1, "u_passable")
+            .build()?;
+        #[rustfmt::skip]
+        let quad = gfx
+            .create_vertex_buffer()
+            .with_info(&info)
+            .with_data(&[
+                -1.0, -1.0, 1.0, -1.0, 1.0, 1.0,
+                -1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
+            ])
+            .build()?;
+        Ok(Self { pipeline, quad })
+    }
+
+    /// [`field::goal_distance`] on the GPU.
+    pub fn goal_distance(
+        &self,
+        gfx: &mut Device,
+        grid: &Grid,
+        goal: &Goal,
+    ) -> Result<DistanceField, String> {
+        let (distances, passable) = inputs(grid, &field::goal_sources(grid, goal), |cell| {
+            field::passable(grid, cell)
+        });
+        self.relax(gfx, grid.size, &distances, &passable)
+    }
+
+    /// [`field::obstacle_distance`] on the GPU.
+    pub fn obstacle_distance(
+        &self,
+        gfx: &mut Device,
+        grid: &Grid,
+    ) -> Result<DistanceField, String> {
+        let (distances, passable) = inputs(grid, &field::obstacle_sources(grid), |_| true);
+        self.relax(gfx, grid.size, &distances, &passable)
+    }
+
+    /// Draws passes, ping-ponging between two render textures, until a read
+    /// back matches the previous one (see [`converge`]).
+    fn relax(
+        &self,
+        gfx: &mut Device,
+        size: (i32, i32),
+        distances: &[f32],
+        passable: &[u8],
+    ) -> Result<DistanceField, String> {
+        let (width, height) = (size.0 as u32, size.1 as u32);
+        let initial = gfx
+            .create_texture()
+            .from_bytes(bytemuck_f32(distances), width, height)
+            .with_format(TextureFormat::R32Float)
+            .with_filter(TextureFilter::Nearest, TextureFilter::Nearest)
+            .build()?;
+        let passable = gfx
+            .create_texture()
+            .from_bytes(passable, width, height)
+            .with_format(TextureFormat::R8)
+            .with_filter(TextureFilter::Nearest, TextureFilter::Nearest)
+            .build()?;
+        let targets = [(); 2].map(|_| {
+            gfx.create_render_texture(width, height)
+                .with_format(TextureFormat::R32Float)
+                .with_filter(TextureFilter::Nearest, TextureFilter::Nearest)
+                .build()
+        });
+        let [Ok(first), Ok(second)] = targets else {
+            return Err("couldn't create the field's render textures".to_string());
+        };
+
+        let mut source = &initial;
+        let mut drawn = 0;
+        let bytes = converge(distances, distances.len(), |passes, bytes| {
+            for _ in 0..passes {
+                let target = if drawn % 2 == 0 { &first } else { &second };
+                let mut renderer = Renderer::new(width, height);
+                renderer.begin(None);
+                renderer.set_pipeline(&self.pipeline);
+                renderer.bind_texture(0, source);
+                renderer.bind_texture(1, &passable);
+                renderer.bind_buffer(&self.quad);
+                renderer.draw(0, 6);
+                renderer.end();
+                gfx.render_to(target, renderer.commands());
+                source = target.texture();
+                drawn += 1;
+            }
+            gfx.read_pixels(source).read_to(bytes)
+        })?;
+        Ok(decode(size, &bytes))
+    }
+}
+
+/// Runs `draw(passes, bytes)`, which draws that many passes and reads the
+/// field back into `bytes`, until a read back matches the previous one or
+/// `max_passes` have been drawn. Returns the last read back, or `initial`
+/// if no pass was drawn. Every pass settles at least one more cell along
+/// each shortest path, so one pass per cell always converges.
+fn converge(
+    initial: &[f32],
+    max_passes: usize,
+    mut draw: impl FnMut(usize, &mut [u8]) -> Result<(), String>,
+) -> Result<Vec<u8>, String> {
+    let mut previous = bytemuck_f32(initial).to_vec();
+    let mut bytes = vec![0; previous.len()];
+    let mut passes = 0;
+    while passes < max_passes {
+        let batch = PASSES_PER_CHECK.min(max_passes - passes);
+        draw(batch, &mut bytes)?;
+        passes += batch;
+        if bytes == previous {
+            break;
+        }
+        std::mem::swap(&mut bytes, &mut previous);
+    }
+    Ok(previous)
+}
+
+/// Starting distances, zero at `sources`, and which cells can be entered,
+/// row by row from the grid's origin.
+fn inputs(grid: &Grid, sources: &[IVec2], passable: impl Fn(IVec2) -> bool) -> (Vec<f32>, Vec<u8>) {
+    let mut distances = vec![UNREACHED; (grid.size.0 * grid.size.1) as usize];
+    for source in sources {
+        distances[grid.index(source.x, source.y)] = 0.0;
+    }
+    let cells = (0..grid.size.1)
+        .flat_map(|y| (0..grid.size.0).map(move |x| IVec2::new(x, y)))
+        .map(|cell| if passable(cell) { 255 } else { 0 })
+        .collect();
+    (distances, cells)
+}
+
+fn bytemuck_f32(values: &[f32]) -> &[u8] {
+    // SAFETY: `f32` has no padding or invalid bit patterns, and `u8` has no
+    // alignment requirement.
+    unsafe { std::slice::from_raw_parts(values.as_ptr().cast(), std::mem::size_of_val(values)) }
+}
+
+/// A read back field, unreached cells back at `u32::MAX`.
+fn decode(size: (i32, i32), bytes: &[u8]) -> DistanceField {
+    DistanceField {
+        size,
+        values: bytes
+            .chunks_exact(4)
+            .map(|texel| f32::from_ne_bytes([texel[0], texel[1], texel[2], texel[3]]))
+            .map(|value| {
+                if value >= UNREACHED {
+                    u32::MAX
+                } else {
+                    value.round() as u32
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{DIAGONAL_COST, STRAIGHT_COST};
+
+    /// The shader's pass, run on the CPU.
+    fn relax_on_cpu(size: (i32, i32), distances: &[f32], passable: &[u8]) -> Vec<f32> {
+        let mut next = distances.to_vec();
+        for y in 0..size.1 {
+            for x in 0..size.0 {
+                let index = (y * size.0 + x) as usize;
+                if passable[index] == 0 {
+                    continue;
+                }
+                for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if (dx == 0 && dy == 0) || nx < 0 || ny < 0 || nx >= size.0 || ny >= size.1 {
+                        continue;
+                    }
+                    let step = if dx != 0 && dy != 0 {
+                        DIAGONAL_COST
+                    } else {
+                        STRAIGHT_COST
+                    };
+                    let neighbor = distances[(ny * size.0 + nx) as usize] + step as f32;
+                    next[index] = next[index].min(neighbor);
+                }
+            }
+        }
+        next
+    }
+
+    #[test]
+    fn test_relaxation_matches_dijkstra() {
+        let mut grid = Grid::new(1.0, 12, 9);
+        for y in 0..=6 {
+            grid.set_cell(5, y, true);
+        }
+        let goal = Goal::Cell(IVec2::new(9, 2));
+        let (initial, passable) = inputs(&grid, &field::goal_sources(&grid, &goal), |cell| {
+            field::passable(&grid, cell)
+        });
+        // `relax`'s loop, with the CPU pass standing in for the draw calls.
+        let run = |max_passes| {
+            let mut distances = initial.clone();
+            let mut drawn = 0;
+            let bytes = converge(&initial, max_passes, |passes, bytes| {
+                for _ in 0..passes {
+                    distances = relax_on_cpu(grid.size, &distances, &passable);
+                    drawn += 1;
+                }
+                bytes.copy_from_slice(bytemuck_f32(&distances));
+                Ok(())
+            })
+            .unwrap();
+            (decode(grid.size, &bytes), drawn)
+        };
+
+        let (relaxed, drawn) = run(initial.len());
+        assert_eq!(relaxed, field::goal_distance(&grid, &goal));
+        assert_eq!(relaxed.at(IVec2::new(5, 3)), u32::MAX);
+        // Stops at the first read back that changes nothing.
+        assert_eq!(drawn, 2 * PASSES_PER_CHECK);
+
+        // Without passes it hands back the sources, not a field of zeros.
+        let (untouched, drawn) = run(0);
+        assert_eq!(drawn, 0);
+        assert_eq!(untouched.at(IVec2::new(9, 2)), 0);
+        assert_eq!(untouched.at(IVec2::new(0, 0)), u32::MAX);
+
+        // A budget short of a full batch draws only what's left of it.
+        let (partial, drawn) = run(3);
+        assert_eq!(drawn, 3);
+        assert_eq!(partial.at(IVec2::new(6, 2)), 3 * STRAIGHT_COST);
+        assert_eq!(partial.at(IVec2::new(0, 0)), u32::MAX);
+    }
+
+    #[test]
+    fn test_shader_matches_cpu_pass() {
+        // Drawing needs a GL context, so check the shader's step costs and
+        // passability test against the CPU mirror's instead.
+        let step = format!("? {DIAGONAL_COST}.0 : {STRAIGHT_COST}.0");
+        for (_, source) in RELAX.sources {
+            let source = std::str::from_utf8(source).unwrap();
+            assert!(source.contains(&step));
+            assert!(source.contains("u_passable, cell, 0).r > 0.5"));
+        }
+    }
+}
@@ -0,0 +1,1026 @@
+use geo::{BoundingRect, Contains, ConvexHull, Coord, Intersects, MultiPolygon, Polygon, Rect};
+
+use crate::agent::Agent;
+use crate::annotations::Annotations;
+use crate::bitarray::BitArray;
+use crate::cell::Cell;
+use crate::collision;
+use crate::congestion::CongestionMap;
+use crate::door::Door;
+use crate::patch::{BlockedRun, MapPatch, PatchError};
+use notan::math::{IVec2, Vec2};
+use std::f32::consts::TAU;
+
+/// Lower bound on the flow cost factor, so moves with the current are never free.
+pub const MIN_FLOW_FACTOR: f32 = 0.1;
+
+/// Slack on [`HeadingRange::contains`], so an increment landing exactly on
+/// a range's edge isn't lost to rounding.
+const HEADING_EPSILON: f32 = 1e-4;
+
+/// Headings within `tolerance` radians of `center`, either way. Angles are
+/// in radians counter-clockwise from +x, like
+/// [`Cell::increment_to_heading`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeadingRange {
+    pub center: f32,
+    pub tolerance: f32,
+}
+
+impl HeadingRange {
+    pub fn new(center: f32, tolerance: f32) -> Self {
+        Self { center, tolerance }
+    }
+
+    pub fn contains(&self, angle: f32) -> bool {
+        let offset = (angle - self.center).rem_euclid(TAU);
+        offset.min(TAU - offset) <= self.tolerance + HEADING_EPSILON
+    }
+}
+
+pub struct Grid {
+    /// Drawn size of a cell, in screen units.
+    pub cell_size: f32,
+    /// Real-world size of a cell, in meters.
+    pub resolution: f32,
+    pub size: (i32, i32),
+    pub cells: BitArray,
+    /// World-space obstacle outlines, kept alongside their rasterized cells.
+    pub polygons: Vec<Polygon<f64>>,
+    /// World-space zones the footprint may never overlap. Unlike obstacle
+    /// polygons they aren't rasterized.
+    pub keep_out: Vec<Polygon<f64>>,
+    /// World-space boundaries the whole footprint must stay inside one of,
+    /// unless there are none.
+    pub keep_in: Vec<Polygon<f64>>,
+    /// Optional per-cell current (conveyor flow, water current), allocated on first use.
+    pub flow: Option<Vec<Vec2>>,
+    /// Optional heightmap, in cell units, allocated on first use.
+    pub heights: Option<Vec<f32>>,
+    /// Optional occupancy probability per cell, 0 free to 255 certainly
+    /// blocked, allocated on first use. Independent of the blocked cells.
+    pub occupancy: Option<Vec<u8>>,
+    /// Optional extra cost for covering each cell, for people or fragile
+    /// zones that are avoided when possible but never blocked. Allocated
+    /// on first use.
+    pub soft_costs: Option<Vec<u32>>,
+    /// Optional top speed inside each cell, in meters per second, for
+    /// facility rules like walking pace near pick stations. Unlimited
+    /// cells are infinite. Allocated on first use.
+    pub speed_limits: Option<Vec<f32>>,
+    /// Optional headings the footprint may cover each cell at, for
+    /// overhangs a vehicle only fits under when aligned. Kept as angles so
+    /// they hold at any number of increments; an empty list leaves the cell
+    /// unrestricted. Allocated on first use.
+    pub heading_ranges: Option<Vec<Vec<HeadingRange>>>,
+    /// Named cell groups that block the grid while closed.
+    pub doors: Vec<Door>,
+    /// Optional traffic history charged on top of move costs.
+    pub congestion: Option<CongestionMap>,
+    /// Named docks, chargers and spawn points, see [`crate::persist::save_annotations`].
+    pub annotations: Annotations,
+}
+impl Grid {
+    pub fn new(cell_size: f32, width: i32, height: i32) -> Self {
+        let size = (width / cell_size as i32, height / cell_size as i32);
+        println!("Grid size: {:?}", size);
+        let cells = BitArray::new((size.0 * size.1) as usize);
+        Self {
+            cell_size,
+            resolution: 1.0,
+            size,
+            cells,
+            polygons: Vec::new(),
+            keep_out: Vec::new(),
+            keep_in: Vec::new(),
+            flow: None,
+            heights: None,
+            occupancy: None,
+            soft_costs: None,
+            speed_limits: None,
+            heading_ranges: None,
+            doors: Vec::new(),
+            congestion: None,
+            annotations: Annotations::default(),
+        }
+    }
+
+    pub fn index(&self, x: i32, y: i32) -> usize {
+        (y * self.size.0 + x) as usize
+    }
+
+    pub fn xy(&self, index: usize) -> (i32, i32) {
+        let x = index as i32 % self.size.0;
+        let y = index as i32 / self.size.0;
+        (x, y)
+    }
+
+    /// Cell containing the world-space `point`. Points left of or above the
+    /// grid map to negative cells rather than cell zero.
+    pub fn world_to_cell(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    /// World-space center of `cell`.
+    pub fn cell_to_world_center(&self, cell: IVec2) -> Vec2 {
+        (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    /// Pose at the cell containing `point`, facing the heading increment
+    /// nearest to `heading` (radians).
+    pub fn snap_pose(&self, point: Vec2, heading: f32, max_increments: u16) -> Cell {
+        Cell::new(
+            Cell::heading_to_increment(heading, max_increments),
+            self.world_to_cell(point),
+        )
+    }
+
+    /// Converts a distance in cells to meters.
+    pub fn to_meters(&self, cells: f32) -> f32 {
+        cells * self.resolution
+    }
+
+    /// Converts a distance in meters to cells.
+    pub fn to_cells(&self, meters: f32) -> f32 {
+        meters / self.resolution
+    }
+
+    /// Center of `cell`, in meters from the grid origin.
+    pub fn cell_to_meters(&self, cell: IVec2) -> Vec2 {
+        (cell.as_vec2() + Vec2::splat(0.5)) * self.resolution
+    }
+
+    /// Cell containing the point `meters` from the grid origin.
+    pub fn meters_to_cell(&self, meters: Vec2) -> IVec2 {
+        (meters / self.resolution).floor().as_ivec2()
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.size.0 && y >= 0 && y < self.size.1
+    }
+
+    pub fn is_cell_blocked(&self, x: i32, y: i32) -> bool {
+        if !self.in_bounds(x, y) {
+            return true;
+        }
+        self.cells.get_bool(self.index(x, y))
+    }
+
+    /// The 64 cells of row `y` starting at column `x`, bit `i` set if cell
+    /// `x + i` is blocked. Cells outside the grid count as blocked.
+    pub fn row_bits(&self, x: i32, y: i32) -> u64 {
+        if y < 0 || y >= self.size.1 {
+            return u64::MAX;
+        }
+        let start = x.max(0);
+        let end = (x + 64).min(self.size.0);
+        if start >= end {
+            return u64::MAX;
+        }
+        let len = (end - start) as u32;
+        let in_row = if len >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << len) - 1
+        };
+        let bits = self.cells.bits_from(self.index(start, y)) & in_row;
+        let shift = (start - x) as u32;
+        (bits << shift) | !(in_row << shift)
+    }
+
+    /// Checks the cell under `pose` and the agent's footprint around it.
+    pub fn is_pose_blocked(&self, agent: &Agent, pose: &Cell) -> bool {
+        if self.heading_ranges.is_some() && self.is_pose_heading_restricted(agent, pose) {
+            return true;
+        }
+        if let Some(rows) = agent.row_masks(pose.rotation) {
+            // Rows are fetched four at a time, one AVX2 test's worth, so no
+            // footprint allocates however tall it is.
+            return rows
+                .origins
+                .chunks(4)
+                .zip(rows.masks.chunks(4))
+                .any(|(origins, masks)| {
+                    let mut words = [0; 4];
+                    for (word, origin) in words.iter_mut().zip(origins) {
+                        *word =
+                            self.row_bits(origin.x + pose.position.x, origin.y + pose.position.y);
+                    }
+                    collision::any_overlap(masks, &words[..origins.len()])
+                });
+        }
+        self.is_cell_blocked(pose.position.x, pose.position.y)
+            || agent.rotation_footprint(pose.rotation).iter().any(|cell| {
+                self.is_cell_blocked(cell.x + pose.position.x, cell.y + pose.position.y)
+            })
+    }
+
+    /// Limits the headings the footprint may cover `(x, y)` at to `ranges`.
+    /// No ranges lifts the restriction.
+    pub fn set_allowed_headings(&mut self, x: i32, y: i32, ranges: &[HeadingRange]) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            let len = (self.size.0 * self.size.1) as usize;
+            self.heading_ranges
+                .get_or_insert_with(|| vec![Vec::new(); len])[index] = ranges.to_vec();
+        }
+    }
+
+    /// Ranges the footprint may cover `(x, y)` at, empty if any heading is.
+    pub fn allowed_headings(&self, x: i32, y: i32) -> &[HeadingRange] {
+        match &self.heading_ranges {
+            Some(ranges) if self.in_bounds(x, y) => &ranges[self.index(x, y)],
+            _ => &[],
+        }
+    }
+
+    /// Whether the footprint may cover `(x, y)` heading at `angle` radians.
+    pub fn is_heading_allowed(&self, x: i32, y: i32, angle: f32) -> bool {
+        let ranges = self.allowed_headings(x, y);
+        ranges.is_empty() || ranges.iter().any(|range| range.contains(angle))
+    }
+
+    /// Whether the footprint at `pose` covers a cell that forbids its
+    /// heading. Rotations are taken in the agent's increments and wrap.
+    pub fn is_pose_heading_restricted(&self, agent: &Agent, pose: &Cell) -> bool {
+        self.heading_ranges.is_some()
+            && agent
+                .heading_restricted_cell(self, pose.position, pose.rotation)
+                .is_some()
+    }
+
+    /// Returns the first blocked cell on the supercover line from `from` to
+    /// `to`, including both endpoints, or `None` if the line is clear.
+    pub fn raycast(&self, from: IVec2, to: IVec2) -> Option<IVec2> {
+        supercover_line(from, to)
+            .into_iter()
+            .find(|cell| self.is_cell_blocked(cell.x, cell.y))
+    }
+
+    /// Checks whether the agent can slide in a straight line from `a` to
+    /// `b`, testing the footprints of both headings on every cell of the line.
+    pub fn footprint_visible(&self, a: &Cell, b: &Cell, agent: &Agent) -> bool {
+        supercover_line(a.position, b.position)
+            .into_iter()
+            .all(|position| {
+                !self.is_pose_blocked(agent, &Cell::new(a.rotation, position))
+                    && !self.is_pose_blocked(agent, &Cell::new(b.rotation, position))
+            })
+    }
+
+    pub fn toggle_cell(&mut self, x: i32, y: i32) {
+        let index = self.index(x, y);
+        let existing = self.cells.get_bool(index);
+        self.cells.set_bool(index, !existing);
+    }
+
+    pub fn set_cell(&mut self, x: i32, y: i32, blocked: bool) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            self.cells.set_bool(index, blocked);
+        }
+    }
+
+    /// Adds a door, blocking or clearing its cells to match its state.
+    pub fn add_door(&mut self, door: Door) {
+        self.doors.push(door);
+        self.apply_door(self.doors.len() - 1);
+    }
+
+    /// Opens or closes the door called `name`. Returns `false` if there is
+    /// no such door.
+    pub fn set_door_open(&mut self, name: &str, open: bool) -> bool {
+        let Some(index) = self.doors.iter().position(|door| door.name == name) else {
+            return false;
+        };
+        self.doors[index].open = open;
+        self.apply_door(index);
+        true
+    }
+
+    /// Opens and closes scheduled doors for the given tick.
+    pub fn update_doors(&mut self, time: u32) {
+        for index in 0..self.doors.len() {
+            if let Some(schedule) = self.doors[index].schedule {
+                self.doors[index].open = schedule.is_open_at(time);
+                self.apply_door(index);
+            }
+        }
+    }
+
+    pub fn is_closed_door(&self, x: i32, y: i32) -> bool {
+        let position = IVec2::new(x, y);
+        self.doors
+            .iter()
+            .any(|door| !door.open && door.contains(position))
+    }
+
+    /// Checks whether `pose` overlaps closed doors and nothing else that
+    /// blocks it.
+    pub fn is_pose_blocked_by_doors(&self, agent: &Agent, pose: &Cell) -> bool {
+        let cells = agent
+            .rotation_footprint(pose.rotation)
+            .iter()
+            .map(|cell| *cell + pose.position)
+            .chain(std::iter::once(pose.position));
+        let mut any_door = false;
+        for cell in cells {
+            if self.is_cell_blocked(cell.x, cell.y) {
+                if !self.is_closed_door(cell.x, cell.y) {
+                    return false;
+                }
+                any_door = true;
+            }
+        }
+        any_door
+    }
+
+    fn apply_door(&mut self, index: usize) {
+        let door = &self.doors[index];
+        let blocked = !door.open;
+        for cell in door.cells.clone() {
+            self.set_cell(cell.x, cell.y, blocked);
+        }
+    }
+
+    pub fn set_flow(&mut self, x: i32, y: i32, flow: Vec2) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            let len = (self.size.0 * self.size.1) as usize;
+            self.flow.get_or_insert_with(|| vec![Vec2::ZERO; len])[index] = flow;
+        }
+    }
+
+    pub fn flow_at(&self, x: i32, y: i32) -> Vec2 {
+        match &self.flow {
+            Some(flow) if self.in_bounds(x, y) => flow[self.index(x, y)],
+            _ => Vec2::ZERO,
+        }
+    }
+
+    /// Cost multiplier for moving from `from` to `to` through the current at
+    /// `from`: below 1 when moving with it, above 1 when moving against it.
+    pub fn flow_cost_factor(&self, from: IVec2, to: IVec2) -> f32 {
+        let direction = (to - from).as_vec2().normalize_or_zero();
+        let flow = self.flow_at(from.x, from.y);
+        (1.0 - flow.dot(direction)).max(MIN_FLOW_FACTOR)
+    }
+
+    pub fn set_height(&mut self, x: i32, y: i32, height: f32) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            let len = (self.size.0 * self.size.1) as usize;
+            self.heights.get_or_insert_with(|| vec![0.0; len])[index] = height;
+        }
+    }
+
+    pub fn height_at(&self, x: i32, y: i32) -> f32 {
+        match &self.heights {
+            Some(heights) if self.in_bounds(x, y) => heights[self.index(x, y)],
+            _ => 0.0,
+        }
+    }
+
+    pub fn set_occupancy(&mut self, x: i32, y: i32, occupancy: u8) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            let len = (self.size.0 * self.size.1) as usize;
+            self.occupancy.get_or_insert_with(|| vec![0; len])[index] = occupancy;
+        }
+    }
+
+    pub fn set_soft_cost(&mut self, x: i32, y: i32, cost: u32) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            let len = (self.size.0 * self.size.1) as usize;
+            self.soft_costs.get_or_insert_with(|| vec![0; len])[index] = cost;
+        }
+    }
+
+    pub fn soft_cost_at(&self, x: i32, y: i32) -> u32 {
+        match &self.soft_costs {
+            Some(costs) if self.in_bounds(x, y) => costs[self.index(x, y)],
+            _ => 0,
+        }
+    }
+
+    /// Highest soft cost under the agent's footprint at `pose`.
+    pub fn pose_soft_cost(&self, agent: &Agent, pose: &Cell) -> u32 {
+        if self.soft_costs.is_none() {
+            return 0;
+        }
+        agent
+            .rotation_footprint(pose.rotation)
+            .iter()
+            .map(|cell| *cell + pose.position)
+            .chain(std::iter::once(pose.position))
+            .map(|cell| self.soft_cost_at(cell.x, cell.y))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn set_speed_limit(&mut self, x: i32, y: i32, limit: f32) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            let len = (self.size.0 * self.size.1) as usize;
+            self.speed_limits
+                .get_or_insert_with(|| vec![f32::INFINITY; len])[index] = limit;
+        }
+    }
+
+    /// Top speed in the cell, in meters per second; infinite if unlimited.
+    pub fn speed_limit_at(&self, x: i32, y: i32) -> f32 {
+        match &self.speed_limits {
+            Some(limits) if self.in_bounds(x, y) => limits[self.index(x, y)],
+            _ => f32::INFINITY,
+        }
+    }
+
+    /// Lowest speed limit under the agent's footprint at `pose`.
+    pub fn pose_speed_limit(&self, agent: &Agent, pose: &Cell) -> f32 {
+        if self.speed_limits.is_none() {
+            return f32::INFINITY;
+        }
+        agent
+            .rotation_footprint(pose.rotation)
+            .iter()
+            .map(|cell| *cell + pose.position)
+            .chain(std::iter::once(pose.position))
+            .map(|cell| self.speed_limit_at(cell.x, cell.y))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Limits every cell the world-space `zone` touches to `limit`, keeping
+    /// lower limits already set.
+    pub fn add_speed_zone(&mut self, zone: &Polygon<f64>, limit: f32) {
+        for cell in self.polygon_cells(zone) {
+            let limit = limit.min(self.speed_limit_at(cell.x, cell.y));
+            self.set_speed_limit(cell.x, cell.y, limit);
+        }
+    }
+
+    /// The changes that turn this grid into `other`, for sending map
+    /// updates instead of whole grids. Both must be the same size.
+    pub fn diff(&self, other: &Grid) -> MapPatch {
+        let mut patch = MapPatch {
+            size: IVec2::new(self.size.0, self.size.1),
+            ..MapPatch::default()
+        };
+        for index in 0..(self.size.0 * self.size.1) as usize {
+            let (x, y) = self.xy(index);
+            let blocked = other.is_cell_blocked(x, y);
+            if blocked != self.is_cell_blocked(x, y) {
+                match patch.blocked.last_mut() {
+                    Some(run)
+                        if run.blocked == blocked && (run.start + run.len) as usize == index =>
+                    {
+                        run.len += 1
+                    }
+                    _ => patch.blocked.push(BlockedRun {
+                        start: index as u32,
+                        len: 1,
+                        blocked,
+                    }),
+                }
+            }
+            let cost = other.soft_cost_at(x, y);
+            if cost != self.soft_cost_at(x, y) {
+                patch.soft_costs.push((index as u32, cost));
+            }
+            let limit = other.speed_limit_at(x, y);
+            if limit != self.speed_limit_at(x, y) {
+                patch.speed_limits.push((index as u32, limit));
+            }
+        }
+        patch
+    }
+
+    /// Applies a patch from [`Grid::diff`] made against a grid like this
+    /// one. A patch that doesn't fit (see [`MapPatch::validate`]) changes
+    /// nothing.
+    pub fn apply_patch(&mut self, patch: &MapPatch) -> Result<(), PatchError> {
+        patch.validate(IVec2::new(self.size.0, self.size.1))?;
+        for run in &patch.blocked {
+            for index in run.start..run.start + run.len {
+                let (x, y) = self.xy(index as usize);
+                self.set_cell(x, y, run.blocked);
+            }
+        }
+        for (index, cost) in &patch.soft_costs {
+            let (x, y) = self.xy(*index as usize);
+            self.set_soft_cost(x, y, *cost);
+        }
+        for (index, limit) in &patch.speed_limits {
+            let (x, y) = self.xy(*index as usize);
+            self.set_speed_limit(x, y, *limit);
+        }
+        Ok(())
+    }
+
+    /// Probability that the cell is occupied. Cells outside the grid are.
+    pub fn occupancy_at(&self, x: i32, y: i32) -> f32 {
+        if !self.in_bounds(x, y) {
+            return 1.0;
+        }
+        match &self.occupancy {
+            Some(occupancy) => occupancy[self.index(x, y)] as f32 / 255.0,
+            None => 0.0,
+        }
+    }
+
+    /// Rise per cell traveled going from `from` to `to`; negative downhill.
+    pub fn grade(&self, from: IVec2, to: IVec2) -> f32 {
+        let distance = from.as_vec2().distance(to.as_vec2());
+        if distance == 0.0 {
+            return 0.0;
+        }
+        (self.height_at(to.x, to.y) - self.height_at(from.x, from.y)) / distance
+    }
+
+    /// A grid `factor` times coarser, where a merged cell is blocked if any
+    /// of the cells it covers is (conservative blocking).
+    pub fn downsample(&self, factor: i32) -> Grid {
+        let size = (
+            (self.size.0 + factor - 1) / factor,
+            (self.size.1 + factor - 1) / factor,
+        );
+        let mut coarse = Grid::new(1.0, size.0, size.1);
+        coarse.cell_size = self.cell_size * factor as f32;
+        coarse.resolution = self.resolution * factor as f32;
+        for y in 0..self.size.1 {
+            for x in 0..self.size.0 {
+                if self.is_cell_blocked(x, y) {
+                    coarse.set_cell(x / factor, y / factor, true);
+                }
+            }
+        }
+        // Geofences are in world space, so they carry over as they are.
+        coarse.keep_out = self.keep_out.clone();
+        coarse.keep_in = self.keep_in.clone();
+        coarse
+    }
+
+    /// Adds a world-space obstacle polygon, blocking every cell it touches.
+    pub fn add_polygon(&mut self, polygon: Polygon<f64>) {
+        for cell in self.polygon_cells(&polygon) {
+            self.set_cell(cell.x, cell.y, true);
+        }
+        self.polygons.push(polygon);
+    }
+
+    /// Cells in the grid that `polygon` touches.
+    fn polygon_cells(&self, polygon: &Polygon<f64>) -> Vec<IVec2> {
+        let mut cells = Vec::new();
+        let Some(bounds) = polygon.bounding_rect() else {
+            return cells;
+        };
+        let cell_size = self.cell_size as f64;
+        let min_x = ((bounds.min().x / cell_size).floor() as i32).max(0);
+        let min_y = ((bounds.min().y / cell_size).floor() as i32).max(0);
+        let max_x = ((bounds.max().x / cell_size).floor() as i32).min(self.size.0 - 1);
+        let max_y = ((bounds.max().y / cell_size).floor() as i32).min(self.size.1 - 1);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let cell = Rect::new(
+                    Coord {
+                        x: x as f64 * cell_size,
+                        y: y as f64 * cell_size,
+                    },
+                    Coord {
+                        x: (x + 1) as f64 * cell_size,
+                        y: (y + 1) as f64 * cell_size,
+                    },
+                );
+                if polygon.intersects(&cell) {
+                    cells.push(IVec2::new(x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Checks the footprint at `pose` against the keep-out zones and keep-in
+    /// boundaries.
+    pub fn violates_geofence(&self, agent: &Agent, pose: &Cell) -> bool {
+        if self.keep_out.is_empty() && self.keep_in.is_empty() {
+            return false;
+        }
+        let footprint = agent.footprint_polygon(pose.position, pose.rotation, self.cell_size);
+        let outside = !self.keep_in.is_empty()
+            && !self.keep_in.iter().any(|fence| fence.contains(&footprint));
+        outside || self.keep_out.iter().any(|zone| zone.intersects(&footprint))
+    }
+
+    /// Checks the footprint swept between two poses against the original
+    /// obstacle polygons, which is tighter than the rasterized cells.
+    pub fn sweep_hits_polygons(&self, agent: &Agent, from: &Cell, to: &Cell) -> bool {
+        if self.polygons.is_empty() {
+            return false;
+        }
+        let swept = MultiPolygon::new(vec![
+            agent.footprint_polygon(from.position, from.rotation, self.cell_size),
+            agent.footprint_polygon(to.position, to.rotation, self.cell_size),
+        ])
+        .convex_hull();
+        self.polygons
+            .iter()
+            .any(|polygon| polygon.intersects(&swept))
+    }
+}
+
+/// Every cell the segment between the centers of `from` and `to` touches.
+/// When the segment passes exactly through a corner, both cells beside the
+/// corner are included.
+pub fn supercover_line(from: IVec2, to: IVec2) -> Vec<IVec2> {
+    let delta = to - from;
+    let (nx, ny) = (delta.x.abs(), delta.y.abs());
+    let step = IVec2::new(delta.x.signum(), delta.y.signum());
+
+    let mut cells = Vec::with_capacity((nx + ny + 1) as usize);
+    let mut position = from;
+    cells.push(position);
+    let (mut ix, mut iy) = (0, 0);
+    while ix < nx || iy < ny {
+        let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+        if decision == 0 {
+            cells.push(position + IVec2::new(step.x, 0));
+            cells.push(position + IVec2::new(0, step.y));
+            position += step;
+            ix += 1;
+            iy += 1;
+        } else if decision < 0 {
+            position.x += step.x;
+            ix += 1;
+        } else {
+            position.y += step.y;
+            iy += 1;
+        }
+        cells.push(position);
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{polygon, LineString};
+
+    use super::*;
+    use crate::door::DoorSchedule;
+    use crate::test_support::point_agent;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_world_snapping() {
+        let grid = Grid::new(20.0, 10, 10);
+        assert_eq!(grid.world_to_cell(Vec2::new(45.0, 19.9)), IVec2::new(2, 0));
+        assert_eq!(grid.world_to_cell(Vec2::new(-1.0, 0.0)), IVec2::new(-1, 0));
+        assert_eq!(
+            grid.cell_to_world_center(IVec2::new(2, 0)),
+            Vec2::new(50.0, 10.0)
+        );
+        let pose = grid.snap_pose(Vec2::new(45.0, 30.0), -0.1, 8);
+        assert_eq!(pose, Cell::new(0, IVec2::new(2, 1)));
+        let pose = grid.snap_pose(Vec2::new(45.0, 30.0), 3.0 * PI / 4.0 + 0.2, 8);
+        assert_eq!(pose.rotation, 3);
+        assert_eq!(Cell::heading_to_increment(-PI / 2.0, 8), 6);
+        assert_eq!(Cell::increment_to_heading(2, 8), PI / 2.0);
+    }
+
+    #[test]
+    fn test_row_masks_match_cells() {
+        let mut grid = Grid::new(1.0, 70, 20);
+        let mut seed = 12345u32;
+        for y in 0..20 {
+            for x in 0..70 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                grid.set_cell(x, y, (seed >> 16).is_multiple_of(9));
+            }
+        }
+        let agent = Agent::new(IVec2::new(0, 0), Vec2::new(5.5, 2.5), 0, 32);
+        for rotation in 0..32 {
+            for y in -3..23 {
+                for x in -3..73 {
+                    let pose = Cell::new(rotation, IVec2::new(x, y));
+                    let by_cells = grid.is_cell_blocked(x, y)
+                        || agent
+                            .footprint(pose.position, rotation)
+                            .iter()
+                            .any(|cell| grid.is_cell_blocked(cell.x, cell.y));
+                    assert_eq!(grid.is_pose_blocked(&agent, &pose), by_cells);
+                }
+            }
+        }
+        assert_eq!(grid.row_bits(-2, 0) & 0b11, 0b11);
+        assert_eq!(grid.row_bits(0, 20), u64::MAX);
+    }
+
+    #[test]
+    fn test_tall_footprints() {
+        // 40 rows tall, past the 16 rows the collision check used to keep
+        // on the stack.
+        let mut grid = Grid::new(1.0, 60, 60);
+        let agent = Agent::new(IVec2::ZERO, Vec2::new(40.0, 1.0), 2, 8);
+        let pose = Cell::new(2, IVec2::new(30, 30));
+        assert!(agent.row_masks(2).unwrap().masks.len() > 16);
+        assert!(!grid.is_pose_blocked(&agent, &pose));
+        // Only the last row is hit.
+        grid.set_cell(30, 49, true);
+        assert!(grid.is_pose_blocked(&agent, &pose));
+        assert_eq!(
+            agent.first_colliding_cell(&grid, pose.position, pose.rotation),
+            Some(IVec2::new(30, 49))
+        );
+    }
+
+    #[test]
+    fn test_collision_queries() {
+        let mut grid = Grid::new(1.0, 12, 12);
+        grid.set_cell(7, 5, true);
+        grid.set_cell(8, 6, true);
+        let agent = Agent::new(IVec2::new(0, 0), Vec2::new(3.0, 3.0), 0, 8);
+
+        assert!(!agent.collides_at(&grid, IVec2::new(3, 5), 0));
+        assert_eq!(agent.first_colliding_cell(&grid, IVec2::new(3, 5), 0), None);
+        assert!(agent.collides_at(&grid, IVec2::new(7, 6), 8));
+        assert_eq!(
+            agent.first_colliding_cell(&grid, IVec2::new(7, 6), 0),
+            Some(IVec2::new(7, 5))
+        );
+        // Off the grid counts as blocked.
+        assert!(agent
+            .first_colliding_cell(&grid, IVec2::new(3, 0), 0)
+            .is_some_and(|cell| cell.y < 0));
+
+        grid.set_allowed_headings(3, 9, &[HeadingRange::new(PI / 2.0, 0.1)]);
+        assert!(agent.collides_at(&grid, IVec2::new(3, 9), 0));
+        assert!(!agent.collides_at(&grid, IVec2::new(3, 9), 2));
+        assert_eq!(
+            agent.first_colliding_cell(&grid, IVec2::new(3, 9), 0),
+            Some(IVec2::new(3, 9))
+        );
+
+        // Geofences count like they do for the planner.
+        grid.keep_out.push(polygon![
+            (x: 0.0, y: 0.0),
+            (x: 2.0, y: 0.0),
+            (x: 2.0, y: 2.0),
+            (x: 0.0, y: 2.0),
+        ]);
+        let fenced = Cell::new(0, IVec2::new(2, 3));
+        assert!(!grid.is_pose_blocked(&agent, &fenced));
+        assert!(grid.violates_geofence(&agent, &fenced));
+        assert!(agent.collides_at(&grid, fenced.position, 0));
+        assert_eq!(
+            agent.first_colliding_cell(&grid, fenced.position, 0),
+            Some(fenced.position)
+        );
+
+        for rotation in 0..8 {
+            for y in -1..13 {
+                for x in -1..13 {
+                    let position = IVec2::new(x, y);
+                    assert_eq!(
+                        agent.collides_at(&grid, position, rotation),
+                        agent
+                            .first_colliding_cell(&grid, position, rotation)
+                            .is_some()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_doors() {
+        let mut grid = Grid::new(1.0, 4, 4);
+        let agent = point_agent(IVec2::new(0, 0));
+        let mut door = Door::new("dock", vec![IVec2::new(1, 0), IVec2::new(1, 1)]);
+        door.schedule = Some(DoorSchedule {
+            period: 4,
+            open_for: 1,
+            offset: 0,
+        });
+        grid.add_door(door);
+        assert!(grid.is_cell_blocked(1, 0));
+        assert!(grid.is_closed_door(1, 1));
+        assert!(grid.is_pose_blocked_by_doors(&agent, &Cell::new(0, IVec2::new(1, 0))));
+        assert!(!grid.is_pose_blocked_by_doors(&agent, &Cell::new(0, IVec2::new(2, 0))));
+
+        assert!(grid.set_door_open("dock", true));
+        assert!(!grid.is_cell_blocked(1, 0));
+        assert!(!grid.set_door_open("gate", true));
+
+        grid.update_doors(1);
+        assert!(grid.is_cell_blocked(1, 1));
+        grid.update_doors(4);
+        assert!(!grid.is_cell_blocked(1, 1));
+    }
+
+    #[test]
+    fn test_downsample() {
+        let mut grid = Grid::new(2.0, 10, 10);
+        grid.set_cell(3, 0, true);
+        grid.keep_out.push(polygon![
+            (x: 7.0, y: 7.0),
+            (x: 13.0, y: 7.0),
+            (x: 13.0, y: 13.0),
+            (x: 7.0, y: 13.0),
+        ]);
+        let coarse = grid.downsample(2);
+        assert_eq!(coarse.size, (3, 3));
+        assert_eq!(coarse.cell_size, 4.0);
+        assert!(coarse.is_cell_blocked(1, 0));
+        assert!(!coarse.is_cell_blocked(0, 0));
+        assert!(!coarse.is_cell_blocked(2, 2));
+
+        assert_eq!(coarse.keep_out, grid.keep_out);
+        let agent = point_agent(IVec2::ZERO);
+        assert!(coarse.violates_geofence(&agent, &Cell::new(0, IVec2::new(2, 2))));
+        assert!(!coarse.violates_geofence(&agent, &Cell::new(0, IVec2::new(0, 0))));
+    }
+
+    #[test]
+    fn test_grade() {
+        let mut grid = Grid::new(1.0, 4, 4);
+        assert!(grid.heights.is_none());
+        grid.set_height(1, 0, 2.0);
+        grid.set_height(1, 1, 1.0);
+
+        assert_eq!(grid.grade(IVec2::new(0, 0), IVec2::new(1, 0)), 2.0);
+        assert_eq!(grid.grade(IVec2::new(1, 0), IVec2::new(2, 0)), -2.0);
+        assert_eq!(grid.grade(IVec2::new(0, 0), IVec2::new(2, 0)), 0.0);
+        assert!((grid.grade(IVec2::new(0, 0), IVec2::new(1, 1)) - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_flow_cost_factor() {
+        let mut grid = Grid::new(1.0, 4, 4);
+        assert!(grid.flow.is_none());
+        assert_eq!(
+            grid.flow_cost_factor(IVec2::new(0, 0), IVec2::new(1, 0)),
+            1.0
+        );
+
+        grid.set_flow(0, 0, Vec2::new(0.5, 0.0));
+        let from = IVec2::new(0, 0);
+        assert_eq!(grid.flow_cost_factor(from, IVec2::new(1, 0)), 0.5);
+        assert_eq!(grid.flow_cost_factor(from, IVec2::new(-1, 0)), 1.5);
+        assert_eq!(grid.flow_cost_factor(from, IVec2::new(0, 1)), 1.0);
+
+        grid.set_flow(1, 0, Vec2::new(5.0, 0.0));
+        assert_eq!(
+            grid.flow_cost_factor(IVec2::new(1, 0), IVec2::new(2, 0)),
+            MIN_FLOW_FACTOR
+        );
+    }
+
+    #[test]
+    fn test_polygon_rasterization_is_conservative() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        // a thin sliver that only grazes the cells it crosses
+        grid.add_polygon(polygon![
+            (x: 2.5, y: 2.9),
+            (x: 6.5, y: 2.9),
+            (x: 6.5, y: 3.1),
+            (x: 2.5, y: 3.1),
+        ]);
+        for x in 2..=6 {
+            assert!(grid.is_cell_blocked(x, 2), "({}, 2)", x);
+            assert!(grid.is_cell_blocked(x, 3), "({}, 3)", x);
+        }
+        assert!(!grid.is_cell_blocked(1, 3));
+        assert!(!grid.is_cell_blocked(7, 3));
+        assert!(!grid.is_cell_blocked(4, 4));
+        assert_eq!(grid.polygons.len(), 1);
+    }
+
+    #[test]
+    fn test_heading_ranges() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        let agent = Agent::new(IVec2::ZERO, Vec2::new(3.0, 1.0), 0, 8);
+        let pose = |x, y, rotation| Cell::new(rotation, IVec2::new(x, y));
+        // A low canopy over (5, 5) the vehicle fits under only lengthwise.
+        let lengthwise = [HeadingRange::new(0.0, 0.1), HeadingRange::new(PI, 0.1)];
+        grid.set_allowed_headings(5, 5, &lengthwise);
+        assert_eq!(grid.allowed_headings(5, 5), &lengthwise);
+        assert!(grid.allowed_headings(4, 5).is_empty());
+
+        assert!(!grid.is_pose_blocked(&agent, &pose(5, 5, 0)));
+        assert!(!grid.is_pose_blocked(&agent, &pose(6, 5, 4)));
+        assert!(grid.is_pose_blocked(&agent, &pose(5, 5, 2)));
+        assert!(grid.is_pose_blocked(&agent, &pose(5, 6, 2)));
+        assert!(!grid.is_pose_blocked(&agent, &pose(7, 7, 2)));
+        assert!(!grid.is_cell_blocked(5, 5));
+
+        // The same ranges hold at any number of increments, all the way
+        // round, like the fine stage of a docking plan.
+        let fine = Agent::new(IVec2::ZERO, Vec2::new(3.0, 1.0), 0, 128);
+        assert!(!grid.is_pose_blocked(&fine, &pose(5, 5, 64)));
+        assert!(!grid.is_pose_blocked(&fine, &pose(5, 5, 126)));
+        assert!(grid.is_pose_blocked(&fine, &pose(5, 5, 100)));
+        assert!(grid.is_pose_blocked(&fine, &pose(5, 5, 5)));
+        // Rotations out of range wrap rather than skip the check.
+        assert!(grid.is_pose_heading_restricted(&agent, &pose(5, 5, -6)));
+        assert!(!grid.is_pose_heading_restricted(&agent, &pose(5, 5, 12)));
+
+        grid.set_allowed_headings(5, 5, &[]);
+        assert!(!grid.is_pose_blocked(&agent, &pose(5, 5, 2)));
+    }
+
+    #[test]
+    fn test_geofences() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        let agent = Agent::new(IVec2::ZERO, Vec2::new(2.0, 1.0), 0, 8);
+        let pose = |x, y, rotation| Cell::new(rotation, IVec2::new(x, y));
+        assert!(!grid.violates_geofence(&agent, &pose(5, 5, 0)));
+
+        grid.keep_in.push(polygon![
+            (x: 1.0, y: 1.0),
+            (x: 9.0, y: 1.0),
+            (x: 9.0, y: 9.0),
+            (x: 1.0, y: 9.0),
+        ]);
+        assert!(!grid.violates_geofence(&agent, &pose(5, 5, 0)));
+        // Center inside, nose over the fence.
+        assert!(grid.violates_geofence(&agent, &pose(8, 5, 0)));
+        assert!(!grid.violates_geofence(&agent, &pose(8, 5, 2)));
+
+        grid.keep_out.push(polygon![
+            (x: 4.0, y: 4.0),
+            (x: 5.0, y: 4.0),
+            (x: 5.0, y: 5.0),
+            (x: 4.0, y: 5.0),
+        ]);
+        assert!(grid.violates_geofence(&agent, &pose(5, 4, 0)));
+        assert!(!grid.violates_geofence(&agent, &pose(5, 7, 0)));
+        assert!(!grid.is_cell_blocked(4, 4));
+    }
+
+    #[test]
+    fn test_sweep_hits_polygons() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.add_polygon(Polygon::new(
+            LineString::from(vec![(4.2, 4.2), (4.8, 4.2), (4.8, 4.8), (4.2, 4.8)]),
+            vec![],
+        ));
+        let agent = Agent::new(IVec2::ZERO, Vec2::new(0.5, 0.5), 0, 8);
+
+        let from = Cell::new(0, IVec2::new(2, 4));
+        let through = Cell::new(0, IVec2::new(6, 4));
+        let beside = Cell::new(0, IVec2::new(2, 2));
+        assert!(grid.sweep_hits_polygons(&agent, &from, &through));
+        assert!(!grid.sweep_hits_polygons(&agent, &from, &beside));
+    }
+
+    #[test]
+    fn test_supercover_line() {
+        let line = supercover_line(IVec2::new(0, 0), IVec2::new(3, 1));
+        assert_eq!(line.first(), Some(&IVec2::new(0, 0)));
+        assert_eq!(line.last(), Some(&IVec2::new(3, 1)));
+        assert!(line.contains(&IVec2::new(1, 0)));
+        assert!(line.contains(&IVec2::new(2, 1)));
+
+        // exact diagonals include the cells on both sides of each corner
+        let line = supercover_line(IVec2::new(0, 0), IVec2::new(2, 2));
+        assert_eq!(line.len(), 7);
+        assert!(line.contains(&IVec2::new(1, 0)));
+        assert!(line.contains(&IVec2::new(0, 1)));
+
+        let line = supercover_line(IVec2::new(2, 2), IVec2::new(2, 2));
+        assert_eq!(line, vec![IVec2::new(2, 2)]);
+    }
+
+    #[test]
+    fn test_raycast() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.toggle_cell(5, 2);
+        grid.toggle_cell(6, 2);
+        assert_eq!(
+            grid.raycast(IVec2::new(0, 2), IVec2::new(9, 2)),
+            Some(IVec2::new(5, 2))
+        );
+        assert_eq!(
+            grid.raycast(IVec2::new(9, 2), IVec2::new(0, 2)),
+            Some(IVec2::new(6, 2))
+        );
+        assert_eq!(grid.raycast(IVec2::new(0, 3), IVec2::new(9, 3)), None);
+        assert_eq!(
+            grid.raycast(IVec2::new(0, 3), IVec2::new(12, 3)),
+            Some(IVec2::new(10, 3))
+        );
+    }
+
+    #[test]
+    fn test_footprint_visible() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.toggle_cell(5, 4);
+        let agent = Agent::new(IVec2::ZERO, Vec2::new(1.0, 2.5), 0, 8);
+        let a = Cell::new(0, IVec2::new(1, 2));
+        let b = Cell::new(0, IVec2::new(8, 2));
+        assert!(grid.footprint_visible(&a, &b, &agent));
+        let a = Cell::new(0, IVec2::new(1, 3));
+        let b = Cell::new(0, IVec2::new(8, 3));
+        assert!(!grid.footprint_visible(&a, &b, &agent));
+    }
+}
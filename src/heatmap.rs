@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+use crate::reachability::Components;
+
+/// How hard the search worked to reach one goal cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GoalSample {
+    pub goal: IVec2,
+    pub elapsed: Duration,
+    /// Poses expanded, whether or not a path was found.
+    pub expanded: usize,
+    pub cost: Option<u32>,
+}
+
+/// What a [`PlanningHeatmap`] shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    Time,
+    Expansions,
+}
+
+impl GoalSample {
+    pub fn value(&self, metric: HeatmapMetric) -> f32 {
+        match metric {
+            HeatmapMetric::Time => self.elapsed.as_secs_f32(),
+            HeatmapMetric::Expansions => self.expanded as f32,
+        }
+    }
+}
+
+/// Planning effort from one start to every sampled goal cell, for finding
+/// regions where the heuristic misleads the search.
+#[derive(Clone, Debug)]
+pub struct PlanningHeatmap {
+    pub start: Cell,
+    /// Every `stride`-th cell along both axes is sampled.
+    pub stride: i32,
+    /// Free goal cells reachable from the start, in row order.
+    pub samples: Vec<GoalSample>,
+}
+
+impl PlanningHeatmap {
+    /// Plans from `start` to every `stride`-th free cell in the start's
+    /// connected component. Cells outside it are skipped, since searching
+    /// for them only exhausts the component.
+    pub fn compute(
+        grid: &Grid,
+        agent: &Agent,
+        neighbor_cache: &NeighborCacheRef,
+        start: &Cell,
+        config: &PlannerConfig,
+        stride: i32,
+    ) -> Self {
+        let stride = stride.max(1);
+        let components = Components::compute(grid);
+        let mut samples = Vec::new();
+        for y in (0..grid.size.1).step_by(stride as usize) {
+            for x in (0..grid.size.0).step_by(stride as usize) {
+                let goal = IVec2::new(x, y);
+                if grid.is_cell_blocked(x, y) || !components.is_reachable(start.position, goal) {
+                    continue;
+                }
+                let started = Instant::now();
+                let result = planner::search(
+                    grid,
+                    agent,
+                    neighbor_cache,
+                    start.clone(),
+                    goal,
+                    config,
+                    |_, _| 0,
+                );
+                let elapsed = started.elapsed();
+                samples.push(match result {
+                    Ok(result) => GoalSample {
+                        goal,
+                        elapsed,
+                        expanded: result.expanded,
+                        cost: Some(result.cost),
+                    },
+                    Err((_, explored)) => GoalSample {
+                        goal,
+                        elapsed,
+                        expanded: explored.len(),
+                        cost: None,
+                    },
+                });
+            }
+        }
+        Self {
+            start: start.clone(),
+            stride,
+            samples,
+        }
+    }
+
+    /// The largest value of `metric`, for normalizing colors.
+    pub fn max_value(&self, metric: HeatmapMetric) -> f32 {
+        self.samples
+            .iter()
+            .map(|sample| sample.value(metric))
+            .fold(0.0, f32::max)
+    }
+
+    /// The `count` goals with the highest value of `metric`, highest first.
+    pub fn worst(&self, metric: HeatmapMetric, count: usize) -> Vec<&GoalSample> {
+        let mut samples: Vec<&GoalSample> = self.samples.iter().collect();
+        samples.sort_by(|a, b| b.value(metric).total_cmp(&a.value(metric)));
+        samples.truncate(count);
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_goals_behind_wall_expand_more() {
+        // The greedy search runs into the wall for goals right behind it.
+        let mut grid = Grid::new(1.0, 16, 16);
+        for y in 4..12 {
+            grid.set_cell(8, y, true);
+        }
+        let (agent, cache, config) = Fixture::new(16, 16).at(IVec2::new(2, 8)).build();
+        let start = Cell::new(0, agent.position);
+
+        let heatmap = PlanningHeatmap::compute(&grid, &agent, &cache, &start, &config, 2);
+        let free = (0..16)
+            .step_by(2)
+            .flat_map(|y| (0..16).step_by(2).map(move |x| (x, y)))
+            .filter(|&(x, y)| !grid.is_cell_blocked(x, y))
+            .count();
+        assert_eq!(heatmap.samples.len(), free);
+        assert!(heatmap.samples.iter().all(|sample| sample.cost.is_some()));
+
+        let sample_at = |x, y| {
+            heatmap
+                .samples
+                .iter()
+                .find(|sample| sample.goal == IVec2::new(x, y))
+                .unwrap()
+        };
+        let open = sample_at(4, 8).expanded;
+        let behind = sample_at(10, 8).expanded;
+        assert!(behind > open, "{behind} <= {open}");
+        let worst = heatmap.worst(HeatmapMetric::Expansions, 1);
+        assert_eq!(
+            worst[0].value(HeatmapMetric::Expansions),
+            heatmap.max_value(HeatmapMetric::Expansions)
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::f32::consts::PI;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::lanes::{LaneGraph, LaneRoute};
+use crate::planner::{self, PlannerConfig};
+
+/// How a [`HybridLeg`] is driven.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LegKind {
+    /// Planned on the grid, off the lanes.
+    Grid,
+    /// Following the lane graph.
+    Lane,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HybridLeg {
+    pub kind: LegKind,
+    pub path: Vec<Cell>,
+    pub cost: u32,
+}
+
+/// A path onto the lane network, along it, and off it to the goal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HybridRoute {
+    pub legs: Vec<HybridLeg>,
+    pub cost: u32,
+    /// Lane nodes the vehicle joins and leaves the network at.
+    pub entry: usize,
+    pub exit: usize,
+}
+
+impl HybridRoute {
+    /// The whole route, without repeating the poses where legs meet.
+    pub fn path(&self) -> Vec<Cell> {
+        let mut path = Vec::new();
+        for leg in &self.legs {
+            let skip = if path.is_empty() { 0 } else { 1 };
+            path.extend(leg.path.iter().skip(skip).cloned());
+        }
+        path
+    }
+}
+
+fn node_pose(graph: &LaneGraph, node: usize, max_increments: u16) -> Cell {
+    let node = &graph.nodes[node];
+    Cell::new(
+        Cell::heading_to_increment(node.heading, max_increments),
+        node.position.round().as_ivec2(),
+    )
+}
+
+/// Poses along the lane route, one per cell on the straight line between
+/// consecutive nodes and facing along it, with every node at its own
+/// heading. Curved lanes need nodes close enough for that to hold.
+fn lane_path(graph: &LaneGraph, route: &LaneRoute, max_increments: u16) -> Vec<Cell> {
+    let mut path = vec![node_pose(graph, route.nodes[0], max_increments)];
+    for pair in route.nodes.windows(2) {
+        let from = graph.nodes[pair[0]].position.round().as_ivec2();
+        let to = graph.nodes[pair[1]].position.round().as_ivec2();
+        let delta = to - from;
+        let steps = delta.abs().max_element();
+        let direction = delta.as_vec2();
+        let heading = Cell::heading_to_increment(direction.y.atan2(direction.x), max_increments);
+        for step in 1..steps {
+            let position = from.as_vec2() + direction * step as f32 / steps as f32;
+            path.push(Cell::new(heading, position.round().as_ivec2()));
+        }
+        path.push(node_pose(graph, pair[1], max_increments));
+    }
+    path
+}
+
+/// Plans from `start` to `goal` by joining the lane network at the node
+/// nearest the start that faces about the same way, following the lanes
+/// to the node nearest the goal, and planning the final approach on the
+/// grid. The grid legs end and start exactly at the nodes' poses, so the
+/// headings line up where the legs meet. Returns `None` if either grid
+/// leg or the lane route fails.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_hybrid(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    graph: &LaneGraph,
+    start: Cell,
+    goal: IVec2,
+    max_curvature: Option<f32>,
+    config: &PlannerConfig,
+) -> Option<HybridRoute> {
+    let max_increments = config.max_increments;
+    let start_heading = Cell::increment_to_heading(start.rotation, max_increments);
+    let entry = graph.nearest_node(start.position.as_vec2(), start_heading, PI / 4.0)?;
+    let exit = graph.nearest_node(goal.as_vec2(), 0.0, PI)?;
+    let route = graph.route(entry, exit, max_curvature)?;
+
+    let entry_pose = node_pose(graph, entry, max_increments);
+    let onto = planner::plan(
+        grid,
+        agent,
+        neighbor_cache,
+        start,
+        Goal::Oriented {
+            goal: Box::new(Goal::Cell(entry_pose.position)),
+            heading: entry_pose.rotation,
+            tolerance: 0,
+        },
+        config,
+    )
+    .ok()?;
+    let off = planner::plan(
+        grid,
+        agent,
+        neighbor_cache,
+        node_pose(graph, exit, max_increments),
+        goal,
+        config,
+    )
+    .ok()?;
+
+    let legs = vec![
+        HybridLeg {
+            kind: LegKind::Grid,
+            path: onto.path,
+            cost: onto.cost,
+        },
+        HybridLeg {
+            kind: LegKind::Lane,
+            path: lane_path(graph, &route, max_increments),
+            cost: route.cost,
+        },
+        HybridLeg {
+            kind: LegKind::Grid,
+            path: off.path,
+            cost: off.cost,
+        },
+    ];
+    Some(HybridRoute {
+        cost: legs.iter().map(|leg| leg.cost).sum(),
+        legs,
+        entry,
+        exit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_joins_and_leaves_lanes() {
+        let grid = Grid::new(1.0, 40, 24);
+        let graph = LaneGraph::from_json(
+            r#"{
+                "nodes": [
+                    {"id": "west", "x": 6, "y": 6, "heading": 0},
+                    {"id": "mid", "x": 20, "y": 6, "heading": 0},
+                    {"id": "east", "x": 34, "y": 6, "heading": 0},
+                    {"id": "back", "x": 20, "y": 2, "heading": 180}
+                ],
+                "edges": [
+                    {"from": "west", "to": "mid"},
+                    {"from": "mid", "to": "east"},
+                    {"from": "east", "to": "back"},
+                    {"from": "back", "to": "west"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (agent, cache, config) = Fixture::new(40, 24).at(IVec2::new(3, 14)).build();
+        let start = Cell::new(0, agent.position);
+        let goal = IVec2::new(36, 14);
+
+        let route = plan_hybrid(
+            &grid,
+            &agent,
+            &cache,
+            &graph,
+            start.clone(),
+            goal,
+            None,
+            &config,
+        )
+        .unwrap();
+        assert_eq!((route.entry, route.exit), (0, 2));
+        let kinds: Vec<LegKind> = route.legs.iter().map(|leg| leg.kind).collect();
+        assert_eq!(kinds, vec![LegKind::Grid, LegKind::Lane, LegKind::Grid]);
+        // Each leg starts where the last one ended, heading included.
+        for pair in route.legs.windows(2) {
+            assert_eq!(pair[0].path.last(), pair[1].path.first());
+        }
+        assert_eq!(route.legs[1].path.len(), 34 - 6 + 1);
+        let path = route.path();
+        assert_eq!(path[0], start);
+        assert_eq!(path.last().unwrap().position, goal);
+        assert!(path
+            .windows(2)
+            .all(|pair| (pair[1].position - pair[0].position).abs().max_element() <= 1));
+        assert_eq!(
+            route.cost,
+            route.legs.iter().map(|leg| leg.cost).sum::<u32>()
+        );
+    }
+}
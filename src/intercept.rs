@@ -0,0 +1,158 @@
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::pathfind::optimized_astar;
+use crate::planner::{self, Candidates, PlanResult, PlannerConfig};
+use crate::reservation::WAIT_COST;
+
+/// A target moving at constant velocity, like a pallet on a conveyor or
+/// another vehicle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovingTarget {
+    /// Position in cells when planning starts.
+    pub position: Vec2,
+    /// Cells per tick.
+    pub velocity: Vec2,
+}
+
+impl MovingTarget {
+    /// The cell the target is in `ticks` after planning starts.
+    pub fn cell_at(&self, ticks: u32) -> IVec2 {
+        (self.position + self.velocity * ticks as f32)
+            .round()
+            .as_ivec2()
+    }
+}
+
+/// A plan meeting a moving target.
+#[derive(Clone, Debug)]
+pub struct Intercept {
+    /// One pose per tick, like [`crate::reservation::plan_reserved`].
+    pub result: PlanResult,
+    /// Ticks until the vehicle meets the target.
+    pub time: u32,
+    /// Where the target is by then.
+    pub point: IVec2,
+}
+
+/// Plans to meet `target`, searching over arrival times so the vehicle
+/// heads for where the target will be rather than where it is. Every move
+/// and every wait takes one tick; the target counts as met once the
+/// vehicle is within `radius` cells of it along both axes. The search
+/// gives up past `horizon` ticks.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_intercept(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    target: &MovingTarget,
+    radius: i32,
+    horizon: u32,
+    config: &PlannerConfig,
+) -> Option<Intercept> {
+    let costs = planner::cost_cache(neighbor_cache, config);
+    let expanded = std::cell::Cell::new(0);
+    let (path, cost) = optimized_astar(
+        (start, 0),
+        config.max_states,
+        |(action, time): &(Cell, u32)| {
+            expanded.set(expanded.get() + 1);
+            let next = time + 1;
+            if next > horizon {
+                return Vec::new();
+            }
+            let mut result = Vec::new();
+            let mut candidates = Candidates::new();
+            planner::motion_candidates(
+                agent,
+                neighbor_cache,
+                &costs,
+                action,
+                config,
+                &mut candidates,
+            );
+            for (neigh, cost) in candidates {
+                if planner::is_move_blocked(grid, agent, action, &neigh)
+                    || planner::is_move_outside_geofence(grid, agent, action, &neigh)
+                {
+                    continue;
+                }
+                if let Some(cost) = planner::terrain_cost(grid, agent, action, &neigh, cost, config)
+                {
+                    result.push(((neigh, next), cost));
+                }
+            }
+            result.push(((action.clone(), next), WAIT_COST));
+            result
+        },
+        |(action, time)| {
+            // Lead the target by the ticks it takes to get to it at a
+            // cell per tick.
+            let ticks = (target.cell_at(*time) - action.position)
+                .abs()
+                .max_element();
+            let aim = target.cell_at(time + ticks as u32);
+            action.heuristic(aim, config.max_increments)
+        },
+        |(action, time)| {
+            (target.cell_at(*time) - action.position)
+                .abs()
+                .max_element()
+                <= radius
+        },
+    )?;
+
+    let time = path.last()?.1;
+    Some(Intercept {
+        result: PlanResult {
+            path: path.into_iter().map(|(pose, _)| pose).collect(),
+            cost,
+            start_adjustment: None,
+            suboptimality_bound: None,
+            expanded: expanded.get(),
+        },
+        time,
+        point: target.cell_at(time),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{Fixture, MAX_INCREMENTS};
+
+    #[test]
+    fn test_heads_for_where_target_will_be() {
+        let grid = Grid::new(1.0, 30, 16);
+        let (agent, cache, config) = Fixture::new(30, 16)
+            .at(IVec2::new(4, 12))
+            .holonomic(1)
+            .states_per_cell(4 * MAX_INCREMENTS as usize)
+            .build();
+        let target = MovingTarget {
+            position: Vec2::new(4.0, 2.0),
+            velocity: Vec2::new(0.5, 0.0),
+        };
+        let start = Cell::new(0, agent.position);
+
+        let intercept =
+            plan_intercept(&grid, &agent, &cache, start, &target, 0, 40, &config).unwrap();
+        let path = &intercept.result.path;
+        assert_eq!(intercept.time as usize, path.len() - 1);
+        assert_eq!(path.last().unwrap().position, intercept.point);
+        assert_eq!(intercept.point, target.cell_at(intercept.time));
+        // The target has moved on from where it started by the time it's met.
+        assert!(intercept.point.x > 4 + 2);
+
+        // A target out of reach within the horizon can't be met.
+        let fleeing = MovingTarget {
+            position: Vec2::new(20.0, 2.0),
+            velocity: Vec2::new(1.0, 0.0),
+        };
+        let start = Cell::new(0, agent.position);
+        assert!(plan_intercept(&grid, &agent, &cache, start, &fleeing, 0, 10, &config).is_none());
+    }
+}
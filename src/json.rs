@@ -0,0 +1,310 @@
+/// Deepest nesting of arrays and objects [`Json::parse`] accepts, so a
+/// hostile file can't overflow the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// A parsed JSON value, for the few plain data files the planner reads.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members in file order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses a whole document, failing with the byte offset of the first
+    /// error.
+    pub fn parse(source: &str) -> Result<Json, usize> {
+        let mut parser = Parser {
+            bytes: source.as_bytes(),
+            at: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.at != parser.bytes.len() {
+            return Err(parser.at);
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), usize> {
+        if self.bytes[self.at..].starts_with(literal.as_bytes()) {
+            self.at += literal.len();
+            Ok(())
+        } else {
+            Err(self.at)
+        }
+    }
+
+    /// Consumes `byte` if it's next.
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.bytes.get(self.at) == Some(&byte);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    /// Consumes a run of digits, returning `false` if there were none.
+    fn digits(&mut self) -> bool {
+        let start = self.at;
+        while self.bytes.get(self.at).is_some_and(u8::is_ascii_digit) {
+            self.at += 1;
+        }
+        self.at > start
+    }
+
+    /// Parses a value nested in `depth` arrays and objects.
+    fn value(&mut self, depth: usize) -> Result<Json, usize> {
+        self.skip_whitespace();
+        if matches!(self.bytes.get(self.at), Some(b'[' | b'{')) && depth == MAX_DEPTH {
+            return Err(self.at);
+        }
+        match self.bytes.get(self.at) {
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.at += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.at) == Some(&b']') {
+                    self.at += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.bytes.get(self.at) {
+                        Some(b',') => self.at += 1,
+                        Some(b']') => {
+                            self.at += 1;
+                            return Ok(Json::Array(values));
+                        }
+                        _ => return Err(self.at),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.at += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.at) == Some(&b'}') {
+                    self.at += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.bytes.get(self.at) {
+                        Some(b',') => self.at += 1,
+                        Some(b'}') => {
+                            self.at += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.at),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.at),
+        }
+    }
+
+    /// A number in JSON's grammar, which is stricter than Rust's: no
+    /// leading `+` or zeros, and digits on both sides of the point.
+    fn number(&mut self) -> Result<Json, usize> {
+        let start = self.at;
+        self.eat(b'-');
+        if !self.eat(b'0') && !self.digits() {
+            return Err(self.at);
+        }
+        if self.eat(b'.') && !self.digits() {
+            return Err(self.at);
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            let _ = self.eat(b'+') || self.eat(b'-');
+            if !self.digits() {
+                return Err(self.at);
+            }
+        }
+        std::str::from_utf8(&self.bytes[start..self.at])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or(start)
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn hex4(&mut self) -> Result<u32, usize> {
+        let hex = self.bytes.get(self.at..self.at + 4).ok_or(self.at)?;
+        if !hex.iter().all(u8::is_ascii_hexdigit) {
+            return Err(self.at);
+        }
+        self.at += 4;
+        Ok(hex.iter().fold(0, |code, digit| {
+            code * 16 + (*digit as char).to_digit(16).unwrap_or(0)
+        }))
+    }
+
+    fn string(&mut self) -> Result<String, usize> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        loop {
+            let start = self.at;
+            // Control characters have to be escaped, so they end the run
+            // and fail below.
+            while self
+                .bytes
+                .get(self.at)
+                .is_some_and(|byte| *byte != b'"' && *byte != b'\\' && *byte >= 0x20)
+            {
+                self.at += 1;
+            }
+            // Only ASCII delimiters were skipped, so this is still UTF-8.
+            out.push_str(std::str::from_utf8(&self.bytes[start..self.at]).map_err(|_| start)?);
+            match self.bytes.get(self.at) {
+                Some(b'"') => {
+                    self.at += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    let escape = *self.bytes.get(self.at + 1).ok_or(self.at)?;
+                    self.at += 2;
+                    out.push(match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let escape = self.at - 2;
+                            let code = match self.hex4()? {
+                                // Characters past the BMP come as a pair of
+                                // escapes; a lone half isn't a character.
+                                high @ 0xd800..=0xdbff => {
+                                    self.expect("\\u").map_err(|_| escape)?;
+                                    let low = self.hex4()?;
+                                    if !(0xdc00..=0xdfff).contains(&low) {
+                                        return Err(escape);
+                                    }
+                                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                                }
+                                0xdc00..=0xdfff => return Err(escape),
+                                code => code,
+                            };
+                            char::from_u32(code).ok_or(escape)?
+                        }
+                        _ => return Err(self.at - 1),
+                    });
+                }
+                _ => return Err(self.at),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let json = Json::parse(
+            r#" {"name": "yard \"A\"é", "size": [12, -3.5e1], "open": true, "gate": null, "empty": {}} "#,
+        )
+        .unwrap();
+        assert_eq!(json.get("name").unwrap().as_str(), Some("yard \"A\"é"));
+        let size = json.get("size").unwrap().as_array().unwrap();
+        assert_eq!(size[1].as_f64(), Some(-35.0));
+        assert_eq!(json.get("open"), Some(&Json::Bool(true)));
+        assert_eq!(json.get("gate"), Some(&Json::Null));
+        assert_eq!(json.get("empty"), Some(&Json::Object(Vec::new())));
+        assert!(json.get("missing").is_none());
+
+        assert_eq!(Json::parse("[1, 2,]"), Err(6));
+        assert_eq!(Json::parse("{} x"), Err(3));
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(Json::parse(&nested(MAX_DEPTH + 1)), Err(MAX_DEPTH));
+        // Deep enough to overflow the stack without the limit.
+        assert!(Json::parse(&"[{\"a\":".repeat(100_000)).is_err());
+
+        let string = |source: &str| Json::parse(source).map(|json| json.as_str().map(String::from));
+        assert_eq!(
+            string(r#""\ud83d\ude97 \u00e9""#),
+            Ok(Some("\u{1f697} é".into()))
+        );
+        assert_eq!(string(r#""\ud83d""#), Err(1));
+        assert_eq!(string(r#""\ud83dx""#), Err(1));
+        assert_eq!(string(r#""\ude97""#), Err(1));
+        assert_eq!(string(r#""\u+041""#), Err(3));
+        assert_eq!(string(r#""\u00""#), Err(3));
+        assert_eq!(string("\"tab\there\""), Err(4));
+
+        assert_eq!(Json::parse("1e+2"), Ok(Json::Number(100.0)));
+        assert_eq!(Json::parse("-0.5E-1"), Ok(Json::Number(-0.05)));
+        for number in ["01", "1.", ".5", "+1", "-", "1e", "1e+", "--1", "1.2.3"] {
+            assert!(Json::parse(number).is_err(), "{number}");
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use notan::math::Vec2;
+
+use crate::cell::Cell;
+use crate::json::Json;
+use crate::pathfind::optimized_astar;
+
+/// A point on a lane, where lanes meet or bend.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LaneNode {
+    pub id: String,
+    /// In cells.
+    pub position: Vec2,
+    /// Driving direction at the node, in radians.
+    pub heading: f32,
+}
+
+/// A lane from one node to another, driven in that direction only.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LaneEdge {
+    pub from: usize,
+    pub to: usize,
+    /// In cells, at least the straight-line distance.
+    pub length: f32,
+    /// Tightest curvature along the lane, in 1 / cells.
+    pub curvature: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LaneError {
+    /// Not JSON, with the byte offset of the error.
+    Syntax(usize),
+    /// A node or edge is missing this field, or has the wrong type.
+    Field(&'static str),
+    /// An edge names a node that isn't in the file.
+    UnknownNode(String),
+    DuplicateNode(String),
+}
+
+fn number(value: &Json, key: &'static str) -> Result<f32, LaneError> {
+    value
+        .get(key)
+        .and_then(Json::as_f64)
+        .map(|number| number as f32)
+        .ok_or(LaneError::Field(key))
+}
+
+fn text<'a>(value: &'a Json, key: &'static str) -> Result<&'a str, LaneError> {
+    value
+        .get(key)
+        .and_then(Json::as_str)
+        .ok_or(LaneError::Field(key))
+}
+
+/// A route along the lanes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LaneRoute {
+    /// Node indices, from the start node to the goal node.
+    pub nodes: Vec<usize>,
+    /// In the grid planner's units, 1000 per cell.
+    pub cost: u32,
+}
+
+/// A directed lane network, like the marked lanes of a truck yard. Routing
+/// along it is far cheaper than a grid search, and the grid planner is
+/// only needed off the lanes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LaneGraph {
+    pub nodes: Vec<LaneNode>,
+    pub edges: Vec<LaneEdge>,
+}
+
+impl LaneGraph {
+    /// Reads a graph like
+    /// `{"nodes": [{"id": "a", "x": 2, "y": 3, "heading": 90}, ...],
+    /// "edges": [{"from": "a", "to": "b", "curvature": 0.1}, ...]}`,
+    /// with positions in cells and headings in degrees. Edge lengths
+    /// default to the straight-line distance and curvatures to 0.
+    pub fn from_json(source: &str) -> Result<Self, LaneError> {
+        let json = Json::parse(source).map_err(LaneError::Syntax)?;
+        let list = |key: &'static str| {
+            json.get(key)
+                .and_then(Json::as_array)
+                .ok_or(LaneError::Field(key))
+        };
+
+        let mut graph = LaneGraph::default();
+        for node in list("nodes")? {
+            let id = text(node, "id")?;
+            if graph.node(id).is_some() {
+                return Err(LaneError::DuplicateNode(id.to_string()));
+            }
+            graph.nodes.push(LaneNode {
+                id: id.to_string(),
+                position: Vec2::new(number(node, "x")?, number(node, "y")?),
+                heading: number(node, "heading")?.to_radians(),
+            });
+        }
+        for edge in list("edges")? {
+            let index = |key| {
+                let id = text(edge, key)?;
+                graph
+                    .node(id)
+                    .ok_or_else(|| LaneError::UnknownNode(id.to_string()))
+            };
+            let (from, to) = (index("from")?, index("to")?);
+            let straight = graph.nodes[from]
+                .position
+                .distance(graph.nodes[to].position);
+            let length = number(edge, "length").unwrap_or(straight).max(straight);
+            let curvature = number(edge, "curvature").unwrap_or(0.0).abs();
+            graph.edges.push(LaneEdge {
+                from,
+                to,
+                length,
+                curvature,
+            });
+        }
+        Ok(graph)
+    }
+
+    /// Index of the node named `id`.
+    pub fn node(&self, id: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.id == id)
+    }
+
+    pub fn outgoing(&self, node: usize) -> impl Iterator<Item = &LaneEdge> {
+        self.edges.iter().filter(move |edge| edge.from == node)
+    }
+
+    /// The node nearest to `position`, preferring nodes whose heading is
+    /// within `max_heading_error` radians of `heading` if any are.
+    pub fn nearest_node(
+        &self,
+        position: Vec2,
+        heading: f32,
+        max_heading_error: f32,
+    ) -> Option<usize> {
+        let nearest = |aligned: bool| {
+            self.nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| {
+                    !aligned
+                        || Vec2::from_angle(node.heading)
+                            .angle_between(Vec2::from_angle(heading))
+                            .abs()
+                            <= max_heading_error
+                })
+                .min_by(|(_, a), (_, b)| {
+                    a.position
+                        .distance_squared(position)
+                        .total_cmp(&b.position.distance_squared(position))
+                })
+                .map(|(index, _)| index)
+        };
+        nearest(true).or_else(|| nearest(false))
+    }
+
+    /// Cheapest route from node `from` to node `to`, skipping lanes curving
+    /// tighter than `max_curvature` if set.
+    pub fn route(&self, from: usize, to: usize, max_curvature: Option<f32>) -> Option<LaneRoute> {
+        if from >= self.nodes.len() {
+            return None;
+        }
+        let goal = self.nodes.get(to)?.position;
+        let (nodes, cost) = optimized_astar(
+            from,
+            self.nodes.len(),
+            |node| {
+                self.outgoing(*node)
+                    .filter(|edge| max_curvature.is_none_or(|max| edge.curvature <= max))
+                    .map(|edge| (edge.to, (edge.length * 1000.0).round() as u32))
+                    .collect::<Vec<_>>()
+            },
+            // Lanes are never shorter than the straight line, so this is
+            // admissible.
+            |node| (self.nodes[*node].position.distance(goal) * 1000.0) as u32,
+            |node| *node == to,
+        )?;
+        Some(LaneRoute { nodes, cost })
+    }
+
+    /// Grid poses of the route's nodes, for stitching onto grid paths or
+    /// drawing.
+    pub fn poses(&self, route: &LaneRoute, max_increments: u16) -> Vec<Cell> {
+        route
+            .nodes
+            .iter()
+            .map(|node| {
+                let node = &self.nodes[*node];
+                Cell::new(
+                    Cell::heading_to_increment(node.heading, max_increments),
+                    node.position.round().as_ivec2(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+
+    /// A loop around a yard with a tight shortcut from `b` to `d`.
+    const YARD: &str = r#"{
+        "nodes": [
+            {"id": "a", "x": 0, "y": 0, "heading": 0},
+            {"id": "b", "x": 20, "y": 0, "heading": 0},
+            {"id": "c", "x": 20, "y": 20, "heading": 90},
+            {"id": "d", "x": 0, "y": 20, "heading": 180}
+        ],
+        "edges": [
+            {"from": "a", "to": "b"},
+            {"from": "b", "to": "c", "length": 31.4, "curvature": 0.1},
+            {"from": "c", "to": "d"},
+            {"from": "b", "to": "d", "length": 30, "curvature": 0.5},
+            {"from": "d", "to": "a"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_lane_routing() {
+        let graph = LaneGraph::from_json(YARD).unwrap();
+        let (a, d) = (graph.node("a").unwrap(), graph.node("d").unwrap());
+
+        let route = graph.route(a, d, None).unwrap();
+        assert_eq!(route.nodes, vec![a, 1, d]);
+        assert_eq!(route.cost, 20_000 + 30_000);
+        // Too tight for a long truck; take the long way round.
+        let route = graph.route(a, d, Some(0.2)).unwrap();
+        assert_eq!(route.nodes, vec![a, 1, 2, d]);
+        // Lanes are one-way.
+        assert_eq!(graph.route(d, a, None).unwrap().nodes, vec![d, a]);
+        assert_eq!(graph.route(1, a, Some(0.0)), None);
+
+        let poses = graph.poses(&route, 8);
+        assert_eq!(poses[2], Cell::new(2, IVec2::new(20, 20)));
+        assert_eq!(graph.nearest_node(Vec2::new(19.0, 18.0), 0.0, 0.5), Some(1));
+        assert_eq!(graph.nearest_node(Vec2::new(19.0, 18.0), 1.5, 0.5), Some(2));
+
+        assert_eq!(LaneGraph::from_json("{").err(), Some(LaneError::Syntax(1)));
+        assert_eq!(
+            LaneGraph::from_json(&YARD.replace(r#""to": "a""#, r#""to": "z""#)).err(),
+            Some(LaneError::UnknownNode("z".to_string()))
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::pathfind::optimized_astar;
+use crate::planner::{self, Candidates, PlanResult, PlannerConfig};
+
+/// Cost of driving over a ramp from one layer to another.
+pub const RAMP_COST: u32 = 1000;
+
+/// A transition cell connecting two layers, usable in both directions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ramp {
+    pub position: IVec2,
+    pub lower: u8,
+    pub upper: u8,
+}
+
+impl Ramp {
+    /// The layer reached by taking the ramp from `layer`, if it connects there.
+    pub fn other_layer(&self, layer: u8) -> Option<u8> {
+        if layer == self.lower {
+            Some(self.upper)
+        } else if layer == self.upper {
+            Some(self.lower)
+        } else {
+            None
+        }
+    }
+}
+
+/// Stacked grids of the same size, e.g. a warehouse floor and its
+/// mezzanine, or a road and the overpass above it.
+pub struct LayeredMap {
+    pub layers: Vec<Grid>,
+    pub ramps: Vec<Ramp>,
+}
+
+impl LayeredMap {
+    pub fn new(layers: Vec<Grid>) -> Self {
+        Self {
+            layers,
+            ramps: Vec::new(),
+        }
+    }
+
+    pub fn add_ramp(&mut self, position: IVec2, lower: u8, upper: u8) {
+        self.ramps.push(Ramp {
+            position,
+            lower,
+            upper,
+        });
+    }
+
+    pub fn layer(&self, layer: u8) -> Option<&Grid> {
+        self.layers.get(layer as usize)
+    }
+
+    /// Same as [`Grid::is_pose_blocked`], on the pose's own layer. Poses on
+    /// missing layers are always blocked.
+    pub fn is_pose_blocked(&self, agent: &Agent, pose: &Cell) -> bool {
+        self.layer(pose.layer)
+            .is_none_or(|grid| grid.is_pose_blocked(agent, pose))
+    }
+
+    /// Layer transitions available from `pose`.
+    pub fn transitions(&self, pose: &Cell) -> Vec<Cell> {
+        self.ramps
+            .iter()
+            .filter(|ramp| ramp.position == pose.position)
+            .filter_map(|ramp| ramp.other_layer(pose.layer))
+            .map(|layer| pose.clone().with_layer(layer))
+            .collect()
+    }
+}
+
+/// Plans across the layers of `map`, switching layers only at ramps. The
+/// goal is only accepted on `goal_layer`.
+pub fn plan_layered(
+    map: &LayeredMap,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    goal_layer: u8,
+    config: &PlannerConfig,
+) -> Option<PlanResult> {
+    let goal = goal.into();
+    let costs = planner::cost_cache(neighbor_cache, config);
+    let expanded = std::cell::Cell::new(0);
+    let (path, cost) = optimized_astar(
+        start,
+        config.max_states * map.layers.len(),
+        |action| {
+            expanded.set(expanded.get() + 1);
+            let Some(grid) = map.layer(action.layer) else {
+                return Vec::new();
+            };
+            let mut result = Vec::new();
+            let mut candidates = Candidates::new();
+            planner::motion_candidates(
+                agent,
+                neighbor_cache,
+                &costs,
+                action,
+                config,
+                &mut candidates,
+            );
+            for (neigh, cost) in candidates {
+                if let Some(cost) = planner::expand_move(grid, agent, action, &neigh, cost, config)
+                {
+                    result.push((neigh, cost));
+                }
+            }
+            for neigh in map.transitions(action) {
+                if !map.is_pose_blocked(agent, &neigh) {
+                    result.push((neigh, RAMP_COST));
+                }
+            }
+            result
+        },
+        |action| action.heuristic(goal.nearest_point(action.position), config.max_increments),
+        |action| action.layer == goal_layer && goal.accepts(agent, action),
+    )?;
+
+    Some(PlanResult {
+        path,
+        cost,
+        start_adjustment: None,
+        suboptimality_bound: None,
+        expanded: expanded.get(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_plan_through_ramp() {
+        // The upper layer is closed off except for a strip along the top
+        // row, reachable only through the ramp at (0, 0).
+        let ground = Grid::new(1.0, 10, 10);
+        let mut upper = Grid::new(1.0, 10, 10);
+        for y in 1..10 {
+            for x in 0..10 {
+                upper.set_cell(x, y, true);
+            }
+        }
+        let mut map = LayeredMap::new(vec![ground, upper]);
+        map.add_ramp(IVec2::new(0, 0), 0, 1);
+
+        let (agent, cache, config) = Fixture::new(10, 10).build();
+        let start = Cell::new(0, agent.position);
+
+        let result = plan_layered(&map, &agent, &cache, start, IVec2::new(5, 0), 1, &config)
+            .expect("upper layer should be reachable through the ramp");
+        let last = result.path.last().unwrap();
+        assert_eq!((last.layer, last.position), (1, IVec2::new(5, 0)));
+        assert!(result.path.windows(2).all(|pair| {
+            pair[0].layer == pair[1].layer || pair[0].position == IVec2::new(0, 0)
+        }));
+
+        // Without the ramp the upper layer can't be entered at all.
+        map.ramps.clear();
+        let start = Cell::new(0, agent.position);
+        assert!(plan_layered(&map, &agent, &cache, start, IVec2::new(5, 0), 1, &config).is_none());
+    }
+}
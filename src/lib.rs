@@ -0,0 +1,137 @@
+//! The planner core: bit arrays, cell math, the neighbor cache and the
+//! search algorithms. Builds on `no_std + alloc` with default features off,
+//! for embedded vehicle controllers. The rest of the planner, which the demo
+//! in `main.rs` and the fuzz target drive, builds on notan's math types and
+//! comes with the `gui` feature.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "gui")]
+pub mod agent;
+#[cfg(feature = "gui")]
+pub mod alternatives;
+#[cfg(feature = "gui")]
+pub mod annotations;
+#[cfg(feature = "gui")]
+pub mod batch;
+pub mod bitarray;
+pub mod cell;
+#[cfg(feature = "gui")]
+pub mod chokepoints;
+#[cfg(feature = "gui")]
+pub mod collision;
+#[cfg(feature = "gui")]
+pub mod comparison;
+#[cfg(feature = "gui")]
+pub mod congestion;
+#[cfg(feature = "gui")]
+pub mod control;
+#[cfg(feature = "gui")]
+pub mod corridor;
+#[cfg(feature = "gui")]
+pub mod coverage;
+#[cfg(feature = "gui")]
+pub mod diagnostics;
+#[cfg(feature = "gui")]
+pub mod dispatch;
+#[cfg(feature = "gui")]
+pub mod docking;
+#[cfg(feature = "gui")]
+pub mod door;
+#[cfg(feature = "std")]
+pub mod encoding;
+#[cfg(feature = "gui")]
+pub mod energy;
+#[cfg(feature = "gui")]
+pub mod eta;
+#[cfg(feature = "gui")]
+pub mod exploration;
+#[cfg(feature = "gui")]
+pub mod field;
+#[cfg(feature = "gui")]
+pub mod finish;
+pub mod fixed;
+#[cfg(feature = "gui")]
+pub mod fleet;
+#[cfg(feature = "gui")]
+pub mod forecast;
+#[cfg(feature = "gui")]
+pub mod fuzzing;
+#[cfg(feature = "gui")]
+pub mod goal;
+#[cfg(feature = "gpu-field")]
+pub mod gpu_field;
+#[cfg(feature = "gui")]
+pub mod grid;
+#[cfg(feature = "gui")]
+pub mod heatmap;
+#[cfg(feature = "gui")]
+pub mod hybrid;
+#[cfg(feature = "gui")]
+pub mod intercept;
+#[cfg(feature = "gui")]
+pub mod json;
+#[cfg(feature = "gui")]
+pub mod lanes;
+#[cfg(feature = "gui")]
+pub mod layers;
+#[cfg(feature = "gui")]
+pub mod loads;
+#[cfg(feature = "gui")]
+pub mod local;
+#[cfg(feature = "gui")]
+pub mod maneuver;
+#[cfg(feature = "map-sync")]
+pub mod map_sync;
+#[cfg(feature = "gui")]
+pub mod metrics;
+#[cfg(feature = "gui")]
+pub mod mission;
+pub mod neighbor_rules;
+#[cfg(feature = "gui")]
+pub mod parking;
+#[cfg(feature = "gui")]
+pub mod patch;
+pub mod path_codec;
+pub mod pathfind;
+#[cfg(feature = "gui")]
+pub mod persist;
+#[cfg(feature = "gui")]
+pub mod planner;
+#[cfg(feature = "gui")]
+pub mod pursuit;
+#[cfg(feature = "gui")]
+pub mod queue;
+#[cfg(feature = "gui")]
+pub mod reachability;
+#[cfg(feature = "gui")]
+pub mod reservation;
+#[cfg(feature = "gui")]
+pub mod resolution;
+#[cfg(feature = "gui")]
+pub mod risk;
+#[cfg(feature = "gui")]
+pub mod robustness;
+#[cfg(feature = "gui")]
+pub mod ros_map;
+#[cfg(feature = "gui")]
+pub mod route;
+#[cfg(feature = "gui")]
+pub mod selection;
+#[cfg(feature = "gui")]
+pub mod sensor;
+#[cfg(feature = "gui")]
+pub mod simulation;
+#[cfg(feature = "gui")]
+pub mod smoothing;
+#[cfg(feature = "gui")]
+pub mod svg;
+#[cfg(all(test, feature = "gui"))]
+mod test_support;
+#[cfg(feature = "gui")]
+pub mod tiled;
+#[cfg(feature = "gui")]
+pub mod units;
+#[cfg(feature = "gui")]
+pub mod validation;
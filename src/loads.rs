@@ -0,0 +1,135 @@
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+
+/// A stop where the vehicle's outline changes, like picking up or dropping
+/// off a pallet.
+#[derive(Clone, Debug)]
+pub struct LoadStop {
+    pub goal: Goal,
+    /// Footprint set in use after the stop.
+    pub footprint: String,
+}
+
+/// The path to one stop, driven with a single footprint set.
+#[derive(Clone, Debug)]
+pub struct LoadLeg {
+    pub footprint: String,
+    pub path: Vec<Cell>,
+    pub cost: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    /// The agent has no footprint set of this name.
+    UnknownFootprint(String),
+    /// The new footprint collides at the stop with this index.
+    DoesNotFit(usize),
+    /// No path to the stop with this index.
+    NoPath(usize),
+}
+
+/// Plans from `start` through every stop in turn, switching the agent's
+/// footprint set at each one. Leg `i` ends at stop `i` and is driven with
+/// the footprint set in use before it.
+pub fn plan_with_loads(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    stops: &[LoadStop],
+    config: &PlannerConfig,
+) -> Result<Vec<LoadLeg>, LoadError> {
+    let mut agent = agent.clone();
+    let mut legs: Vec<LoadLeg> = Vec::with_capacity(stops.len());
+    let mut pose = start;
+    for (index, stop) in stops.iter().enumerate() {
+        let result = planner::plan(
+            grid,
+            &agent,
+            neighbor_cache,
+            pose,
+            stop.goal.clone(),
+            config,
+        )
+        .map_err(|_| LoadError::NoPath(index))?;
+        pose = result.path.last().expect("plans are never empty").clone();
+        legs.push(LoadLeg {
+            footprint: agent.footprint_name().to_string(),
+            path: result.path,
+            cost: result.cost,
+        });
+
+        if !agent.set_footprint(&stop.footprint) {
+            return Err(LoadError::UnknownFootprint(stop.footprint.clone()));
+        }
+        if grid.is_pose_blocked(&agent, &pose) {
+            return Err(LoadError::DoesNotFit(index));
+        }
+    }
+    Ok(legs)
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::{IVec2, Vec2};
+
+    use super::*;
+    use crate::agent::{MotionModel, DEFAULT_FOOTPRINT};
+    use crate::test_support::{neighbor_cache, point_agent, Fixture};
+
+    #[test]
+    fn test_switching_footprints() {
+        let mut agent = point_agent(IVec2::ZERO);
+        let empty = agent.rotation_footprint(0).clone();
+        agent.add_footprint_set("pallet", Vec2::new(3.0, 3.0));
+        assert_eq!(agent.footprint_name(), DEFAULT_FOOTPRINT);
+        assert!(!agent.set_footprint("crate"));
+
+        assert!(agent.set_footprint("pallet"));
+        assert_eq!(agent.size, Vec2::new(3.0, 3.0));
+        assert!(agent.rotation_footprint(0).len() > empty.len());
+        assert_eq!(
+            agent.footprint_names().collect::<Vec<_>>(),
+            ["pallet", DEFAULT_FOOTPRINT]
+        );
+
+        assert!(agent.set_footprint(DEFAULT_FOOTPRINT));
+        assert_eq!(agent.rotation_footprint(0), &empty);
+    }
+
+    #[test]
+    fn test_pallet_blocks_gap() {
+        // A wall across the grid with a one cell gap.
+        let mut grid = Grid::new(1.0, 15, 15);
+        for x in 0..15 {
+            if x != 7 {
+                grid.set_cell(x, 7, true);
+            }
+        }
+        let mut agent = point_agent(IVec2::new(7, 2));
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        agent.add_footprint_set("pallet", Vec2::new(3.0, 3.0));
+        let cache = neighbor_cache();
+        let config = Fixture::new(15, 15).config();
+        let start = Cell::new(0, agent.position);
+        let stop = |x, y, footprint: &str| LoadStop {
+            goal: IVec2::new(x, y).into(),
+            footprint: footprint.to_string(),
+        };
+
+        // Picking up before the gap leaves no way through it.
+        let stops = [stop(4, 3, "pallet"), stop(7, 12, DEFAULT_FOOTPRINT)];
+        let result = plan_with_loads(&grid, &agent, &cache, start.clone(), &stops, &config);
+        assert_eq!(result.unwrap_err(), LoadError::NoPath(1));
+
+        // Picking up past the gap works.
+        let stops = [stop(4, 11, "pallet"), stop(10, 11, DEFAULT_FOOTPRINT)];
+        let legs = plan_with_loads(&grid, &agent, &cache, start, &stops, &config).unwrap();
+        assert_eq!(legs[0].footprint, DEFAULT_FOOTPRINT);
+        assert_eq!(legs[1].footprint, "pallet");
+        assert_eq!(legs[1].path.last().unwrap().position, IVec2::new(10, 11));
+    }
+}
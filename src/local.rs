@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::planner::{self, Candidates, PlannerConfig};
+
+/// Dynamic-window style local planner. Each step it tries every move the
+/// vehicle can make right now and picks the one that best tracks a point
+/// ahead on the global path while keeping away from obstacles that were not
+/// on the map when the path was planned.
+#[derive(Clone, Debug)]
+pub struct LocalPlanner {
+    /// Obstacles seen at runtime, on top of the grid.
+    pub obstacles: HashSet<IVec2>,
+    /// How many path poses ahead of the closest one to steer towards.
+    pub lookahead: usize,
+    /// Runtime obstacles further away than this many cells are ignored.
+    pub clearance_radius: i32,
+    pub progress_weight: f32,
+    pub heading_weight: f32,
+    pub clearance_weight: f32,
+}
+
+impl LocalPlanner {
+    pub fn new(lookahead: usize) -> Self {
+        Self {
+            obstacles: HashSet::new(),
+            lookahead,
+            clearance_radius: 2,
+            progress_weight: 1.0,
+            heading_weight: 0.1,
+            clearance_weight: 0.5,
+        }
+    }
+
+    pub fn add_obstacle(&mut self, position: IVec2) {
+        self.obstacles.insert(position);
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
+    }
+
+    /// Checks the grid and the runtime obstacles against the agent at `pose`.
+    pub fn is_pose_blocked(&self, grid: &Grid, agent: &Agent, pose: &Cell) -> bool {
+        grid.is_pose_blocked(agent, pose)
+            || self.obstacles.contains(&pose.position)
+            || agent
+                .rotation_footprint(pose.rotation)
+                .iter()
+                .any(|cell| self.obstacles.contains(&(*cell + pose.position)))
+    }
+
+    /// Chebyshev distance to the closest runtime obstacle, capped at
+    /// `clearance_radius`.
+    fn clearance(&self, position: IVec2) -> i32 {
+        self.obstacles
+            .iter()
+            .map(|obstacle| {
+                let offset = (*obstacle - position).abs();
+                offset.x.max(offset.y)
+            })
+            .min()
+            .unwrap_or(self.clearance_radius)
+            .min(self.clearance_radius)
+    }
+
+    /// The pose on `path` to steer towards from `current`.
+    pub fn target<'a>(&self, current: &Cell, path: &'a [Cell]) -> Option<&'a Cell> {
+        let nearest = path
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, pose)| (pose.position - current.position).length_squared())
+            .map(|(index, _)| index)?;
+        path.get((nearest + self.lookahead).min(path.len() - 1))
+    }
+
+    /// Picks the next pose for an agent at `current` following `path`, or
+    /// `None` if every move is blocked.
+    pub fn next_pose(
+        &self,
+        grid: &Grid,
+        agent: &Agent,
+        neighbor_cache: &NeighborCacheRef,
+        config: &PlannerConfig,
+        current: &Cell,
+        path: &[Cell],
+    ) -> Option<Cell> {
+        let target = self.target(current, path)?;
+        let mut candidates = Candidates::new();
+        let costs = planner::cost_cache(neighbor_cache, config);
+        planner::motion_candidates(
+            agent,
+            neighbor_cache,
+            &costs,
+            current,
+            config,
+            &mut candidates,
+        );
+        candidates
+            .into_iter()
+            .filter(|(pose, _)| {
+                !planner::is_move_blocked(grid, agent, current, pose)
+                    && !self.is_pose_blocked(grid, agent, pose)
+            })
+            .map(|(pose, _)| {
+                let distance = (target.position - pose.position).as_vec2().length();
+                let heading = pose.rotation_to(target.rotation, config.max_increments as i16);
+                let score = self.progress_weight * distance + self.heading_weight * heading as f32
+                    - self.clearance_weight * self.clearance(pose.position) as f32;
+                (pose, score)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(pose, _)| pose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_dodges_unmapped_obstacle() {
+        let grid = Grid::new(1.0, 10, 10);
+        let (agent, cache, config) = Fixture::new(10, 10)
+            .at(IVec2::new(0, 2))
+            .holonomic(1)
+            .build();
+        let path: Vec<Cell> = (0..10).map(|x| Cell::new(0, IVec2::new(x, 2))).collect();
+
+        let mut local = LocalPlanner::new(3);
+        local.add_obstacle(IVec2::new(4, 2));
+
+        let mut current = path[0].clone();
+        for _ in 0..20 {
+            if current.position == IVec2::new(9, 2) {
+                break;
+            }
+            current = local
+                .next_pose(&grid, &agent, &cache, &config, &current, &path)
+                .unwrap();
+            assert!(!local.is_pose_blocked(&grid, &agent, &current));
+        }
+        assert_eq!(current.position, IVec2::new(9, 2));
+    }
+}
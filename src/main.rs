@@ -3,8 +3,6 @@ use std::rc::Rc;
 use std::time::Instant;
 
 use agent::Agent;
-use bitarray::BitArray;
-use geo::{Simplify, SimplifyIdx};
 use noise::NoiseFn;
 use notan::app::crevice::std140::WriteStd140;
 use notan::draw::*;
@@ -12,22 +10,73 @@ use notan::math::{IVec2, Vec2};
 use notan::prelude::*;
 use pathfinding::directed::astar::astar;
 
-pub mod agent;
-pub mod bitarray;
-pub mod cell;
-pub mod pathfind;
+// The planner lives in the library, so the fuzz target can drive it and
+// its core also builds without `std`.
+#[cfg(feature = "gpu-field")]
+pub use vehicle_pathfinding::gpu_field;
+#[cfg(feature = "map-sync")]
+pub use vehicle_pathfinding::map_sync;
+pub use vehicle_pathfinding::{
+    agent, alternatives, annotations, batch, bitarray, cell, chokepoints, collision, comparison,
+    congestion, control, corridor, coverage, diagnostics, dispatch, docking, door, encoding,
+    energy, eta, exploration, field, finish, fixed, fleet, forecast, goal, grid, heatmap, hybrid,
+    intercept, json, lanes, layers, loads, local, maneuver, metrics, mission, neighbor_rules,
+    parking, patch, path_codec, pathfind, persist, planner, pursuit, queue, reachability,
+    reservation, resolution, risk, robustness, ros_map, route, selection, sensor, simulation,
+    smoothing, svg, tiled, units, validation,
+};
 
 use cell::Cell;
+use chokepoints::Chokepoint;
+use comparison::Trial;
+use congestion::CongestionMap;
+use control::{BicycleModel, Controller, Tracker, TrackingReport};
+use corridor::CorridorRect;
+use goal::Goal;
+use grid::Grid;
+use heatmap::{HeatmapMetric, PlanningHeatmap};
+use local::LocalPlanner;
+#[cfg(feature = "map-sync")]
+use map_sync::MapSync;
+use parking::ParkingBay;
+use reachability::Components;
+use route::PinnedRoute;
+use selection::Selection;
+use sensor::{Discovery, Lidar};
+use simulation::Simulation;
+use smoothing::ArcLengthPath;
 
 use mimalloc::MiMalloc;
 
-use crate::pathfind::optimized_astar;
+use crate::planner::{EscapeMode, OpenListKind, PlannerConfig};
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 const ARC: u16 = 1;
 const MAX_INCREMENTS: u16 = 32;
+const MAX_STRAIGHT_LENGTH: i32 = 4;
+const CORRIDOR_EXTENT: i32 = 6;
+const GOAL_RADIUS: f32 = 4.0;
+const CONGESTION_DECAY: f32 = 0.9;
+const CONGESTION_WEIGHT: u32 = 500;
+const PEDESTRIAN_COST: u32 = 20_000;
+const HEATMAP_STRIDE: i32 = 4;
+const SIM_TIMESTEP: f32 = 1.0 / 30.0;
+const SIM_SPEED: f32 = 8.0;
+const TRACK_SPEED: f32 = 6.0;
+const TRACK_LOOKAHEAD: f32 = 3.0;
+const TRACK_MAX_STEERING: f32 = 0.6;
+/// Cells between the points the tracker follows.
+const TRACK_SPACING: f32 = 0.25;
+const LOCAL_LOOKAHEAD: usize = 4;
+const EXPLORATION_MAX_GOALS: usize = 50;
+const OPEN_LIST_BUCKET_WIDTH: u32 = 100;
+const PRECOMPUTE_DIR: &str = "target/precompute";
+const LIDAR: Lidar = Lidar {
+    range: 12.0,
+    rays: 180,
+};
 const CELL_SIZE: f32 = 16.0;
 const SCREEN_SIZE: (u32, u32) = (1600, 800);
 const CELL_COUNT: (i32, i32) = (
@@ -41,51 +90,52 @@ const PATHFIND_STATE_SIZE: usize =
 pub struct State {
     font: Option<Font>,
     grid: Grid,
+    components: Components,
     agent: Agent,
     mouse_pos: (f32, f32),
     path: Option<Vec<Cell>>,
+    goal: Option<Goal>,
+    corridor: Vec<CorridorRect>,
     neighbor_cache: cell::NeighborCacheRef,
+    escape: EscapeMode,
+    open_list: OpenListKind,
+    simulation: Simulation,
+    local: LocalPlanner,
+    discovery: Option<Discovery>,
+    route: Option<PinnedRoute>,
+    dragging: Option<DragTarget>,
+    /// Vertices of a geofence being drawn, in world space.
+    fence_draft: Vec<(f64, f64)>,
+    /// Whether queries also run a second planner configuration.
+    comparing: bool,
+    /// The last query under each configuration while comparing.
+    comparison: Vec<Trial>,
+    /// Planning effort from the agent to every sampled goal.
+    heatmap: Option<PlanningHeatmap>,
+    heatmap_metric: HeatmapMetric,
+    /// Heading right-click goals must end at, `None` for any.
+    goal_heading: Option<i16>,
+    /// Box of obstacles to move, copy or delete as a group.
+    selection: Option<Selection>,
+    /// Corner the selection box is dragged from while V is held.
+    selection_anchor: Option<IVec2>,
+    /// Bicycle model following the smoothed path, with the path it was
+    /// smoothed from.
+    tracker: Option<(Tracker, Vec<Cell>)>,
+    /// Passages too narrow for the agent, while shown.
+    chokepoints: Vec<Chokepoint>,
+    /// Map shared with other instances, when hosting or joining one.
+    #[cfg(feature = "map-sync")]
+    map_sync: Option<MapSync>,
+    /// Exact point, in cells, the last plain right-click asked for.
+    goal_point: Option<Vec2>,
 }
 
-struct Grid {
-    cell_size: f32,
-    size: (i32, i32),
-    cells: BitArray,
-}
-impl Grid {
-    fn new(cell_size: f32, width: i32, height: i32) -> Self {
-        let size = (width / cell_size as i32, height / cell_size as i32);
-        println!("Grid size: {:?}", size);
-        let cells = BitArray::new((size.0 * size.1) as usize);
-        Self {
-            cell_size,
-            size,
-            cells,
-        }
-    }
-
-    fn index(&self, x: i32, y: i32) -> usize {
-        (y * self.size.0 + x) as usize
-    }
-
-    fn xy(&self, index: usize) -> (i32, i32) {
-        let x = index as i32 % self.size.0;
-        let y = index as i32 / self.size.0;
-        (x, y)
-    }
-
-    fn is_cell_blocked(&self, x: i32, y: i32) -> bool {
-        if x < 0 || x >= self.size.0 || y < 0 || y >= self.size.1 {
-            return true;
-        }
-        self.cells.get_bool(self.index(x, y))
-    }
-
-    fn toggle_cell(&mut self, x: i32, y: i32) {
-        let index = self.index(x, y);
-        let existing = self.cells.get_bool(index);
-        self.cells.set_bool(index, !existing);
-    }
+/// What a Ctrl+left drag grabbed: a pose of the path or an existing pin.
+#[derive(Clone, Copy, Debug)]
+enum DragTarget {
+    Path(usize),
+    Pin(usize),
 }
 
 #[notan_main]
@@ -106,123 +156,312 @@ fn setup(gfx: &mut Graphics) -> State {
         .create_font(include_bytes!("assets/quicksand.ttf"))
         .expect("Error loading font");
     let cell_size = CELL_SIZE;
-    let grid = Grid::new(cell_size, SCREEN_SIZE.0 as i32, SCREEN_SIZE.1 as i32);
+    let mut grid = Grid::new(cell_size, SCREEN_SIZE.0 as i32, SCREEN_SIZE.1 as i32);
+    grid.congestion = Some(CongestionMap::new(
+        grid.size,
+        CONGESTION_DECAY,
+        CONGESTION_WEIGHT,
+    ));
+    #[cfg(feature = "map-sync")]
+    let map_sync = MapSync::from_env(&mut grid);
+    let precompute_dir = std::path::Path::new(PRECOMPUTE_DIR);
+    let mut neighbor_cache = persist::cached_neighbor_cache(precompute_dir, MAX_INCREMENTS, ARC);
+    neighbor_cache.add_straight_primitives(MAX_STRAIGHT_LENGTH);
     State {
         font: Some(font),
+        components: Components::compute(&grid),
         grid,
-        agent: Agent::new(IVec2::new(3, 3), Vec2::new(2.35, 1.75), 0, MAX_INCREMENTS),
+        agent: persist::cached_agent(
+            precompute_dir,
+            IVec2::new(3, 3),
+            Vec2::new(2.35, 1.75),
+            0,
+            MAX_INCREMENTS,
+        ),
         mouse_pos: (0.0, 0.0),
         path: None,
-        neighbor_cache: Rc::new(RefCell::new(cell::NeighborCache::new_precomputed(
-            MAX_INCREMENTS,
-            ARC,
-        ))),
+        goal: None,
+        corridor: Vec::new(),
+        neighbor_cache: Rc::new(RefCell::new(neighbor_cache)),
+        escape: EscapeMode::Penalized { penalty: 10_000 },
+        open_list: OpenListKind::BinaryHeap,
+        simulation: Simulation::new(SIM_TIMESTEP),
+        local: LocalPlanner::new(LOCAL_LOOKAHEAD),
+        discovery: None,
+        route: None,
+        dragging: None,
+        fence_draft: Vec::new(),
+        comparing: false,
+        comparison: Vec::new(),
+        heatmap: None,
+        heatmap_metric: HeatmapMetric::Expansions,
+        goal_heading: None,
+        selection: None,
+        selection_anchor: None,
+        tracker: None,
+        chokepoints: Vec::new(),
+        #[cfg(feature = "map-sync")]
+        map_sync,
+        goal_point: None,
     }
 }
 
-fn pathfind(state: &mut State, to: IVec2, arc: u16, max_increment: u16) {
+fn pathfind(state: &mut State, to: impl Into<Goal>, arc: u16, max_increment: u16) {
     let start = Instant::now();
+    let to = to.into();
     let start_action = Cell::new(state.agent.rotation, state.agent.position);
-    let neighbors_cache = state.neighbor_cache.clone();
-
-    // let result = astar(
-    //     &start_action,
-    //     |action| {
-    //         let mut result = Vec::with_capacity(128);
-
-    //         for neigh in action.neighbors(&neighbors_cache, arc, max_increment) {
-    //             if !state
-    //                 .grid
-    //                 .is_cell_blocked(neigh.position.x as i32, neigh.position.y as i32)
-    //             {
-    //                 let cost = neigh.cost(Some(action.clone()), arc, max_increment);
-    //                 let rotation_footprint = state.agent.rotation_footprint(neigh.rotation);
-    //                 if rotation_footprint.iter().all(|cell| {
-    //                     !state.grid.is_cell_blocked(
-    //                         cell.x as i32 + neigh.position.x,
-    //                         cell.y as i32 + neigh.position.y,
-    //                     )
-    //                 }) {
-    //                     result.push((neigh, cost));
-    //                 }
-    //             }
-    //         }
-
-    //         result
-    //     },
-    //     |action| action.heuristic(to, max_increment),
-    //     |action| {
-    //         let (x, y) = (action.position.x as i32, action.position.y as i32);
-    //         let (goal_x, goal_y) = (to.x as i32, to.y as i32);
-    //         x == goal_x && y == goal_y
-    //     },
-    // );
-    let result = optimized_astar(
-        start_action,
-        PATHFIND_STATE_SIZE,
-        |action| {
-            let mut result = Vec::with_capacity(128);
-
-            for neigh in action.neighbors(&neighbors_cache, arc, max_increment) {
-                if !state
-                    .grid
-                    .is_cell_blocked(neigh.position.x as i32, neigh.position.y as i32)
-                {
-                    let cost = neigh.cost(Some(action.clone()), arc, max_increment);
-                    let rotation_footprint = state.agent.rotation_footprint(neigh.rotation);
-                    if rotation_footprint.iter().all(|cell| {
-                        !state.grid.is_cell_blocked(
-                            cell.x as i32 + neigh.position.x,
-                            cell.y as i32 + neigh.position.y,
-                        )
-                    }) {
-                        result.push((neigh, cost));
-                    }
-                }
-            }
 
-            result
-        },
-        |action| action.heuristic(to, MAX_INCREMENTS),
-        |action| {
-            let (x, y) = (action.position.x as i32, action.position.y as i32);
-            let (goal_x, goal_y) = (to.x as i32, to.y as i32);
-            x == goal_x && y == goal_y
-        },
+    // A blocked start may still escape, so only free starts are pre-checked.
+    let start_free = !state
+        .grid
+        .is_cell_blocked(state.agent.position.x, state.agent.position.y);
+    let (min, max) = to.bounds();
+    let reachable = (min.y..=max.y).any(|y| {
+        (min.x..=max.x).any(|x| {
+            let cell = IVec2::new(x, y);
+            to.contains(cell) && state.components.is_reachable(state.agent.position, cell)
+        })
+    });
+    if start_free && !reachable {
+        println!("Goal {:?} is unreachable", to);
+        state.comparison.clear();
+        state.corridor.clear();
+        state.route = None;
+        state.path = None;
+        return;
+    }
+
+    let mut config = PlannerConfig::new(arc, max_increment, PATHFIND_STATE_SIZE);
+    config.escape = state.escape;
+    config.open_list = state.open_list;
+    if state.comparing {
+        // the current settings against an admissible heuristic
+        let mut admissible = config.clone();
+        admissible.heuristic_weight = Some(1.0);
+        state.comparison = comparison::compare(
+            &state.grid,
+            &state.agent,
+            &state.neighbor_cache,
+            &start_action,
+            &to,
+            &[("current", config.clone()), ("admissible", admissible)],
+        );
+        print!("{}", comparison::table(&state.comparison));
+    }
+    let result = diagnostics::plan_diagnosed(
+        &state.grid,
+        &state.agent,
+        &state.neighbor_cache,
+        start_action,
+        to.clone(),
+        &config,
     );
+    state.goal = Some(to);
 
-    if let Some((path, _)) = result {
-        state.path = Some(path.iter().map(|a| a.clone()).collect());
-    } else {
+    if let Ok(result) = result {
+        if let Some(adjustment) = &result.start_adjustment {
+            println!(
+                "Start adjusted from {:?} to {:?} after {} escape steps",
+                adjustment.requested, adjustment.adjusted, adjustment.escape_steps
+            );
+        }
+        if let Some(bound) = result.suboptimality_bound {
+            println!("Path cost {} is within {}x of optimal", result.cost, bound);
+        }
+        println!(
+            "Path length {:.1} m, cost {:.1} m",
+            state.grid.to_meters(units::path_length(&result.path)),
+            units::cost_to_meters(&state.grid, result.cost)
+        );
+        for maneuver in maneuver::segment_path(&result.path, max_increment) {
+            println!("Maneuver: {}", maneuver);
+        }
+        if let Some(congestion) = &mut state.grid.congestion {
+            congestion.decay();
+            congestion.record_path(&result.path);
+        }
+        state.corridor = corridor::extract_corridor(&state.grid, &result.path, CORRIDOR_EXTENT);
+        state.route = Some(PinnedRoute::new(result.path.clone()));
+        state.path = Some(result.path);
+    } else if let Err(failure) = result {
+        println!(
+            "No path ({:?}) after {} poses, closest {:?}, frontier died at {} cells",
+            failure.cause,
+            failure.explored,
+            failure.closest.first().map(|pose| pose.position),
+            failure.boundary.len()
+        );
+        state.corridor.clear();
+        state.route = None;
         state.path = None;
     }
 
     println!("Pathfinding took: {:?}", start.elapsed());
 }
 
+/// Parks into a bay centered on `center`, sized to the agent with a cell
+/// of slack on every side and facing the agent's current heading.
+fn park(state: &mut State, center: IVec2) {
+    let start = Instant::now();
+    let half_extent = (state.agent.size / 2.0).ceil().as_ivec2() + IVec2::ONE;
+    let half_extent = if state.agent.rotation % (MAX_INCREMENTS as i16 / 2) == 0 {
+        half_extent
+    } else {
+        IVec2::new(half_extent.y, half_extent.x)
+    };
+    let bay = ParkingBay::new(
+        center - half_extent,
+        center + half_extent,
+        state.agent.rotation,
+    );
+
+    let config = PlannerConfig::new(ARC, MAX_INCREMENTS, PATHFIND_STATE_SIZE);
+    let result = parking::plan_parking(
+        &state.grid,
+        &state.agent,
+        &state.neighbor_cache,
+        Cell::new(state.agent.rotation, state.agent.position),
+        &bay,
+        &config,
+    );
+    state.goal = Some(bay.goal());
+    match result {
+        Ok(maneuver) => {
+            for phase in &maneuver.phases {
+                let direction = if phase.reverse { "reverse" } else { "forward" };
+                println!(
+                    "Parking phase: {} {} steps",
+                    direction,
+                    phase.path.len() - 1
+                );
+            }
+            let path = maneuver.path();
+            state.corridor = corridor::extract_corridor(&state.grid, &path, CORRIDOR_EXTENT);
+            state.route = None;
+            state.path = Some(path);
+        }
+        Err(error) => {
+            println!("Parking failed: {:?}", error);
+            state.corridor.clear();
+            state.route = None;
+            state.path = None;
+        }
+    }
+    println!("Parking took: {:?}", start.elapsed());
+}
+
 fn update(app: &mut App, state: &mut State) {
     let (x, y) = app.mouse.position();
     state.mouse_pos = (x, y);
-    if app.mouse.was_pressed(MouseButton::Left) {
-        let grid_x = (x / state.grid.cell_size) as i32;
-        let grid_y = (y / state.grid.cell_size) as i32;
-        state.grid.toggle_cell(grid_x, grid_y);
+    let cursor = state.grid.world_to_cell(Vec2::new(x, y));
+    if app.mouse.was_pressed(MouseButton::Left) && app.keyboard.ctrl() {
+        // grab a pin, or a point of the path to pin
+        if let Some(route) = &state.route {
+            state.dragging = route.pin_near(cursor, 1).map(DragTarget::Pin).or_else(|| {
+                route
+                    .path()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pose)| (pose.position - cursor).abs().max_element() <= 1)
+                    .min_by_key(|(_, pose)| (pose.position - cursor).length_squared())
+                    .map(|(index, _)| DragTarget::Path(index))
+            });
+        }
+    } else if app.mouse.was_pressed(MouseButton::Left) {
+        state.grid.toggle_cell(cursor.x, cursor.y);
+        state.components = Components::compute(&state.grid);
+    }
+    if app.mouse.was_released(MouseButton::Left) {
+        if let (Some(target), Some(route)) = (state.dragging.take(), &mut state.route) {
+            let config = PlannerConfig::new(ARC, MAX_INCREMENTS, PATHFIND_STATE_SIZE);
+            let (grid, agent, cache) = (&state.grid, &state.agent, &state.neighbor_cache);
+            let replanned = match target {
+                DragTarget::Path(index) => {
+                    route.insert_pin(index, cursor, grid, agent, cache, &config)
+                }
+                DragTarget::Pin(pin) => route.move_pin(pin, cursor, grid, agent, cache, &config),
+            };
+            if replanned {
+                state.path = Some(route.path());
+            } else {
+                println!("Can't route through {:?}", cursor);
+            }
+        }
     }
     if app.mouse.was_pressed(MouseButton::Middle) {
-        state.agent.position = IVec2::new(
-            (x / state.grid.cell_size) as i32,
-            (y / state.grid.cell_size) as i32,
-        );
+        state.agent.position = cursor;
     }
     if app.mouse.was_pressed(MouseButton::Right) {
-        let to = (
-            (x / state.grid.cell_size) as i32,
-            (y / state.grid.cell_size) as i32,
-        );
-        pathfind(state, IVec2::new(to.0, to.1), ARC, MAX_INCREMENTS);
+        let to = cursor;
+        state.goal_point = None;
+        if app.keyboard.shift() {
+            // plan anywhere into a circular area around the click
+            let goal = Goal::Circle {
+                center: to,
+                radius: GOAL_RADIUS,
+            };
+            pathfind(state, goal, ARC, MAX_INCREMENTS);
+        } else if let Some(heading) = state.goal_heading {
+            let goal = Goal::Oriented {
+                goal: Box::new(Goal::Cell(to)),
+                heading,
+                tolerance: 0,
+            };
+            pathfind(state, goal, ARC, MAX_INCREMENTS);
+        } else {
+            // plan to the cell, then curve to the exact point clicked
+            state.goal_point = Some(Vec2::new(x, y) / state.grid.cell_size);
+            pathfind(state, to, ARC, MAX_INCREMENTS);
+        }
+    }
+    let scroll = app.mouse.wheel_delta.y;
+    if scroll != 0.0 {
+        // the wheel turns the agent, or the goal heading with shift held
+        let step = scroll.signum() as i16;
+        if app.keyboard.shift() {
+            let heading = state.goal_heading.unwrap_or(state.agent.rotation);
+            state.goal_heading = Some(Cell::clamp_rotation(heading + step, MAX_INCREMENTS as i16));
+        } else {
+            state.agent.rotation =
+                Cell::clamp_rotation(state.agent.rotation + step, MAX_INCREMENTS as i16);
+        }
+    }
+    if app.keyboard.was_pressed(KeyCode::V) {
+        // box-select obstacles from here to where V is released
+        state.selection_anchor = Some(cursor);
+    }
+    if let (true, Some(anchor)) = (app.keyboard.is_down(KeyCode::V), state.selection_anchor) {
+        state.selection = Some(Selection::new(anchor, cursor));
+    }
+    if let Some(selection) = &mut state.selection {
+        // arrows move the selected obstacles, shift+arrows copy them
+        let offset = [
+            (KeyCode::Left, IVec2::new(-1, 0)),
+            (KeyCode::Right, IVec2::new(1, 0)),
+            (KeyCode::Up, IVec2::new(0, -1)),
+            (KeyCode::Down, IVec2::new(0, 1)),
+        ]
+        .into_iter()
+        .filter(|(key, _)| app.keyboard.was_pressed(*key))
+        .map(|(_, offset)| offset)
+        .sum::<IVec2>();
+        let edited = if offset != IVec2::ZERO {
+            selection.shift(&mut state.grid, offset, app.keyboard.shift()) > 0
+        } else if app.keyboard.was_pressed(KeyCode::Delete) {
+            selection.delete(&mut state.grid) > 0
+        } else {
+            false
+        };
+        if edited {
+            state.components = Components::compute(&state.grid);
+        }
+        if app.keyboard.was_pressed(KeyCode::Escape) {
+            state.selection = None;
+        }
     }
-    if app.keyboard.is_down(KeyCode::Space) {
-        state.agent.rotation = (state.agent.rotation + 1) % MAX_INCREMENTS as i16;
+    if app.keyboard.was_pressed(KeyCode::R) {
+        // accept any heading at the goal again
+        state.goal_heading = None;
     }
     if app.keyboard.is_down(KeyCode::N) {
         // generate map with noise
@@ -237,6 +476,288 @@ fn update(app: &mut App, state: &mut State) {
                 }
             }
         }
+        state.components = Components::compute(&state.grid);
+    }
+    if app.keyboard.was_pressed(KeyCode::P) {
+        // drop a triangular obstacle polygon under the cursor
+        let size = state.grid.cell_size as f64 * 3.0;
+        let (x, y) = (x as f64, y as f64);
+        state.grid.add_polygon(geo::Polygon::new(
+            geo::LineString::from(vec![
+                (x, y - size),
+                (x + size, y + size),
+                (x - size, y + size),
+            ]),
+            vec![],
+        ));
+        state.components = Components::compute(&state.grid);
+    }
+    if app.keyboard.was_pressed(KeyCode::G) {
+        // add a geofence vertex under the cursor
+        state.fence_draft.push((x as f64, y as f64));
+    }
+    if app.keyboard.was_pressed(KeyCode::K) || app.keyboard.was_pressed(KeyCode::I) {
+        // close the drawn geofence as a keep-out zone or a keep-in boundary
+        let vertices = std::mem::take(&mut state.fence_draft);
+        if vertices.len() >= 3 {
+            let fence = geo::Polygon::new(geo::LineString::from(vertices), vec![]);
+            if app.keyboard.was_pressed(KeyCode::K) {
+                state.grid.keep_out.push(fence);
+            } else {
+                state.grid.keep_in.push(fence);
+            }
+        }
+    }
+    if app.keyboard.was_pressed(KeyCode::Q) {
+        // switch open lists to compare search times
+        state.open_list = match state.open_list {
+            OpenListKind::BinaryHeap => OpenListKind::Buckets {
+                width: OPEN_LIST_BUCKET_WIDTH,
+            },
+            OpenListKind::Buckets { .. } => OpenListKind::BinaryHeap,
+        };
+        println!("Open list: {:?}", state.open_list);
+    }
+    if app.keyboard.was_pressed(KeyCode::E) {
+        // export the scene and path for documentation
+        let path = state.path.as_deref().unwrap_or(&[]);
+        let svg = svg::scene_svg(
+            &state.grid,
+            &state.agent,
+            path,
+            MAX_INCREMENTS,
+            &svg::SvgStyle::default(),
+        );
+        match std::fs::write("scene.svg", svg) {
+            Ok(()) => println!("Exported scene.svg"),
+            Err(error) => println!("Could not export scene.svg: {}", error),
+        }
+    }
+    if app.keyboard.was_pressed(KeyCode::A) {
+        // toggle comparing planner configurations on every query
+        state.comparing = !state.comparing;
+        state.comparison.clear();
+        println!("Comparison mode: {}", state.comparing);
+    }
+    if app.keyboard.was_pressed(KeyCode::M) && app.keyboard.shift() {
+        // switch the heatmap between planning time and expansions
+        state.heatmap_metric = match state.heatmap_metric {
+            HeatmapMetric::Time => HeatmapMetric::Expansions,
+            HeatmapMetric::Expansions => HeatmapMetric::Time,
+        };
+        println!("Heatmap metric: {:?}", state.heatmap_metric);
+    } else if app.keyboard.was_pressed(KeyCode::M) {
+        // plan to every sampled goal from the agent, or hide the heatmap
+        state.heatmap = match state.heatmap {
+            Some(_) => None,
+            None => {
+                let start = Instant::now();
+                let mut config = PlannerConfig::new(ARC, MAX_INCREMENTS, PATHFIND_STATE_SIZE);
+                config.open_list = state.open_list;
+                let heatmap = PlanningHeatmap::compute(
+                    &state.grid,
+                    &state.agent,
+                    &state.neighbor_cache,
+                    &Cell::new(state.agent.rotation, state.agent.position),
+                    &config,
+                    HEATMAP_STRIDE,
+                );
+                for sample in heatmap.worst(state.heatmap_metric, 5) {
+                    println!(
+                        "Slow goal {:?}: {:?}, {} poses expanded",
+                        sample.goal, sample.elapsed, sample.expanded
+                    );
+                }
+                println!(
+                    "Planned to {} goals, took {:?}",
+                    heatmap.samples.len(),
+                    start.elapsed()
+                );
+                Some(heatmap)
+            }
+        };
+    }
+    if app.keyboard.was_pressed(KeyCode::H) {
+        // place or remove a pedestrian the planner steers around
+        let cost = match state.grid.soft_cost_at(cursor.x, cursor.y) {
+            0 => PEDESTRIAN_COST,
+            _ => 0,
+        };
+        state.grid.set_soft_cost(cursor.x, cursor.y, cost);
+    }
+    if app.keyboard.was_pressed(KeyCode::B) {
+        park(state, cursor);
+    }
+    if app.keyboard.was_pressed(KeyCode::S) {
+        // send a copy of the agent down the current path
+        if let Some(path) = &state.path {
+            state
+                .simulation
+                .add_agent(state.agent.clone(), path.clone(), SIM_SPEED);
+        }
+    }
+    state.simulation.advance(app.timer.delta_f32());
+    if app.keyboard.was_pressed(KeyCode::F) {
+        // track the smoothed path with a bicycle model, Shift for Stanley
+        state.tracker = state.path.as_ref().map(|path| {
+            let controller = if app.keyboard.shift() {
+                Controller::Stanley { gain: 2.0 }
+            } else {
+                Controller::PurePursuit {
+                    lookahead: TRACK_LOOKAHEAD,
+                }
+            };
+            let model = track_model(&state.agent);
+            // Each stretch smoothed on its own, so the switches stay sharp.
+            let segments = control::direction_runs(path, MAX_INCREMENTS)
+                .into_iter()
+                .map(|(cells, reverse)| {
+                    let (spline, _) = smoothing::smooth_path_within_curvature(
+                        cells,
+                        1.0,
+                        MAX_INCREMENTS,
+                        model.max_curvature(),
+                    );
+                    (ArcLengthPath::new(spline).resample(TRACK_SPACING), reverse)
+                })
+                .collect();
+            (
+                Tracker::with_segments(model, controller, segments, TRACK_SPEED),
+                path.clone(),
+            )
+        });
+    }
+    if let Some((tracker, _)) = &mut state.tracker {
+        tracker.step(app.timer.delta_f32().min(SIM_TIMESTEP));
+    }
+    if app.keyboard.was_pressed(KeyCode::J) {
+        // show the passages too narrow for the agent, or hide them
+        state.chokepoints = match state.chokepoints.is_empty() {
+            true => chokepoints::find_chokepoints(&state.grid, state.agent.size.y),
+            false => Vec::new(),
+        };
+        println!("Chokepoints: {}", state.chokepoints.len());
+    }
+    if app.keyboard.was_pressed(KeyCode::O) {
+        // drop an obstacle the global planner doesn't know about
+        state.local.add_obstacle(cursor);
+    }
+    if app.keyboard.was_pressed(KeyCode::L) {
+        // take one local planner step along the current path
+        if let Some(path) = &state.path {
+            let config = PlannerConfig::new(ARC, MAX_INCREMENTS, PATHFIND_STATE_SIZE);
+            let current = Cell::new(state.agent.rotation, state.agent.position);
+            if let Some(next) = state.local.next_pose(
+                &state.grid,
+                &state.agent,
+                &state.neighbor_cache,
+                &config,
+                &current,
+                path,
+            ) {
+                state.agent.position = next.position;
+                state.agent.rotation = next.rotation;
+            }
+        }
+    }
+    if app.keyboard.was_pressed(KeyCode::U) {
+        // hide the map behind the lidar, or reveal it again
+        match state.discovery.take() {
+            Some(discovery) => {
+                let congestion = state.grid.congestion.take();
+                state.grid = discovery.truth;
+                state.grid.congestion = congestion;
+            }
+            None => {
+                let truth = std::mem::replace(&mut state.grid, Grid::new(1.0, 0, 0));
+                let mut discovery = Discovery::new(truth);
+                state.grid = discovery.blank_grid();
+                state.grid.congestion = discovery.truth.congestion.take();
+                state.discovery = Some(discovery);
+            }
+        }
+        state.components = Components::compute(&state.grid);
+    }
+    if app.keyboard.was_pressed(KeyCode::C) {
+        // sweep the whole map lane by lane
+        let start = Instant::now();
+        let config = PlannerConfig::new(ARC, MAX_INCREMENTS, PATHFIND_STATE_SIZE);
+        let plan = coverage::plan_coverage(
+            &state.grid,
+            &state.agent,
+            &state.neighbor_cache,
+            Cell::new(state.agent.rotation, state.agent.position),
+            &config,
+            None,
+        );
+        println!(
+            "Covered {} of {} free cells, {} sweeps skipped, took {:?}",
+            plan.covered,
+            plan.free,
+            plan.skipped.len(),
+            start.elapsed()
+        );
+        state.route = None;
+        state.path = Some(plan.path);
+    }
+    if app.keyboard.was_pressed(KeyCode::X) {
+        // explore the hidden map frontier by frontier
+        if let Some(discovery) = &mut state.discovery {
+            let config = PlannerConfig::new(ARC, MAX_INCREMENTS, PATHFIND_STATE_SIZE);
+            let report = exploration::explore(
+                discovery,
+                &mut state.grid,
+                &mut state.agent,
+                &state.neighbor_cache,
+                &LIDAR,
+                &config,
+                EXPLORATION_MAX_GOALS,
+            );
+            println!(
+                "Explored {:.1}% ({} of {} cells, {} free), {} goals reached, {} failed",
+                report.stats.ratio() * 100.0,
+                report.stats.known,
+                report.stats.total,
+                report.stats.known_free,
+                report.goals_reached,
+                report.goals_failed
+            );
+            state.components = Components::compute(&state.grid);
+            state.route = None;
+            state.path = Some(report.path);
+        }
+    }
+    if let Some(discovery) = &mut state.discovery {
+        let found = discovery.scan(&mut state.grid, state.agent.position, &LIDAR);
+        if !found.is_empty() {
+            state.components = Components::compute(&state.grid);
+            let invalidated = state
+                .path
+                .as_ref()
+                .is_some_and(|path| sensor::is_path_invalidated(&state.grid, &state.agent, path));
+            if let (true, Some(goal)) = (invalidated, state.goal.clone()) {
+                pathfind(state, goal, ARC, MAX_INCREMENTS);
+            }
+        }
+    }
+    #[cfg(feature = "map-sync")]
+    if let Some(map_sync) = &mut state.map_sync {
+        match map_sync.sync(&mut state.grid) {
+            Ok(true) => {
+                state.components = Components::compute(&state.grid);
+                let invalidated = state.path.as_ref().is_some_and(|path| {
+                    sensor::is_path_invalidated(&state.grid, &state.agent, path)
+                });
+                if let (true, Some(goal)) = (invalidated, state.goal.clone()) {
+                    pathfind(state, goal, ARC, MAX_INCREMENTS);
+                }
+            }
+            Ok(false) => {}
+            Err(error) => {
+                eprintln!("Lost the map host: {error}");
+                state.map_sync = None;
+            }
+        }
     }
     if app.keyboard.is_down(KeyCode::T) {
         if let Some(path) = &state.path {
@@ -258,6 +779,94 @@ fn draw_selection(draw: &mut Draw, position: (i32, i32), size: f32, color: Color
     rect(draw, x_grid + size - line_width, y_grid, line_width, size);
     rect(draw, x_grid, y_grid + size - line_width, size, line_width);
 }
+/// Draws `pose` as an arrow along its heading, red if it reverses from
+/// `from`.
+fn draw_pose(
+    pose: &Cell,
+    from: Option<&Cell>,
+    draw: &mut Draw,
+    font: &Font,
+    cell_size: f32,
+    max_increments: u16,
+) {
+    // Define the color based on the reverse flag
+    let reverse = if let Some(from) = from {
+        pose.is_reverse_to(from, max_increments as i16)
+    } else {
+        false
+    };
+    let color = if reverse { Color::RED } else { Color::BLUE };
+
+    // Calculate the center of the current cell as the starting point
+    let center = Vec2::new(
+        pose.position.x as f32 * cell_size + cell_size / 2.0,
+        pose.position.y as f32 * cell_size + cell_size / 2.0,
+    );
+
+    // Determine the rotation angle, adjusting for reverse if necessary
+    let rotation_angle = pose.rotation as f32 * 2.0 * std::f32::consts::PI / max_increments as f32;
+
+    // Calculate the end point of the arrow based on the rotation angle
+    // Ensuring it remains visually centered within the cell
+    let arrow_length = cell_size / 2.0; // Adjust this value to change the arrow's length
+    let end = center + Vec2::from_angle(rotation_angle).normalize() * arrow_length;
+
+    // Draw the arrow from center to the calculated end point
+    draw_arrow(draw, center, end, color);
+
+    // Write the data (rotation) below the arrow
+    let text = format!("R: {}", pose.rotation);
+    let text_position = Vec2::new(
+        pose.position.x as f32 * cell_size,
+        (pose.position.y as f32 + 1.0) * cell_size - 20.0, // Adjust this to position the text below the cell
+    );
+    draw.text(font, &text)
+        .translate(text_position.x, text_position.y)
+        .size(15.0)
+        .color(Color::WHITE);
+}
+/// A small panel in the bottom left plotting the cross-track error over
+/// time, scaled to the largest error, above the report's numbers.
+fn draw_tracking_report(draw: &mut Draw, font: &Font, report: &TrackingReport) {
+    let (width, height) = (320.0, 140.0);
+    let origin = Vec2::new(10.0, SCREEN_SIZE.1 as f32 - height - 10.0);
+    draw.rect((origin.x, origin.y), (width, height))
+        .color(Color::BLACK)
+        .alpha(0.8);
+    let plot_height = 60.0;
+    let axis = origin.y + 10.0 + plot_height / 2.0;
+    draw.line((origin.x, axis), (origin.x + width, axis))
+        .color(Color::GRAY);
+    let scale = plot_height / 2.0 / report.max_error.max(0.1);
+    let duration = report.duration.max(f32::EPSILON);
+    let point =
+        |(time, error): (f32, f32)| (origin.x + time / duration * width, axis + error * scale);
+    for pair in report.errors.windows(2) {
+        draw.line(point(pair[0]), point(pair[1])).color(Color::TEAL);
+    }
+    let lines = [
+        format!(
+            "cross-track max {:.2} mean {:.2} cells",
+            report.max_error, report.mean_error
+        ),
+        format!("max curvature {:.3} / cell", report.max_curvature),
+        format!(
+            "switches planned {} driven {}{}",
+            report.planned_switches,
+            report.executed_switches,
+            if report.arrived { "" } else { " (driving)" }
+        ),
+    ];
+    for (row, line) in lines.iter().enumerate() {
+        draw.text(font, line)
+            .translate(
+                origin.x + 6.0,
+                origin.y + plot_height + 20.0 + row as f32 * 18.0,
+            )
+            .size(15.0)
+            .color(Color::WHITE);
+    }
+}
 fn draw_arrow(draw: &mut Draw, from: Vec2, to: Vec2, color: Color) {
     if from.is_finite() == false || to.is_finite() == false {
         return;
@@ -289,74 +898,33 @@ fn draw_arrow(draw: &mut Draw, from: Vec2, to: Vec2, color: Color) {
     draw.line((arrow_end.x, arrow_end.y), (arrow_end2.x, arrow_end2.y))
         .color(color);
 }
-fn draw_path_spline(draw: &mut Draw, path: &[Cell], color: Color, cell_size: f32) {
-    use geo::{Coord, LineString};
-    use splines::{Interpolation, Key, Spline};
-
-    let line_string = LineString::new(
-        path.iter()
-            .map(|cell| Coord {
-                x: cell.position.x as f64,
-                y: cell.position.y as f64,
-            })
-            .collect(),
-    );
-    let simplified_path = line_string.simplify_idx(&0.5);
-    // add back direction nodes, where reverse
-    // switches to the opposite direction and back
-    let mut reverse_keys = Vec::new();
-    let simplified_path = path
-        .iter()
-        .enumerate()
-        .filter(|(i, cell)| {
-            let (i, cell) = (*i, *cell);
-            if i == 0 || i == path.len() - 1 {
-                return true;
-            }
-
-            let next = &path[i + 1];
-            let reverse = next.is_reverse_to(cell, MAX_INCREMENTS as i16);
-            if reverse {
-                reverse_keys.push(i);
-                return true;
-            }
-
-            simplified_path.contains(&i)
-        })
-        .map(|(i, _)| i)
-        .collect::<Vec<_>>();
-
-    let mut keys = Vec::with_capacity(path.len());
-    for (i, cell_i) in simplified_path.iter().enumerate() {
-        let cell = &path[*cell_i];
-        let x = (cell.position.x as f32 + 0.5) * cell_size;
-        let y = (cell.position.y as f32 + 0.5) * cell_size;
-        let xy = Vec2::new(x, y);
-
-        let angle = (cell.rotation as f32 / MAX_INCREMENTS as f32) * std::f32::consts::PI * 2.0;
-        let angle_vector = Vec2::from_angle(angle);
-        let tangent = xy + angle_vector * cell_size;
-        let reverse = reverse_keys.contains(cell_i);
-        let interpolation = if reverse {
-            Interpolation::Linear
-        } else {
-            Interpolation::Bezier(tangent)
-        };
-        keys.push(Key::new(i as f32, xy, interpolation));
+/// The vehicle the tracker simulates for `agent`.
+fn track_model(agent: &Agent) -> BicycleModel {
+    BicycleModel {
+        wheelbase: agent.size.x * 0.6,
+        max_steering: TRACK_MAX_STEERING,
     }
+}
 
-    let spline = Spline::from_vec(keys);
-    // now sample and draw the spline at a higher resolution
-    let mut last = None;
-    for i in 0..path.len() * 10 {
-        let t = i as f32 / 10.0;
-        let point = spline
-            .clamped_sample(t)
-            .expect(format!("Failed to sample x at {} (len: {})", t, spline.len()).as_str());
-        if let Some((last_x, last_y)) = last {
-            draw.line((last_x, last_y), (point.x, point.y)).color(color);
+/// Marks where the path's spline bends tighter than the tracked vehicle
+/// can steer.
+fn draw_curvature_violations(draw: &mut Draw, path: &[Cell], agent: &Agent, cell_size: f32) {
+    let points = smoothing::smooth_path(path, cell_size, MAX_INCREMENTS);
+    let max_curvature = track_model(agent).max_curvature() / cell_size;
+    for violation in smoothing::curvature_violations(&points, max_curvature) {
+        for point in &points[violation.samples] {
+            draw.circle(cell_size * 0.15)
+                .position(point.x, point.y)
+                .color(Color::RED);
         }
-        last = Some((point.x, point.y));
+    }
+}
+
+fn draw_path_spline(draw: &mut Draw, path: &[Cell], color: Color, grid: &Grid, agent: &Agent) {
+    let points = smoothing::smooth_path_collision_free(grid, agent, path);
+    for pair in points.windows(2) {
+        draw.line((pair[0].x, pair[0].y), (pair[1].x, pair[1].y))
+            .color(color);
     }
 }
 
@@ -401,6 +969,264 @@ fn draw(gfx: &mut Graphics, state: &mut State) {
         }
     }
 
+    // Draw the congestion heatmap
+    if let Some(congestion) = &state.grid.congestion {
+        let max_value = congestion.max_value();
+        if max_value > 0.0 {
+            for (index, value) in congestion.values().iter().enumerate() {
+                if *value <= 0.0 {
+                    continue;
+                }
+                let (x, y) = state.grid.xy(index);
+                draw.rect(
+                    (
+                        x as f32 * state.grid.cell_size,
+                        y as f32 * state.grid.cell_size,
+                    ),
+                    (state.grid.cell_size, state.grid.cell_size),
+                )
+                .color(Color::PURPLE)
+                .alpha(0.5 * value / max_value);
+            }
+        }
+    }
+
+    // Tint soft obstacles
+    if let Some(costs) = &state.grid.soft_costs {
+        for (index, cost) in costs.iter().enumerate() {
+            if *cost == 0 {
+                continue;
+            }
+            let (x, y) = state.grid.xy(index);
+            draw.rect(
+                (
+                    x as f32 * state.grid.cell_size,
+                    y as f32 * state.grid.cell_size,
+                ),
+                (state.grid.cell_size, state.grid.cell_size),
+            )
+            .color(Color::PINK)
+            .alpha(0.6);
+        }
+    }
+
+    // Tint chokepoints, labeled with their narrowest width
+    for chokepoint in &state.chokepoints {
+        for cell in &chokepoint.cells {
+            draw.rect(
+                (
+                    cell.x as f32 * state.grid.cell_size,
+                    cell.y as f32 * state.grid.cell_size,
+                ),
+                (state.grid.cell_size, state.grid.cell_size),
+            )
+            .color(Color::OLIVE)
+            .alpha(0.6);
+        }
+        if let Some(font) = &state.font {
+            let center = (chokepoint.center() + Vec2::splat(0.5)) * state.grid.cell_size;
+            draw.text(
+                font,
+                &format!("{} {:.0}", chokepoint.name, chokepoint.min_clearance),
+            )
+            .translate(center.x, center.y)
+            .size(13.0)
+            .color(Color::WHITE);
+        }
+    }
+
+    // Draw the planning effort heatmap
+    if let Some(heatmap) = &state.heatmap {
+        let max_value = heatmap.max_value(state.heatmap_metric);
+        let size = heatmap.stride as f32 * state.grid.cell_size;
+        for sample in &heatmap.samples {
+            if max_value <= 0.0 {
+                break;
+            }
+            draw.rect(
+                (
+                    sample.goal.x as f32 * state.grid.cell_size,
+                    sample.goal.y as f32 * state.grid.cell_size,
+                ),
+                (size, size),
+            )
+            .color(Color::RED)
+            .alpha(0.7 * sample.value(state.heatmap_metric) / max_value);
+        }
+    }
+
+    // Tint free cells the agent can't reach
+    let agent_label = state.components.label(state.agent.position);
+    for y in 0..state.grid.size.1 {
+        for x in 0..state.grid.size.0 {
+            let label = state.components.label(IVec2::new(x, y));
+            if label != 0 && agent_label != 0 && label != agent_label {
+                draw.rect(
+                    (
+                        x as f32 * state.grid.cell_size,
+                        y as f32 * state.grid.cell_size,
+                    ),
+                    (state.grid.cell_size, state.grid.cell_size),
+                )
+                .color(Color::MAROON)
+                .alpha(0.4);
+            }
+        }
+    }
+
+    // Draw the goal area
+    if let Some(goal) = &state.goal {
+        let (min, max) = goal.bounds();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if goal.contains(IVec2::new(x, y)) {
+                    draw.rect(
+                        (
+                            x as f32 * state.grid.cell_size,
+                            y as f32 * state.grid.cell_size,
+                        ),
+                        (state.grid.cell_size, state.grid.cell_size),
+                    )
+                    .color(Color::GREEN)
+                    .alpha(0.2);
+                }
+            }
+        }
+    }
+
+    // Draw the original obstacle polygons
+    for polygon in &state.grid.polygons {
+        let mut path = draw.path();
+        for (i, coord) in polygon.exterior().coords().enumerate() {
+            if i == 0 {
+                path.move_to(coord.x as f32, coord.y as f32);
+            } else {
+                path.line_to(coord.x as f32, coord.y as f32);
+            }
+        }
+        path.close().stroke(2.0).color(Color::ORANGE);
+    }
+
+    // Draw the geofences and the one being drawn
+    let keep_out = state.grid.keep_out.iter().map(|fence| (fence, Color::RED));
+    let keep_in = state.grid.keep_in.iter().map(|fence| (fence, Color::AQUA));
+    for (fence, color) in keep_out.chain(keep_in) {
+        let mut path = draw.path();
+        for (i, coord) in fence.exterior().coords().enumerate() {
+            if i == 0 {
+                path.move_to(coord.x as f32, coord.y as f32);
+            } else {
+                path.line_to(coord.x as f32, coord.y as f32);
+            }
+        }
+        path.close().stroke(2.0).color(color);
+    }
+    for pair in state.fence_draft.windows(2) {
+        draw.line(
+            (pair[0].0 as f32, pair[0].1 as f32),
+            (pair[1].0 as f32, pair[1].1 as f32),
+        )
+        .color(Color::YELLOW);
+    }
+
+    // Draw the obstacle selection
+    if let Some(selection) = &state.selection {
+        let size = (selection.max - selection.min + IVec2::ONE).as_vec2() * state.grid.cell_size;
+        let corner = selection.min.as_vec2() * state.grid.cell_size;
+        draw.rect((corner.x, corner.y), (size.x, size.y))
+            .color(Color::YELLOW)
+            .alpha(0.15);
+        draw.rect((corner.x, corner.y), (size.x, size.y))
+            .stroke(2.0)
+            .color(Color::YELLOW);
+    }
+
+    // Draw the corridor
+    for rect in &state.corridor {
+        let size = rect.size();
+        draw.rect(
+            (
+                rect.min.x as f32 * state.grid.cell_size,
+                rect.min.y as f32 * state.grid.cell_size,
+            ),
+            (
+                size.x as f32 * state.grid.cell_size,
+                size.y as f32 * state.grid.cell_size,
+            ),
+        )
+        .color(Color::AQUA)
+        .alpha(0.15);
+    }
+
+    // Dim cells the lidar hasn't seen yet
+    if let Some(discovery) = &state.discovery {
+        for y in 0..state.grid.size.1 {
+            for x in 0..state.grid.size.0 {
+                if !discovery.is_known(x, y) {
+                    draw.rect(
+                        (
+                            x as f32 * state.grid.cell_size,
+                            y as f32 * state.grid.cell_size,
+                        ),
+                        (state.grid.cell_size, state.grid.cell_size),
+                    )
+                    .color(Color::GRAY)
+                    .alpha(0.25);
+                }
+            }
+        }
+    }
+
+    // Draw obstacles only the local planner knows about
+    for obstacle in &state.local.obstacles {
+        draw.rect(
+            (
+                obstacle.x as f32 * state.grid.cell_size,
+                obstacle.y as f32 * state.grid.cell_size,
+            ),
+            (state.grid.cell_size, state.grid.cell_size),
+        )
+        .color(Color::PINK);
+    }
+
+    // Draw pinned waypoints
+    if let Some(route) = &state.route {
+        for pin in &route.pins {
+            draw.circle(state.grid.cell_size / 2.0)
+                .position(
+                    (pin.x as f32 + 0.5) * state.grid.cell_size,
+                    (pin.y as f32 + 0.5) * state.grid.cell_size,
+                )
+                .color(Color::MAGENTA);
+        }
+    }
+
+    // Draw simulated agents, red while colliding
+    for index in 0..state.simulation.agents.len() {
+        let color = if state.simulation.is_colliding(index) {
+            Color::RED
+        } else {
+            Color::SILVER
+        };
+        state.simulation.agents[index]
+            .agent
+            .draw(&mut draw, color, state.grid.cell_size);
+    }
+
+    // Draw the tracked vehicle: its trace and its wheelbase
+    if let Some((tracker, _)) = &state.tracker {
+        let size = state.grid.cell_size;
+        for pair in tracker.trace.windows(2) {
+            let (a, b) = (pair[0].1.position * size, pair[1].1.position * size);
+            draw.line((a.x, a.y), (b.x, b.y)).color(Color::TEAL);
+        }
+        let rear = tracker.state.position * size;
+        let front = tracker.model.front_axle(&tracker.state) * size;
+        draw.line((rear.x, rear.y), (front.x, front.y))
+            .color(Color::TEAL)
+            .width(3.0);
+    }
+
     // Draw the agent
     state
         .agent
@@ -410,7 +1236,8 @@ fn draw(gfx: &mut Graphics, state: &mut State) {
     if let Some(path) = &state.path {
         let mut last = None;
         for action in path {
-            action.draw(
+            draw_pose(
+                action,
                 last,
                 &mut draw,
                 &state.font.unwrap(),
@@ -422,17 +1249,105 @@ fn draw(gfx: &mut Graphics, state: &mut State) {
     }
     // Draw the path as a spline
     if let Some(path) = &state.path {
-        draw_path_spline(&mut draw, path, Color::GREEN, state.grid.cell_size);
+        draw_path_spline(&mut draw, path, Color::GREEN, &state.grid, &state.agent);
+        draw_curvature_violations(&mut draw, path, &state.agent, state.grid.cell_size);
+        let finish = state
+            .goal_point
+            .and_then(|point| finish::finish_path(&state.grid, &state.agent, path, point));
+        if let Some(finish) = finish {
+            let size = state.grid.cell_size;
+            for pair in finish.points(16).windows(2) {
+                draw.line(
+                    (pair[0].x * size, pair[0].y * size),
+                    (pair[1].x * size, pair[1].y * size),
+                )
+                .color(Color::GREEN);
+            }
+        }
+    }
+
+    // Overlay the compared path and the comparison stats
+    if let Some(result) = state
+        .comparison
+        .get(1)
+        .and_then(|trial| trial.result.as_ref())
+    {
+        draw_path_spline(
+            &mut draw,
+            &result.path,
+            Color::ORANGE,
+            &state.grid,
+            &state.agent,
+        );
+    }
+    if let (Some(font), false) = (&state.font, state.comparison.is_empty()) {
+        let table = comparison::table(&state.comparison);
+        for (row, line) in table.lines().enumerate() {
+            let color = match row {
+                1 => Color::GREEN,
+                2 => Color::ORANGE,
+                _ => Color::WHITE,
+            };
+            draw.text(font, line)
+                .translate(10.0, 10.0 + row as f32 * 20.0)
+                .size(18.0)
+                .color(color);
+        }
+    }
+
+    // Plot the tracking error over time, with the rest of the report
+    if let (Some(font), Some((tracker, planned))) = (&state.font, &state.tracker) {
+        draw_tracking_report(&mut draw, font, &tracker.report(planned, MAX_INCREMENTS));
     }
 
     // Draw the selection
     let (x, y) = state.mouse_pos;
+    let cursor = state.grid.world_to_cell(Vec2::new(x, y));
+
+    // Show the agent's and the goal's heading next to the cursor
+    if let Some(font) = &state.font {
+        let degrees = |rotation| Cell::increment_to_heading(rotation, MAX_INCREMENTS).to_degrees();
+        let mut text = format!("{:.0}°", degrees(state.agent.rotation));
+        if let Some(heading) = state.goal_heading {
+            text += &format!(" / goal {:.0}°", degrees(heading));
+        }
+        draw.text(font, &text)
+            .translate(x + 16.0, y + 16.0)
+            .size(15.0)
+            .color(Color::WHITE);
+    }
+
+    // Preview placing the agent under the cursor, red where it collides
+    if state.grid.in_bounds(cursor.x, cursor.y) && state.dragging.is_none() {
+        let rotation = state.agent.rotation;
+        let blocked = state
+            .agent
+            .first_colliding_cell(&state.grid, cursor, rotation);
+        let color = if blocked.is_some() {
+            Color::RED
+        } else {
+            Color::GREEN
+        };
+        state.agent.draw_footprint_at(
+            &mut draw,
+            cursor,
+            rotation,
+            color,
+            0.3,
+            state.grid.cell_size,
+        );
+        if let Some(cell) = blocked {
+            draw_selection(
+                &mut draw,
+                (cell.x, cell.y),
+                state.grid.cell_size,
+                Color::RED,
+            );
+        }
+    }
     draw_selection(
         &mut draw,
-        (
-            (x / state.grid.cell_size) as i32,
-            (y / state.grid.cell_size) as i32,
-        ),
+        (cursor.x, cursor.y),
         state.grid.cell_size,
         Color::GREEN,
     );
@@ -453,14 +1368,37 @@ mod tests {
         let agent = Agent::new(IVec2::new(0, 0), Vec2::new(0.01, 0.01), 0, max_increment);
         State {
             font: None,
+            components: Components::compute(&grid),
             grid,
             agent,
             mouse_pos: (0.0, 0.0),
             path: None,
+            goal: None,
+            corridor: Vec::new(),
             neighbor_cache: Rc::new(RefCell::new(cell::NeighborCache::new_precomputed(
                 max_increment,
                 arc,
             ))),
+            escape: EscapeMode::Disabled,
+            open_list: OpenListKind::BinaryHeap,
+            simulation: Simulation::new(SIM_TIMESTEP),
+            local: LocalPlanner::new(LOCAL_LOOKAHEAD),
+            discovery: None,
+            route: None,
+            dragging: None,
+            fence_draft: Vec::new(),
+            comparing: false,
+            comparison: Vec::new(),
+            heatmap: None,
+            heatmap_metric: HeatmapMetric::Expansions,
+            goal_heading: None,
+            selection: None,
+            selection_anchor: None,
+            tracker: None,
+            chokepoints: Vec::new(),
+            #[cfg(feature = "map-sync")]
+            map_sync: None,
+            goal_point: None,
         }
     }
     fn default_state() -> State {
@@ -498,6 +1436,16 @@ mod tests {
         assert!(state.path.is_none());
     }
     #[test]
+    fn test_pathfind_unreachable() {
+        let mut state = default_state();
+        for y in 0..state.grid.size.1 {
+            state.grid.toggle_cell(10, y);
+        }
+        state.components = Components::compute(&state.grid);
+        pathfind(&mut state, IVec2::new(20, 5), 1, 8);
+        assert!(state.path.is_none());
+    }
+    #[test]
     fn test_pathfind_turn() {
         let mut state = default_state();
         pathfind(&mut state, IVec2::new(5, 5), 1, 8);
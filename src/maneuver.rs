@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::cell::Cell;
+
+/// What the vehicle does during a maneuver. Screen y points down, so an
+/// increasing rotation turns clockwise, which is a right turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ManeuverKind {
+    Straight,
+    ArcLeft,
+    ArcRight,
+    /// Rotating without moving.
+    PointTurn,
+}
+
+/// A run of consecutive path steps of the same kind and direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Maneuver {
+    pub kind: ManeuverKind,
+    pub reverse: bool,
+    /// Distance traveled, in cells.
+    pub length: f32,
+    pub start_heading: i16,
+    pub end_heading: i16,
+    /// Indices of the first and last pose of the maneuver in the path.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Maneuver {
+    pub fn heading_degrees(heading: i16, max_increments: u16) -> f32 {
+        heading as f32 * 360.0 / max_increments as f32
+    }
+}
+
+impl fmt::Display for Maneuver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = if self.reverse { "reverse" } else { "forward" };
+        match self.kind {
+            ManeuverKind::Straight => write!(f, "{} {:.1} cells", direction, self.length),
+            ManeuverKind::ArcLeft => {
+                write!(f, "{} {:.1} cells turning left", direction, self.length)
+            }
+            ManeuverKind::ArcRight => {
+                write!(f, "{} {:.1} cells turning right", direction, self.length)
+            }
+            ManeuverKind::PointTurn => write!(
+                f,
+                "turn in place from {} to {}",
+                self.start_heading, self.end_heading
+            ),
+        }
+    }
+}
+
+/// Signed heading change from `from` to `to`, in `(-max / 2, max / 2]`.
+pub fn signed_rotation(from: i16, to: i16, max_increments: u16) -> i16 {
+    let max = max_increments as i16;
+    let diff = (to - from).rem_euclid(max);
+    if diff > max / 2 {
+        diff - max
+    } else {
+        diff
+    }
+}
+
+/// Groups a path's per-cell poses into maneuvers.
+pub fn segment_path(path: &[Cell], max_increments: u16) -> Vec<Maneuver> {
+    let mut maneuvers: Vec<Maneuver> = Vec::new();
+    for (i, pair) in path.windows(2).enumerate() {
+        let (from, to) = (&pair[0], &pair[1]);
+        let length = from.position.as_vec2().distance(to.position.as_vec2());
+        let rotation = signed_rotation(from.rotation, to.rotation, max_increments);
+        let kind = if length == 0.0 {
+            ManeuverKind::PointTurn
+        } else if rotation > 0 {
+            ManeuverKind::ArcRight
+        } else if rotation < 0 {
+            ManeuverKind::ArcLeft
+        } else {
+            ManeuverKind::Straight
+        };
+        let reverse = length > 0.0 && to.is_reverse_to(from, max_increments as i16);
+
+        match maneuvers.last_mut() {
+            Some(last) if last.kind == kind && last.reverse == reverse => {
+                last.length += length;
+                last.end_heading = to.rotation;
+                last.end = i + 1;
+            }
+            _ => maneuvers.push(Maneuver {
+                kind,
+                reverse,
+                length,
+                start_heading: from.rotation,
+                end_heading: to.rotation,
+                start: i,
+                end: i + 1,
+            }),
+        }
+    }
+    maneuvers
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+
+    #[test]
+    fn test_signed_rotation() {
+        assert_eq!(signed_rotation(0, 1, 8), 1);
+        assert_eq!(signed_rotation(0, 7, 8), -1);
+        assert_eq!(signed_rotation(7, 0, 8), 1);
+        assert_eq!(signed_rotation(2, 6, 8), 4);
+    }
+
+    #[test]
+    fn test_segment_path() {
+        let path = vec![
+            Cell::new(0, IVec2::new(0, 0)),
+            Cell::new(0, IVec2::new(1, 0)),
+            Cell::new(0, IVec2::new(2, 0)),
+            Cell::new(1, IVec2::new(3, 1)),
+            Cell::new(2, IVec2::new(3, 2)),
+            Cell::new(2, IVec2::new(3, 1)),
+            Cell::new(3, IVec2::new(3, 1)),
+        ];
+        let maneuvers = segment_path(&path, 8);
+        let kinds: Vec<_> = maneuvers.iter().map(|m| (m.kind, m.reverse)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ManeuverKind::Straight, false),
+                (ManeuverKind::ArcRight, false),
+                (ManeuverKind::Straight, true),
+                (ManeuverKind::PointTurn, false),
+            ]
+        );
+        assert_eq!(maneuvers[0].length, 2.0);
+        assert_eq!((maneuvers[1].start, maneuvers[1].end), (2, 4));
+        assert_eq!(maneuvers[1].start_heading, 0);
+        assert_eq!(maneuvers[1].end_heading, 2);
+        assert_eq!(maneuvers[2].to_string(), "reverse 1.0 cells");
+    }
+}
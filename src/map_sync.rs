@@ -0,0 +1,393 @@
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use notan::math::IVec2;
+
+use crate::grid::Grid;
+use crate::patch::MapPatch;
+
+/// How long a write to a client may stall before the client is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An empty grid `size` cells big.
+fn blank(cell_size: f32, size: IVec2) -> Grid {
+    let scale = cell_size as i32;
+    Grid::new(cell_size, size.x * scale, size.y * scale)
+}
+
+/// A grid like `grid` with only the layers a [`MapPatch`] covers copied.
+pub fn copy_layers(grid: &Grid) -> Grid {
+    let mut copy = blank(grid.cell_size, IVec2::new(grid.size.0, grid.size.1));
+    copy.resolution = grid.resolution;
+    let patch = copy.diff(grid);
+    copy.apply_patch(&patch).expect("same size");
+    copy
+}
+
+fn send(stream: &TcpStream, patch: &MapPatch) -> io::Result<()> {
+    let mut writer = BufWriter::new(stream);
+    patch.write_to(&mut writer)?;
+    writer.flush()
+}
+
+/// `patch` on the wire, encoded once for every subscriber.
+fn encode(patch: &MapPatch) -> Arc<[u8]> {
+    let mut bytes = Vec::new();
+    patch
+        .write_to(&mut bytes)
+        .expect("writing to a Vec can't fail");
+    bytes.into()
+}
+
+/// Locks `mutex` even if a thread panicked holding it: patches are checked
+/// before they're applied, so a panic can't leave the map half patched.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+struct Subscriber {
+    id: usize,
+    /// Encoded patches for the client's writer thread, so a slow client
+    /// never holds up the lock.
+    outbox: Sender<Arc<[u8]>>,
+}
+
+/// State shared between the host and its connection threads.
+struct Shared {
+    /// The authoritative map.
+    grid: Grid,
+    subscribers: Vec<Subscriber>,
+    next_id: usize,
+    /// Observations from clients, for the host's own copy of the map.
+    observations: Sender<MapPatch>,
+}
+
+impl Shared {
+    /// Applies `patch`, which must fit the map, and queues it for every
+    /// subscriber but `from`, dropping those whose writer gave up.
+    fn broadcast(&mut self, patch: &MapPatch, from: Option<usize>) {
+        if self.grid.apply_patch(patch).is_err() {
+            return;
+        }
+        let bytes = encode(patch);
+        self.subscribers.retain(|subscriber| {
+            Some(subscriber.id) == from || subscriber.outbox.send(Arc::clone(&bytes)).is_ok()
+        });
+    }
+}
+
+/// Hosts the authoritative map for clients on other processes or machines.
+/// A client gets the whole map when it connects, then every change as a
+/// [`MapPatch`]; the changes it observes itself are sent back, applied
+/// and forwarded to the other clients. Patches overwrite cells, so the
+/// last change to reach the host wins. Every client has its own writer
+/// thread, so one that stops reading is dropped after [`WRITE_TIMEOUT`]
+/// without stalling the host or the other clients.
+pub struct MapHost {
+    shared: Arc<Mutex<Shared>>,
+    observations: Receiver<MapPatch>,
+    address: SocketAddr,
+}
+
+impl MapHost {
+    /// Starts hosting a copy of `grid` on `address`, accepting clients on a
+    /// background thread.
+    pub fn bind(grid: &Grid, address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let (sender, observations) = mpsc::channel();
+        let shared = Arc::new(Mutex::new(Shared {
+            grid: copy_layers(grid),
+            subscribers: Vec::new(),
+            next_id: 0,
+            observations: sender,
+        }));
+        let accepting = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = Self::subscribe(&accepting, stream);
+            }
+        });
+        Ok(Self {
+            shared,
+            observations,
+            address,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn clients(&self) -> usize {
+        lock(&self.shared).subscribers.len()
+    }
+
+    /// Registers the client on `stream`, with a writer thread draining its
+    /// outbox and a reader thread taking in its observations.
+    fn subscribe(shared: &Arc<Mutex<Shared>>, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let reader = stream.try_clone()?;
+        let (outbox, pending) = mpsc::channel::<Arc<[u8]>>();
+        let (id, size) = {
+            // Snapshot and registration happen under one lock, so the
+            // client misses no change in between.
+            let mut shared = lock(shared);
+            let size = IVec2::new(shared.grid.size.0, shared.grid.size.1);
+            let snapshot = blank(shared.grid.cell_size, size).diff(&shared.grid);
+            let _ = outbox.send(encode(&snapshot));
+            let id = shared.next_id;
+            shared.next_id += 1;
+            shared.subscribers.push(Subscriber { id, outbox });
+            (id, size)
+        };
+        thread::spawn(move || {
+            for bytes in pending {
+                if (&stream).write_all(&bytes).is_err() {
+                    break;
+                }
+            }
+            // Ends the reader thread too, which unsubscribes the client.
+            let _ = stream.shutdown(Shutdown::Both);
+        });
+        let shared = Arc::clone(shared);
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(patch) = MapPatch::read_from(&mut reader) {
+                // A client sending patches that don't fit the map is
+                // dropped before they get near the lock.
+                if patch.validate(size).is_err() {
+                    break;
+                }
+                let mut shared = lock(&shared);
+                shared.broadcast(&patch, Some(id));
+                let _ = shared.observations.send(patch);
+            }
+            lock(&shared)
+                .subscribers
+                .retain(|subscriber| subscriber.id != id);
+        });
+        Ok(())
+    }
+
+    /// Brings `grid`, the host's own working copy of the map, up to date
+    /// with the clients' observations, then publishes the edits made to it
+    /// since the last sync. Call it once per frame or planning cycle.
+    /// Returns whether a client changed `grid`.
+    pub fn sync(&self, grid: &mut Grid) -> bool {
+        let mut shared = lock(&self.shared);
+        let mut changed = false;
+        for patch in self.observations.try_iter() {
+            changed |= grid.apply_patch(&patch).is_ok();
+        }
+        let patch = shared.grid.diff(grid);
+        if !patch.is_empty() {
+            shared.broadcast(&patch, None);
+        }
+        changed
+    }
+}
+
+/// A process following a [`MapHost`]'s map.
+pub struct MapClient {
+    stream: TcpStream,
+    /// The map as last agreed with the host, to tell local edits apart.
+    base: Grid,
+    updates: Receiver<MapPatch>,
+}
+
+impl MapClient {
+    /// Connects to the host at `address` and waits for the whole map,
+    /// sized in cells of `cell_size` screen units.
+    pub fn connect(address: impl ToSocketAddrs, cell_size: f32) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let snapshot = MapPatch::read_from(&mut reader)?;
+        let mut base = blank(cell_size, snapshot.size);
+        base.apply_patch(&snapshot)
+            .expect("blank grid has the snapshot's size");
+        let (sender, updates) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(patch) = MapPatch::read_from(&mut reader) {
+                if sender.send(patch).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            stream,
+            base,
+            updates,
+        })
+    }
+
+    /// The host's map as of the last sync.
+    pub fn map(&self) -> &Grid {
+        &self.base
+    }
+
+    /// Applies the host's changes to `grid`, the client's working copy of
+    /// the map, then sends the host whatever the client changed in it since
+    /// the last sync, such as obstacles its sensors found. Returns whether
+    /// the host changed `grid`.
+    pub fn sync(&mut self, grid: &mut Grid) -> io::Result<bool> {
+        let mut changed = false;
+        for patch in self.updates.try_iter() {
+            let _ = self.base.apply_patch(&patch);
+            changed |= grid.apply_patch(&patch).is_ok();
+        }
+        let observed = self.base.diff(grid);
+        if !observed.is_empty() {
+            send(&self.stream, &observed)?;
+            let _ = self.base.apply_patch(&observed);
+        }
+        Ok(changed)
+    }
+
+    /// Blocks until the host sends a change or `timeout` passes, applying
+    /// it like [`MapClient::sync`]. Returns whether one came.
+    pub fn wait(&mut self, grid: &mut Grid, timeout: Duration) -> bool {
+        match self.updates.recv_timeout(timeout) {
+            Ok(patch) => {
+                let _ = self.base.apply_patch(&patch);
+                let _ = grid.apply_patch(&patch);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// The demo's side of a shared map, picked with environment variables:
+/// `VP_HOST=<address>` hosts the map, `VP_JOIN=<address>` follows another
+/// instance's.
+pub enum MapSync {
+    Host(MapHost),
+    Client(Box<MapClient>),
+}
+
+impl MapSync {
+    /// Hosts or joins as the environment asks, replacing `grid` with the
+    /// host's map when joining. `None` when neither is set or the
+    /// connection failed, which is reported on stderr.
+    pub fn from_env(grid: &mut Grid) -> Option<Self> {
+        let result = if let Ok(address) = std::env::var("VP_HOST") {
+            MapHost::bind(grid, address).map(|host| {
+                println!("Hosting the map on {}", host.address());
+                MapSync::Host(host)
+            })
+        } else if let Ok(address) = std::env::var("VP_JOIN") {
+            MapClient::connect(address, grid.cell_size).map(|client| {
+                let _ = grid.apply_patch(&grid.diff(client.map()));
+                MapSync::Client(Box::new(client))
+            })
+        } else {
+            return None;
+        };
+        result
+            .map_err(|error| eprintln!("Map sync unavailable: {error}"))
+            .ok()
+    }
+
+    /// Returns whether another instance changed `grid`.
+    pub fn sync(&mut self, grid: &mut Grid) -> io::Result<bool> {
+        match self {
+            MapSync::Host(host) => Ok(host.sync(grid)),
+            MapSync::Client(client) => client.sync(grid),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_host_and_clients_share_edits() {
+        let mut host_grid = Grid::new(1.0, 16, 8);
+        host_grid.set_cell(3, 3, true);
+        let host = MapHost::bind(&host_grid, "127.0.0.1:0").unwrap();
+
+        let mut first = MapClient::connect(host.address(), 1.0).unwrap();
+        let mut second = MapClient::connect(host.address(), 1.0).unwrap();
+        let mut first_grid = copy_layers(first.map());
+        let mut second_grid = copy_layers(second.map());
+        assert!(first_grid.is_cell_blocked(3, 3));
+        assert_eq!(first_grid.size, (16, 8));
+
+        // An edit on the host reaches both clients.
+        host_grid.set_soft_cost(5, 5, 700);
+        assert!(!host.sync(&mut host_grid));
+        assert!(first.wait(&mut first_grid, TIMEOUT));
+        assert!(second.wait(&mut second_grid, TIMEOUT));
+        assert_eq!(second_grid.soft_cost_at(5, 5), 700);
+
+        // An obstacle one client observes reaches the host and the other
+        // client, but isn't echoed back to it.
+        first_grid.set_cell(10, 2, true);
+        first.sync(&mut first_grid).unwrap();
+        assert!(second.wait(&mut second_grid, TIMEOUT));
+        assert!(second_grid.is_cell_blocked(10, 2));
+        assert!(host.sync(&mut host_grid));
+        assert!(host_grid.is_cell_blocked(10, 2));
+        assert!(!first.wait(&mut first_grid, Duration::from_millis(50)));
+        assert_eq!(host.clients(), 2);
+
+        // A client sending a patch outside the map is dropped, and the host
+        // carries on.
+        let bad = MapPatch {
+            size: IVec2::new(16, 8),
+            soft_costs: vec![(u32::MAX, 1)],
+            ..MapPatch::default()
+        };
+        send(&first.stream, &bad).unwrap();
+        let deadline = std::time::Instant::now() + TIMEOUT;
+        while host.clients() == 2 && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(host.clients(), 1);
+        host_grid.set_cell(0, 0, true);
+        assert!(!host.sync(&mut host_grid));
+        assert!(second.wait(&mut second_grid, TIMEOUT));
+        assert!(second_grid.is_cell_blocked(0, 0));
+    }
+
+    #[test]
+    fn test_stalled_client_doesnt_block_host() {
+        let mut host_grid = Grid::new(1.0, 256, 256);
+        let host = MapHost::bind(&host_grid, "127.0.0.1:0").unwrap();
+        // Connects but never reads, so its socket buffers fill up.
+        let _stalled = TcpStream::connect(host.address()).unwrap();
+        let mut client = MapClient::connect(host.address(), 1.0).unwrap();
+        let mut client_grid = copy_layers(client.map());
+        let deadline = std::time::Instant::now() + TIMEOUT;
+        while host.clients() < 2 && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(host.clients(), 2);
+
+        // Far more than the stalled socket buffers, at 8 bytes a cell.
+        let started = std::time::Instant::now();
+        for round in 1..=32 {
+            for y in 0..256 {
+                for x in 0..256 {
+                    host_grid.set_soft_cost(x, y, round);
+                }
+            }
+            host.sync(&mut host_grid);
+            while client.wait(&mut client_grid, Duration::ZERO) {}
+        }
+        assert!(started.elapsed() < WRITE_TIMEOUT);
+        while client_grid.soft_cost_at(255, 255) != 32 {
+            assert!(client.wait(&mut client_grid, TIMEOUT));
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::diagnostics::FailureCause;
+
+/// Upper bounds of the planning latency histogram, in seconds.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Upper bounds of the expanded poses histogram.
+pub const EXPANSION_BUCKETS: [f64; 6] = [100.0, 1e3, 1e4, 1e5, 1e6, 1e7];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanStatus {
+    Planned,
+    Failed(FailureCause),
+    Expired,
+    Cancelled,
+}
+
+impl PlanStatus {
+    /// Label value used for the status in exported metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            PlanStatus::Planned => "planned",
+            PlanStatus::Failed(FailureCause::FloatCostTerms) => "float_cost_terms",
+            PlanStatus::Failed(FailureCause::StartBlocked) => "start_blocked",
+            PlanStatus::Failed(FailureCause::GoalBlocked) => "goal_blocked",
+            PlanStatus::Failed(FailureCause::Blockage) => "blockage",
+            PlanStatus::Failed(FailureCause::Clearance) => "clearance",
+            PlanStatus::Failed(FailureCause::Kinematic) => "kinematic",
+            PlanStatus::Failed(FailureCause::BudgetExhausted) => "budget_exhausted",
+            PlanStatus::Expired => "expired",
+            PlanStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Whether a search ran, so latency and expansions mean something.
+    pub fn searched(&self) -> bool {
+        matches!(self, PlanStatus::Planned | PlanStatus::Failed(_))
+    }
+}
+
+/// What happened to one planning request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlanEvent {
+    pub status: PlanStatus,
+    pub latency: Duration,
+    pub expanded: usize,
+}
+
+/// Receives an event per planning request, from whichever worker thread
+/// handled it.
+pub trait MetricsSink: Send + Sync {
+    fn record(&self, event: &PlanEvent);
+}
+
+#[derive(Clone, Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// Observations at or below each bound, not cumulative.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(slot) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[slot] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum {}", self.sum);
+        let _ = writeln!(out, "{name}_count {}", self.count);
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Counts {
+    statuses: BTreeMap<&'static str, u64>,
+    latency: Histogram,
+    expanded: Histogram,
+}
+
+/// A [`MetricsSink`] keeping counters and histograms for export in the
+/// Prometheus text format. Rates like plans per second and quantiles like
+/// p99 latency are left to the monitoring side.
+#[derive(Debug)]
+pub struct PlannerMetrics {
+    counts: Mutex<Counts>,
+}
+
+impl Default for PlannerMetrics {
+    fn default() -> Self {
+        Self {
+            counts: Mutex::new(Counts {
+                statuses: BTreeMap::new(),
+                latency: Histogram::new(&LATENCY_BUCKETS),
+                expanded: Histogram::new(&EXPANSION_BUCKETS),
+            }),
+        }
+    }
+}
+
+impl PlannerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests recorded with this status.
+    pub fn total(&self, status: PlanStatus) -> u64 {
+        let counts = self.counts.lock().expect("metrics lock poisoned");
+        counts.statuses.get(status.label()).copied().unwrap_or(0)
+    }
+
+    /// Every metric in the Prometheus text exposition format, ready to be
+    /// served from a `/metrics` endpoint.
+    pub fn render_prometheus(&self) -> String {
+        let counts = self.counts.lock().expect("metrics lock poisoned");
+        let mut out = String::new();
+        out.push_str("# HELP planner_requests_total Planning requests by outcome.\n");
+        out.push_str("# TYPE planner_requests_total counter\n");
+        for (status, total) in &counts.statuses {
+            let _ = writeln!(out, "planner_requests_total{{status=\"{status}\"}} {total}");
+        }
+        counts.latency.render(
+            &mut out,
+            "planner_latency_seconds",
+            "Time spent searching per request.",
+        );
+        counts.expanded.render(
+            &mut out,
+            "planner_expanded_poses",
+            "Poses expanded per search.",
+        );
+        out
+    }
+}
+
+impl MetricsSink for PlannerMetrics {
+    fn record(&self, event: &PlanEvent) {
+        let mut counts = self.counts.lock().expect("metrics lock poisoned");
+        *counts.statuses.entry(event.status.label()).or_default() += 1;
+        if event.status.searched() {
+            counts.latency.observe(event.latency.as_secs_f64());
+            counts.expanded.observe(event.expanded as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_export() {
+        let metrics = PlannerMetrics::new();
+        let event = |status, millis, expanded| PlanEvent {
+            status,
+            latency: Duration::from_millis(millis),
+            expanded,
+        };
+        metrics.record(&event(PlanStatus::Planned, 3, 500));
+        metrics.record(&event(PlanStatus::Planned, 40, 20_000));
+        metrics.record(&event(PlanStatus::Failed(FailureCause::Clearance), 8, 900));
+        metrics.record(&event(PlanStatus::Cancelled, 0, 0));
+
+        assert_eq!(metrics.total(PlanStatus::Planned), 2);
+        assert_eq!(metrics.total(PlanStatus::Expired), 0);
+        let text = metrics.render_prometheus();
+        for line in [
+            "planner_requests_total{status=\"planned\"} 2",
+            "planner_requests_total{status=\"clearance\"} 1",
+            "planner_requests_total{status=\"cancelled\"} 1",
+            "planner_latency_seconds_bucket{le=\"0.005\"} 1",
+            "planner_latency_seconds_bucket{le=\"0.01\"} 2",
+            "planner_latency_seconds_bucket{le=\"+Inf\"} 3",
+            "planner_latency_seconds_count 3",
+            "planner_expanded_poses_bucket{le=\"1000\"} 2",
+            "planner_expanded_poses_sum 21400",
+        ] {
+            assert!(text.lines().any(|candidate| candidate == line), "{line}");
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::loads::{self, LoadError, LoadLeg, LoadStop};
+use crate::planner::{self, PlannerConfig};
+
+/// Drive empty to `pickup`, take the load, and drive loaded to `dropoff`.
+#[derive(Clone, Debug)]
+pub struct PickAndPlace {
+    pub pickup: Cell,
+    pub dropoff: Cell,
+    /// Footprint sets for driving without and with the load.
+    pub empty: String,
+    pub loaded: String,
+}
+
+/// A planned pick-and-place: the empty leg, then the loaded one.
+#[derive(Clone, Debug)]
+pub struct Mission {
+    pub legs: Vec<LoadLeg>,
+    pub cost: u32,
+}
+
+impl Mission {
+    /// The whole mission, without repeating the pickup pose.
+    pub fn path(&self) -> Vec<Cell> {
+        let mut path = Vec::new();
+        for leg in &self.legs {
+            let skip = if path.is_empty() { 0 } else { 1 };
+            path.extend(leg.path.iter().skip(skip).cloned());
+        }
+        path
+    }
+
+    /// Index of the pickup pose in [`Mission::path`].
+    pub fn pickup_index(&self) -> usize {
+        self.legs[0].path.len() - 1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MissionError {
+    Load(LoadError),
+    /// Pose `index` of leg `leg` collides, or doesn't continue from the
+    /// pose before it.
+    Invalid {
+        leg: usize,
+        index: usize,
+    },
+}
+
+impl From<LoadError> for MissionError {
+    fn from(error: LoadError) -> Self {
+        MissionError::Load(error)
+    }
+}
+
+fn exact_pose(pose: &Cell) -> Goal {
+    Goal::Oriented {
+        goal: Box::new(Goal::Cell(pose.position)),
+        heading: pose.rotation,
+        tolerance: 0,
+    }
+}
+
+/// Plans `task` from `start`, which must be driven with the empty
+/// footprint, and checks the combined plan with [`validate_mission`].
+pub fn plan_pick_and_place(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    task: &PickAndPlace,
+    config: &PlannerConfig,
+) -> Result<Mission, MissionError> {
+    let mut agent = agent.clone();
+    if !agent.set_footprint(&task.empty) {
+        return Err(LoadError::UnknownFootprint(task.empty.clone()).into());
+    }
+    let stops = [
+        LoadStop {
+            goal: exact_pose(&task.pickup),
+            footprint: task.loaded.clone(),
+        },
+        LoadStop {
+            goal: exact_pose(&task.dropoff),
+            footprint: task.empty.clone(),
+        },
+    ];
+    let legs = loads::plan_with_loads(grid, &agent, neighbor_cache, start, &stops, config)?;
+    let mission = Mission {
+        cost: legs.iter().map(|leg| leg.cost).sum(),
+        legs,
+    };
+    validate_mission(grid, &agent, &mission)?;
+    Ok(mission)
+}
+
+/// Checks that every leg continues from the last and that every pose and
+/// move is free for the footprint set the leg is driven with.
+pub fn validate_mission(grid: &Grid, agent: &Agent, mission: &Mission) -> Result<(), MissionError> {
+    let mut agent = agent.clone();
+    let mut previous: Option<&Cell> = None;
+    for (leg_index, leg) in mission.legs.iter().enumerate() {
+        let invalid = |index| MissionError::Invalid {
+            leg: leg_index,
+            index,
+        };
+        if !agent.set_footprint(&leg.footprint) {
+            return Err(LoadError::UnknownFootprint(leg.footprint.clone()).into());
+        }
+        for (index, pose) in leg.path.iter().enumerate() {
+            let continues = match (index, previous) {
+                (0, Some(previous)) => previous == pose,
+                (_, Some(previous)) => {
+                    !planner::is_move_blocked(grid, &agent, previous, pose)
+                        && !planner::is_move_outside_geofence(grid, &agent, previous, pose)
+                }
+                (_, None) => true,
+            };
+            if !continues || grid.is_pose_blocked(&agent, pose) {
+                return Err(invalid(index));
+            }
+            previous = Some(pose);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::{IVec2, Vec2};
+
+    use super::*;
+    use crate::agent::{MotionModel, DEFAULT_FOOTPRINT};
+    use crate::test_support::{neighbor_cache, point_agent, Fixture};
+
+    #[test]
+    fn test_pick_and_place() {
+        let mut grid = Grid::new(1.0, 20, 20);
+        for y in 0..12 {
+            grid.set_cell(10, y, true);
+        }
+        let mut agent = point_agent(IVec2::new(2, 2));
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        agent.add_footprint_set("pallet", Vec2::new(3.0, 3.0));
+        let cache = neighbor_cache();
+        let config = Fixture::new(20, 20).config();
+        let task = PickAndPlace {
+            pickup: Cell::new(2, IVec2::new(5, 5)),
+            dropoff: Cell::new(6, IVec2::new(15, 5)),
+            empty: DEFAULT_FOOTPRINT.to_string(),
+            loaded: "pallet".to_string(),
+        };
+        let start = Cell::new(0, agent.position);
+
+        let mission = plan_pick_and_place(&grid, &agent, &cache, start, &task, &config).unwrap();
+        let path = mission.path();
+        assert_eq!(path[mission.pickup_index()], task.pickup);
+        assert_eq!(path.last(), Some(&task.dropoff));
+        assert_eq!(mission.legs[1].footprint, "pallet");
+        assert_eq!(mission.cost, mission.legs[0].cost + mission.legs[1].cost);
+
+        // A loaded leg cut through the wall is rejected.
+        let mut broken = mission.clone();
+        broken.legs[1].path[1] = Cell::new(0, IVec2::new(10, 5));
+        assert!(matches!(
+            validate_mission(&grid, &agent, &broken),
+            Err(MissionError::Invalid { leg: 1, .. })
+        ));
+    }
+}
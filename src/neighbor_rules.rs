@@ -0,0 +1,172 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use glam::IVec2;
+
+use crate::cell::Cell;
+
+/// A move considered for the neighbor cache, before any rule ran.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NeighborCandidate {
+    /// Heading the move starts from.
+    pub from_rotation: i16,
+    pub offset: IVec2,
+    /// Heading after the move.
+    pub rotation: i16,
+    pub reverse: bool,
+}
+
+impl NeighborCandidate {
+    pub fn is_straight(&self) -> bool {
+        self.rotation == self.from_rotation
+    }
+
+    pub fn turn(&self, max_increments: u16) -> i16 {
+        Cell::new(self.rotation, self.offset).rotation_to(self.from_rotation, max_increments as i16)
+    }
+}
+
+/// What the rules know about the cache being built.
+#[derive(Clone, Debug)]
+pub struct RuleContext {
+    pub max_increments: u16,
+    /// Increments pointing most directly at each of the 8 grid neighbors.
+    pub aligned: Vec<i16>,
+}
+
+/// One step of the pipeline that decides which moves go into the neighbor
+/// cache. A move is kept only if every rule allows it. Closures taking the
+/// same arguments are rules too.
+pub trait NeighborRule {
+    fn allows(&self, candidate: &NeighborCandidate, context: &RuleContext) -> bool;
+}
+
+impl<F> NeighborRule for F
+where
+    F: Fn(&NeighborCandidate, &RuleContext) -> bool,
+{
+    fn allows(&self, candidate: &NeighborCandidate, context: &RuleContext) -> bool {
+        self(candidate, context)
+    }
+}
+
+/// Drops straight moves (forward or reverse) along headings that don't
+/// point at a grid neighbor, since the one-cell step would leave the
+/// heading. Turning moves are always allowed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlignedStraight;
+
+impl NeighborRule for AlignedStraight {
+    fn allows(&self, candidate: &NeighborCandidate, context: &RuleContext) -> bool {
+        !candidate.is_straight() || context.aligned.contains(&candidate.rotation)
+    }
+}
+
+/// Drops straight moves along diagonals, e.g. for vehicles that should
+/// only drive straight along the grid axes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoDiagonalStraight;
+
+impl NeighborRule for NoDiagonalStraight {
+    fn allows(&self, candidate: &NeighborCandidate, _context: &RuleContext) -> bool {
+        !candidate.is_straight() || candidate.offset.x == 0 || candidate.offset.y == 0
+    }
+}
+
+/// Drops moves turning more than this many increments, tightening the arc
+/// without rebuilding it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaxTurn(pub u16);
+
+impl NeighborRule for MaxTurn {
+    fn allows(&self, candidate: &NeighborCandidate, context: &RuleContext) -> bool {
+        candidate.turn(context.max_increments) <= self.0 as i16
+    }
+}
+
+/// The rules [`crate::cell::NeighborCache::precompute`] applies.
+pub fn default_rules() -> Vec<Box<dyn NeighborRule>> {
+    vec![Box::new(AlignedStraight)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::NeighborCache;
+
+    fn context() -> RuleContext {
+        RuleContext {
+            max_increments: 16,
+            aligned: vec![0, 2, 4, 6, 8, 10, 12, 14],
+        }
+    }
+
+    fn candidate(offset: IVec2, rotation: i16) -> NeighborCandidate {
+        NeighborCandidate {
+            from_rotation: 1,
+            offset,
+            rotation,
+            reverse: false,
+        }
+    }
+
+    #[test]
+    fn test_aligned_straight() {
+        let context = context();
+        // Heading 1 sits between the x axis and the diagonal.
+        assert!(!AlignedStraight.allows(&candidate(IVec2::new(1, 0), 1), &context));
+        assert!(AlignedStraight.allows(&candidate(IVec2::new(1, 0), 0), &context));
+        let aligned = NeighborCandidate {
+            from_rotation: 2,
+            ..candidate(IVec2::new(1, 1), 2)
+        };
+        assert!(AlignedStraight.allows(&aligned, &context));
+    }
+
+    #[test]
+    fn test_no_diagonal_straight() {
+        let context = context();
+        let diagonal = NeighborCandidate {
+            from_rotation: 2,
+            ..candidate(IVec2::new(1, 1), 2)
+        };
+        assert!(!NoDiagonalStraight.allows(&diagonal, &context));
+        assert!(NoDiagonalStraight.allows(&candidate(IVec2::new(1, 1), 2), &context));
+        assert!(NoDiagonalStraight.allows(&candidate(IVec2::new(1, 0), 1), &context));
+    }
+
+    #[test]
+    fn test_max_turn() {
+        let context = context();
+        assert!(MaxTurn(1).allows(&candidate(IVec2::new(1, 0), 0), &context));
+        assert!(!MaxTurn(1).allows(&candidate(IVec2::new(1, 1), 3), &context));
+        // Turning across zero counts the short way around.
+        let wrapped = NeighborCandidate {
+            from_rotation: 15,
+            ..candidate(IVec2::new(1, 0), 0)
+        };
+        assert!(MaxTurn(1).allows(&wrapped, &context));
+    }
+
+    #[test]
+    fn test_custom_pipeline() {
+        let forward_only = |candidate: &NeighborCandidate, _: &RuleContext| !candidate.reverse;
+        let rules: Vec<Box<dyn NeighborRule>> =
+            vec![Box::new(AlignedStraight), Box::new(forward_only)];
+        let mut cache = NeighborCache::new(8, 1);
+        cache.precompute_with_rules(8, 1, 2, &rules);
+        let from = Cell::new(0, IVec2::ZERO);
+        let neighbors = cache.get(0).unwrap();
+        assert_eq!(neighbors.len(), 3);
+        assert!(neighbors.iter().all(|neighbor| {
+            !neighbor.reverse
+                && !Cell::new(neighbor.rotation, neighbor.offset).is_reverse_to(&from, 8)
+        }));
+
+        let default = NeighborCache::new_precomputed(8, 1);
+        let mut explicit = NeighborCache::new(8, 1);
+        explicit.precompute_with_rules(8, 1, 2, &default_rules());
+        assert_eq!(default.get(3), explicit.get(3));
+    }
+}
@@ -0,0 +1,227 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+
+/// Reverse moves are only twice as expensive while parking, since backing
+/// into a bay is usually the whole point.
+pub const PARKING_REVERSE_FACTOR: u32 = 2;
+
+/// Reverse moves may turn this many times as far as forward ones while
+/// parking, one more than elsewhere, to swing the tail into the bay.
+pub const PARKING_REVERSE_ARC_FACTOR: u16 = 3;
+
+/// A parking bay the agent has to end up fully inside, facing `heading`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParkingBay {
+    pub min: IVec2,
+    pub max: IVec2,
+    pub heading: i16,
+    /// Allowed deviation from `heading`, in increments.
+    pub heading_tolerance: i16,
+}
+
+impl ParkingBay {
+    pub fn new(min: IVec2, max: IVec2, heading: i16) -> Self {
+        Self {
+            min,
+            max,
+            heading,
+            heading_tolerance: 1,
+        }
+    }
+
+    pub fn goal(&self) -> Goal {
+        Goal::Oriented {
+            goal: Box::new(Goal::Rect {
+                min: self.min,
+                max: self.max,
+            }),
+            heading: self.heading,
+            tolerance: self.heading_tolerance,
+        }
+    }
+
+    /// Whether `pose` sits fully inside the bay with an acceptable heading.
+    pub fn is_aligned(&self, agent: &Agent, pose: &Cell) -> bool {
+        self.goal().accepts(agent, pose)
+    }
+
+    /// Whether the footprint fits inside the bay at any allowed heading.
+    pub fn fits(&self, agent: &Agent) -> bool {
+        let max_increments = agent.max_increments as i16;
+        (-self.heading_tolerance..=self.heading_tolerance).any(|offset| {
+            let rotation = Cell::clamp_rotation(self.heading + offset, max_increments);
+            (self.min.y..=self.max.y).any(|y| {
+                (self.min.x..=self.max.x)
+                    .any(|x| self.is_aligned(agent, &Cell::new(rotation, IVec2::new(x, y))))
+            })
+        })
+    }
+}
+
+/// One stretch of a maneuver driven in a single direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManeuverPhase {
+    pub reverse: bool,
+    /// Poses of this phase, starting with the last pose of the previous one.
+    pub path: Vec<Cell>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParkingManeuver {
+    pub phases: Vec<ManeuverPhase>,
+    pub cost: u32,
+}
+
+impl ParkingManeuver {
+    /// The whole maneuver as a single path.
+    pub fn path(&self) -> Vec<Cell> {
+        let mut path: Vec<Cell> = Vec::new();
+        for phase in &self.phases {
+            let skip = if path.is_empty() { 0 } else { 1 };
+            path.extend(phase.path.iter().skip(skip).cloned());
+        }
+        path
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParkingError {
+    /// The footprint doesn't fit inside the bay at the required heading.
+    BayTooSmall,
+    NoPath,
+}
+
+/// Splits a path into phases wherever the driving direction flips.
+pub fn split_phases(path: &[Cell], max_increments: u16) -> Vec<ManeuverPhase> {
+    let mut phases: Vec<ManeuverPhase> = Vec::new();
+    for pair in path.windows(2) {
+        let reverse = pair[1].is_reverse_to(&pair[0], max_increments as i16);
+        match phases.last_mut() {
+            Some(phase) if phase.reverse == reverse => phase.path.push(pair[1].clone()),
+            _ => phases.push(ManeuverPhase {
+                reverse,
+                path: vec![pair[0].clone(), pair[1].clone()],
+            }),
+        }
+    }
+    phases
+}
+
+/// Plans into `bay` with a parking preset on top of `config`: the goal
+/// tolerates any pose inside the bay near the required heading, reverse
+/// moves are made much cheaper, and `neighbor_cache` gets wider reverse
+/// turns. The goal only accepts aligned poses, so every maneuver ends
+/// aligned.
+pub fn plan_parking(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    bay: &ParkingBay,
+    config: &PlannerConfig,
+) -> Result<ParkingManeuver, ParkingError> {
+    if !bay.fits(agent) {
+        return Err(ParkingError::BayTooSmall);
+    }
+
+    let mut config = config.clone();
+    config.reverse_factor = PARKING_REVERSE_FACTOR;
+    config.reverse_arc = config
+        .reverse_arc
+        .max(config.arc * PARKING_REVERSE_ARC_FACTOR);
+    let parking_cache = Rc::new(RefCell::new(
+        neighbor_cache.borrow().with_reverse_arc(config.reverse_arc),
+    ));
+    let result = planner::plan(grid, agent, &parking_cache, start, bay.goal(), &config)
+        .map_err(|_| ParkingError::NoPath)?;
+
+    Ok(ParkingManeuver {
+        phases: split_phases(&result.path, config.max_increments),
+        cost: result.cost,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::Vec2;
+
+    use super::*;
+    use crate::test_support::{Fixture, MAX_INCREMENTS};
+
+    #[test]
+    fn test_split_phases() {
+        let path = vec![
+            Cell::new(0, IVec2::new(0, 0)),
+            Cell::new(0, IVec2::new(1, 0)),
+            Cell::new(0, IVec2::new(2, 0)),
+            Cell::new(0, IVec2::new(1, 0)),
+        ];
+        let phases = split_phases(&path, MAX_INCREMENTS);
+        assert_eq!(phases.len(), 2);
+        assert!(!phases[0].reverse);
+        assert_eq!(phases[0].path.len(), 3);
+        assert!(phases[1].reverse);
+        assert_eq!(phases[1].path.len(), 2);
+
+        let maneuver = ParkingManeuver { phases, cost: 0 };
+        assert_eq!(maneuver.path(), path);
+    }
+
+    #[test]
+    fn test_plan_parking() {
+        let grid = Grid::new(1.0, 20, 20);
+        let (agent, cache, config) = Fixture::new(20, 20)
+            .at(IVec2::new(3, 10))
+            .size(Vec2::new(2.5, 1.0))
+            .build();
+        let bay = ParkingBay::new(IVec2::new(10, 3), IVec2::new(12, 7), 2);
+        let start = Cell::new(0, agent.position);
+
+        let maneuver = plan_parking(&grid, &agent, &cache, start, &bay, &config).unwrap();
+        assert!(!maneuver.phases.is_empty());
+        let path = maneuver.path();
+        assert!(bay.is_aligned(&agent, path.last().unwrap()));
+        for pair in maneuver.phases.windows(2) {
+            assert_ne!(pair[0].reverse, pair[1].reverse);
+        }
+    }
+
+    #[test]
+    fn test_widens_reverse_arc() {
+        let grid = Grid::new(1.0, 10, 10);
+        let (agent, cache, mut config) = Fixture::new(10, 10).at(IVec2::new(5, 5)).build();
+        config.allow_forward = false;
+        config.reverse_arc = 0;
+        let start = Cell::new(0, agent.position);
+        let bay = ParkingBay::new(IVec2::new(2, 7), IVec2::new(3, 8), 0);
+
+        // Backing straight only stays on the start row.
+        let goal = bay.goal();
+        assert!(planner::plan(&grid, &agent, &cache, start.clone(), goal, &config).is_err());
+        let maneuver = plan_parking(&grid, &agent, &cache, start, &bay, &config).unwrap();
+        assert!(maneuver.phases.iter().all(|phase| phase.reverse));
+        assert!(bay.is_aligned(&agent, maneuver.path().last().unwrap()));
+    }
+
+    #[test]
+    fn test_bay_too_small() {
+        let grid = Grid::new(1.0, 20, 20);
+        let (agent, cache, config) = Fixture::new(20, 20)
+            .at(IVec2::new(3, 10))
+            .size(Vec2::new(2.5, 1.0))
+            .build();
+        let bay = ParkingBay::new(IVec2::new(10, 3), IVec2::new(11, 7), 0);
+        let start = Cell::new(0, agent.position);
+
+        let result = plan_parking(&grid, &agent, &cache, start, &bay, &config);
+        assert_eq!(result.unwrap_err(), ParkingError::BayTooSmall);
+    }
+}
@@ -0,0 +1,258 @@
+use std::io::{self, Read, Write};
+
+use notan::math::IVec2;
+
+use crate::encoding::{read_ivec2, read_u32, write_ivec2, write_u32};
+
+/// Leads every serialized patch.
+const MAGIC: &[u8; 4] = b"VPP1";
+
+/// Consecutive cells, by [`crate::grid::Grid::index`], that all became
+/// blocked or all became free.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockedRun {
+    pub start: u32,
+    pub len: u32,
+    pub blocked: bool,
+}
+
+/// The changes turning one grid into another of the same size, from
+/// [`crate::grid::Grid::diff`]. Covers the blocked cells, soft costs and
+/// speed limits, the layers a facility edits while vehicles are running;
+/// everything else is left alone.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapPatch {
+    /// Size of the grids the patch is between.
+    pub size: IVec2,
+    pub blocked: Vec<BlockedRun>,
+    /// New soft cost per changed cell index.
+    pub soft_costs: Vec<(u32, u32)>,
+    /// New speed limit per changed cell index.
+    pub speed_limits: Vec<(u32, f32)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch is for a grid of this size.
+    SizeMismatch(IVec2),
+    /// A run reaches past the grid's last cell.
+    RunOutOfBounds(BlockedRun),
+    /// A soft cost or speed limit is for a cell past the grid's last one.
+    IndexOutOfBounds(u32),
+}
+
+impl MapPatch {
+    pub fn is_empty(&self) -> bool {
+        self.blocked.is_empty() && self.soft_costs.is_empty() && self.speed_limits.is_empty()
+    }
+
+    /// Checks the patch fits a grid of `size`, so applying it touches
+    /// nothing outside the grid. Patches read from the network may not.
+    pub fn validate(&self, size: IVec2) -> Result<(), PatchError> {
+        if self.size != size {
+            return Err(PatchError::SizeMismatch(self.size));
+        }
+        let cells = size.x.max(0) as u64 * size.y.max(0) as u64;
+        if let Some(run) = self
+            .blocked
+            .iter()
+            .find(|run| run.start as u64 + run.len as u64 > cells)
+        {
+            return Err(PatchError::RunOutOfBounds(*run));
+        }
+        let indices = self.soft_costs.iter().map(|(index, _)| *index);
+        let indices = indices.chain(self.speed_limits.iter().map(|(index, _)| *index));
+        for index in indices {
+            if index as u64 >= cells {
+                return Err(PatchError::IndexOutOfBounds(index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Little-endian, a few bytes per changed run or cell, so a handful of
+    /// edits is far smaller than the grid.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_ivec2(writer, self.size)?;
+        write_u32(writer, self.blocked.len() as u32)?;
+        for run in &self.blocked {
+            write_u32(writer, run.start)?;
+            // The top bit of the length holds the new state; no grid has
+            // 2^31 cells.
+            write_u32(writer, run.len | (run.blocked as u32) << 31)?;
+        }
+        write_u32(writer, self.soft_costs.len() as u32)?;
+        for (index, cost) in &self.soft_costs {
+            write_u32(writer, *index)?;
+            write_u32(writer, *cost)?;
+        }
+        write_u32(writer, self.speed_limits.len() as u32)?;
+        for (index, limit) in &self.speed_limits {
+            write_u32(writer, *index)?;
+            write_u32(writer, limit.to_bits())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a map patch",
+            ));
+        }
+        let size = read_ivec2(reader)?;
+        let count = read_u32(reader)?;
+        let blocked = (0..count)
+            .map(|_| {
+                let start = read_u32(reader)?;
+                let len = read_u32(reader)?;
+                Ok(BlockedRun {
+                    start,
+                    len: len & !(1 << 31),
+                    blocked: len >> 31 == 1,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let count = read_u32(reader)?;
+        let soft_costs = (0..count)
+            .map(|_| Ok((read_u32(reader)?, read_u32(reader)?)))
+            .collect::<io::Result<_>>()?;
+        let count = read_u32(reader)?;
+        let speed_limits = (0..count)
+            .map(|_| Ok((read_u32(reader)?, f32::from_bits(read_u32(reader)?))))
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            size,
+            blocked,
+            soft_costs,
+            speed_limits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    /// Blocked cells, soft costs and speed limits of every cell.
+    fn layers(grid: &Grid) -> Vec<(bool, u32, f32)> {
+        (0..grid.size.1)
+            .flat_map(|y| (0..grid.size.0).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                (
+                    grid.is_cell_blocked(x, y),
+                    grid.soft_cost_at(x, y),
+                    grid.speed_limit_at(x, y),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let base = || {
+            let mut grid = Grid::new(1.0, 32, 16);
+            for x in 4..12 {
+                grid.set_cell(x, 3, true);
+            }
+            grid.set_soft_cost(1, 1, 500);
+            grid
+        };
+        let mut client = base();
+        let mut server = base();
+        for x in 6..20 {
+            server.set_cell(x, 3, !(10..14).contains(&x));
+        }
+        server.set_soft_cost(1, 1, 0);
+        server.set_soft_cost(2, 9, 800);
+        server.set_speed_limit(5, 5, 0.5);
+
+        let patch = client.diff(&server);
+        assert_eq!(
+            patch.blocked,
+            vec![
+                BlockedRun {
+                    start: 3 * 32 + 10,
+                    len: 2,
+                    blocked: false
+                },
+                BlockedRun {
+                    start: 3 * 32 + 14,
+                    len: 6,
+                    blocked: true
+                },
+            ]
+        );
+        let mut bytes = Vec::new();
+        patch.write_to(&mut bytes).unwrap();
+        assert!(bytes.len() < 100);
+        let patch = MapPatch::read_from(&mut bytes.as_slice()).unwrap();
+
+        client.apply_patch(&patch).unwrap();
+        assert_eq!(layers(&client), layers(&server));
+        assert!(client.diff(&server).is_empty());
+        assert_eq!(
+            Grid::new(1.0, 8, 8).apply_patch(&patch),
+            Err(PatchError::SizeMismatch(IVec2::new(32, 16)))
+        );
+    }
+
+    #[test]
+    fn test_rejects_patches_outside_grid() {
+        let mut grid = Grid::new(1.0, 8, 8);
+        let run = |start, len| MapPatch {
+            size: IVec2::new(8, 8),
+            blocked: vec![
+                BlockedRun {
+                    start: 0,
+                    len: 1,
+                    blocked: true,
+                },
+                BlockedRun {
+                    start,
+                    len,
+                    blocked: true,
+                },
+            ],
+            ..MapPatch::default()
+        };
+        // Would overflow `start + len`, or loop for billions of cells.
+        for (start, len) in [(u32::MAX, 2), (60, 5), (0, u32::MAX >> 1)] {
+            assert_eq!(
+                grid.apply_patch(&run(start, len)),
+                Err(PatchError::RunOutOfBounds(BlockedRun {
+                    start,
+                    len,
+                    blocked: true
+                }))
+            );
+        }
+        let soft_cost = MapPatch {
+            size: IVec2::new(8, 8),
+            soft_costs: vec![(64, 100)],
+            ..run(0, 1)
+        };
+        assert_eq!(
+            grid.apply_patch(&soft_cost),
+            Err(PatchError::IndexOutOfBounds(64))
+        );
+        let speed_limit = MapPatch {
+            size: IVec2::new(8, 8),
+            speed_limits: vec![(u32::MAX, 0.5)],
+            ..MapPatch::default()
+        };
+        assert_eq!(
+            grid.apply_patch(&speed_limit),
+            Err(PatchError::IndexOutOfBounds(u32::MAX))
+        );
+        // Nothing was applied, not even the valid run ahead of the bad one.
+        assert!(!grid.is_cell_blocked(0, 0));
+        grid.apply_patch(&run(60, 4)).unwrap();
+        assert!(grid.is_cell_blocked(7, 7));
+    }
+}
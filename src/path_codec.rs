@@ -0,0 +1,325 @@
+//! Compact binary paths for sending many agents' plans per network tick.
+//!
+//! A path is a header (heading count, step count, first pose) followed by
+//! one token per step. Most steps move at most one cell and turn a few
+//! increments, which fits a single byte; anything else is spelled out in
+//! varints. With run-length encoding on, repeats of the previous step (long
+//! straights, mostly) collapse into one byte per 64 steps.
+
+use alloc::vec::Vec;
+
+use glam::IVec2;
+
+use crate::cell::Cell;
+
+/// Short step: `0b0rrr_yyxx`, offsets biased by 1, turn biased by 3.
+const LONG_STEP: u8 = 0b1100_0000;
+/// `0b10nn_nnnn`: repeat the previous step `n + 1` more times.
+const REPEAT: u8 = 0b1000_0000;
+const MAX_REPEAT: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathDecodeError {
+    /// The data ended in the middle of a token.
+    Truncated,
+    /// A token that [`encode_path`] never writes.
+    Invalid,
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_signed(out: &mut Vec<u8>, value: i32) {
+    write_varint(out, ((value << 1) ^ (value >> 31)) as u32);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, PathDecodeError> {
+        let (first, rest) = self.bytes.split_first().ok_or(PathDecodeError::Truncated)?;
+        self.bytes = rest;
+        Ok(*first)
+    }
+
+    fn varint(&mut self) -> Result<u32, PathDecodeError> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u32)
+                .checked_shl(shift)
+                .ok_or(PathDecodeError::Invalid)?;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(PathDecodeError::Invalid)
+    }
+
+    fn signed(&mut self) -> Result<i32, PathDecodeError> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+    }
+
+    /// A varint that has to fit an `i16`.
+    fn short(&mut self) -> Result<i16, PathDecodeError> {
+        i16::try_from(self.varint()?).map_err(|_| PathDecodeError::Invalid)
+    }
+}
+
+/// Change between two poses, with the turn wrapped to the shorter way.
+#[derive(Clone, Copy, PartialEq)]
+struct Step {
+    offset: IVec2,
+    turn: i16,
+    layer: u8,
+}
+
+impl Step {
+    fn between(from: &Cell, to: &Cell, max_increments: i16) -> Self {
+        let mut turn = (to.rotation - from.rotation).rem_euclid(max_increments);
+        if turn > max_increments / 2 {
+            turn -= max_increments;
+        }
+        Self {
+            offset: to.position - from.position,
+            turn,
+            layer: to.layer,
+        }
+    }
+
+    /// Pose after taking this step from `from`, `Invalid` if it would leave
+    /// the `i32` coordinates.
+    fn apply(&self, from: &Cell, max_increments: i16) -> Result<Cell, PathDecodeError> {
+        // Widened, so any turn wraps instead of overflowing.
+        let rotation = (from.rotation as i32 + self.turn as i32).rem_euclid(max_increments as i32);
+        let x = from.position.x.checked_add(self.offset.x);
+        let y = from.position.y.checked_add(self.offset.y);
+        let (Some(x), Some(y)) = (x, y) else {
+            return Err(PathDecodeError::Invalid);
+        };
+        Ok(Cell::new(rotation as i16, IVec2::new(x, y)).with_layer(self.layer))
+    }
+}
+
+/// Encodes `path`, whose headings come in `max_increments` steps. With
+/// `run_length`, repeated steps are stored as counts.
+pub fn encode_path(path: &[Cell], max_increments: u16, run_length: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(&mut out, max_increments as u32);
+    write_varint(&mut out, path.len() as u32);
+    let Some(first) = path.first() else {
+        return out;
+    };
+    write_signed(&mut out, first.position.x);
+    write_signed(&mut out, first.position.y);
+    let rotation = (first.rotation as i32).rem_euclid(max_increments.max(1) as i32);
+    write_varint(&mut out, rotation as u32);
+    out.push(first.layer);
+
+    let mut previous: Option<Step> = None;
+    let mut repeats = 0;
+    for pair in path.windows(2) {
+        let step = Step::between(&pair[0], &pair[1], max_increments as i16);
+        if run_length && previous == Some(step) {
+            repeats += 1;
+            if repeats == MAX_REPEAT {
+                out.push(REPEAT | (MAX_REPEAT - 1) as u8);
+                repeats = 0;
+            }
+            continue;
+        }
+        if repeats > 0 {
+            out.push(REPEAT | (repeats - 1) as u8);
+            repeats = 0;
+        }
+
+        let short = step.offset.abs().max_element() <= 1
+            && step.turn.abs() <= 3
+            && step.layer == pair[0].layer;
+        if short {
+            let x = (step.offset.x + 1) as u8;
+            let y = (step.offset.y + 1) as u8;
+            let turn = (step.turn + 3) as u8;
+            out.push(turn << 4 | y << 2 | x);
+        } else {
+            out.push(LONG_STEP);
+            write_signed(&mut out, step.offset.x);
+            write_signed(&mut out, step.offset.y);
+            write_signed(&mut out, step.turn as i32);
+            out.push(step.layer);
+        }
+        previous = Some(step);
+    }
+    if repeats > 0 {
+        out.push(REPEAT | (repeats - 1) as u8);
+    }
+    out
+}
+
+/// Decodes a path written by [`encode_path`].
+pub fn decode_path(bytes: &[u8]) -> Result<Vec<Cell>, PathDecodeError> {
+    let mut reader = Reader { bytes };
+    let max_increments = reader.short()?;
+    let len = reader.varint()? as usize;
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    if max_increments <= 0 {
+        return Err(PathDecodeError::Invalid);
+    }
+    let position = IVec2::new(reader.signed()?, reader.signed()?);
+    let rotation = Cell::clamp_rotation(reader.short()?, max_increments);
+    let first = Cell::new(rotation, position).with_layer(reader.byte()?);
+
+    // Every token holds at least one step, so a short input can't claim a
+    // huge path.
+    let mut path = Vec::with_capacity(len.min(bytes.len() * MAX_REPEAT + 1));
+    path.push(first);
+    let mut previous: Option<Step> = None;
+    while path.len() < len {
+        let token = reader.byte()?;
+        let last = &path[path.len() - 1];
+        if token & LONG_STEP == REPEAT {
+            let step = previous.ok_or(PathDecodeError::Invalid)?;
+            let count = (token & !LONG_STEP) as usize + 1;
+            if path.len() + count > len {
+                return Err(PathDecodeError::Invalid);
+            }
+            for _ in 0..count {
+                let next = step.apply(&path[path.len() - 1], max_increments)?;
+                path.push(next);
+            }
+            continue;
+        }
+        let step = if token == LONG_STEP {
+            Step {
+                offset: IVec2::new(reader.signed()?, reader.signed()?),
+                turn: i16::try_from(reader.signed()?).map_err(|_| PathDecodeError::Invalid)?,
+                layer: reader.byte()?,
+            }
+        } else if token & REPEAT == 0 {
+            let (x, y, turn) = (token & 0b11, token >> 2 & 0b11, token >> 4);
+            if x > 2 || y > 2 || turn > 6 {
+                return Err(PathDecodeError::Invalid);
+            }
+            Step {
+                offset: IVec2::new(x as i32 - 1, y as i32 - 1),
+                turn: turn as i16 - 3,
+                layer: last.layer,
+            }
+        } else {
+            return Err(PathDecodeError::Invalid);
+        };
+        let next = step.apply(last, max_increments)?;
+        path.push(next);
+        previous = Some(step);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight(from: IVec2, direction: IVec2, rotation: i16, len: i32) -> Vec<Cell> {
+        (0..len)
+            .map(|i| Cell::new(rotation, from + direction * i))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut path = straight(IVec2::new(-3, 40), IVec2::new(1, 0), 0, 5);
+        // Turn across heading 0, a multi-cell primitive and a layer change.
+        path.push(Cell::new(15, IVec2::new(2, 39)));
+        path.push(Cell::new(14, IVec2::new(3, 37)));
+        path.push(Cell::new(2, IVec2::new(6, 37)).with_layer(1));
+        path.extend(straight(IVec2::new(6, 38), IVec2::new(0, 1), 2, 100));
+        for run_length in [false, true] {
+            let bytes = encode_path(&path, 16, run_length);
+            assert_eq!(decode_path(&bytes).unwrap(), path);
+        }
+        assert_eq!(
+            decode_path(&encode_path(&[], 16, true)).unwrap(),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn test_run_length_is_compact() {
+        let path = straight(IVec2::new(10, 10), IVec2::new(1, 1), 2, 200);
+        let plain = encode_path(&path, 8, false);
+        let compressed = encode_path(&path, 8, true);
+        // One byte per step without runs; one plus a few counts with them.
+        assert!(plain.len() > 199);
+        assert!(compressed.len() < 16);
+        assert_eq!(decode_path(&compressed).unwrap(), path);
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let path = straight(IVec2::ZERO, IVec2::new(1, 0), 0, 10);
+        let bytes = encode_path(&path, 8, true);
+        assert_eq!(
+            decode_path(&bytes[..bytes.len() - 1]),
+            Err(PathDecodeError::Truncated)
+        );
+        let mut bad = encode_path(&path[..2], 8, false);
+        *bad.last_mut().unwrap() = 0b0111_0000;
+        assert_eq!(decode_path(&bad), Err(PathDecodeError::Invalid));
+    }
+
+    fn regressions() -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions/path_codec")
+    }
+
+    fn regression(name: &str) -> Vec<u8> {
+        std::fs::read(regressions().join(name)).unwrap()
+    }
+
+    /// Replays every input the `path_codec` fuzz target recorded under
+    /// `fuzz/regressions/path_codec`.
+    #[test]
+    fn test_replay_regressions() {
+        let directory = regressions();
+        let mut replayed = 0;
+        for entry in std::fs::read_dir(&directory).expect("regressions directory") {
+            let _ = decode_path(&std::fs::read(entry.unwrap().path()).unwrap());
+            replayed += 1;
+        }
+        assert!(replayed > 0, "no inputs in {}", directory.display());
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_values() {
+        // A first heading near i16::MAX wraps into range before turning.
+        let path = decode_path(&regression("first_rotation_overflow")).unwrap();
+        assert_eq!((path[0].rotation, path[1].rotation), (7, 2));
+        let path = decode_path(&regression("turn_overflow")).unwrap();
+        assert_eq!(path[1].rotation, 6);
+        for name in ["position_overflow", "max_increments_truncated"] {
+            assert_eq!(
+                decode_path(&regression(name)),
+                Err(PathDecodeError::Invalid)
+            );
+        }
+
+        // Turns past i16 and first headings past i16 are never written.
+        let mut bytes = vec![8, 2, 0, 0, 7, 0, LONG_STEP, 0, 0];
+        write_signed(&mut bytes, 40_000);
+        bytes.push(0);
+        assert_eq!(decode_path(&bytes), Err(PathDecodeError::Invalid));
+        let mut bytes = vec![8, 1, 0, 0];
+        write_varint(&mut bytes, 70_000);
+        bytes.push(0);
+        assert_eq!(decode_path(&bytes), Err(PathDecodeError::Invalid));
+    }
+}
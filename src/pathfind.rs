@@ -1,6 +1,13 @@
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
-use typed_arena::Arena;
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// Map used on the search's hot path; FxHash with the `fxhash` feature.
+#[cfg(feature = "fxhash")]
+pub type FastHashMap<K, V> = rustc_hash::FxHashMap<K, V>;
+#[cfg(all(feature = "std", not(feature = "fxhash")))]
+pub type FastHashMap<K, V> = std::collections::HashMap<K, V>;
 
 #[derive(Debug, Clone)]
 pub struct AStarNode<T> {
@@ -36,7 +43,86 @@ impl<T> AStarNode<T> {
     }
 }
 
-pub fn optimized_astar<T, F, H, G>(
+/// Priority queue of nodes waiting to be expanded, cheapest `f_cost` first.
+pub trait OpenList<T> {
+    fn push(&mut self, node: AStarNode<T>);
+    fn pop(&mut self) -> Option<AStarNode<T>>;
+}
+
+impl<T> OpenList<T> for BinaryHeap<AStarNode<T>> {
+    fn push(&mut self, node: AStarNode<T>) {
+        BinaryHeap::push(self, node);
+    }
+
+    fn pop(&mut self) -> Option<AStarNode<T>> {
+        BinaryHeap::pop(self)
+    }
+}
+
+/// Buckets a [`BucketQueue`] grows to at most; costlier nodes go to its
+/// overflow heap.
+pub const MAX_BUCKETS: usize = 1 << 16;
+
+/// Dial-style bucket queue: nodes are binned by `f_cost / width`, so pushes
+/// and pops are O(1) amortized. Nodes in the same bucket come out in LIFO
+/// order, so a `width` above 1 trades exactness for fewer buckets.
+///
+/// The loss is bounded: with an admissible heuristic, [`explored_astar_with`]
+/// finds paths costing at most `width - 1` more than the optimum. The goal
+/// leaves the lowest non-empty bucket, and the optimal path always has an
+/// open node with `f_cost` at most the optimum, so the goal's bucket is never
+/// above the optimum's.
+///
+/// At most [`MAX_BUCKETS`] buckets are kept; nodes past the last one, like
+/// those behind a saturated heuristic, wait in a binary heap and come out
+/// after every bucket is empty.
+#[derive(Debug, Clone)]
+pub struct BucketQueue<T> {
+    width: u32,
+    buckets: Vec<Vec<AStarNode<T>>>,
+    overflow: BinaryHeap<AStarNode<T>>,
+    /// No bucket below this one holds nodes.
+    cursor: usize,
+}
+
+impl<T> BucketQueue<T> {
+    pub fn new(width: u32) -> Self {
+        Self {
+            width: width.max(1),
+            buckets: Vec::new(),
+            overflow: BinaryHeap::new(),
+            cursor: 0,
+        }
+    }
+}
+
+impl<T> OpenList<T> for BucketQueue<T> {
+    fn push(&mut self, node: AStarNode<T>) {
+        let bucket = (node.f_cost / self.width) as usize;
+        if bucket >= MAX_BUCKETS {
+            self.overflow.push(node);
+            return;
+        }
+        if bucket >= self.buckets.len() {
+            self.buckets.resize_with(bucket + 1, Vec::new);
+        }
+        self.buckets[bucket].push(node);
+        self.cursor = self.cursor.min(bucket);
+    }
+
+    fn pop(&mut self) -> Option<AStarNode<T>> {
+        while self.cursor < self.buckets.len() {
+            if let Some(node) = self.buckets[self.cursor].pop() {
+                return Some(node);
+            }
+            self.cursor += 1;
+        }
+        self.overflow.pop()
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn optimized_astar<T, F, I, H, G>(
     start: T,
     max_states: usize,
     neighbors_fn: F,
@@ -44,49 +130,788 @@ pub fn optimized_astar<T, F, H, G>(
     goal_fn: G,
 ) -> Option<(Vec<T>, u32)>
 where
-    T: Eq + Clone + std::hash::Hash,
-    F: Fn(&T) -> Vec<(T, u32)>,
+    T: Eq + Clone + core::hash::Hash,
+    F: Fn(&T) -> I,
+    I: IntoIterator<Item = (T, u32)>,
     H: Fn(&T) -> u32,
     G: Fn(&T) -> bool,
 {
-    let arena = Arena::new();
-    let mut open_set = BinaryHeap::with_capacity(max_states);
-    let mut came_from: HashMap<T, T> = HashMap::with_capacity(max_states);
-    let mut g_score: HashMap<T, u32> = HashMap::with_capacity(max_states);
+    explored_astar(start, max_states, neighbors_fn, heuristic_fn, goal_fn).ok()
+}
 
-    let start_node = arena.alloc(AStarNode::new(start.clone(), 0, heuristic_fn(&start)));
+/// [`explored_astar_with`] on a binary heap.
+#[cfg(feature = "std")]
+pub fn explored_astar<T, F, I, H, G>(
+    start: T,
+    max_states: usize,
+    neighbors_fn: F,
+    heuristic_fn: H,
+    goal_fn: G,
+) -> Result<(Vec<T>, u32), Vec<T>>
+where
+    T: Eq + Clone + core::hash::Hash,
+    F: Fn(&T) -> I,
+    I: IntoIterator<Item = (T, u32)>,
+    H: Fn(&T) -> u32,
+    G: Fn(&T) -> bool,
+{
+    explored_astar_with(
+        BinaryHeap::with_capacity(max_states),
+        start,
+        max_states,
+        neighbors_fn,
+        heuristic_fn,
+        goal_fn,
+    )
+}
 
-    open_set.push(start_node.clone());
-    g_score.insert(start.clone(), 0);
+/// Same as [`optimized_astar`] on the given open list, but a failed search
+/// returns every state it expanded, in expansion order and possibly with
+/// repeats.
+///
+/// Each state is stored once, in `states`; the open list, parents and
+/// scores refer to it by index.
+#[cfg(feature = "std")]
+pub fn explored_astar_with<T, O, F, I, H, G>(
+    mut open_set: O,
+    start: T,
+    max_states: usize,
+    neighbors_fn: F,
+    heuristic_fn: H,
+    goal_fn: G,
+) -> Result<(Vec<T>, u32), Vec<T>>
+where
+    T: Eq + Clone + core::hash::Hash,
+    O: OpenList<usize>,
+    F: Fn(&T) -> I,
+    I: IntoIterator<Item = (T, u32)>,
+    H: Fn(&T) -> u32,
+    G: Fn(&T) -> bool,
+{
+    let mut explored = Vec::new();
+    let mut states: Vec<T> = Vec::with_capacity(max_states);
+    let mut came_from: Vec<usize> = Vec::with_capacity(max_states);
+    let mut g_score: Vec<u32> = Vec::with_capacity(max_states);
+    let mut indices: FastHashMap<T, usize> =
+        FastHashMap::with_capacity_and_hasher(max_states, Default::default());
+
+    let start_f = heuristic_fn(&start);
+    if start_f == u32::MAX {
+        return Err(explored);
+    }
+    open_set.push(AStarNode::new(0, 0, start_f));
+    indices.insert(start.clone(), 0);
+    states.push(start);
+    came_from.push(usize::MAX);
+    g_score.push(0);
 
     while let Some(current_node) = open_set.pop() {
-        if goal_fn(&current_node.state) {
-            let mut total_path = vec![current_node.state.clone()];
-            let mut current = current_node.state.clone();
-            while let Some(next) = came_from.get(&current) {
-                total_path.push(next.clone());
-                current = next.clone();
+        let current = current_node.state;
+        // A cheaper way here was found after this node was queued.
+        if current_node.g_cost > g_score[current] {
+            continue;
+        }
+        if goal_fn(&states[current]) {
+            let mut total_path = vec![states[current].clone()];
+            let mut index = came_from[current];
+            while index != usize::MAX {
+                total_path.push(states[index].clone());
+                index = came_from[index];
             }
             total_path.reverse();
-            return Some((total_path, current_node.g_cost));
+            return Ok((total_path, current_node.g_cost));
         }
 
-        let current_state = current_node.state.clone();
+        explored.push(states[current].clone());
 
-        for (neighbor, move_cost) in neighbors_fn(&current_state) {
-            let tentative_g_score = g_score[&current_state] + move_cost;
-            if tentative_g_score < *g_score.get(&neighbor).unwrap_or(&u32::MAX) {
-                came_from.insert(neighbor.clone(), current_state.clone());
-                g_score.insert(neighbor.clone(), tentative_g_score);
+        for (neighbor, move_cost) in neighbors_fn(&states[current]) {
+            let tentative_g_score = current_node.g_cost.saturating_add(move_cost);
+            let index = match indices.get(&neighbor) {
+                Some(&index) if tentative_g_score >= g_score[index] => continue,
+                Some(&index) => {
+                    came_from[index] = current;
+                    g_score[index] = tentative_g_score;
+                    index
+                }
+                None => {
+                    let index = states.len();
+                    indices.insert(neighbor.clone(), index);
+                    states.push(neighbor);
+                    came_from.push(current);
+                    g_score.push(tentative_g_score);
+                    index
+                }
+            };
 
-                let f_cost = tentative_g_score + heuristic_fn(&neighbor);
-                let neighbor_node =
-                    arena.alloc(AStarNode::new(neighbor.clone(), tentative_g_score, f_cost));
+            let f_cost = tentative_g_score.saturating_add(heuristic_fn(&states[index]));
+            // A saturated estimate marks a dead end.
+            if f_cost == u32::MAX {
+                continue;
+            }
+            open_set.push(AStarNode::new(index, tentative_g_score, f_cost));
+        }
+    }
+
+    Err(explored)
+}
 
-                open_set.push(neighbor_node.clone());
+/// A* that never hashes: `index_fn` maps every state to a slot in
+/// `0..capacity`, and scores and parents live in arrays of that size
+/// allocated up front. For `no_std` targets whose states index densely, like
+/// poses on a bounded grid (see [`crate::cell::Cell::dense_index`]). States
+/// without a slot are skipped.
+pub fn fixed_astar<T, X, F, I, H, G>(
+    start: T,
+    capacity: usize,
+    index_fn: X,
+    neighbors_fn: F,
+    heuristic_fn: H,
+    goal_fn: G,
+) -> Option<(Vec<T>, u32)>
+where
+    T: Clone,
+    X: Fn(&T) -> Option<usize>,
+    F: Fn(&T) -> I,
+    I: IntoIterator<Item = (T, u32)>,
+    H: Fn(&T) -> u32,
+    G: Fn(&T) -> bool,
+{
+    let mut states: Vec<Option<T>> = vec![None; capacity];
+    let mut came_from = vec![usize::MAX; capacity];
+    let mut g_score = vec![u32::MAX; capacity];
+    let mut open_set = BinaryHeap::new();
+
+    let root = index_fn(&start).filter(|index| *index < capacity)?;
+    open_set.push(AStarNode::new(root, 0, heuristic_fn(&start)));
+    states[root] = Some(start);
+    g_score[root] = 0;
+
+    while let Some(current_node) = open_set.pop() {
+        let current = current_node.state;
+        if current_node.g_cost > g_score[current] {
+            continue;
+        }
+        let state = states[current].clone()?;
+        if goal_fn(&state) {
+            let mut total_path = vec![state];
+            let mut index = came_from[current];
+            while index != usize::MAX {
+                total_path.push(states[index].clone()?);
+                index = came_from[index];
+            }
+            total_path.reverse();
+            return Some((total_path, current_node.g_cost));
+        }
+
+        for (neighbor, move_cost) in neighbors_fn(&state) {
+            let Some(index) = index_fn(&neighbor).filter(|index| *index < capacity) else {
+                continue;
+            };
+            let tentative_g_score = current_node.g_cost.saturating_add(move_cost);
+            if tentative_g_score >= g_score[index] {
+                continue;
             }
+            came_from[index] = current;
+            g_score[index] = tentative_g_score;
+            let f_cost = tentative_g_score.saturating_add(heuristic_fn(&neighbor));
+            states[index] = Some(neighbor);
+            open_set.push(AStarNode::new(index, tentative_g_score, f_cost));
         }
     }
 
     None
 }
+
+/// Marks an empty slot in [`FixedBuffers`].
+const NO_SLOT: u32 = u32::MAX;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FixedSearchError {
+    /// A state mapped to a slot at or past the buffers' capacity.
+    CapacityExceeded,
+    NoPath,
+}
+
+/// Everything [`astar_fixed`] needs, for `N` state slots, in plain arrays:
+/// keep one in a `static` or on the stack and reuse it, so planning never
+/// touches the heap.
+pub struct FixedBuffers<T, const N: usize> {
+    states: [Option<T>; N],
+    g_score: [u32; N],
+    f_score: [u32; N],
+    came_from: [u32; N],
+    /// Binary min-heap of slots by `f_score`, each slot at most once.
+    heap: [u32; N],
+    /// Where each slot sits in `heap`, [`NO_SLOT`] if it isn't queued.
+    heap_position: [u32; N],
+    heap_len: usize,
+}
+
+impl<T, const N: usize> FixedBuffers<T, N> {
+    const EMPTY: Option<T> = None;
+
+    pub const fn new() -> Self {
+        Self {
+            states: [Self::EMPTY; N],
+            g_score: [u32::MAX; N],
+            f_score: [u32::MAX; N],
+            came_from: [NO_SLOT; N],
+            heap: [NO_SLOT; N],
+            heap_position: [NO_SLOT; N],
+            heap_len: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.states.iter_mut().for_each(|state| *state = None);
+        self.g_score.fill(u32::MAX);
+        self.f_score.fill(u32::MAX);
+        self.came_from.fill(NO_SLOT);
+        self.heap_position.fill(NO_SLOT);
+        self.heap_len = 0;
+    }
+
+    /// States from `goal` back to the start of the last search.
+    pub fn path(&self, goal: usize) -> impl Iterator<Item = &T> {
+        let mut slot = goal as u32;
+        core::iter::from_fn(move || {
+            let state = self.states.get(slot as usize)?.as_ref()?;
+            slot = self.came_from[slot as usize];
+            Some(state)
+        })
+    }
+
+    /// Queues `slot`, or moves it up after its `f_score` dropped.
+    fn push_or_decrease(&mut self, slot: usize) {
+        let mut position = match self.heap_position[slot] {
+            NO_SLOT => {
+                self.heap_len += 1;
+                self.heap_len - 1
+            }
+            position => position as usize,
+        };
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if self.f_score[self.heap[parent] as usize] <= self.f_score[slot] {
+                break;
+            }
+            self.place(self.heap[parent] as usize, position);
+            position = parent;
+        }
+        self.place(slot, position);
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        if self.heap_len == 0 {
+            return None;
+        }
+        let top = self.heap[0] as usize;
+        self.heap_position[top] = NO_SLOT;
+        self.heap_len -= 1;
+        if self.heap_len > 0 {
+            let last = self.heap[self.heap_len] as usize;
+            let mut position = 0;
+            loop {
+                let mut smallest = position;
+                for child in [position * 2 + 1, position * 2 + 2] {
+                    let best = if smallest == position {
+                        last
+                    } else {
+                        self.heap[smallest] as usize
+                    };
+                    if child < self.heap_len
+                        && self.f_score[self.heap[child] as usize] < self.f_score[best]
+                    {
+                        smallest = child;
+                    }
+                }
+                if smallest == position {
+                    break;
+                }
+                self.place(self.heap[smallest] as usize, position);
+                position = smallest;
+            }
+            self.place(last, position);
+        }
+        Some(top)
+    }
+
+    fn place(&mut self, slot: usize, position: usize) {
+        self.heap[position] = slot as u32;
+        self.heap_position[slot] = position as u32;
+    }
+}
+
+impl<T, const N: usize> Default for FixedBuffers<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A* that allocates nothing: every score, parent and queue entry lives in
+/// `buffers`, with `index_fn` giving each state its slot (`None` skips the
+/// state, e.g. off the map). `neighbors_fn` hands moves to the callback
+/// instead of returning a collection. Returns the goal's slot, to walk
+/// with [`FixedBuffers::path`], and the path cost.
+pub fn astar_fixed<const N: usize, T, X, F, H, G>(
+    buffers: &mut FixedBuffers<T, N>,
+    start: T,
+    index_fn: X,
+    neighbors_fn: F,
+    heuristic_fn: H,
+    goal_fn: G,
+) -> Result<(usize, u32), FixedSearchError>
+where
+    X: Fn(&T) -> Option<usize>,
+    F: Fn(&T, &mut dyn FnMut(T, u32)),
+    H: Fn(&T) -> u32,
+    G: Fn(&T) -> bool,
+{
+    buffers.clear();
+    let root = index_fn(&start).ok_or(FixedSearchError::NoPath)?;
+    if root >= N {
+        return Err(FixedSearchError::CapacityExceeded);
+    }
+    buffers.g_score[root] = 0;
+    buffers.f_score[root] = heuristic_fn(&start);
+    buffers.states[root] = Some(start);
+    buffers.push_or_decrease(root);
+
+    while let Some(current) = buffers.pop() {
+        let Some(state) = buffers.states[current].take() else {
+            continue;
+        };
+        if goal_fn(&state) {
+            let cost = buffers.g_score[current];
+            buffers.states[current] = Some(state);
+            return Ok((current, cost));
+        }
+
+        let g_cost = buffers.g_score[current];
+        let mut exceeded = false;
+        neighbors_fn(&state, &mut |neighbor, move_cost| {
+            let Some(slot) = index_fn(&neighbor) else {
+                return;
+            };
+            if slot >= N {
+                exceeded = true;
+                return;
+            }
+            let tentative_g_score = g_cost.saturating_add(move_cost);
+            if tentative_g_score >= buffers.g_score[slot] {
+                return;
+            }
+            buffers.came_from[slot] = current as u32;
+            buffers.g_score[slot] = tentative_g_score;
+            buffers.f_score[slot] = tentative_g_score.saturating_add(heuristic_fn(&neighbor));
+            buffers.states[slot] = Some(neighbor);
+            buffers.push_or_decrease(slot);
+        });
+        buffers.states[current] = Some(state);
+        if exceeded {
+            return Err(FixedSearchError::CapacityExceeded);
+        }
+    }
+
+    Err(FixedSearchError::NoPath)
+}
+
+/// Iterative deepening A*: repeated depth-first searches, each pruning
+/// nodes with an `f_cost` above a threshold that grows to the cheapest
+/// pruned one. Keeps only the current path and its pending neighbors in
+/// memory, at the price of expanding nodes many times over. States are only
+/// checked against the current path, so gives up after `max_expansions`
+/// expansions in total.
+pub fn ida_star<T, F, I, H, G>(
+    start: T,
+    max_expansions: usize,
+    neighbors_fn: F,
+    heuristic_fn: H,
+    goal_fn: G,
+) -> Option<(Vec<T>, u32)>
+where
+    T: Eq + Clone,
+    F: Fn(&T) -> I,
+    I: IntoIterator<Item = (T, u32)>,
+    H: Fn(&T) -> u32,
+    G: Fn(&T) -> bool,
+{
+    ida_star_inspected(
+        start,
+        max_expansions,
+        neighbors_fn,
+        heuristic_fn,
+        goal_fn,
+        |_| {},
+    )
+}
+
+/// Same as [`ida_star`], but a failed search returns every state it
+/// expanded, in expansion order and with repeats. A search that ran out of
+/// expansions returns exactly `max_expansions` of them.
+pub fn explored_ida_star<T, F, I, H, G>(
+    start: T,
+    max_expansions: usize,
+    neighbors_fn: F,
+    heuristic_fn: H,
+    goal_fn: G,
+) -> Result<(Vec<T>, u32), Vec<T>>
+where
+    T: Eq + Clone,
+    F: Fn(&T) -> I,
+    I: IntoIterator<Item = (T, u32)>,
+    H: Fn(&T) -> u32,
+    G: Fn(&T) -> bool,
+{
+    let mut explored = Vec::new();
+    ida_star_inspected(
+        start,
+        max_expansions,
+        neighbors_fn,
+        heuristic_fn,
+        goal_fn,
+        |state| explored.push(state.clone()),
+    )
+    .ok_or(explored)
+}
+
+/// [`ida_star`], calling `on_expand` with each state it expands.
+fn ida_star_inspected<T, F, I, H, G, E>(
+    start: T,
+    max_expansions: usize,
+    neighbors_fn: F,
+    heuristic_fn: H,
+    goal_fn: G,
+    mut on_expand: E,
+) -> Option<(Vec<T>, u32)>
+where
+    T: Eq + Clone,
+    F: Fn(&T) -> I,
+    I: IntoIterator<Item = (T, u32)>,
+    H: Fn(&T) -> u32,
+    G: Fn(&T) -> bool,
+    E: FnMut(&T),
+{
+    if goal_fn(&start) {
+        return Some((vec![start], 0));
+    }
+    let mut threshold = heuristic_fn(&start);
+    if threshold == u32::MAX {
+        return None;
+    }
+    let mut expansions = 0;
+    loop {
+        let mut next_threshold = u32::MAX;
+        let mut path = vec![start.clone()];
+        let mut g_costs: Vec<u32> = vec![0];
+        let mut pending = vec![neighbors_fn(&start).into_iter()];
+        expansions += 1;
+        on_expand(&start);
+
+        while let Some(neighbors) = pending.last_mut() {
+            let Some((neighbor, move_cost)) = neighbors.next() else {
+                pending.pop();
+                path.pop();
+                g_costs.pop();
+                continue;
+            };
+            let g_cost = g_costs[g_costs.len() - 1].saturating_add(move_cost);
+            let f_cost = g_cost.saturating_add(heuristic_fn(&neighbor));
+            if f_cost > threshold {
+                next_threshold = next_threshold.min(f_cost);
+                continue;
+            }
+            if path.contains(&neighbor) {
+                continue;
+            }
+            if goal_fn(&neighbor) {
+                path.push(neighbor);
+                return Some((path, g_cost));
+            }
+            if expansions >= max_expansions {
+                return None;
+            }
+            expansions += 1;
+            on_expand(&neighbor);
+            pending.push(neighbors_fn(&neighbor).into_iter());
+            path.push(neighbor);
+            g_costs.push(g_cost);
+        }
+
+        if next_threshold == u32::MAX {
+            return None;
+        }
+        threshold = next_threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5x5 grid with a wall at x = 2 open only at y = 4.
+    fn grid_neighbors(&(x, y): &(i32, i32)) -> Vec<((i32, i32), u32)> {
+        [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .into_iter()
+            .map(|(dx, dy)| (x + dx, y + dy))
+            .filter(|&(x, y)| (0..5).contains(&x) && (0..5).contains(&y) && (x != 2 || y == 4))
+            .map(|next| (next, 1))
+            .collect()
+    }
+
+    fn manhattan(&(x, y): &(i32, i32)) -> u32 {
+        ((4 - x).abs() + y.abs()) as u32
+    }
+
+    #[test]
+    fn test_fixed_astar_on_poses() {
+        use crate::cell::{Cell, NeighborCache};
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+        use glam::IVec2;
+
+        let (width, height, max_increments) = (8, 8, 8);
+        let cache = Rc::new(RefCell::new(NeighborCache::new_precomputed(
+            max_increments,
+            1,
+        )));
+        let goal = IVec2::new(6, 5);
+        let neighbors = |pose: &Cell| {
+            let mut result = Vec::new();
+            pose.for_each_neighbor(&cache, |neighbor| {
+                let cost = neighbor.cost(Some(pose.clone()), 1, max_increments);
+                result.push((neighbor, cost));
+            });
+            result
+        };
+        let (path, cost) = fixed_astar(
+            Cell::new(0, IVec2::new(1, 1)),
+            (width * height) as usize * max_increments as usize,
+            |pose: &Cell| pose.dense_index(width, height, max_increments),
+            neighbors,
+            |pose| pose.heuristic(goal, max_increments),
+            |pose| pose.position == goal,
+        )
+        .unwrap();
+        assert_eq!(path.last().unwrap().position, goal);
+        assert!(path
+            .iter()
+            .all(|pose| (0..width).contains(&pose.position.x)
+                && (0..height).contains(&pose.position.y)));
+        let steps: u32 = path
+            .windows(2)
+            .map(|pair| pair[1].cost(Some(pair[0].clone()), 1, max_increments))
+            .sum();
+        assert_eq!(cost, steps);
+
+        // Without a slot for the start there is nothing to search from.
+        let outside = Cell::new(0, IVec2::new(-1, 0));
+        assert!(fixed_astar(
+            outside,
+            512,
+            |pose: &Cell| pose.dense_index(width, height, max_increments),
+            neighbors,
+            |_| 0,
+            |_| true,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_astar_fixed_matches_fixed_astar() {
+        use crate::cell::{Cell, NeighborCache};
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+        use glam::IVec2;
+
+        let (width, height, max_increments) = (8, 8, 8);
+        let cache = Rc::new(RefCell::new(NeighborCache::new_precomputed(
+            max_increments,
+            1,
+        )));
+        let start = Cell::new(0, IVec2::new(1, 1));
+        let goal = IVec2::new(6, 5);
+        let index = |pose: &Cell| pose.dense_index(width, height, max_increments);
+        let neighbors = |pose: &Cell, add: &mut dyn FnMut(Cell, u32)| {
+            pose.for_each_neighbor(&cache, |neighbor| {
+                let cost = neighbor.cost(Some(pose.clone()), 1, max_increments);
+                add(neighbor, cost);
+            });
+        };
+
+        let mut buffers: FixedBuffers<Cell, 512> = FixedBuffers::new();
+        let (slot, cost) = astar_fixed(
+            &mut buffers,
+            start.clone(),
+            index,
+            neighbors,
+            |_| 0,
+            |pose| pose.position == goal,
+        )
+        .unwrap();
+        let path: Vec<Cell> = buffers.path(slot).cloned().collect();
+        assert_eq!(path.first().unwrap().position, goal);
+        assert_eq!(path.last(), Some(&start));
+
+        let (_, expected) = fixed_astar(
+            start.clone(),
+            512,
+            index,
+            |pose: &Cell| {
+                let mut result = Vec::new();
+                neighbors(pose, &mut |neighbor, cost| result.push((neighbor, cost)));
+                result
+            },
+            |_| 0,
+            |pose| pose.position == goal,
+        )
+        .unwrap();
+        assert_eq!(cost, expected);
+
+        // The same buffers are reusable, and too few slots is an error.
+        assert!(astar_fixed(
+            &mut buffers,
+            start.clone(),
+            index,
+            neighbors,
+            |_| 0,
+            |_| true
+        )
+        .is_ok());
+        let mut small: FixedBuffers<Cell, 100> = FixedBuffers::new();
+        assert_eq!(
+            astar_fixed(
+                &mut small,
+                start,
+                index,
+                neighbors,
+                |_| 0,
+                |pose| { pose.position == goal }
+            ),
+            Err(FixedSearchError::CapacityExceeded)
+        );
+    }
+
+    #[test]
+    fn test_ida_star_matches_astar() {
+        let is_goal = |state: &(i32, i32)| *state == (4, 0);
+        let (path, cost) = ida_star((0, 0), 100_000, grid_neighbors, manhattan, is_goal).unwrap();
+        let (_, optimal) = optimized_astar((0, 0), 25, grid_neighbors, manhattan, is_goal).unwrap();
+        assert_eq!(cost, optimal);
+        assert_eq!(path.len() as u32, cost + 1);
+        assert!(path.contains(&(2, 4)));
+
+        assert!(ida_star((0, 0), 10, grid_neighbors, manhattan, is_goal).is_none());
+        let unreachable = |state: &(i32, i32)| *state == (9, 9);
+        assert!(ida_star((0, 0), 100_000, grid_neighbors, manhattan, unreachable).is_none());
+
+        // Failures list their expansions, all of the budget when it ran out.
+        let explored = explored_ida_star((0, 0), 10, grid_neighbors, manhattan, is_goal);
+        assert_eq!(explored.unwrap_err().len(), 10);
+        let explored =
+            explored_ida_star((0, 0), 100_000, grid_neighbors, manhattan, unreachable).unwrap_err();
+        assert!(explored.len() < 100_000);
+        assert!(explored.contains(&(4, 4)));
+    }
+
+    #[test]
+    fn test_bucket_queue_order() {
+        let mut queue = BucketQueue::new(10);
+        for (state, f_cost) in [(0, 35), (1, 5), (2, 12), (3, 38), (4, 0)] {
+            queue.push(AStarNode::new(state, 0, f_cost));
+        }
+        let mut order = Vec::new();
+        while let Some(node) = OpenList::pop(&mut queue) {
+            order.push(node.state);
+            // A cheaper push after pops still comes out next.
+            if node.state == 2 {
+                queue.push(AStarNode::new(5, 0, 1));
+            }
+        }
+        // 1 and 4 share a bucket, as do 0 and 3, and come out last-in first.
+        assert_eq!(order, vec![4, 1, 2, 5, 3, 0]);
+    }
+
+    #[test]
+    fn test_bucket_queue_error_bound() {
+        // Random entry costs from 1 to 9, so many nodes share wide buckets.
+        let mut state = 0x9e37_79b9_u32;
+        let costs: Vec<u32> = (0..16 * 16)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                1 + state % 9
+            })
+            .collect();
+        let neighbors = |&(x, y): &(i32, i32)| -> Vec<((i32, i32), u32)> {
+            [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .into_iter()
+                .map(|(dx, dy)| (x + dx, y + dy))
+                .filter(|&(x, y)| (0..16).contains(&x) && (0..16).contains(&y))
+                .map(|(x, y)| ((x, y), costs[(y * 16 + x) as usize]))
+                .collect()
+        };
+        let mut worst = 0;
+        for goal in [(15, 15), (15, 0), (3, 12), (9, 7)] {
+            // Every step costs at least 1, so this never overestimates.
+            let heuristic = |&(x, y): &(i32, i32)| ((goal.0 - x).abs() + (goal.1 - y).abs()) as u32;
+            let is_goal = |state: &(i32, i32)| *state == goal;
+            let (_, optimal) = optimized_astar((0, 0), 256, neighbors, heuristic, is_goal).unwrap();
+            for width in [1, 2, 5, 10, 20, 40, 80, 200] {
+                let (_, cost) = explored_astar_with(
+                    BucketQueue::new(width),
+                    (0, 0),
+                    256,
+                    neighbors,
+                    heuristic,
+                    is_goal,
+                )
+                .unwrap();
+                assert!(
+                    cost >= optimal && cost < optimal + width,
+                    "{goal:?} {width}"
+                );
+                worst = worst.max(cost - optimal);
+            }
+        }
+        // Wide buckets do lose something, or the bound wasn't tested.
+        assert!(worst > 0);
+    }
+
+    #[test]
+    fn test_bucket_queue_unreachable_heuristic() {
+        // Far past the last bucket, nodes wait in the overflow heap instead
+        // of growing the buckets to match.
+        let mut queue = BucketQueue::new(1);
+        for (state, f_cost) in [(0, u32::MAX - 1), (1, 3), (2, u32::MAX / 2)] {
+            queue.push(AStarNode::new(state, 0, f_cost));
+        }
+        assert!(queue.buckets.len() <= MAX_BUCKETS);
+        let order: Vec<_> = core::iter::from_fn(|| OpenList::pop(&mut queue))
+            .map(|node| node.state)
+            .collect();
+        assert_eq!(order, vec![1, 2, 0]);
+
+        // States the heuristic rules out are never queued.
+        let walled = |state: &(i32, i32)| {
+            if state.0 >= 2 {
+                u32::MAX
+            } else {
+                manhattan(state)
+            }
+        };
+        let is_goal = |state: &(i32, i32)| *state == (4, 0);
+        let explored = explored_astar_with(
+            BucketQueue::new(1),
+            (0, 0),
+            25,
+            grid_neighbors,
+            walled,
+            is_goal,
+        )
+        .unwrap_err();
+        assert!(explored.iter().all(|state| state.0 < 2));
+        assert_eq!(explored.len(), 10);
+    }
+}
@@ -0,0 +1,198 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::Agent;
+use crate::annotations::Annotations;
+use crate::cell::NeighborCache;
+use crate::encoding::{read_ivec2, read_u32, write_ivec2, write_u32};
+
+/// Leads every cache file, bumped whenever the layout or the precompute
+/// itself changes so stale files are rebuilt instead of misread.
+const MAGIC: &[u8; 4] = b"VPC2";
+
+/// Leads annotation files. Unlike the caches they're edited by hand and
+/// can't be rebuilt, so this only changes along with a migration.
+const ANNOTATIONS_MAGIC: &[u8; 4] = b"VPA1";
+
+fn write_footprints(writer: &mut impl Write, footprints: &[Vec<IVec2>]) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    write_u32(writer, footprints.len() as u32)?;
+    for footprint in footprints {
+        write_u32(writer, footprint.len() as u32)?;
+        for cell in footprint {
+            write_ivec2(writer, *cell)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_footprints(reader: &mut impl Read) -> io::Result<Vec<Vec<IVec2>>> {
+    check_magic(reader)?;
+    let count = read_u32(reader)?;
+    (0..count)
+        .map(|_| {
+            let len = read_u32(reader)?;
+            (0..len).map(|_| read_ivec2(reader)).collect()
+        })
+        .collect()
+}
+
+fn check_magic(reader: &mut impl Read) -> io::Result<()> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a current precompute cache file",
+        ));
+    }
+    Ok(())
+}
+
+pub fn neighbor_cache_path(dir: &Path, max_increments: u16, arc: u16) -> PathBuf {
+    dir.join(format!("neighbors-{}-{}.bin", max_increments, arc))
+}
+
+/// Keyed by the exact bits of `size`, since any change can alter the
+/// rasterization.
+pub fn footprints_path(dir: &Path, size: Vec2, max_increments: u16) -> PathBuf {
+    dir.join(format!(
+        "footprints2-{}-{:08x}-{:08x}.bin",
+        max_increments,
+        size.x.to_bits(),
+        size.y.to_bits()
+    ))
+}
+
+pub fn save_neighbor_cache(path: &Path, cache: &NeighborCache) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    cache.write_to(&mut writer)?;
+    writer.flush()
+}
+
+pub fn load_neighbor_cache(path: &Path) -> io::Result<NeighborCache> {
+    let mut reader = BufReader::new(File::open(path)?);
+    check_magic(&mut reader)?;
+    NeighborCache::read_from(&mut reader)
+}
+
+/// Annotations live next to the map file, with the same name and an
+/// `annotations` extension.
+pub fn annotations_path(map: &Path) -> PathBuf {
+    map.with_extension("annotations")
+}
+
+pub fn save_annotations(path: &Path, annotations: &Annotations) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(ANNOTATIONS_MAGIC)?;
+    annotations.write_to(&mut writer)?;
+    writer.flush()
+}
+
+pub fn load_annotations(path: &Path) -> io::Result<Annotations> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != ANNOTATIONS_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an annotations file",
+        ));
+    }
+    Annotations::read_from(&mut reader)
+}
+
+/// Loads the neighbor cache for these parameters from `dir`, or precomputes
+/// it and saves it there for the next run. Failing to save only costs the
+/// next run the precompute again, so it's only reported on stderr.
+pub fn cached_neighbor_cache(dir: &Path, max_increments: u16, arc: u16) -> NeighborCache {
+    let path = neighbor_cache_path(dir, max_increments, arc);
+    if let Ok(cache) = load_neighbor_cache(&path) {
+        return cache;
+    }
+    let cache = NeighborCache::new_precomputed(max_increments, arc);
+    if let Err(error) = fs::create_dir_all(dir).and_then(|_| save_neighbor_cache(&path, &cache)) {
+        eprintln!("Could not save neighbor cache to {:?}: {}", path, error);
+    }
+    cache
+}
+
+/// Like [`Agent::new`], but loads the rasterized footprints from `dir` when
+/// an earlier run saved them. Failing to save them is reported on stderr,
+/// like in [`cached_neighbor_cache`].
+pub fn cached_agent(
+    dir: &Path,
+    position: IVec2,
+    size: Vec2,
+    rotation: i16,
+    max_increments: u16,
+) -> Agent {
+    let path = footprints_path(dir, size, max_increments);
+    let loaded = File::open(&path)
+        .and_then(|file| read_footprints(&mut BufReader::new(file)))
+        .ok()
+        .filter(|footprints| footprints.len() == max_increments as usize);
+    if let Some(footprints) = loaded {
+        return Agent::from_footprints(position, size, rotation, max_increments, footprints);
+    }
+    let agent = Agent::new(position, size, rotation, max_increments);
+    let saved = fs::create_dir_all(dir)
+        .and_then(|_| File::create(&path))
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write_footprints(&mut writer, agent.footprints())?;
+            writer.flush()
+        });
+    if let Err(error) = saved {
+        eprintln!("Could not save footprints to {:?}: {}", path, error);
+    }
+    agent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "vehicle-pathfinding-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_neighbor_cache_round_trip() {
+        let dir = temp_dir("neighbors");
+        let computed = cached_neighbor_cache(&dir, 16, 2);
+        assert!(neighbor_cache_path(&dir, 16, 2).exists());
+        let loaded = cached_neighbor_cache(&dir, 16, 2);
+        for rotation in 0..16 {
+            assert_eq!(computed.get(rotation), loaded.get(rotation));
+        }
+
+        fs::write(neighbor_cache_path(&dir, 16, 2), b"junk").unwrap();
+        assert!(load_neighbor_cache(&neighbor_cache_path(&dir, 16, 2)).is_err());
+        let rebuilt = cached_neighbor_cache(&dir, 16, 2);
+        assert_eq!(computed.get(3), rebuilt.get(3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_footprints_round_trip() {
+        let dir = temp_dir("footprints");
+        let size = Vec2::new(4.5, 2.25);
+        let computed = cached_agent(&dir, IVec2::new(1, 2), size, 3, 16);
+        assert!(footprints_path(&dir, size, 16).exists());
+        let loaded = cached_agent(&dir, IVec2::new(1, 2), size, 3, 16);
+        assert_eq!(computed.footprints(), loaded.footprints());
+        assert_eq!(loaded.row_masks(5), computed.row_masks(5));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1452 @@
+use std::cell::RefCell;
+use std::collections::BinaryHeap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use notan::math::IVec2;
+use smallvec::SmallVec;
+
+use crate::agent::{Agent, MotionModel};
+use crate::cell::{Cell, CostCache, HeadingCache, NeighborCacheRef};
+use crate::field::DistanceField;
+use crate::fixed;
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::pathfind::{explored_astar_with, explored_ida_star, BucketQueue};
+
+/// What the planner does when the start pose already overlaps obstacles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EscapeMode {
+    /// Treat the start like any other pose, which usually means failing.
+    Disabled,
+    /// Allow moves that keep overlapping obstacles until the footprint is
+    /// free again, charging `penalty` on top of every such move.
+    Penalized { penalty: u32 },
+    /// Look for the nearest free pose within `max_radius` cells and start
+    /// the search from there instead.
+    Reroot { max_radius: i32 },
+}
+
+/// Priority queue backing the search.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpenListKind {
+    BinaryHeap,
+    /// [`BucketQueue`] with buckets `width` cost units wide. Paths cost up
+    /// to `width - 1` more than the heap would find with the same
+    /// heuristic; the bound is additive, so [`PlanResult`] reports none.
+    Buckets {
+        width: u32,
+    },
+}
+
+/// How the planner searches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchAlgorithm {
+    /// A* over the configured open list, remembering every reached pose.
+    AStar,
+    /// [`ida_star`](crate::pathfind::ida_star), for targets that can't
+    /// afford the closed set: memory stays at the path length, but poses get
+    /// expanded many times and `max_states` caps the total expansions. Pairs
+    /// best with an admissible `heuristic_weight` of 1, which prunes far
+    /// harder than the greedy heuristic. Tiny cost differences, like a
+    /// holonomic `heading_weight` of 1, make the threshold crawl.
+    IterativeDeepening,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlannerConfig {
+    pub arc: u16,
+    /// Widest heading change of a reverse move, in increments. Moves from
+    /// the neighbor cache turning more than this are skipped.
+    pub reverse_arc: u16,
+    pub max_increments: u16,
+    /// Capacity hint for the search, usually `cells * increments`.
+    pub max_states: usize,
+    pub escape: EscapeMode,
+    /// Set either of these to `false` to drop forward or reverse moves
+    /// from the neighbor cache entirely, e.g. for reverse-only docking.
+    pub allow_forward: bool,
+    pub allow_reverse: bool,
+    /// Multiplier applied to the cost of reverse moves.
+    pub reverse_factor: u32,
+    /// Extra cost of waiting for or opening a closed door on the way.
+    /// `None` treats closed doors like walls.
+    pub door_cost: Option<u32>,
+    pub open_list: OpenListKind,
+    /// Ignored by [`SearchAlgorithm::IterativeDeepening`].
+    pub algorithm: SearchAlgorithm,
+    /// Precomputed travel costs to the goal (see [`crate::field`]) used as
+    /// the heuristic instead of straight-line distance.
+    pub heuristic_field: Option<Arc<DistanceField>>,
+    /// Scales an admissible straight-line heuristic by this weight, so the
+    /// plan costs at most `weight` times the optimum (see
+    /// [`PlanResult::suboptimality_bound`]). `None` keeps the faster greedy
+    /// heuristic, which gives no guarantee.
+    pub heuristic_weight: Option<f32>,
+    /// Computes move costs and heuristics with integer math only (see
+    /// [`crate::fixed`]), so every target plans the same paths. Agents and
+    /// grids with [`float_cost_terms`] can't be planned this way.
+    pub fixed_point: bool,
+}
+
+impl PlannerConfig {
+    pub fn new(arc: u16, max_increments: u16, max_states: usize) -> Self {
+        Self {
+            arc,
+            reverse_arc: arc * 2,
+            max_increments,
+            max_states,
+            escape: EscapeMode::Disabled,
+            allow_forward: true,
+            allow_reverse: true,
+            reverse_factor: 10,
+            door_cost: None,
+            open_list: OpenListKind::BinaryHeap,
+            algorithm: SearchAlgorithm::AStar,
+            heuristic_field: None,
+            heuristic_weight: None,
+            fixed_point: false,
+        }
+    }
+}
+
+/// Reports how the start pose was moved out of collision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartAdjustment {
+    /// The pose the plan was requested from.
+    pub requested: Cell,
+    /// The first collision-free pose of the plan.
+    pub adjusted: Cell,
+    /// Number of path steps spent overlapping obstacles before `adjusted`.
+    pub escape_steps: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlanResult {
+    pub path: Vec<Cell>,
+    pub cost: u32,
+    pub start_adjustment: Option<StartAdjustment>,
+    /// How many times the optimal cost `cost` can at most be, when the
+    /// search can guarantee it: a weighted heuristic, the binary heap open
+    /// list or iterative deepening, and no speed profile or flow field
+    /// discounting moves.
+    pub suboptimality_bound: Option<f32>,
+    /// Poses the search expanded.
+    pub expanded: usize,
+}
+
+/// Why [`plan`] returned no path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanError {
+    /// [`PlannerConfig::fixed_point`] is set, but the agent or grid has
+    /// [`float_cost_terms`].
+    FloatCostTerms,
+    /// The footprint collides at the start, and the escape mode doesn't
+    /// get it out.
+    StartBlocked,
+    /// The footprint collides at every pose the goal accepts. Found before
+    /// searching, so it costs next to nothing.
+    GoalBlocked,
+    /// The search ran out of poses to try without reaching the goal.
+    /// [`crate::diagnostics::plan_diagnosed`] explains why.
+    NoPath,
+}
+
+/// Checks every cell a (possibly multi-cell) move passes through.
+pub fn is_move_blocked(grid: &Grid, agent: &Agent, from: &Cell, to: &Cell) -> bool {
+    let steps = from.steps_to(to);
+    let step = (to.position - from.position) / steps;
+    (1..=steps).any(|i| {
+        let pose = Cell::new(to.rotation, from.position + step * i);
+        grid.is_pose_blocked(agent, &pose)
+    })
+}
+
+/// Checks every pose a (possibly multi-cell) move passes through against
+/// the grid's geofences.
+pub fn is_move_outside_geofence(grid: &Grid, agent: &Agent, from: &Cell, to: &Cell) -> bool {
+    if grid.keep_out.is_empty() && grid.keep_in.is_empty() {
+        return false;
+    }
+    let steps = from.steps_to(to);
+    let step = (to.position - from.position) / steps;
+    (1..=steps).any(|i| {
+        let pose = Cell::new(to.rotation, from.position + step * i);
+        grid.violates_geofence(agent, &pose)
+    })
+}
+
+/// Checks that every blocked cell a move passes through is a closed door.
+pub fn is_move_through_doors(grid: &Grid, agent: &Agent, from: &Cell, to: &Cell) -> bool {
+    let steps = from.steps_to(to);
+    let step = (to.position - from.position) / steps;
+    (1..=steps).all(|i| {
+        let pose = Cell::new(to.rotation, from.position + step * i);
+        !grid.is_pose_blocked(agent, &pose) || grid.is_pose_blocked_by_doors(agent, &pose)
+    })
+}
+
+/// Why [`check_move`] turned a move down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveRejection {
+    OutsideGeofence,
+    /// Blocked by an obstacle, or a closed door without a door cost.
+    Blocked,
+    /// Steeper than the agent can climb.
+    TooSteep,
+}
+
+/// A move [`check_move`] allowed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CheckedMove {
+    /// The move's cost with doors and terrain included.
+    pub cost: u32,
+    /// Whether it waits for or opens a closed door.
+    pub through_doors: bool,
+}
+
+/// Applies the rules every search follows to a candidate move costing
+/// `cost`, in order: geofences, obstacles (passable at the door cost if
+/// they're closed doors and `config` has one), then [`terrain_cost`].
+pub fn check_move(
+    grid: &Grid,
+    agent: &Agent,
+    from: &Cell,
+    to: &Cell,
+    mut cost: u32,
+    config: &PlannerConfig,
+) -> Result<CheckedMove, MoveRejection> {
+    if is_move_outside_geofence(grid, agent, from, to) {
+        return Err(MoveRejection::OutsideGeofence);
+    }
+    let through_doors = is_move_blocked(grid, agent, from, to);
+    if through_doors {
+        match config.door_cost {
+            Some(door_cost) if is_move_through_doors(grid, agent, from, to) => cost += door_cost,
+            _ => return Err(MoveRejection::Blocked),
+        }
+    }
+    let cost = terrain_cost(grid, agent, from, to, cost, config).ok_or(MoveRejection::TooSteep)?;
+    Ok(CheckedMove {
+        cost,
+        through_doors,
+    })
+}
+
+/// The cost of a candidate move after [`check_move`], or `None` if the
+/// agent can't make it.
+pub fn expand_move(
+    grid: &Grid,
+    agent: &Agent,
+    from: &Cell,
+    to: &Cell,
+    cost: u32,
+    config: &PlannerConfig,
+) -> Option<u32> {
+    check_move(grid, agent, from, to, cost, config)
+        .ok()
+        .map(|checked| checked.cost)
+}
+
+/// Whether some pose `goal` accepts is free for the footprint, counting
+/// closed doors as free if `through_doors`. Without one no search can
+/// succeed.
+pub fn goal_has_free_pose(grid: &Grid, agent: &Agent, goal: &Goal, through_doors: bool) -> bool {
+    let (min, max) = goal.bounds();
+    (min.y..=max.y).any(|y| {
+        (min.x..=max.x).any(|x| {
+            let position = IVec2::new(x, y);
+            goal.contains(position)
+                && (0..agent.max_increments as i16).any(|rotation| {
+                    let pose = Cell::new(rotation, position);
+                    goal.accepts(agent, &pose)
+                        && (!grid.is_pose_blocked(agent, &pose)
+                            || through_doors && grid.is_pose_blocked_by_doors(agent, &pose))
+                })
+        })
+    })
+}
+
+/// Extra cost of climbing along a move over the grid's heightmap, or `None`
+/// if any step is steeper than the agent can handle.
+pub fn climb_cost(grid: &Grid, agent: &Agent, from: &Cell, to: &Cell) -> Option<u32> {
+    let steps = from.steps_to(to);
+    let step = (to.position - from.position) / steps;
+    let mut climbed = 0.0;
+    for i in 0..steps {
+        let a = from.position + step * i;
+        let b = a + step;
+        if agent.max_grade.is_some_and(|max| grid.grade(a, b) > max) {
+            return None;
+        }
+        climbed += (grid.height_at(b.x, b.y) - grid.height_at(a.x, a.y)).max(0.0);
+    }
+    Some((climbed * agent.climb_cost as f32) as u32)
+}
+
+/// Whether [`terrain_cost`] scales moves with `f32` math for this agent
+/// and grid: speed profiles, car dynamics, heightmaps and flow fields. Fixed
+/// point searches reject these instead of giving up determinism.
+pub fn float_cost_terms(grid: &Grid, agent: &Agent) -> bool {
+    agent.speed_profile.is_some()
+        || (agent.dynamics.is_some() && agent.motion == MotionModel::Car)
+        || grid.heights.is_some()
+        || grid.flow.is_some()
+}
+
+/// Checks that going from `from` to `to` stays within the agent's
+/// rotation rate, scaled by how far the move travels.
+pub fn within_rotation_rate(agent: &Agent, from: &Cell, to: &Cell, max_increments: u16) -> bool {
+    let rotation_change = to.rotation_to(from.rotation, max_increments as i16);
+    let distance = from.position.as_vec2().distance(to.position.as_vec2());
+    agent.can_rotate(rotation_change, distance)
+}
+
+/// Moves of a regular steered vehicle, following the neighbor cache.
+fn car_neighbors(
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    costs: &CostCache,
+    action: &Cell,
+    config: &PlannerConfig,
+    result: &mut Candidates,
+) {
+    let mut add = |neigh: Cell, cost: u32, reverse: bool| {
+        let in_place = neigh.position == action.position;
+        // Turns a zero arc doesn't allow.
+        if cost == u32::MAX {
+            return;
+        }
+        if !in_place && !within_rotation_rate(agent, action, &neigh, config.max_increments) {
+            return;
+        }
+        if !in_place
+            && reverse
+            && neigh.rotation_to(action.rotation, config.max_increments as i16)
+                > config.reverse_arc as i16
+        {
+            return;
+        }
+        result.push((neigh, cost));
+    };
+
+    if let Some(move_costs) = costs.get(action.rotation) {
+        let mut move_costs = move_costs.iter();
+        action.for_each_move(neighbor_cache, |neigh, cached| {
+            if let Some(cost) = move_costs.next() {
+                add(neigh, *cost, cached.reverse);
+            }
+        });
+    }
+    if let Some(turn_cost) = agent.turn_in_place_cost {
+        for delta in [-1, 1] {
+            let rotation =
+                Cell::clamp_rotation(action.rotation + delta, config.max_increments as i16);
+            add(
+                Cell::new(rotation, action.position).with_layer(action.layer),
+                turn_cost,
+                false,
+            );
+        }
+    }
+}
+
+/// Costs of the moves in `neighbor_cache` under `config`, for
+/// [`motion_candidates`].
+pub(crate) fn cost_cache(neighbor_cache: &NeighborCacheRef, config: &PlannerConfig) -> CostCache {
+    if config.fixed_point {
+        return CostCache::from_fn(&neighbor_cache.borrow(), |from, to, cached| {
+            fixed::move_cost(
+                from,
+                to,
+                cached.reverse,
+                config.arc,
+                config.max_increments,
+                config.reverse_factor,
+            )
+        });
+    }
+    CostCache::new(
+        &neighbor_cache.borrow(),
+        config.arc,
+        config.max_increments,
+        config.reverse_factor,
+    )
+}
+
+/// Turning cost still needed from each heading to end within the goal's
+/// heading tolerance, or `None` if the goal accepts any heading.
+fn goal_turn_costs(
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    goal: &Goal,
+    config: &PlannerConfig,
+) -> Option<Vec<u32>> {
+    let Goal::Oriented {
+        heading, tolerance, ..
+    } = goal
+    else {
+        return None;
+    };
+    let headings = match agent.motion {
+        MotionModel::Car => HeadingCache::new(
+            &neighbor_cache.borrow(),
+            config.arc,
+            config.max_increments,
+            agent.turn_in_place_cost,
+        ),
+        MotionModel::Holonomic { heading_weight } => {
+            HeadingCache::uniform(config.max_increments, heading_weight)
+        }
+    };
+    let max_increments = config.max_increments as i16;
+    let accepted: Vec<i16> = (0..max_increments)
+        .filter(|rotation| {
+            Cell::new(*rotation, IVec2::ZERO).rotation_to(*heading, max_increments) <= *tolerance
+        })
+        .collect();
+    Some(
+        (0..max_increments)
+            .map(|from| {
+                accepted
+                    .iter()
+                    .map(|to| headings.get(from, *to))
+                    .min()
+                    .unwrap_or(u32::MAX)
+            })
+            .collect(),
+    )
+}
+
+/// Moves of an omnidirectional vehicle: a step in any of the 8 directions
+/// keeping the heading, or a one increment rotation in place.
+fn holonomic_neighbors(
+    action: &Cell,
+    max_increments: u16,
+    heading_weight: u32,
+    fixed_point: bool,
+    result: &mut Candidates,
+) {
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+            let neigh = Cell::new(action.rotation, action.position + IVec2::new(dx, dy))
+                .with_layer(action.layer);
+            let cost = if fixed_point {
+                fixed::holonomic_cost(action, &neigh, max_increments, heading_weight)
+            } else {
+                neigh.holonomic_cost(action, max_increments, heading_weight)
+            };
+            result.push((neigh, cost));
+        }
+    }
+    for delta in [-1, 1] {
+        let rotation = Cell::clamp_rotation(action.rotation + delta, max_increments as i16);
+        let neigh = Cell::new(rotation, action.position).with_layer(action.layer);
+        result.push((neigh, heading_weight));
+    }
+}
+
+/// Moves with their costs, sized so expanding a pose doesn't allocate.
+pub(crate) type Candidates = SmallVec<[(Cell, u32); 32]>;
+
+/// Appends the candidate moves from `action` for the agent's motion model,
+/// before any collision checks, to `candidates`.
+pub(crate) fn motion_candidates(
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    costs: &CostCache,
+    action: &Cell,
+    config: &PlannerConfig,
+    candidates: &mut Candidates,
+) {
+    match agent.motion {
+        MotionModel::Car => car_neighbors(agent, neighbor_cache, costs, action, config, candidates),
+        MotionModel::Holonomic { heading_weight } => holonomic_neighbors(
+            action,
+            config.max_increments,
+            heading_weight,
+            config.fixed_point,
+            candidates,
+        ),
+    }
+}
+
+/// Applies the speed profile, dynamics, heightmap, flow field, congestion,
+/// and soft costs to the cost of a move, or returns `None` if the terrain is
+/// too steep for it.
+pub(crate) fn terrain_cost(
+    grid: &Grid,
+    agent: &Agent,
+    action: &Cell,
+    neigh: &Cell,
+    mut cost: u32,
+    config: &PlannerConfig,
+) -> Option<u32> {
+    if neigh.position == action.position {
+        return Some(cost);
+    }
+    if let Some(profile) = &agent.speed_profile {
+        let motion = (neigh.position - action.position).as_vec2();
+        let heading =
+            action.rotation as f32 * 2.0 * std::f32::consts::PI / config.max_increments as f32;
+        cost = (cost as f32 * profile.cost_factor(heading, motion)) as u32;
+    }
+    if let (Some(dynamics), MotionModel::Car) = (&agent.dynamics, agent.motion) {
+        let max_increments = config.max_increments;
+        let distance = (neigh.position - action.position).as_vec2().length();
+        let turn = neigh.rotation_to(action.rotation, max_increments as i16);
+        let turn = Cell::increment_to_heading(turn, max_increments);
+        let reverse = neigh.is_reverse_to(action, max_increments as i16);
+        // A second at cruising speed costs as much as the cells it covers.
+        let seconds = dynamics.time_lost(distance, turn, reverse);
+        cost += (seconds * dynamics.cruise_speed * 1000.0) as u32;
+    }
+    if grid.heights.is_some() {
+        cost += climb_cost(grid, agent, action, neigh)?;
+    }
+    if grid.flow.is_some() {
+        let factor = grid.flow_cost_factor(action.position, neigh.position);
+        cost = (cost as f32 * factor) as u32;
+    }
+    if let Some(congestion) = &grid.congestion {
+        cost += congestion.cost_at(neigh.position);
+    }
+    cost += grid.pose_soft_cost(agent, neigh);
+    Some(cost)
+}
+
+/// Finds the free pose closest to `start`, preferring smaller position
+/// changes first and smaller rotation changes second.
+pub fn nearest_free_pose(
+    grid: &Grid,
+    agent: &Agent,
+    start: &Cell,
+    max_radius: i32,
+    max_increments: u16,
+) -> Option<Cell> {
+    let mut best: Option<((i32, i16), Cell)> = None;
+    for dy in -max_radius..=max_radius {
+        for dx in -max_radius..=max_radius {
+            let position = start.position + IVec2::new(dx, dy);
+            for rotation in 0..max_increments as i16 {
+                let key = (
+                    dx * dx + dy * dy,
+                    start.rotation_to(rotation, max_increments as i16),
+                );
+                if best.as_ref().is_some_and(|(best_key, _)| *best_key <= key) {
+                    continue;
+                }
+                let pose = Cell::new(rotation, position);
+                if !grid.is_pose_blocked(agent, &pose) {
+                    best = Some((key, pose));
+                }
+            }
+        }
+    }
+    best.map(|(_, pose)| pose)
+}
+
+pub fn plan(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+) -> Result<PlanResult, PlanError> {
+    plan_with_extra_cost(grid, agent, neighbor_cache, start, goal, config, |_| 0)
+}
+
+/// Same as [`plan`], charging `extra_cost` for every pose moved into.
+pub(crate) fn plan_with_extra_cost(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+    extra_cost: impl Fn(&Cell) -> u32,
+) -> Result<PlanResult, PlanError> {
+    let move_cost = |_: &Cell, to: &Cell| extra_cost(to);
+    plan_with_move_cost(grid, agent, neighbor_cache, start, goal, config, move_cost)
+}
+
+/// Same as [`plan`], charging `extra_cost(from, to)` for every move, for
+/// costs that depend on how a pose is reached.
+pub(crate) fn plan_with_move_cost(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+    extra_cost: impl Fn(&Cell, &Cell) -> u32,
+) -> Result<PlanResult, PlanError> {
+    search(grid, agent, neighbor_cache, start, goal, config, extra_cost).map_err(|(error, _)| error)
+}
+
+/// Runs the search behind [`plan`], returning why it failed along with
+/// every pose it expanded.
+pub(crate) fn search(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+    extra_cost: impl Fn(&Cell, &Cell) -> u32,
+) -> Result<PlanResult, (PlanError, Vec<Cell>)> {
+    let goal = goal.into();
+    if config.fixed_point && float_cost_terms(grid, agent) {
+        return Err((PlanError::FloatCostTerms, Vec::new()));
+    }
+    let filtered_cache;
+    let neighbor_cache = if config.allow_forward && config.allow_reverse {
+        neighbor_cache
+    } else {
+        filtered_cache = Rc::new(RefCell::new(
+            neighbor_cache
+                .borrow()
+                .filtered(config.allow_forward, config.allow_reverse),
+        ));
+        &filtered_cache
+    };
+
+    // An impossible goal would otherwise cost an exhaustive search.
+    if !goal.accepts(agent, &start)
+        && !goal_has_free_pose(grid, agent, &goal, config.door_cost.is_some())
+    {
+        return Err((PlanError::GoalBlocked, Vec::new()));
+    }
+
+    let start_blocked = grid.is_pose_blocked(agent, &start);
+    let root = match config.escape {
+        // Moves out of a blocked start would still pass the collision
+        // checks, which only look at the poses moved into.
+        EscapeMode::Disabled if start_blocked => return Err((PlanError::StartBlocked, Vec::new())),
+        EscapeMode::Reroot { max_radius } if start_blocked => {
+            nearest_free_pose(grid, agent, &start, max_radius, config.max_increments)
+                .ok_or((PlanError::StartBlocked, Vec::new()))?
+        }
+        _ => start.clone(),
+    };
+    let (escaping, penalty) = match config.escape {
+        EscapeMode::Penalized { penalty } => (start_blocked, penalty),
+        _ => (false, 0),
+    };
+
+    let costs = cost_cache(neighbor_cache, config);
+    let scratch = RefCell::new(Candidates::new());
+    let expanded = std::cell::Cell::new(0);
+    let neighbors = |action: &Cell| {
+        expanded.set(expanded.get() + 1);
+        let mut candidates = scratch.borrow_mut();
+        candidates.clear();
+        motion_candidates(
+            agent,
+            neighbor_cache,
+            &costs,
+            action,
+            config,
+            &mut candidates,
+        );
+        let mut result = Candidates::new();
+        // Blocked poses can only be chained from a blocked start, so an
+        // escaping path never walks back into obstacles later on.
+        let action_blocked = escaping && grid.is_pose_blocked(agent, action);
+
+        for (neigh, cost) in candidates.drain(..) {
+            let cost = match check_move(grid, agent, action, &neigh, cost, config) {
+                Ok(checked) => Some(checked.cost),
+                // Escaping moves may cross obstacles, but not geofences,
+                // which were checked first.
+                Err(MoveRejection::Blocked)
+                    if action_blocked && grid.in_bounds(neigh.position.x, neigh.position.y) =>
+                {
+                    terrain_cost(grid, agent, action, &neigh, cost, config)
+                }
+                Err(_) => None,
+            };
+            let Some(mut cost) = cost else {
+                continue;
+            };
+            if action_blocked {
+                cost += penalty;
+            }
+            cost = cost.saturating_add(extra_cost(action, &neigh));
+            result.push((neigh, cost));
+        }
+
+        result
+    };
+    let turn_costs = goal_turn_costs(agent, neighbor_cache, &goal, config);
+    let heuristic = |action: &Cell| {
+        let position = action.position;
+        // Cells the field didn't reach, like the obstacles an escape starts
+        // in, fall back to straight-line distance.
+        let field_distance = config
+            .heuristic_field
+            .as_ref()
+            .map(|field| field.at(position))
+            .filter(|distance| *distance != u32::MAX);
+        let distance = match (field_distance, config.heuristic_weight) {
+            (Some(distance), _) => distance,
+            (None, Some(_)) if config.fixed_point => goal.fixed_distance_lower_bound(position),
+            (None, Some(_)) => (goal.distance_lower_bound(position) * 1000.0) as u32,
+            (None, None) if config.fixed_point => {
+                fixed::heuristic(position, goal.nearest_point(position))
+            }
+            (None, None) => action.heuristic(goal.nearest_point(position), config.max_increments),
+        };
+        let turn = turn_costs
+            .as_ref()
+            .map_or(0, |costs| costs[action.rotation as usize]);
+        let estimate = distance.saturating_add(turn);
+        match config.heuristic_weight {
+            Some(_) if estimate == u32::MAX => estimate,
+            Some(weight) if config.fixed_point => {
+                let permille = (weight * 1000.0) as u64;
+                (estimate as u64 * permille / 1000).min(u32::MAX as u64) as u32
+            }
+            Some(weight) => (estimate as f32 * weight) as u32,
+            None => estimate,
+        }
+    };
+    let is_goal = |action: &Cell| {
+        goal.accepts(agent, action) && !(escaping && grid.is_pose_blocked(agent, action))
+    };
+    let result = match (config.algorithm, config.open_list) {
+        (SearchAlgorithm::IterativeDeepening, _) => explored_ida_star(
+            root.clone(),
+            config.max_states,
+            neighbors,
+            heuristic,
+            is_goal,
+        ),
+        (SearchAlgorithm::AStar, OpenListKind::BinaryHeap) => explored_astar_with(
+            BinaryHeap::with_capacity(config.max_states),
+            root.clone(),
+            config.max_states,
+            neighbors,
+            heuristic,
+            is_goal,
+        ),
+        (SearchAlgorithm::AStar, OpenListKind::Buckets { width }) => explored_astar_with(
+            BucketQueue::new(width),
+            root.clone(),
+            config.max_states,
+            neighbors,
+            heuristic,
+            is_goal,
+        ),
+    };
+
+    let (path, cost) = result.map_err(|explored| (PlanError::NoPath, explored))?;
+    let start_adjustment = if root != start {
+        Some(StartAdjustment {
+            requested: start,
+            adjusted: root,
+            escape_steps: 0,
+        })
+    } else if escaping {
+        let escape_steps = path
+            .iter()
+            .take_while(|pose| grid.is_pose_blocked(agent, pose))
+            .count();
+        path.get(escape_steps).map(|adjusted| StartAdjustment {
+            requested: start,
+            adjusted: adjusted.clone(),
+            escape_steps,
+        })
+    } else {
+        None
+    };
+
+    let discounted = agent.speed_profile.is_some() || grid.flow.is_some();
+    let exact_order = config.algorithm == SearchAlgorithm::IterativeDeepening
+        || config.open_list == OpenListKind::BinaryHeap;
+    let suboptimality_bound = match config.heuristic_weight {
+        Some(weight) if exact_order && !discounted => Some(weight.max(1.0)),
+        _ => None,
+    };
+
+    Ok(PlanResult {
+        path,
+        cost,
+        start_adjustment,
+        suboptimality_bound,
+        expanded: expanded.get(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::polygon;
+    use notan::math::Vec2;
+
+    use super::*;
+    use crate::agent::{Dynamics, SpeedProfile};
+    use crate::cell::NeighborCache;
+    use crate::congestion::CongestionMap;
+    use crate::door::Door;
+    use crate::test_support::{neighbor_cache, point_agent, Fixture, MAX_INCREMENTS};
+
+    fn boxed_in_setup() -> (Grid, Agent, NeighborCacheRef) {
+        let mut grid = Grid::new(1.0, 10, 10);
+        for y in 1..=3 {
+            for x in 1..=3 {
+                grid.toggle_cell(x, y);
+            }
+        }
+        let agent = point_agent(IVec2::new(2, 2));
+        let cache = neighbor_cache();
+        (grid, agent, cache)
+    }
+
+    fn config(escape: EscapeMode) -> PlannerConfig {
+        let mut config = Fixture::new(10, 10).config();
+        config.escape = escape;
+        config
+    }
+
+    #[test]
+    fn test_blocked_start_fails_without_escape() {
+        let (grid, agent, cache) = boxed_in_setup();
+        let start = Cell::new(0, agent.position);
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start,
+            IVec2::new(7, 7),
+            &config(EscapeMode::Disabled),
+        );
+        assert_eq!(result.unwrap_err(), PlanError::StartBlocked);
+    }
+
+    #[test]
+    fn test_plan_error() {
+        let mut grid = Grid::new(1.0, 12, 12);
+        for y in 0..12 {
+            grid.set_cell(6, y, true);
+        }
+        grid.set_cell(10, 6, true);
+        let agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let start = Cell::new(0, IVec2::new(2, 6));
+        let config = config(EscapeMode::Disabled);
+        let error = |goal| plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap_err();
+        assert_eq!(error(IVec2::new(10, 6)), PlanError::GoalBlocked);
+        assert_eq!(error(IVec2::new(10, 4)), PlanError::NoPath);
+    }
+
+    #[test]
+    fn test_penalized_escape() {
+        let (grid, agent, cache) = boxed_in_setup();
+        let start = Cell::new(0, agent.position);
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(7, 7),
+            &config(EscapeMode::Penalized { penalty: 1000 }),
+        )
+        .expect("escape should find a path");
+
+        let adjustment = result.start_adjustment.expect("start was blocked");
+        assert_eq!(adjustment.requested, start);
+        assert!(adjustment.escape_steps >= 1);
+        assert!(!grid.is_pose_blocked(&agent, &adjustment.adjusted));
+        assert!(result.path[adjustment.escape_steps..]
+            .iter()
+            .all(|pose| !grid.is_pose_blocked(&agent, pose)));
+    }
+
+    #[test]
+    fn test_rotation_rate_limits_turning() {
+        let grid = Grid::new(1.0, 10, 10);
+        let mut agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let start = Cell::new(0, agent.position);
+
+        // Every turn changes at least one increment over at most a diagonal
+        // step, so half an increment per cell forbids turning altogether.
+        agent.max_rotation_rate = Some(0.5);
+        let straight = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(5, 0),
+            &config,
+        );
+        assert!(straight.is_ok());
+        let turn = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(5, 5),
+            &config,
+        );
+        assert!(turn.is_err());
+
+        agent.max_rotation_rate = Some(1.0);
+        let turn = plan(&grid, &agent, &cache, start, IVec2::new(5, 5), &config);
+        assert!(turn.is_ok());
+    }
+
+    #[test]
+    fn test_straight_primitives_check_intermediate_cells() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.toggle_cell(2, 0);
+        let agent = point_agent(IVec2::new(0, 0));
+        let start = Cell::new(0, agent.position);
+        assert!(!is_move_blocked(
+            &grid,
+            &agent,
+            &start,
+            &Cell::new(0, IVec2::new(1, 0))
+        ));
+        assert!(is_move_blocked(
+            &grid,
+            &agent,
+            &start,
+            &Cell::new(0, IVec2::new(3, 0))
+        ));
+
+        let mut cache = NeighborCache::new_precomputed(MAX_INCREMENTS, 1);
+        cache.add_straight_primitives(4);
+        let cache = Rc::new(RefCell::new(cache));
+        let config = config(EscapeMode::Disabled);
+        let result = plan(&grid, &agent, &cache, start, IVec2::new(8, 5), &config).unwrap();
+        for pair in result.path.windows(2) {
+            assert!(!is_move_blocked(&grid, &agent, &pair[0], &pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_direction_modes() {
+        let grid = Grid::new(1.0, 10, 10);
+        let agent = point_agent(IVec2::new(5, 5));
+        let cache = neighbor_cache();
+        let start = Cell::new(0, agent.position);
+        let count_reverse = |path: &[Cell]| {
+            path.windows(2)
+                .filter(|pair| pair[1].is_reverse_to(&pair[0], MAX_INCREMENTS as i16))
+                .count()
+        };
+
+        let mut reverse_only = config(EscapeMode::Disabled);
+        reverse_only.allow_forward = false;
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(2, 5),
+            &reverse_only,
+        );
+        let path = result.unwrap().path;
+        assert_eq!(count_reverse(&path), path.len() - 1);
+
+        let mut forward_only = config(EscapeMode::Disabled);
+        forward_only.allow_reverse = false;
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start,
+            IVec2::new(5, 6),
+            &forward_only,
+        );
+        assert_eq!(count_reverse(&result.unwrap().path), 0);
+    }
+
+    #[test]
+    fn test_reverse_arc() {
+        let grid = Grid::new(1.0, 10, 10);
+        let agent = point_agent(IVec2::new(5, 5));
+        let cache = neighbor_cache();
+        let mut config = config(EscapeMode::Disabled);
+        config.allow_forward = false;
+        config.reverse_arc = 0;
+        let start = Cell::new(0, agent.position);
+
+        // Backing straight up keeps the heading.
+        let path = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(2, 5),
+            &config,
+        )
+        .unwrap()
+        .path;
+        assert!(path.iter().all(|pose| pose.rotation == 0));
+
+        // Without any steering in reverse, nothing off the line is reachable.
+        assert!(plan(&grid, &agent, &cache, start, IVec2::new(2, 7), &config).is_err());
+    }
+
+    #[test]
+    fn test_plan_into_region() {
+        let grid = Grid::new(1.0, 20, 20);
+        let (agent, cache, config) = Fixture::new(20, 20)
+            .at(IVec2::new(2, 2))
+            .size(Vec2::new(2.5, 1.0))
+            .build();
+        let goal = Goal::Rect {
+            min: IVec2::new(10, 8),
+            max: IVec2::new(16, 12),
+        };
+        let start = Cell::new(0, agent.position);
+        let result = plan(&grid, &agent, &cache, start, goal.clone(), &config).unwrap();
+        assert!(goal.accepts(&agent, result.path.last().unwrap()));
+    }
+
+    #[test]
+    fn test_turn_in_place() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        for y in 0..10 {
+            for x in 0..10 {
+                if !(x == 5 && (3..=5).contains(&y)) {
+                    grid.toggle_cell(x, y);
+                }
+            }
+        }
+        let mut agent = point_agent(IVec2::new(5, 5));
+        let cache = neighbor_cache();
+        // Reversing could still wiggle into the corridor, so only allow
+        // driving forward.
+        let mut config = config(EscapeMode::Disabled);
+        config.allow_reverse = false;
+        let start = Cell::new(0, agent.position);
+
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(5, 3),
+            &config,
+        );
+        assert!(result.is_err());
+
+        agent.turn_in_place_cost = Some(500);
+        let result = plan(&grid, &agent, &cache, start, IVec2::new(5, 3), &config).unwrap();
+        assert!(result
+            .path
+            .windows(2)
+            .any(|pair| pair[0].position == pair[1].position));
+    }
+
+    #[test]
+    fn test_oriented_goal_heuristic() {
+        let grid = Grid::new(1.0, 12, 12);
+        let agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let goal = Goal::Oriented {
+            goal: Box::new(Goal::Cell(IVec2::new(8, 6))),
+            heading: 4,
+            tolerance: 0,
+        };
+        let turns = goal_turn_costs(&agent, &cache, &goal, &config).unwrap();
+        assert_eq!(turns[4], 0);
+        assert_eq!(turns[0], 4000);
+        assert!(goal_turn_costs(&agent, &cache, &Goal::Cell(IVec2::new(8, 6)), &config).is_none());
+
+        let start = Cell::new(0, IVec2::new(3, 6));
+        let result = plan(&grid, &agent, &cache, start, goal, &config).unwrap();
+        let last = result.path.last().unwrap();
+        assert_eq!(last.position, IVec2::new(8, 6));
+        assert_eq!(last.rotation, 4);
+    }
+
+    #[test]
+    fn test_suboptimality_bound() {
+        let mut grid = Grid::new(1.0, 12, 12);
+        for y in 2..10 {
+            grid.set_cell(6, y, true);
+        }
+        let mut agent = point_agent(IVec2::new(0, 0));
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        let cache = neighbor_cache();
+        let start = Cell::new(0, IVec2::new(3, 6));
+        let goal = IVec2::new(9, 6);
+        let mut config = config(EscapeMode::Disabled);
+        let greedy = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert_eq!(greedy.suboptimality_bound, None);
+
+        config.heuristic_weight = Some(1.0);
+        let optimal = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert_eq!(optimal.suboptimality_bound, Some(1.0));
+        assert!(optimal.cost <= greedy.cost);
+
+        config.heuristic_weight = Some(2.0);
+        let weighted = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert_eq!(weighted.suboptimality_bound, Some(2.0));
+        assert!(weighted.cost as f32 <= 2.0 * optimal.cost as f32);
+
+        config.open_list = OpenListKind::Buckets { width: 100 };
+        let bucketed = plan(&grid, &agent, &cache, start, goal, &config).unwrap();
+        assert_eq!(bucketed.suboptimality_bound, None);
+    }
+
+    #[test]
+    fn test_fixed_point_costs() {
+        let mut grid = Grid::new(1.0, 12, 12);
+        for y in 3..9 {
+            grid.set_cell(6, y, true);
+        }
+        let agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let start = Cell::new(0, IVec2::new(2, 6));
+        let goal = IVec2::new(10, 6);
+        let mut config = config(EscapeMode::Disabled);
+        let float = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        config.fixed_point = true;
+        let fixed = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        // Car move costs come out the same either way.
+        assert_eq!(fixed.path, float.path);
+        assert_eq!(fixed.cost, float.cost);
+
+        config.heuristic_weight = Some(1.5);
+        let weighted = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert_eq!(weighted.path.last().unwrap().position, goal);
+        assert_eq!(weighted.suboptimality_bound, Some(1.5));
+
+        // Flow fields scale costs in f32, so fixed point plans refuse them.
+        grid.set_flow(4, 6, Vec2::new(0.5, 0.0));
+        assert!(float_cost_terms(&grid, &agent));
+        assert_eq!(
+            plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap_err(),
+            PlanError::FloatCostTerms
+        );
+        config.fixed_point = false;
+        assert!(plan(&grid, &agent, &cache, start, goal, &config).is_ok());
+    }
+
+    #[test]
+    fn test_iterative_deepening() {
+        let mut grid = Grid::new(1.0, 8, 8);
+        grid.set_cell(4, 2, true);
+        grid.set_cell(4, 3, true);
+        let agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let start = Cell::new(0, IVec2::new(2, 2));
+        let goal = IVec2::new(6, 2);
+        let mut config = PlannerConfig::new(1, MAX_INCREMENTS, 100_000);
+        config.heuristic_weight = Some(1.0);
+        let astar = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+
+        config.algorithm = SearchAlgorithm::IterativeDeepening;
+        let ida = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert_eq!(ida.cost, astar.cost);
+        assert_eq!(ida.suboptimality_bound, Some(1.0));
+        assert_eq!(ida.path.last().unwrap().position, goal);
+        assert!(ida
+            .path
+            .iter()
+            .all(|pose| !grid.is_pose_blocked(&agent, pose)));
+
+        config.max_states = 10;
+        assert!(plan(&grid, &agent, &cache, start, goal, &config).is_err());
+    }
+
+    #[test]
+    fn test_holonomic_motion() {
+        let grid = Grid::new(1.0, 10, 10);
+        let mut agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let start = Cell::new(0, agent.position);
+
+        // a weak heading preference adds next to nothing to the distance
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(0, 5),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(result.cost, 5002);
+        assert!(result.path.iter().all(|pose| pose.position.x == 0));
+
+        // a strong one makes turning to face the motion clearly worth it
+        agent.motion = MotionModel::Holonomic {
+            heading_weight: 500,
+        };
+        let result = plan(&grid, &agent, &cache, start, IVec2::new(0, 9), &config).unwrap();
+        assert!(result.path.iter().any(|pose| pose.rotation == 2));
+    }
+
+    #[test]
+    fn test_speed_profile() {
+        let profile = SpeedProfile {
+            forward: 1.0,
+            sideways: 0.5,
+            backward: 0.25,
+        };
+        assert_eq!(profile.cost_factor(0.0, Vec2::new(1.0, 0.0)), 1.0);
+        assert_eq!(profile.cost_factor(0.0, Vec2::new(0.0, 1.0)), 2.0);
+        assert_eq!(profile.cost_factor(0.0, Vec2::new(-1.0, 0.0)), 4.0);
+
+        let grid = Grid::new(1.0, 10, 10);
+        let mut agent = point_agent(IVec2::new(0, 0));
+        agent.motion = MotionModel::Holonomic { heading_weight: 0 };
+        agent.speed_profile = Some(profile);
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let start = Cell::new(0, agent.position);
+
+        // sliding sideways is slow, so the agent turns to drive forward
+        let result = plan(&grid, &agent, &cache, start, IVec2::new(0, 5), &config).unwrap();
+        assert_eq!(result.cost, 5000);
+        assert_eq!(result.path.last().unwrap().rotation, 2);
+    }
+
+    #[test]
+    fn test_dynamics() {
+        let dynamics = Dynamics {
+            cruise_speed: 2.0,
+            reverse_speed: 1.0,
+            acceleration: 1.0,
+            deceleration: 2.0,
+            lateral_acceleration: 1.0,
+        };
+        let quarter = std::f32::consts::FRAC_PI_4;
+        assert_eq!(dynamics.time_lost(1.0, 0.0, false), 0.0);
+        assert!(dynamics.time_lost(1.0, quarter, false) > 0.0);
+        assert!(dynamics.time_lost(1.0, 0.0, true) > dynamics.time_lost(1.0, quarter, false));
+
+        let grid = Grid::new(1.0, 20, 20);
+        let (mut agent, cache, mut config) = Fixture::new(20, 20).at(IVec2::new(10, 10)).build();
+        config.reverse_factor = 1;
+        let start = Cell::new(0, agent.position);
+        let reverse_moves = |path: &[Cell]| {
+            path.windows(2)
+                .filter(|pair| pair[1].is_reverse_to(&pair[0], MAX_INCREMENTS as i16))
+                .count()
+        };
+
+        // Straight driving keeps cruising speed and costs nothing extra.
+        let ahead = IVec2::new(15, 10);
+        let plain = plan(&grid, &agent, &cache, start.clone(), ahead, &config).unwrap();
+        agent.dynamics = Some(dynamics);
+        let dynamic = plan(&grid, &agent, &cache, start.clone(), ahead, &config).unwrap();
+        assert_eq!(plain.cost, dynamic.cost);
+
+        // Backing up is cheap without dynamics, but the stops make turning
+        // around worth it.
+        let behind = IVec2::new(7, 10);
+        agent.dynamics = None;
+        let plain = plan(&grid, &agent, &cache, start.clone(), behind, &config).unwrap();
+        agent.dynamics = Some(dynamics);
+        let dynamic = plan(&grid, &agent, &cache, start, behind, &config).unwrap();
+        assert!(reverse_moves(&plain.path) > 0);
+        assert!(reverse_moves(&dynamic.path) < reverse_moves(&plain.path));
+    }
+
+    #[test]
+    fn test_soft_costs() {
+        // People standing across the grid, save for a gap at the far end.
+        let mut grid = Grid::new(1.0, 10, 10);
+        for x in 0..9 {
+            grid.set_soft_cost(x, 4, 50_000);
+        }
+        let mut agent = point_agent(IVec2::new(1, 0));
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let start = Cell::new(2, agent.position);
+        let crosses = |grid: &Grid, result: &PlanResult| {
+            result
+                .path
+                .iter()
+                .any(|pose| grid.pose_soft_cost(&agent, pose) > 0)
+        };
+
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(1, 8),
+            &config,
+        )
+        .unwrap();
+        assert!(!crosses(&grid, &result));
+
+        // Closing the gap with a wall leaves crossing as the only way.
+        grid.set_cell(9, 4, true);
+        let result = plan(&grid, &agent, &cache, start, IVec2::new(1, 8), &config).unwrap();
+        assert!(crosses(&grid, &result));
+        assert!(result.cost > 50_000);
+    }
+
+    #[test]
+    fn test_keep_out_zone() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        // A keep-out wall across the grid, open at the right end.
+        grid.keep_out.push(polygon![
+            (x: 0.0, y: 4.2),
+            (x: 8.0, y: 4.2),
+            (x: 8.0, y: 4.8),
+            (x: 0.0, y: 4.8),
+        ]);
+        let mut agent = point_agent(IVec2::new(1, 1));
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let start = Cell::new(2, agent.position);
+
+        let result = plan(&grid, &agent, &cache, start, IVec2::new(1, 8), &config).unwrap();
+        assert!(result.path.iter().any(|pose| pose.position.x >= 8));
+        assert!(result
+            .path
+            .iter()
+            .all(|pose| !grid.violates_geofence(&agent, pose)));
+    }
+
+    #[test]
+    fn test_door_cost() {
+        // A wall across the grid with a closed door in the middle.
+        let mut grid = Grid::new(1.0, 10, 10);
+        for x in 0..10 {
+            grid.set_cell(x, 3, true);
+        }
+        grid.add_door(Door::new("gate", vec![IVec2::new(0, 3)]));
+        let mut agent = point_agent(IVec2::new(0, 0));
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        let cache = neighbor_cache();
+        let mut config = config(EscapeMode::Disabled);
+        let start = Cell::new(2, agent.position);
+        let goal = IVec2::new(0, 5);
+        assert!(plan(&grid, &agent, &cache, start.clone(), goal, &config).is_err());
+
+        let through = (
+            Cell::new(2, IVec2::new(0, 2)),
+            Cell::new(2, IVec2::new(0, 3)),
+        );
+        assert_eq!(
+            check_move(&grid, &agent, &through.0, &through.1, 1000, &config),
+            Err(MoveRejection::Blocked)
+        );
+
+        config.door_cost = Some(20_000);
+        let result = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert_eq!(result.cost, 25_000);
+        assert_eq!(
+            expand_move(&grid, &agent, &through.0, &through.1, 1000, &config),
+            Some(21_000)
+        );
+
+        grid.set_door_open("gate", true);
+        let result = plan(&grid, &agent, &cache, start, goal, &config).unwrap();
+        assert_eq!(result.cost, 5000);
+    }
+
+    #[test]
+    fn test_congestion_spreads_traffic() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.congestion = Some(CongestionMap::new(grid.size, 0.9, 10_000));
+        let mut agent = point_agent(IVec2::new(0, 0));
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let start = Cell::new(0, IVec2::new(0, 2));
+        let goal = IVec2::new(6, 2);
+
+        let first = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+        assert!(first.path.iter().all(|pose| pose.position.y == 2));
+        grid.congestion.as_mut().unwrap().record_path(&first.path);
+
+        let second = plan(&grid, &agent, &cache, start, goal, &config).unwrap();
+        assert!(second.path.iter().any(|pose| pose.position.y != 2));
+    }
+
+    #[test]
+    fn test_climb_cost() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        let mut agent = point_agent(IVec2::new(0, 0));
+        for x in 0..10 {
+            grid.set_height(x, 2, 3.0);
+        }
+        let from = Cell::new(0, IVec2::new(0, 1));
+        let to = Cell::new(0, IVec2::new(0, 2));
+        assert_eq!(climb_cost(&grid, &agent, &from, &to), Some(3000));
+        assert_eq!(climb_cost(&grid, &agent, &to, &from), Some(0));
+
+        agent.max_grade = Some(1.0);
+        assert_eq!(climb_cost(&grid, &agent, &from, &to), None);
+        assert_eq!(climb_cost(&grid, &agent, &to, &from), Some(0));
+
+        // the ridge is too steep to cross anywhere
+        agent.motion = MotionModel::Holonomic { heading_weight: 1 };
+        let cache = neighbor_cache();
+        let config = config(EscapeMode::Disabled);
+        let start = Cell::new(0, agent.position);
+        assert!(plan(&grid, &agent, &cache, start, IVec2::new(0, 5), &config).is_err());
+    }
+
+    #[test]
+    fn test_reroot_escape() {
+        let (grid, agent, cache) = boxed_in_setup();
+        let start = Cell::new(0, agent.position);
+        let result = plan(
+            &grid,
+            &agent,
+            &cache,
+            start.clone(),
+            IVec2::new(7, 7),
+            &config(EscapeMode::Reroot { max_radius: 3 }),
+        )
+        .expect("reroot should find a path");
+
+        let adjustment = result.start_adjustment.expect("start was blocked");
+        assert_eq!(adjustment.requested, start);
+        assert_eq!(adjustment.escape_steps, 0);
+        assert_eq!(result.path[0], adjustment.adjusted);
+        assert_eq!(
+            adjustment
+                .adjusted
+                .position
+                .as_vec2()
+                .distance(start.position.as_vec2()),
+            2.0
+        );
+    }
+
+    #[test]
+    fn test_bucket_open_list() {
+        let mut grid = Grid::new(1.0, 12, 12);
+        for y in 0..9 {
+            grid.set_cell(6, y, true);
+        }
+        let agent = point_agent(IVec2::new(0, 0));
+        let cache = neighbor_cache();
+        let start = Cell::new(0, IVec2::new(2, 2));
+        let goal = IVec2::new(10, 2);
+        for width in [1, 100] {
+            let mut config = config(EscapeMode::Disabled);
+            config.open_list = OpenListKind::Buckets { width };
+            let result = plan(&grid, &agent, &cache, start.clone(), goal, &config).unwrap();
+            assert_eq!(result.path.last().unwrap().position, goal);
+            assert!(result
+                .path
+                .iter()
+                .all(|pose| !grid.is_pose_blocked(&agent, pose)));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::cmp::Reverse;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+
+/// How [`Pursuit::retarget`] followed the target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PursuitUpdate {
+    /// The target hadn't moved.
+    Unchanged,
+    /// A new tail was planned from the pose at this index of the path.
+    Spliced { from: usize },
+    /// The target moved too far, or no tail fit, so the whole path was
+    /// replanned.
+    Replanned,
+}
+
+/// A path chasing a moving target, like a pallet on a conveyor. Small target
+/// moves only replan the end of the path.
+#[derive(Clone, Debug)]
+pub struct Pursuit {
+    /// Starts at the vehicle's pose, see [`Pursuit::advance`].
+    pub path: Vec<Cell>,
+    pub target: IVec2,
+    /// Largest target move, in cells along either axis, that is spliced
+    /// instead of replanned.
+    pub max_shift: i32,
+    /// Poses to back up from the pose closest to the new target before
+    /// splicing, leaving the tail room to turn.
+    pub backoff: usize,
+}
+
+impl Pursuit {
+    pub fn new(path: Vec<Cell>, target: IVec2, max_shift: i32, backoff: usize) -> Self {
+        Self {
+            path,
+            target,
+            max_shift,
+            backoff,
+        }
+    }
+
+    /// Drops the first `driven` poses the vehicle has already passed,
+    /// keeping at least the pose it's at.
+    pub fn advance(&mut self, driven: usize) {
+        let driven = driven.min(self.path.len().saturating_sub(1));
+        self.path.drain(..driven);
+    }
+
+    /// Index to splice the tail for `target` at: a few poses before the one
+    /// closest to it, preferring later poses on ties.
+    fn divergence(&self, target: IVec2) -> usize {
+        let closest = self
+            .path
+            .iter()
+            .enumerate()
+            .min_by_key(|(index, pose)| {
+                ((pose.position - target).length_squared(), Reverse(*index))
+            })
+            .map_or(0, |(index, _)| index);
+        closest.saturating_sub(self.backoff)
+    }
+
+    /// Follows the target to `target`. Returns `None`, leaving the path as
+    /// it was, if even a full replan finds no path.
+    pub fn retarget(
+        &mut self,
+        target: IVec2,
+        grid: &Grid,
+        agent: &Agent,
+        neighbor_cache: &NeighborCacheRef,
+        config: &PlannerConfig,
+    ) -> Option<PursuitUpdate> {
+        if target == self.target && !self.path.is_empty() {
+            return Some(PursuitUpdate::Unchanged);
+        }
+        let shift = (target - self.target).abs().max_element();
+        if shift <= self.max_shift && !self.path.is_empty() {
+            let from = self.divergence(target);
+            let splice = self.path[from].clone();
+            if let Ok(tail) = planner::plan(grid, agent, neighbor_cache, splice, target, config) {
+                self.path.truncate(from);
+                self.path.extend(tail.path);
+                self.target = target;
+                return Some(PursuitUpdate::Spliced { from });
+            }
+        }
+        let start = self.path.first()?.clone();
+        let result = planner::plan(grid, agent, neighbor_cache, start, target, config).ok()?;
+        self.path = result.path;
+        self.target = target;
+        Some(PursuitUpdate::Replanned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_splices_small_moves() {
+        let grid = Grid::new(1.0, 24, 12);
+        let (agent, cache, config) = Fixture::new(24, 12).at(IVec2::new(1, 5)).build();
+        let start = Cell::new(0, agent.position);
+        let target = IVec2::new(18, 5);
+        let path = planner::plan(&grid, &agent, &cache, start.clone(), target, &config)
+            .unwrap()
+            .path;
+        let mut pursuit = Pursuit::new(path.clone(), target, 3, 2);
+
+        let update = pursuit.retarget(target, &grid, &agent, &cache, &config);
+        assert_eq!(update, Some(PursuitUpdate::Unchanged));
+
+        // The target slides along; the path up to the splice stays put.
+        let moved = IVec2::new(19, 6);
+        let update = pursuit.retarget(moved, &grid, &agent, &cache, &config);
+        let Some(PursuitUpdate::Spliced { from }) = update else {
+            panic!("expected a splice, got {update:?}");
+        };
+        assert!(from > 0);
+        assert_eq!(pursuit.path[..from], path[..from]);
+        assert_eq!(pursuit.path.last().unwrap().position, moved);
+        assert!(pursuit
+            .path
+            .windows(2)
+            .all(|pair| !planner::is_move_blocked(&grid, &agent, &pair[0], &pair[1])));
+
+        // Far jumps replan from the vehicle.
+        pursuit.advance(3);
+        let vehicle = pursuit.path[0].clone();
+        let far = IVec2::new(10, 10);
+        let update = pursuit.retarget(far, &grid, &agent, &cache, &config);
+        assert_eq!(update, Some(PursuitUpdate::Replanned));
+        assert_eq!(pursuit.path[0], vehicle);
+        assert_eq!(pursuit.path.last().unwrap().position, far);
+    }
+}
@@ -0,0 +1,417 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::batch::PlanRequest;
+use crate::cell::NeighborCache;
+use crate::diagnostics::{self, PlanFailure};
+use crate::grid::Grid;
+use crate::metrics::{MetricsSink, PlanEvent, PlanStatus};
+use crate::planner::{PlanResult, PlannerConfig};
+
+pub type JobId = u64;
+
+/// A queued request, planned for vehicle `agent_id`.
+#[derive(Clone)]
+pub struct PlanJob {
+    pub id: JobId,
+    pub agent_id: u32,
+    pub request: PlanRequest,
+    /// Higher runs first.
+    pub priority: i32,
+    /// Jobs not started by then are dropped.
+    pub deadline: Option<Instant>,
+    /// The agent's generation when the job was submitted. Submitting a
+    /// newer job for the agent, or cancelling the agent, supersedes it.
+    pub generation: u64,
+}
+
+#[derive(Clone, Debug)]
+pub enum JobOutcome {
+    Planned(PlanResult),
+    Failed(PlanFailure),
+    /// Superseded by a newer job for the same agent, or cancelled by hand,
+    /// whether it was still waiting or already being planned.
+    Cancelled,
+    /// The deadline passed before a worker got to it.
+    Expired,
+}
+
+impl JobOutcome {
+    pub fn status(&self) -> PlanStatus {
+        match self {
+            JobOutcome::Planned(_) => PlanStatus::Planned,
+            JobOutcome::Failed(failure) => PlanStatus::Failed(failure.cause),
+            JobOutcome::Cancelled => PlanStatus::Cancelled,
+            JobOutcome::Expired => PlanStatus::Expired,
+        }
+    }
+
+    fn expanded(&self) -> usize {
+        match self {
+            JobOutcome::Planned(result) => result.expanded,
+            JobOutcome::Failed(failure) => failure.explored,
+            JobOutcome::Cancelled | JobOutcome::Expired => 0,
+        }
+    }
+}
+
+/// A job's id and agent, with how it ended.
+pub type JobReport = (JobId, u32, JobOutcome);
+
+/// What a worker is woken up for.
+enum Wake {
+    /// A job was submitted; take the next one in schedule order.
+    Job,
+    Stop,
+}
+
+/// Locks `mutex` even if a worker panicked holding it: every critical
+/// section leaves the queue consistent before anything can panic.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// State the queue shares with its workers.
+#[derive(Default)]
+struct Shared {
+    pending: Mutex<Vec<PlanJob>>,
+    /// Latest generation of every agent that submitted a job.
+    generations: Mutex<HashMap<u32, u64>>,
+    metrics: Mutex<Option<Arc<dyn MetricsSink>>>,
+}
+
+impl Shared {
+    fn record(&self, outcome: &JobOutcome, latency: Duration) {
+        if let Some(metrics) = lock(&self.metrics).as_ref() {
+            metrics.record(&PlanEvent {
+                status: outcome.status(),
+                latency,
+                expanded: outcome.expanded(),
+            });
+        }
+    }
+
+    fn is_current(&self, job: &PlanJob) -> bool {
+        lock(&self.generations).get(&job.agent_id) == Some(&job.generation)
+    }
+
+    /// Takes the first pending job in [`PlanQueue::scheduled`] order.
+    fn take_next(&self) -> Option<PlanJob> {
+        let mut pending = lock(&self.pending);
+        let index = (0..pending.len()).min_by_key(|&index| schedule_key(&pending[index]))?;
+        Some(pending.remove(index))
+    }
+}
+
+fn schedule_key(job: &PlanJob) -> (Reverse<i32>, bool, Option<Instant>, JobId) {
+    (
+        Reverse(job.priority),
+        job.deadline.is_none(),
+        job.deadline,
+        job.id,
+    )
+}
+
+/// Planning jobs for a fleet, run by priority on a pool of worker threads
+/// that lives as long as the queue. Jobs wait until [`PlanQueue::start`]
+/// spawns the workers, then each worker takes the next job as it frees up
+/// and sends back its outcome, collected with [`PlanQueue::outcomes`] or
+/// [`PlanQueue::recv_outcome`].
+///
+/// A new job for an agent cancels the one still waiting for it, and bumps
+/// the agent's generation: a job already being planned for an older
+/// generation finishes its search but reports [`JobOutcome::Cancelled`].
+pub struct PlanQueue {
+    shared: Arc<Shared>,
+    wake: Sender<Wake>,
+    woken: Arc<Mutex<Receiver<Wake>>>,
+    report: Sender<JobReport>,
+    reports: Receiver<JobReport>,
+    workers: Vec<JoinHandle<()>>,
+    grid: Arc<Mutex<Option<Arc<Grid>>>>,
+    next_id: JobId,
+}
+
+impl Default for PlanQueue {
+    fn default() -> Self {
+        let (wake, woken) = mpsc::channel();
+        let (report, reports) = mpsc::channel();
+        Self {
+            shared: Arc::default(),
+            wake,
+            woken: Arc::new(Mutex::new(woken)),
+            report,
+            reports,
+            workers: Vec::new(),
+            grid: Arc::default(),
+            next_id: 0,
+        }
+    }
+}
+
+impl PlanQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns `workers` threads planning on `grid`, unless they're already
+    /// running. Jobs submitted before are picked up right away.
+    pub fn start(
+        &mut self,
+        grid: Arc<Grid>,
+        neighbor_cache: &NeighborCache,
+        config: &PlannerConfig,
+        workers: usize,
+    ) {
+        if !self.workers.is_empty() {
+            return;
+        }
+        self.set_grid(grid);
+        for _ in 0..workers.max(1) {
+            let shared = self.shared.clone();
+            let woken = self.woken.clone();
+            let report = self.report.clone();
+            let grid = self.grid.clone();
+            let neighbor_cache = neighbor_cache.clone();
+            let config = config.clone();
+            self.workers.push(thread::spawn(move || {
+                let cache = Rc::new(RefCell::new(neighbor_cache));
+                // Holding the receiver's lock while waiting is fine: only
+                // one idle worker needs to be waiting on it at a time.
+                while let Ok(Wake::Job) = lock(&woken).recv() {
+                    let Some(job) = shared.take_next() else {
+                        // Cancelled before anyone got to it.
+                        continue;
+                    };
+                    let grid = lock(&grid).clone().expect("set before the workers start");
+                    let started = Instant::now();
+                    let outcome = if job.deadline.is_some_and(|deadline| deadline <= started) {
+                        JobOutcome::Expired
+                    } else {
+                        let planned = diagnostics::plan_diagnosed(
+                            &grid,
+                            &job.request.agent,
+                            &cache,
+                            job.request.start.clone(),
+                            job.request.goal.clone(),
+                            &config,
+                        );
+                        // Superseded while it was being planned.
+                        if !shared.is_current(&job) {
+                            JobOutcome::Cancelled
+                        } else {
+                            match planned {
+                                Ok(result) => JobOutcome::Planned(result),
+                                Err(failure) => JobOutcome::Failed(failure),
+                            }
+                        }
+                    };
+                    shared.record(&outcome, started.elapsed());
+                    if report.send((job.id, job.agent_id, outcome)).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+    }
+
+    /// Plans jobs started from now on over `grid`; jobs already being
+    /// planned keep the map they started with.
+    pub fn set_grid(&self, grid: Arc<Grid>) {
+        *lock(&self.grid) = Some(grid);
+    }
+
+    /// Cancels every waiting job and stops the workers once they finish
+    /// the jobs they're planning.
+    pub fn stop(&mut self) {
+        let waiting: Vec<JobId> = lock(&self.shared.pending)
+            .iter()
+            .map(|job| job.id)
+            .collect();
+        for id in waiting {
+            self.cancel(id);
+        }
+        // Workers skip the wake ups left for the cancelled jobs, then stop.
+        for _ in &self.workers {
+            // The receiver lives as long as the queue.
+            let _ = self.wake.send(Wake::Stop);
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+
+    /// Queues a request, cancelling any pending one for the same agent and
+    /// superseding any being planned.
+    pub fn submit(
+        &mut self,
+        agent_id: u32,
+        request: PlanRequest,
+        priority: i32,
+        deadline: Option<Instant>,
+    ) -> JobId {
+        self.cancel_agent(agent_id);
+        let generation = *lock(&self.shared.generations).entry(agent_id).or_default();
+        let id = self.next_id;
+        self.next_id += 1;
+        lock(&self.shared.pending).push(PlanJob {
+            id,
+            agent_id,
+            request,
+            priority,
+            deadline,
+            generation,
+        });
+        let _ = self.wake.send(Wake::Job);
+        id
+    }
+
+    /// Cancels a pending job, returning `false` if it isn't pending.
+    pub fn cancel(&mut self, id: JobId) -> bool {
+        let job = {
+            let mut pending = lock(&self.shared.pending);
+            let Some(index) = pending.iter().position(|job| job.id == id) else {
+                return false;
+            };
+            pending.remove(index)
+        };
+        self.shared.record(&JobOutcome::Cancelled, Duration::ZERO);
+        let _ = self
+            .report
+            .send((job.id, job.agent_id, JobOutcome::Cancelled));
+        true
+    }
+
+    /// Cancels the agent's pending job, and the one being planned for it,
+    /// if any. Returns `false` if it had no pending job.
+    pub fn cancel_agent(&mut self, agent_id: u32) -> bool {
+        *lock(&self.shared.generations).entry(agent_id).or_default() += 1;
+        let pending = lock(&self.shared.pending)
+            .iter()
+            .find(|job| job.agent_id == agent_id)
+            .map(|job| job.id);
+        pending.is_some_and(|id| self.cancel(id))
+    }
+
+    /// Reports every job's outcome to `metrics`.
+    pub fn set_metrics(&mut self, metrics: Option<Arc<dyn MetricsSink>>) {
+        *lock(&self.shared.metrics) = metrics;
+    }
+
+    /// Jobs waiting for a worker.
+    pub fn len(&self) -> usize {
+        lock(&self.shared.pending).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pending jobs in the order workers pick them up: by priority, then
+    /// earliest deadline, then submission.
+    pub fn scheduled(&self) -> Vec<PlanJob> {
+        let mut jobs = lock(&self.shared.pending).clone();
+        jobs.sort_by_key(schedule_key);
+        jobs
+    }
+
+    /// Outcomes reported since the last call, in the order they finished,
+    /// without waiting.
+    pub fn outcomes(&self) -> Vec<JobReport> {
+        self.reports.try_iter().collect()
+    }
+
+    /// Waits up to `timeout` for the next outcome.
+    pub fn recv_outcome(&self, timeout: Duration) -> Option<JobReport> {
+        self.reports.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for PlanQueue {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+    use crate::cell::Cell;
+    use crate::metrics::PlannerMetrics;
+    use crate::test_support::{point_agent, Fixture, MAX_INCREMENTS};
+
+    fn request(goal: IVec2) -> PlanRequest {
+        let agent = point_agent(IVec2::new(2, 2));
+        PlanRequest {
+            start: Cell::new(0, agent.position),
+            agent,
+            goal: goal.into(),
+        }
+    }
+
+    #[test]
+    fn test_priorities_and_superseding() {
+        let mut queue = PlanQueue::new();
+        let metrics = Arc::new(PlannerMetrics::new());
+        queue.set_metrics(Some(metrics.clone()));
+        let low = queue.submit(1, request(IVec2::new(10, 2)), 0, None);
+        let stale = queue.submit(2, request(IVec2::new(2, 10)), 5, None);
+        let urgent = queue.submit(3, request(IVec2::new(10, 10)), 9, None);
+        let fresh = queue.submit(2, request(IVec2::new(8, 8)), 5, None);
+        let expired = queue.submit(4, request(IVec2::new(5, 5)), 5, Some(Instant::now()));
+        assert_eq!(queue.len(), 4);
+        let order: Vec<JobId> = queue.scheduled().iter().map(|job| job.id).collect();
+        assert_eq!(order, [urgent, expired, fresh, low]);
+
+        let grid = Arc::new(Grid::new(1.0, 16, 16));
+        let cache = NeighborCache::new_precomputed(MAX_INCREMENTS, 1);
+        let config = Fixture::new(16, 16).config();
+        // The stale job was reported when it was superseded.
+        let mut outcomes = queue.outcomes();
+        // A single worker plans in schedule order.
+        queue.start(grid, &cache, &config, 1);
+        while outcomes.len() < 5 {
+            outcomes.push(queue.recv_outcome(Duration::from_secs(30)).unwrap());
+        }
+        assert!(queue.is_empty());
+        let ids: Vec<JobId> = outcomes.iter().map(|(id, _, _)| *id).collect();
+        assert_eq!(ids, [stale, urgent, expired, fresh, low]);
+        assert!(matches!(outcomes[0], (_, 2, JobOutcome::Cancelled)));
+        assert!(matches!(outcomes[2].2, JobOutcome::Expired));
+        let JobOutcome::Planned(result) = &outcomes[3].2 else {
+            panic!("the fresh job should be planned");
+        };
+        assert_eq!(result.path.last().unwrap().position, IVec2::new(8, 8));
+        assert!(!queue.cancel(low));
+        assert_eq!(metrics.total(PlanStatus::Planned), 3);
+        assert_eq!(metrics.total(PlanStatus::Cancelled), 1);
+        assert_eq!(metrics.total(PlanStatus::Expired), 1);
+    }
+
+    #[test]
+    fn test_supersedes_running_jobs() {
+        let mut queue = PlanQueue::new();
+        let running = queue.submit(7, request(IVec2::new(10, 2)), 0, None);
+        // Taken by a worker, as if its search were under way.
+        let job = queue.shared.take_next().unwrap();
+        assert_eq!(job.id, running);
+        assert!(queue.shared.is_current(&job));
+        let newer = queue.submit(7, request(IVec2::new(2, 10)), 0, None);
+        assert!(!queue.shared.is_current(&job));
+        assert!(queue.shared.is_current(&queue.scheduled()[0]));
+
+        // Stopping cancels what's still waiting.
+        queue.stop();
+        assert!(queue.is_empty());
+        let outcomes = queue.outcomes();
+        assert!(matches!(outcomes[..], [(id, 7, JobOutcome::Cancelled)] if id == newer));
+    }
+}
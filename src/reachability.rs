@@ -0,0 +1,105 @@
+use notan::math::IVec2;
+
+use crate::grid::Grid;
+
+/// Labels connected regions of free cells so reachability between two
+/// cells can be answered without searching.
+///
+/// Cells are 8-connected, matching the diagonal moves of the planner, so
+/// this is a necessary (not sufficient) condition for a path to exist:
+/// the footprint may still not fit through a connection.
+#[derive(Clone, Debug)]
+pub struct Components {
+    size: (i32, i32),
+    /// Component label per cell, `0` for blocked cells.
+    labels: Vec<u32>,
+    count: u32,
+}
+
+impl Components {
+    pub fn compute(grid: &Grid) -> Self {
+        let mut labels = vec![0; (grid.size.0 * grid.size.1) as usize];
+        let mut count = 0;
+        let mut stack = Vec::new();
+
+        for y in 0..grid.size.1 {
+            for x in 0..grid.size.0 {
+                if labels[grid.index(x, y)] != 0 || grid.is_cell_blocked(x, y) {
+                    continue;
+                }
+                count += 1;
+                labels[grid.index(x, y)] = count;
+                stack.push(IVec2::new(x, y));
+
+                while let Some(cell) = stack.pop() {
+                    for dy in -1..=1 {
+                        for dx in -1..=1 {
+                            let next = cell + IVec2::new(dx, dy);
+                            if grid.is_cell_blocked(next.x, next.y) {
+                                continue;
+                            }
+                            let index = grid.index(next.x, next.y);
+                            if labels[index] == 0 {
+                                labels[index] = count;
+                                stack.push(next);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            size: grid.size,
+            labels,
+            count,
+        }
+    }
+
+    /// Label of the component containing `position`, `0` if blocked or
+    /// outside the grid.
+    pub fn label(&self, position: IVec2) -> u32 {
+        if position.x < 0
+            || position.x >= self.size.0
+            || position.y < 0
+            || position.y >= self.size.1
+        {
+            return 0;
+        }
+        self.labels[(position.y * self.size.0 + position.x) as usize]
+    }
+
+    pub fn is_reachable(&self, start: IVec2, goal: IVec2) -> bool {
+        let label = self.label(start);
+        label != 0 && label == self.label(goal)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_components() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        for y in 0..10 {
+            grid.toggle_cell(5, y);
+        }
+        let components = Components::compute(&grid);
+        assert_eq!(components.count(), 2);
+        assert!(components.is_reachable(IVec2::new(0, 0), IVec2::new(4, 9)));
+        assert!(!components.is_reachable(IVec2::new(0, 0), IVec2::new(6, 0)));
+        assert!(!components.is_reachable(IVec2::new(5, 0), IVec2::new(5, 0)));
+        assert!(!components.is_reachable(IVec2::new(0, 0), IVec2::new(-1, 0)));
+
+        // a diagonal gap still connects the two halves
+        grid.toggle_cell(5, 4);
+        let components = Components::compute(&grid);
+        assert_eq!(components.count(), 1);
+        assert!(components.is_reachable(IVec2::new(0, 0), IVec2::new(9, 9)));
+    }
+}
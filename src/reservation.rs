@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::forecast::Forecast;
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::pathfind::optimized_astar;
+use crate::planner::{self, Candidates, PlanResult, PlannerConfig};
+use crate::units::MetricPath;
+
+/// Cost of standing still for one tick, the same as a straight move.
+pub const WAIT_COST: u32 = 1000;
+
+/// Cells claimed by already planned paths over half-open `[start, end)`
+/// tick intervals, for cooperative planning of several vehicles, plus any
+/// forecast of scheduled obstacles.
+#[derive(Clone, Debug, Default)]
+pub struct ReservationTable {
+    slots: HashMap<IVec2, Vec<(u32, u32)>>,
+    forecast: Option<Forecast>,
+}
+
+impl ReservationTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reserve(&mut self, cell: IVec2, start: u32, end: u32) {
+        self.slots.entry(cell).or_default().push((start, end));
+    }
+
+    /// Treats cells the forecast blocks as reserved at those times.
+    pub fn set_forecast(&mut self, forecast: Option<Forecast>) {
+        self.forecast = forecast;
+    }
+
+    pub fn forecast(&self) -> Option<&Forecast> {
+        self.forecast.as_ref()
+    }
+
+    pub fn is_reserved(&self, cell: IVec2, time: u32) -> bool {
+        let claimed = self.slots.get(&cell).is_some_and(|intervals| {
+            intervals
+                .iter()
+                .any(|&(start, end)| start <= time && time < end)
+        });
+        claimed
+            || self
+                .forecast
+                .as_ref()
+                .is_some_and(|forecast| forecast.is_blocked(cell, time))
+    }
+
+    /// Checks the pose's cell and footprint at `time`.
+    pub fn is_pose_reserved(&self, agent: &Agent, pose: &Cell, time: u32) -> bool {
+        footprint_cells(agent, pose).any(|cell| self.is_reserved(cell, time))
+    }
+
+    /// Whether any of the pose's cells is reserved at some tick in
+    /// `[start, end)`.
+    pub fn is_pose_reserved_during(
+        &self,
+        agent: &Agent,
+        pose: &Cell,
+        start: u32,
+        end: u32,
+    ) -> bool {
+        (start..end).any(|time| self.is_pose_reserved(agent, pose, time))
+    }
+
+    /// Whether moving from `from` at `time` to `to` a tick later would swap
+    /// places with a reserved vehicle: `to` is taken while `from` is still
+    /// held, and `from` is taken once `to` is entered. Both checks pass on
+    /// their own, since neither pose is reserved at the tick it's in.
+    pub fn is_swap(&self, agent: &Agent, from: &Cell, to: &Cell, time: u32) -> bool {
+        self.is_pose_reserved(agent, to, time) && self.is_pose_reserved(agent, from, time + 1)
+    }
+
+    /// Whether a multi-cell move from `from` at `time` to `to` a tick later
+    /// passes over cells reserved at either tick on the way.
+    pub fn is_sweep_reserved(&self, agent: &Agent, from: &Cell, to: &Cell, time: u32) -> bool {
+        passing_poses(from, to)
+            .any(|pose| self.is_pose_reserved_during(agent, &pose, time, time + 2))
+    }
+
+    /// Claims every pose of `path`, one tick per pose from `start_time`.
+    /// The final pose stays claimed for `hold` more ticks. The cells a
+    /// multi-cell move passes over are claimed for both ticks it spans.
+    pub fn reserve_path(&mut self, agent: &Agent, path: &[Cell], start_time: u32, hold: u32) {
+        for (i, pose) in path.iter().enumerate() {
+            let time = start_time + i as u32;
+            let end = if i + 1 == path.len() {
+                time + 1 + hold
+            } else {
+                time + 1
+            };
+            for cell in footprint_cells(agent, pose) {
+                self.reserve(cell, time, end);
+            }
+            if let Some(previous) = i.checked_sub(1).map(|previous| &path[previous]) {
+                self.reserve_sweep(agent, previous, pose, time - 1, time + 1);
+            }
+        }
+    }
+
+    /// Claims the poses of `path` for as long as `metric`, its timed
+    /// version from [`crate::units::to_metric`], says the vehicle takes to
+    /// drive through them, so slow zones and turns hold cells longer than a
+    /// tick. Each pose is claimed from the tick it's reached until the tick
+    /// the next one is, and the final pose for `hold` more ticks.
+    pub fn reserve_timed(
+        &mut self,
+        agent: &Agent,
+        path: &[Cell],
+        metric: &MetricPath,
+        seconds_per_tick: f32,
+        start_time: u32,
+        hold: u32,
+    ) {
+        let tick = |seconds: f32| start_time + (seconds / seconds_per_tick).floor() as u32;
+        for (i, pose) in path.iter().enumerate() {
+            let time = tick(metric.poses[i].time);
+            let end = match metric.poses.get(i + 1) {
+                Some(next) => start_time + (next.time / seconds_per_tick).ceil() as u32,
+                None => time + 1 + hold,
+            };
+            for cell in footprint_cells(agent, pose) {
+                self.reserve(cell, time, end.max(time + 1));
+            }
+            if let Some(previous) = i.checked_sub(1) {
+                let left = tick(metric.poses[previous].time);
+                self.reserve_sweep(agent, &path[previous], pose, left, time + 1);
+            }
+        }
+    }
+
+    /// Claims the cells a move from `from` to `to` passes over, over
+    /// `[start, end)`.
+    fn reserve_sweep(&mut self, agent: &Agent, from: &Cell, to: &Cell, start: u32, end: u32) {
+        for pose in passing_poses(from, to) {
+            for cell in footprint_cells(agent, &pose) {
+                self.reserve(cell, start, end);
+            }
+        }
+    }
+
+    /// Drops every claimed interval, keeping the forecast.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+}
+
+fn footprint_cells<'a>(agent: &'a Agent, pose: &'a Cell) -> impl Iterator<Item = IVec2> + 'a {
+    agent
+        .rotation_footprint(pose.rotation)
+        .iter()
+        .map(|cell| *cell + pose.position)
+        .chain(std::iter::once(pose.position))
+}
+
+/// The poses between `from` and `to` that a multi-cell move passes over,
+/// stepped like [`planner::is_move_blocked`] steps them.
+fn passing_poses(from: &Cell, to: &Cell) -> impl Iterator<Item = Cell> {
+    let steps = from.steps_to(to);
+    let step = (to.position - from.position) / steps;
+    let (position, rotation) = (from.position, to.rotation);
+    (1..steps).map(move |i| Cell::new(rotation, position + step * i))
+}
+
+/// Plans through space and time, treating reservations as moving
+/// obstacles. Every move and every wait takes one tick; the path holds one
+/// pose per tick from `start_time`, and a multi-cell move needs the cells
+/// it passes over free at both ticks it spans. The search gives up past
+/// `horizon`. Goal poses are only accepted if nothing else claims them for the rest of
+/// the horizon, since the vehicle stays parked there.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_reserved(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    table: &ReservationTable,
+    start: Cell,
+    start_time: u32,
+    goal: impl Into<Goal>,
+    horizon: u32,
+    config: &PlannerConfig,
+) -> Option<PlanResult> {
+    let goal = goal.into();
+    let costs = planner::cost_cache(neighbor_cache, config);
+    let expanded = std::cell::Cell::new(0);
+    let (path, cost) = optimized_astar(
+        (start, start_time),
+        config.max_states,
+        |(action, time)| {
+            expanded.set(expanded.get() + 1);
+            let next = time + 1;
+            if next > start_time + horizon {
+                return Vec::new();
+            }
+            let mut result = Vec::new();
+            let mut candidates = Candidates::new();
+            planner::motion_candidates(
+                agent,
+                neighbor_cache,
+                &costs,
+                action,
+                config,
+                &mut candidates,
+            );
+            for (neigh, cost) in candidates {
+                if table.is_pose_reserved(agent, &neigh, next)
+                    || table.is_swap(agent, action, &neigh, *time)
+                    || table.is_sweep_reserved(agent, action, &neigh, *time)
+                {
+                    continue;
+                }
+                if let Some(cost) = planner::expand_move(grid, agent, action, &neigh, cost, config)
+                {
+                    result.push(((neigh, next), cost));
+                }
+            }
+            if !table.is_pose_reserved(agent, action, next) {
+                result.push(((action.clone(), next), WAIT_COST));
+            }
+            result
+        },
+        |(action, _)| action.heuristic(goal.nearest_point(action.position), config.max_increments),
+        |(action, time)| {
+            goal.accepts(agent, action)
+                && !table.is_pose_reserved_during(agent, action, time + 1, start_time + horizon + 1)
+        },
+    )?;
+
+    Some(PlanResult {
+        path: path.into_iter().map(|(pose, _)| pose).collect(),
+        cost,
+        start_adjustment: None,
+        suboptimality_bound: None,
+        expanded: expanded.get(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::cell::NeighborCache;
+    use crate::door::Door;
+    use crate::test_support::{point_agent, Fixture, MAX_INCREMENTS};
+    use crate::units::{to_metric, VelocityLimits};
+
+    #[test]
+    fn test_reservation_intervals() {
+        let mut table = ReservationTable::new();
+        table.reserve(IVec2::new(1, 1), 2, 4);
+        assert!(!table.is_reserved(IVec2::new(1, 1), 1));
+        assert!(table.is_reserved(IVec2::new(1, 1), 2));
+        assert!(table.is_reserved(IVec2::new(1, 1), 3));
+        assert!(!table.is_reserved(IVec2::new(1, 1), 4));
+        assert!(!table.is_reserved(IVec2::new(0, 1), 2));
+    }
+
+    #[test]
+    fn test_timed_reservations_hold_slow_zones() {
+        let mut grid = Grid::new(1.0, 10, 3);
+        grid.set_speed_limit(2, 0, 0.25);
+        let agent = point_agent(IVec2::ZERO);
+        let path: Vec<Cell> = (0..4).map(|x| Cell::new(0, IVec2::new(x, 0))).collect();
+        let result = PlanResult {
+            path: path.clone(),
+            cost: 3000,
+            start_adjustment: None,
+            suboptimality_bound: None,
+            expanded: 0,
+        };
+        let limits = VelocityLimits {
+            max_speed: 1.0,
+            max_turn_rate: 1.0,
+        };
+        let metric = to_metric(&grid, &agent, &result, limits);
+
+        let mut table = ReservationTable::new();
+        table.reserve_timed(&agent, &path, &metric, 1.0, 10, 2);
+        // Crawling into and out of cell 2 takes four seconds each way.
+        assert!((11..15).all(|time| table.is_reserved(IVec2::new(1, 0), time)));
+        assert!((15..19).all(|time| table.is_reserved(IVec2::new(2, 0), time)));
+        assert!(!table.is_reserved(IVec2::new(2, 0), 19));
+        assert!(!table.is_reserved(IVec2::new(3, 0), 18));
+        assert!((19..22).all(|time| table.is_reserved(IVec2::new(3, 0), time)));
+        assert!(!table.is_reserved(IVec2::new(3, 0), 22));
+    }
+
+    #[test]
+    fn test_waits_for_reserved_cell() {
+        // A single-lane corridor along the top row.
+        let mut grid = Grid::new(1.0, 10, 3);
+        for x in 0..10 {
+            grid.set_cell(x, 1, true);
+        }
+        let (agent, cache, config) = Fixture::new(10, 3).holonomic(1).build();
+
+        let mut table = ReservationTable::new();
+        table.reserve(IVec2::new(3, 0), 0, 6);
+        let start = Cell::new(0, agent.position);
+        let result = plan_reserved(
+            &grid,
+            &agent,
+            &cache,
+            &table,
+            start,
+            0,
+            IVec2::new(5, 0),
+            20,
+            &config,
+        )
+        .unwrap();
+
+        let arrival = result
+            .path
+            .iter()
+            .position(|pose| pose.position == IVec2::new(3, 0))
+            .unwrap();
+        assert!(arrival >= 6);
+        assert_eq!(result.path.last().unwrap().position, IVec2::new(5, 0));
+
+        // The same path, claimed by the next vehicle's plan.
+        let end = result.path.len() as u32;
+        table.reserve_path(&agent, &result.path, 0, 2);
+        assert!(table.is_reserved(IVec2::new(5, 0), end + 1));
+        assert!(!table.is_reserved(IVec2::new(5, 0), end + 2));
+    }
+
+    #[test]
+    fn test_avoids_swaps_and_reserved_goals() {
+        let (agent, cache, config) = Fixture::new(10, 3).holonomic(1).build();
+        let plan = |grid: &Grid, table: &ReservationTable, goal: IVec2| {
+            let start = Cell::new(0, agent.position);
+            plan_reserved(grid, &agent, &cache, table, start, 0, goal, 20, &config)
+        };
+
+        // Two cells, with another vehicle moving from the second into the
+        // first: the only way through is swapping places with it.
+        let lane = Grid::new(1.0, 2, 1);
+        let mut table = ReservationTable::new();
+        table.reserve(IVec2::new(1, 0), 0, 1);
+        table.reserve(IVec2::new(0, 0), 1, 3);
+        assert!(table.is_swap(
+            &agent,
+            &Cell::new(0, IVec2::new(0, 0)),
+            &Cell::new(0, IVec2::new(1, 0)),
+            0
+        ));
+        assert!(plan(&lane, &table, IVec2::new(1, 0)).is_none());
+        assert!(plan(&lane, &ReservationTable::new(), IVec2::new(1, 0)).is_some());
+
+        // The goal is free when first reached, but claimed from tick 10.
+        let grid = Grid::new(1.0, 10, 3);
+        let mut table = ReservationTable::new();
+        table.reserve(IVec2::new(5, 0), 10, 12);
+        let result = plan(&grid, &table, IVec2::new(5, 0)).unwrap();
+        assert!(result.path.len() > 12);
+        assert_eq!(result.path.last().unwrap().position, IVec2::new(5, 0));
+    }
+
+    #[test]
+    fn test_multi_cell_moves_claim_swept_cells() {
+        let grid = Grid::new(1.0, 10, 3);
+        let agent = point_agent(IVec2::ZERO);
+        let mut cache = NeighborCache::new_precomputed(MAX_INCREMENTS, 1);
+        cache.add_straight_primitives(4);
+        let cache = Rc::new(RefCell::new(cache));
+        let config = Fixture::new(10, 3).config();
+
+        // One tick from x = 1 to x = 4 passes over x = 2 and 3.
+        let path = [
+            Cell::new(0, IVec2::new(1, 0)),
+            Cell::new(0, IVec2::new(4, 0)),
+        ];
+        let mut table = ReservationTable::new();
+        table.reserve_path(&agent, &path, 5, 0);
+        assert!((5..7).all(|time| table.is_reserved(IVec2::new(2, 0), time)));
+        assert!((5..7).all(|time| table.is_reserved(IVec2::new(3, 0), time)));
+        assert!(!table.is_reserved(IVec2::new(3, 0), 7));
+        assert!(!table.is_reserved(IVec2::new(4, 0), 5));
+
+        // A vehicle parked at x = 3 for a while rules out jumping over it
+        // until it's gone.
+        let mut table = ReservationTable::new();
+        table.reserve(IVec2::new(3, 1), 0, 6);
+        let jump = (
+            Cell::new(0, IVec2::new(0, 1)),
+            Cell::new(0, IVec2::new(4, 1)),
+        );
+        assert!(table.is_sweep_reserved(&agent, &jump.0, &jump.1, 5));
+        assert!(!table.is_sweep_reserved(&agent, &jump.0, &jump.1, 6));
+        let start = Cell::new(0, IVec2::new(0, 1));
+        let result = plan_reserved(
+            &grid,
+            &agent,
+            &cache,
+            &table,
+            start,
+            0,
+            IVec2::new(8, 1),
+            20,
+            &config,
+        )
+        .unwrap();
+        for (time, pair) in result.path.windows(2).enumerate() {
+            assert!(!table.is_sweep_reserved(&agent, &pair[0], &pair[1], time as u32));
+            assert!(!table.is_pose_reserved(&agent, &pair[1], time as u32 + 1));
+        }
+        assert_eq!(result.path.last().unwrap().position, IVec2::new(8, 1));
+    }
+
+    #[test]
+    fn test_passes_closed_doors_at_door_cost() {
+        // A wall across the grid with a closed door at its end.
+        let mut grid = Grid::new(1.0, 6, 6);
+        for x in 0..6 {
+            grid.set_cell(x, 2, true);
+        }
+        grid.add_door(Door::new("gate", vec![IVec2::new(0, 2)]));
+        let (agent, cache, mut config) = Fixture::new(6, 6).holonomic(1).build();
+        let table = ReservationTable::new();
+        let plan = |config: &PlannerConfig| {
+            let start = Cell::new(2, agent.position);
+            plan_reserved(
+                &grid,
+                &agent,
+                &cache,
+                &table,
+                start,
+                0,
+                IVec2::new(0, 4),
+                20,
+                config,
+            )
+        };
+        assert!(plan(&config).is_none());
+        config.door_cost = Some(20_000);
+        assert_eq!(plan(&config).unwrap().cost, 24_000);
+    }
+
+    #[test]
+    fn test_waits_for_forecast_to_clear() {
+        let mut grid = Grid::new(1.0, 10, 3);
+        for x in 0..10 {
+            grid.set_cell(x, 1, true);
+        }
+        let (agent, cache, config) = Fixture::new(10, 3).holonomic(1).build();
+
+        // A crane parked over the lane, moving off between ticks 4 and 8.
+        let mut forecast = Forecast::new(grid.size, 0, 4);
+        forecast.push_cells(&[IVec2::new(3, 0)]);
+        forecast.push_cells(&[IVec2::new(3, 0)]);
+        forecast.push_cells(&[]);
+        let mut table = ReservationTable::new();
+        table.set_forecast(Some(forecast));
+        let start = Cell::new(0, agent.position);
+        let result = plan_reserved(
+            &grid,
+            &agent,
+            &cache,
+            &table,
+            start,
+            0,
+            IVec2::new(5, 0),
+            20,
+            &config,
+        )
+        .unwrap();
+
+        let arrival = result
+            .path
+            .iter()
+            .position(|pose| pose.position == IVec2::new(3, 0))
+            .unwrap();
+        // Half cleared by tick 6, before the last snapshot shows it gone.
+        assert!((6..8).contains(&arrival));
+    }
+}
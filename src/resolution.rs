@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::planner::{self, PlanResult, PlannerConfig};
+
+/// The coarse cell containing a fine cell, for a grid downsampled by `factor`.
+pub fn fine_to_coarse(position: IVec2, factor: i32) -> IVec2 {
+    IVec2::new(position.x.div_euclid(factor), position.y.div_euclid(factor))
+}
+
+/// The fine cell at the center of a coarse cell.
+pub fn coarse_to_fine(position: IVec2, factor: i32) -> IVec2 {
+    position * factor + IVec2::splat(factor / 2)
+}
+
+/// Plans on a copy of `grid` downsampled by `factor`, for fast long-distance
+/// queries. The agent shrinks by the same factor, and the returned path is
+/// mapped back to fine cells at the centers of the coarse ones it visits.
+pub fn plan_coarse(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: IVec2,
+    factor: i32,
+    config: &PlannerConfig,
+) -> Option<PlanResult> {
+    let coarse_grid = grid.downsample(factor);
+    let mut coarse_agent = Agent::new(
+        fine_to_coarse(agent.position, factor),
+        agent.size / factor as f32,
+        agent.rotation,
+        agent.max_increments,
+    );
+    coarse_agent.motion = agent.motion;
+    coarse_agent.turn_in_place_cost = agent.turn_in_place_cost;
+    let mut coarse_config = config.clone();
+    coarse_config.max_states = config.max_states / (factor * factor) as usize;
+
+    let coarse_start = Cell::new(start.rotation, fine_to_coarse(start.position, factor));
+    let mut result = planner::plan(
+        &coarse_grid,
+        &coarse_agent,
+        neighbor_cache,
+        coarse_start,
+        fine_to_coarse(goal, factor),
+        &coarse_config,
+    )
+    .ok()?;
+    for pose in &mut result.path {
+        pose.position = coarse_to_fine(pose.position, factor);
+    }
+    Some(result)
+}
+
+/// Plans coarse first, then plans on the full grid charging
+/// `off_corridor_cost` for every pose outside the coarse path (grown by one
+/// coarse cell), which keeps the fine search close to the coarse route.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_refined(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: IVec2,
+    factor: i32,
+    off_corridor_cost: u32,
+    config: &PlannerConfig,
+) -> Option<PlanResult> {
+    let coarse = plan_coarse(
+        grid,
+        agent,
+        neighbor_cache,
+        start.clone(),
+        goal,
+        factor,
+        config,
+    )?;
+    let mut corridor = HashSet::new();
+    for pose in &coarse.path {
+        let center = fine_to_coarse(pose.position, factor);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                corridor.insert(center + IVec2::new(dx, dy));
+            }
+        }
+    }
+    planner::plan_with_extra_cost(grid, agent, neighbor_cache, start, goal, config, |pose| {
+        if corridor.contains(&fine_to_coarse(pose.position, factor)) {
+            0
+        } else {
+            off_corridor_cost
+        }
+    })
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(fine_to_coarse(IVec2::new(5, -1), 4), IVec2::new(1, -1));
+        assert_eq!(coarse_to_fine(IVec2::new(1, 2), 4), IVec2::new(6, 10));
+        assert_eq!(
+            fine_to_coarse(coarse_to_fine(IVec2::new(3, 7), 2), 2),
+            IVec2::new(3, 7)
+        );
+    }
+
+    #[test]
+    fn test_coarse_then_refined() {
+        let mut grid = Grid::new(1.0, 20, 20);
+        for y in 0..14 {
+            grid.set_cell(9, y, true);
+        }
+        let (agent, cache, config) = Fixture::new(20, 20).at(IVec2::new(2, 2)).build();
+        let start = Cell::new(0, agent.position);
+        let goal = IVec2::new(16, 2);
+
+        let coarse = plan_coarse(&grid, &agent, &cache, start.clone(), goal, 2, &config).unwrap();
+        assert_eq!(coarse.path.last().unwrap().position, IVec2::new(17, 3));
+        // The wall merges into coarse column 4, so the route goes below it.
+        assert!(coarse.path.iter().any(|pose| pose.position.y >= 14));
+
+        let refined = plan_refined(&grid, &agent, &cache, start, goal, 2, 10_000, &config).unwrap();
+        assert_eq!(refined.path.last().unwrap().position, goal);
+        assert!(refined
+            .path
+            .iter()
+            .all(|pose| !grid.is_pose_blocked(&agent, pose)));
+    }
+}
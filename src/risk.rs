@@ -0,0 +1,278 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlanResult, PlannerConfig};
+
+/// Risk weights tried in turn, from ignoring risk to avoiding it at almost
+/// any cost.
+const RISK_WEIGHTS: [u32; 7] = [0, 1, 4, 16, 64, 256, 1024];
+
+/// Cap on the risk charged for a single pose. A few thousand capped poses
+/// still saturate the path cost at `u32::MAX`, where the search gives up
+/// like it does out of states.
+const MAX_POSE_RISK_COST: u32 = 1_000_000;
+
+#[derive(Clone, Debug)]
+pub struct RiskPlan {
+    pub result: PlanResult,
+    /// Chance of hitting an occupied cell somewhere along the path.
+    pub collision_probability: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RiskError {
+    NoPath,
+    /// Every path found was riskier than allowed; holds the safest one's
+    /// collision probability.
+    TooRisky(f32),
+}
+
+/// Cells the agent covers at `pose`.
+fn pose_cells<'a>(agent: &'a Agent, pose: &Cell) -> impl Iterator<Item = IVec2> + 'a {
+    let position = pose.position;
+    agent
+        .rotation_footprint(pose.rotation)
+        .iter()
+        .map(move |offset| *offset + position)
+        .chain(std::iter::once(position))
+}
+
+/// Chance of a collision at `pose`, treating cells as independent.
+pub fn pose_collision_probability(grid: &Grid, agent: &Agent, pose: &Cell) -> f32 {
+    let cells: HashSet<IVec2> = pose_cells(agent, pose).collect();
+    collision_probability(grid, &cells)
+}
+
+/// Chance of a collision anywhere along `path`. Each cell the footprint
+/// sweeps counts once, as driving over it again meets the same obstacle.
+pub fn path_collision_probability(grid: &Grid, agent: &Agent, path: &[Cell]) -> f32 {
+    let cells: HashSet<IVec2> = path
+        .iter()
+        .flat_map(|pose| pose_cells(agent, pose))
+        .collect();
+    collision_probability(grid, &cells)
+}
+
+fn collision_probability(grid: &Grid, cells: &HashSet<IVec2>) -> f32 {
+    let free: f32 = cells
+        .iter()
+        .map(|cell| 1.0 - grid.occupancy_at(cell.x, cell.y))
+        .product();
+    1.0 - free
+}
+
+/// Risk charged for moving into `pose` at `weight`: `-ln(1 - p)` in cost
+/// units, capped at [`MAX_POSE_RISK_COST`].
+fn risk_cost(grid: &Grid, agent: &Agent, pose: &Cell, weight: u32) -> u32 {
+    if weight == 0 {
+        return 0;
+    }
+    let probability = pose_collision_probability(grid, agent, pose);
+    let risk = -(1.0 - probability).ln() * 1000.0 * weight as f32;
+    risk.min(MAX_POSE_RISK_COST as f32) as u32
+}
+
+/// Plans to `goal` with a collision probability of at most
+/// `max_probability`, on top of the grid's blocked cells.
+///
+/// Each pose is charged its risk, `-ln(1 - p)`, times a weight that grows
+/// until the path is safe enough, so the cheapest path that meets the bound
+/// at the first weight that works is returned, with the risk charges taken
+/// back out of its cost. Overlapping footprints are charged per pose during
+/// the search, which only makes it more cautious.
+///
+/// Heavier weights can run a search out of `max_states` (see
+/// [`crate::planner::SearchAlgorithm::IterativeDeepening`]) where a lighter
+/// one found a path, and so can risk charges saturating the path cost; the
+/// weights stop there, and the error reports the safest path found so far.
+pub fn plan_chance_constrained(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: Cell,
+    goal: impl Into<Goal>,
+    config: &PlannerConfig,
+    max_probability: f32,
+) -> Result<RiskPlan, RiskError> {
+    let goal = goal.into();
+    if grid.occupancy.is_none() {
+        let result = planner::plan(grid, agent, neighbor_cache, start, goal, config)
+            .map_err(|_| RiskError::NoPath)?;
+        return Ok(RiskPlan {
+            result,
+            collision_probability: 0.0,
+        });
+    }
+
+    let mut safest: Option<f32> = None;
+    for weight in RISK_WEIGHTS {
+        let extra_cost = |pose: &Cell| risk_cost(grid, agent, pose, weight);
+        let Ok(mut result) = planner::plan_with_extra_cost(
+            grid,
+            agent,
+            neighbor_cache,
+            start.clone(),
+            goal.clone(),
+            config,
+            extra_cost,
+        ) else {
+            // Out of states; lighter weights already did what they could.
+            break;
+        };
+        let charged = result
+            .path
+            .iter()
+            .skip(1)
+            .map(extra_cost)
+            .fold(0, u32::saturating_add);
+        result.cost = result.cost.saturating_sub(charged);
+        let collision_probability = path_collision_probability(grid, agent, &result.path);
+        if collision_probability <= max_probability {
+            return Ok(RiskPlan {
+                result,
+                collision_probability,
+            });
+        }
+        safest = Some(safest.map_or(collision_probability, |safest| {
+            safest.min(collision_probability)
+        }));
+    }
+    Err(safest.map_or(RiskError::NoPath, RiskError::TooRisky))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::SearchAlgorithm;
+    use crate::test_support::{point_agent, Fixture};
+    use crate::validation::validate_path;
+
+    #[test]
+    fn test_path_probability_counts_cells_once() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.set_occupancy(1, 0, 51);
+        grid.set_occupancy(2, 0, 51);
+        let agent = point_agent(IVec2::ZERO);
+        let path = vec![
+            Cell::new(0, IVec2::new(0, 0)),
+            Cell::new(0, IVec2::new(1, 0)),
+            Cell::new(4, IVec2::new(1, 0)),
+            Cell::new(0, IVec2::new(2, 0)),
+        ];
+        let probability = path_collision_probability(&grid, &agent, &path);
+        assert!((probability - 0.36).abs() < 1e-5);
+        assert_eq!(grid.occupancy_at(-1, 0), 1.0);
+    }
+
+    #[test]
+    fn test_avoids_uncertain_cells() {
+        // An uncertain band across the grid, with a certainly free gap at
+        // the far end.
+        let mut grid = Grid::new(1.0, 12, 12);
+        for x in 0..10 {
+            grid.set_occupancy(x, 5, 128);
+        }
+        let (agent, cache, config) = Fixture::new(12, 12)
+            .at(IVec2::new(2, 0))
+            .holonomic(1)
+            .build();
+        let start = Cell::new(0, agent.position);
+        let goal = IVec2::new(2, 10);
+        let plan = |max_probability| {
+            plan_chance_constrained(
+                &grid,
+                &agent,
+                &cache,
+                start.clone(),
+                goal,
+                &config,
+                max_probability,
+            )
+        };
+
+        let reckless = plan(1.0).unwrap();
+        assert!(reckless.collision_probability > 0.4);
+        let careful = plan(0.01).unwrap();
+        assert_eq!(careful.collision_probability, 0.0);
+        assert!(careful.result.path.len() > reckless.result.path.len());
+
+        grid.set_occupancy(10, 5, 128);
+        grid.set_occupancy(11, 5, 128);
+        let plan = |max_probability| {
+            plan_chance_constrained(
+                &grid,
+                &agent,
+                &cache,
+                start.clone(),
+                goal,
+                &config,
+                max_probability,
+            )
+        };
+        assert!(matches!(plan(0.01), Err(RiskError::TooRisky(_))));
+    }
+
+    #[test]
+    fn test_risk_charges_and_state_budget() {
+        // A thick uncertain band, thinning to one row at the far end.
+        let mut grid = Grid::new(1.0, 12, 12);
+        for y in 4..7 {
+            for x in 0..10 {
+                grid.set_occupancy(x, y, 26);
+            }
+        }
+        grid.set_occupancy(10, 5, 26);
+        grid.set_occupancy(11, 5, 26);
+        let (agent, cache, mut config) = Fixture::new(12, 12)
+            .at(IVec2::new(2, 0))
+            .holonomic(1)
+            .build();
+        let start = Cell::new(0, agent.position);
+        let goal = IVec2::new(2, 10);
+
+        // The thin end still costs some risk, which the search was charged
+        // but the result isn't.
+        let plan = |config: &PlannerConfig| {
+            plan_chance_constrained(&grid, &agent, &cache, start.clone(), goal, config, 0.15)
+        };
+        let thin = plan(&config).unwrap();
+        assert!(thin.collision_probability > 0.05);
+        let moves = validate_path(
+            &thin.result.path,
+            thin.result.cost,
+            &grid,
+            &agent,
+            &cache,
+            &config,
+        );
+        assert!(moves.is_ok());
+
+        // Small risk charges make iterative deepening crawl past its
+        // budget, after the riskier straight path was found.
+        config.algorithm = SearchAlgorithm::IterativeDeepening;
+        config.heuristic_weight = Some(1.0);
+        config.max_states = 20_000;
+        assert!(matches!(plan(&config), Err(RiskError::TooRisky(p)) if p > 0.25));
+    }
+
+    #[test]
+    fn test_capped_charges_saturate() {
+        // Enough certainly occupied cells in a row that their capped
+        // charges add up past u32::MAX.
+        let length = (u32::MAX / MAX_POSE_RISK_COST) as i32 + 100;
+        let mut grid = Grid::new(1.0, length, 1);
+        for x in 1..length {
+            grid.set_occupancy(x, 0, 255);
+        }
+        let (agent, cache, config) = Fixture::new(length, 1).holonomic(1).build();
+        let start = Cell::new(0, agent.position);
+        let goal = IVec2::new(length - 1, 0);
+        let result = plan_chance_constrained(&grid, &agent, &cache, start, goal, &config, 0.5);
+        assert_eq!(result.unwrap_err(), RiskError::TooRisky(1.0));
+    }
+}
@@ -0,0 +1,263 @@
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::goal::Goal;
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+
+/// How much room a path leaves around the agent, for preferring routes
+/// that survive small changes to the map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathRobustness {
+    /// Smallest clearance of any pose, in cells. A pose touching an
+    /// obstacle has clearance 1.
+    pub min_clearance: i32,
+    pub mean_clearance: f32,
+    /// Number of separate stretches squeezed between obstacles on both sides.
+    pub chokepoints: usize,
+}
+
+impl PathRobustness {
+    /// Higher is safer. Each chokepoint weighs as much as a cell of clearance.
+    pub fn score(&self) -> f32 {
+        self.min_clearance as f32 + self.mean_clearance - self.chokepoints as f32
+    }
+}
+
+/// Chebyshev distance from `position` to the nearest blocked cell, or
+/// `max_radius` if there is none that close.
+pub fn cell_clearance(grid: &Grid, position: IVec2, max_radius: i32) -> i32 {
+    for radius in 0..max_radius {
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if dx.abs() != radius && dy.abs() != radius {
+                    continue;
+                }
+                let cell = position + IVec2::new(dx, dy);
+                if grid.is_cell_blocked(cell.x, cell.y) {
+                    return radius;
+                }
+            }
+        }
+    }
+    max_radius
+}
+
+fn pose_cells(agent: &Agent, pose: &Cell) -> Vec<IVec2> {
+    let mut cells: Vec<IVec2> = agent
+        .rotation_footprint(pose.rotation)
+        .iter()
+        .map(|cell| *cell + pose.position)
+        .collect();
+    cells.push(pose.position);
+    cells
+}
+
+/// Smallest clearance over the cells the agent covers at `pose`.
+pub fn pose_clearance(grid: &Grid, agent: &Agent, pose: &Cell, max_radius: i32) -> i32 {
+    pose_cells(agent, pose)
+        .into_iter()
+        .map(|cell| cell_clearance(grid, cell, max_radius))
+        .min()
+        .unwrap_or(max_radius)
+}
+
+/// Checks whether obstacles touch the agent at `pose` on both its left and
+/// its right.
+pub fn is_chokepoint(grid: &Grid, agent: &Agent, pose: &Cell) -> bool {
+    let angle = pose.rotation as f32 * 2.0 * std::f32::consts::PI / agent.max_increments as f32;
+    let heading = Vec2::from_angle(angle);
+    let cells = pose_cells(agent, pose);
+    let (mut left, mut right) = (false, false);
+    for cell in &cells {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let neighbor = *cell + IVec2::new(dx, dy);
+                if cells.contains(&neighbor) || !grid.is_cell_blocked(neighbor.x, neighbor.y) {
+                    continue;
+                }
+                let side = heading.perp_dot((neighbor - pose.position).as_vec2());
+                left |= side < 0.0;
+                right |= side > 0.0;
+            }
+        }
+    }
+    left && right
+}
+
+/// Scores `path` by its clearance, measured up to `max_radius` cells, and
+/// the number of chokepoints it threads.
+pub fn score_path(grid: &Grid, agent: &Agent, path: &[Cell], max_radius: i32) -> PathRobustness {
+    let clearances: Vec<i32> = path
+        .iter()
+        .map(|pose| pose_clearance(grid, agent, pose, max_radius))
+        .collect();
+    let min_clearance = clearances.iter().copied().min().unwrap_or(max_radius);
+    let mean_clearance = if clearances.is_empty() {
+        max_radius as f32
+    } else {
+        clearances.iter().sum::<i32>() as f32 / clearances.len() as f32
+    };
+
+    let mut chokepoints = 0;
+    let mut in_chokepoint = false;
+    for pose in path {
+        let chokepoint = is_chokepoint(grid, agent, pose);
+        if chokepoint && !in_chokepoint {
+            chokepoints += 1;
+        }
+        in_chokepoint = chokepoint;
+    }
+
+    PathRobustness {
+        min_clearance,
+        mean_clearance,
+        chokepoints,
+    }
+}
+
+/// Copy of `agent` grown by `margin` cells on every side.
+pub fn inflated_agent(agent: &Agent, margin: f32) -> Agent {
+    let mut inflated = Agent::new(
+        agent.position,
+        agent.size + Vec2::splat(2.0 * margin),
+        agent.rotation,
+        agent.max_increments,
+    );
+    inflated.max_rotation_rate = agent.max_rotation_rate;
+    inflated.turn_in_place_cost = agent.turn_in_place_cost;
+    inflated.motion = agent.motion;
+    inflated.speed_profile = agent.speed_profile;
+    inflated.dynamics = agent.dynamics;
+    inflated.max_grade = agent.max_grade;
+    inflated.climb_cost = agent.climb_cost;
+    inflated
+}
+
+/// The widest vehicle that still gets through.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Passage {
+    /// Inflation margin, in cells, added to every side of the agent.
+    pub margin: f32,
+    /// Size of the inflated agent.
+    pub size: Vec2,
+}
+
+/// Binary-searches the largest inflation of `agent`, up to `max_margin`
+/// cells and to within `precision`, that can still be planned from `start`
+/// to `goal`. `None` if the agent doesn't get through even uninflated.
+#[allow(clippy::too_many_arguments)]
+pub fn widest_passage(
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    start: &Cell,
+    goal: &Goal,
+    config: &PlannerConfig,
+    max_margin: f32,
+    precision: f32,
+) -> Option<Passage> {
+    let passes = |margin: f32| {
+        let inflated = inflated_agent(agent, margin);
+        planner::plan(
+            grid,
+            &inflated,
+            neighbor_cache,
+            start.clone(),
+            goal.clone(),
+            config,
+        )
+        .is_ok()
+    };
+    let (mut low, mut high) = (0.0, max_margin);
+    if !passes(low) {
+        return None;
+    }
+    if passes(high) {
+        low = high;
+    }
+    while high - low > precision {
+        let middle = (low + high) / 2.0;
+        if passes(middle) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Some(Passage {
+        margin: low,
+        size: agent.size + Vec2::splat(2.0 * low),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{point_agent, Fixture};
+
+    #[test]
+    fn test_gap_is_chokepoint() {
+        // A wall across row 4 with a single-cell gap at x = 5.
+        let mut grid = Grid::new(1.0, 10, 10);
+        for x in 0..10 {
+            if x != 5 {
+                grid.set_cell(x, 4, true);
+            }
+        }
+        let agent = point_agent(IVec2::new(0, 0));
+        assert_eq!(cell_clearance(&grid, IVec2::new(5, 4), 5), 1);
+        assert_eq!(cell_clearance(&grid, IVec2::new(5, 2), 5), 2);
+        assert_eq!(cell_clearance(&grid, IVec2::new(5, 2), 1), 1);
+
+        // Heading down through the gap.
+        let through: Vec<Cell> = (1..=7).map(|y| Cell::new(2, IVec2::new(5, y))).collect();
+        let narrow = score_path(&grid, &agent, &through, 5);
+        assert_eq!(narrow.min_clearance, 1);
+        assert_eq!(narrow.chokepoints, 1);
+
+        // Heading along the wall, two rows away from it.
+        let along: Vec<Cell> = (2..=7).map(|x| Cell::new(0, IVec2::new(x, 2))).collect();
+        let open = score_path(&grid, &agent, &along, 5);
+        assert_eq!(open.min_clearance, 2);
+        assert_eq!(open.chokepoints, 0);
+        assert!(open.score() > narrow.score());
+    }
+
+    #[test]
+    fn test_widest_passage_through_gap() {
+        // A wall across row 6 with a gap of five cells at x = 4..=8.
+        let mut grid = Grid::new(1.0, 14, 14);
+        for x in 0..14 {
+            if !(4..=8).contains(&x) {
+                grid.set_cell(x, 6, true);
+            }
+        }
+        let (agent, cache, config) = Fixture::new(14, 14).holonomic(1).build();
+        let start = Cell::new(2, IVec2::new(6, 2));
+        let goal = Goal::Cell(IVec2::new(6, 10));
+
+        let passage =
+            widest_passage(&grid, &agent, &cache, &start, &goal, &config, 6.0, 0.05).unwrap();
+        assert!(passage.margin > 0.5);
+        assert!(passage.size.x < 6.0);
+        let fits = inflated_agent(&agent, passage.margin);
+        assert!(planner::plan(&grid, &fits, &cache, start.clone(), goal.clone(), &config).is_ok());
+        let too_wide = inflated_agent(&agent, passage.margin + 0.1);
+        assert!(planner::plan(
+            &grid,
+            &too_wide,
+            &cache,
+            start.clone(),
+            goal.clone(),
+            &config
+        )
+        .is_err());
+
+        // Closing the gap leaves nothing to pass.
+        for x in 4..=8 {
+            grid.set_cell(x, 6, true);
+        }
+        assert!(widest_passage(&grid, &agent, &cache, &start, &goal, &config, 6.0, 0.05).is_none());
+    }
+}
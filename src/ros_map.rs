@@ -0,0 +1,312 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::grid::Grid;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The `map.yaml` that ROS `map_server` keeps next to a map image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RosMapMeta {
+    /// Image file, relative to the YAML file.
+    pub image: String,
+    /// `trinary` or `scale`; both read the same here, since the occupancy
+    /// layer keeps the probability either way.
+    pub mode: String,
+    /// Meters per pixel.
+    pub resolution: f32,
+    /// World pose of the lower-left pixel: x and y in meters, then yaw.
+    pub origin: [f32; 3],
+    /// Whether white means occupied instead of free.
+    pub negate: bool,
+    pub occupied_thresh: f32,
+    pub free_thresh: f32,
+}
+
+impl Default for RosMapMeta {
+    /// What `map_saver` writes.
+    fn default() -> Self {
+        Self {
+            image: "map.pgm".to_string(),
+            mode: "trinary".to_string(),
+            resolution: 0.05,
+            origin: [0.0; 3],
+            negate: false,
+            occupied_thresh: 0.65,
+            free_thresh: 0.196,
+        }
+    }
+}
+
+impl RosMapMeta {
+    /// Reads the flat `key: value` mapping `map_server` uses. Keys missing
+    /// from the file keep their defaults, except `image` and `resolution`
+    /// which are required.
+    pub fn parse(source: &str) -> io::Result<Self> {
+        let mut meta = Self::default();
+        let (mut image, mut resolution) = (false, false);
+        for line in source.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            let number = |value: &str| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| invalid(format!("{} is not a number: {:?}", key, value)))
+            };
+            match key.trim() {
+                "image" => {
+                    meta.image = value.to_string();
+                    image = true;
+                }
+                "mode" => meta.mode = value.to_string(),
+                "resolution" => {
+                    meta.resolution = number(value)?;
+                    resolution = true;
+                }
+                "origin" => {
+                    let values = value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(number)
+                        .collect::<io::Result<Vec<_>>>()?;
+                    meta.origin = values
+                        .try_into()
+                        .map_err(|_| invalid("origin needs x, y and yaw".to_string()))?;
+                }
+                "negate" => meta.negate = matches!(value, "1" | "true"),
+                "occupied_thresh" => meta.occupied_thresh = number(value)?,
+                "free_thresh" => meta.free_thresh = number(value)?,
+                _ => {}
+            }
+        }
+        if !image || !resolution {
+            return Err(invalid("map yaml needs image and resolution".to_string()));
+        }
+        if meta.mode == "raw" {
+            return Err(invalid("raw mode maps aren't supported".to_string()));
+        }
+        Ok(meta)
+    }
+
+    pub fn to_yaml(&self) -> String {
+        format!(
+            "image: {}\nmode: {}\nresolution: {}\norigin: [{}, {}, {}]\nnegate: {}\noccupied_thresh: {}\nfree_thresh: {}\n",
+            self.image,
+            self.mode,
+            self.resolution,
+            self.origin[0],
+            self.origin[1],
+            self.origin[2],
+            self.negate as u8,
+            self.occupied_thresh,
+            self.free_thresh
+        )
+    }
+}
+
+/// Whitespace-separated header fields of a PGM, skipping comments.
+struct PgmReader<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> PgmReader<'a> {
+    fn token(&mut self) -> &'a [u8] {
+        loop {
+            let skip = self
+                .rest
+                .iter()
+                .position(|byte| !byte.is_ascii_whitespace())
+                .unwrap_or(self.rest.len());
+            self.rest = &self.rest[skip..];
+            if self.rest.first() != Some(&b'#') {
+                break;
+            }
+            let end = self
+                .rest
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .unwrap_or(self.rest.len());
+            self.rest = &self.rest[end..];
+        }
+        let end = self
+            .rest
+            .iter()
+            .position(|byte| byte.is_ascii_whitespace())
+            .unwrap_or(self.rest.len());
+        let (token, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        token
+    }
+
+    fn number(&mut self) -> io::Result<u32> {
+        std::str::from_utf8(self.token())
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid("bad PGM header".to_string()))
+    }
+}
+
+/// Pixels of a binary (P5) or plain (P2) PGM, scaled to 0..=255, row by
+/// row from the top.
+fn read_pgm(bytes: &[u8]) -> io::Result<(i32, i32, Vec<u8>)> {
+    let mut reader = PgmReader { rest: bytes };
+    let magic = reader.token();
+    let (width, height, max) = (reader.number()?, reader.number()?, reader.number()?);
+    if max == 0 || max > 255 {
+        return Err(invalid("only 8-bit PGMs are supported".to_string()));
+    }
+    // Grids index cells with `i32`s, so the whole image has to fit one.
+    let len = width
+        .checked_mul(height)
+        .filter(|len| i32::try_from(*len).is_ok())
+        .ok_or_else(|| invalid(format!("PGM of {width}x{height} pixels is too large")))?
+        as usize;
+    let scale = |value: u32| (value.min(max) * 255 / max) as u8;
+    let pixels = match magic {
+        b"P5" => {
+            // One whitespace byte separates the header from the pixels.
+            let data = reader
+                .rest
+                .get(1..1 + len)
+                .ok_or_else(|| invalid("PGM is missing pixels".to_string()))?;
+            data.iter().map(|value| scale(*value as u32)).collect()
+        }
+        b"P2" => (0..len)
+            .map(|_| reader.number().map(scale))
+            .collect::<io::Result<Vec<_>>>()?,
+        _ => return Err(invalid("not a PGM".to_string())),
+    };
+    Ok((width as i32, height as i32, pixels))
+}
+
+/// A grid from a `map_server` image, one cell per pixel. Every cell's
+/// occupancy probability goes in the occupancy layer, and cells above
+/// `occupied_thresh` are blocked. Rows stay in image order, so the top of
+/// the image is row 0, and the grid takes the map's resolution.
+pub fn import_ros_map(meta: &RosMapMeta, pgm: &[u8], cell_size: f32) -> io::Result<Grid> {
+    let (width, height, pixels) = read_pgm(pgm)?;
+    let mut grid = Grid::new(
+        cell_size,
+        (width as f32 * cell_size) as i32,
+        (height as f32 * cell_size) as i32,
+    );
+    grid.resolution = meta.resolution;
+    let occupied = (meta.occupied_thresh * 255.0) as u8;
+    for (index, pixel) in pixels.into_iter().enumerate() {
+        let (x, y) = (index as i32 % width, index as i32 / width);
+        let occupancy = if meta.negate { pixel } else { 255 - pixel };
+        grid.set_occupancy(x, y, occupancy);
+        if occupancy > occupied {
+            grid.set_cell(x, y, true);
+        }
+    }
+    Ok(grid)
+}
+
+/// The grid as a binary PGM `map_server` reads with `meta`. Blocked cells
+/// are fully occupied and other cells keep their occupancy, except that
+/// obstacles erased since the import come out free.
+pub fn export_ros_map(grid: &Grid, meta: &RosMapMeta) -> Vec<u8> {
+    let (width, height) = grid.size;
+    let mut pgm = format!("P5\n{} {}\n255\n", width, height).into_bytes();
+    let occupied = (meta.occupied_thresh * 255.0) as u8;
+    for y in 0..height {
+        for x in 0..width {
+            let occupancy = if grid.is_cell_blocked(x, y) {
+                255
+            } else {
+                match (grid.occupancy_at(x, y) * 255.0).round() as u8 {
+                    occupancy if occupancy > occupied => 0,
+                    occupancy => occupancy,
+                }
+            };
+            pgm.push(if meta.negate {
+                occupancy
+            } else {
+                255 - occupancy
+            });
+        }
+    }
+    pgm
+}
+
+/// Loads `map.yaml` and the image it names.
+pub fn load_ros_map(yaml: &Path, cell_size: f32) -> io::Result<(Grid, RosMapMeta)> {
+    let meta = RosMapMeta::parse(&fs::read_to_string(yaml)?)?;
+    let image = yaml.parent().unwrap_or(Path::new("")).join(&meta.image);
+    let grid = import_ros_map(&meta, &fs::read(image)?, cell_size)?;
+    Ok((grid, meta))
+}
+
+/// Saves the grid as `yaml` plus the image `meta` names next to it, with
+/// the grid's resolution.
+pub fn save_ros_map(yaml: &Path, grid: &Grid, meta: &RosMapMeta) -> io::Result<()> {
+    let meta = RosMapMeta {
+        resolution: grid.resolution,
+        ..meta.clone()
+    };
+    let image = yaml.parent().unwrap_or(Path::new("")).join(&meta.image);
+    fs::write(image, export_ros_map(grid, &meta))?;
+    fs::write(yaml, meta.to_yaml())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let yaml = "# saved by map_saver\nimage: warehouse.pgm\nresolution: 0.050000\norigin: [-10.000000, -5.500000, 0.000000]\nnegate: 0\noccupied_thresh: 0.65\nfree_thresh: 0.196\n";
+        let meta = RosMapMeta::parse(yaml).unwrap();
+        assert_eq!(meta.image, "warehouse.pgm");
+        assert_eq!(meta.origin, [-10.0, -5.5, 0.0]);
+        assert_eq!(RosMapMeta::parse(&meta.to_yaml()).unwrap(), meta);
+        assert!(RosMapMeta::parse("resolution: 0.05").is_err());
+
+        // Free, unknown and occupied, the way map_saver writes them.
+        let mut pgm = b"P5\n3 2\n255\n".to_vec();
+        pgm.extend([254, 205, 0, 0, 254, 254]);
+        let mut grid = import_ros_map(&meta, &pgm, 1.0).unwrap();
+        assert_eq!(grid.size, (3, 2));
+        assert_eq!(grid.resolution, 0.05);
+        assert!(grid.is_cell_blocked(2, 0) && grid.is_cell_blocked(0, 1));
+        assert!(!grid.is_cell_blocked(1, 0));
+        assert_eq!(export_ros_map(&grid, &meta), pgm);
+
+        // Edits made in the planner show up in the export.
+        grid.set_cell(2, 0, false);
+        grid.set_cell(2, 1, true);
+        let exported = export_ros_map(&grid, &meta);
+        assert_eq!(exported[exported.len() - 6..], [254, 205, 255, 0, 254, 0]);
+
+        let plain =
+            import_ros_map(&meta, b"P2\n# plain\n3 2\n15\n15 12 0\n0 15 15\n", 1.0).unwrap();
+        let blocked = |grid: &Grid| {
+            (0..6)
+                .filter(|index| grid.is_cell_blocked(index % 3, index / 3))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(blocked(&plain), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_rejects_oversized_headers() {
+        let meta = RosMapMeta::parse("image: map.pgm\nresolution: 0.05").unwrap();
+        for header in [
+            &b"P5\n4294967295 2\n255\n"[..],
+            b"P5\n65536 65536\n255\n",
+            b"P2\n50000 50000\n255\n0\n",
+        ] {
+            let result = import_ros_map(&meta, header, 1.0);
+            assert!(matches!(result, Err(error) if error.kind() == io::ErrorKind::InvalidData));
+        }
+    }
+}
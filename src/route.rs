@@ -0,0 +1,181 @@
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::planner::{self, PlannerConfig};
+
+/// A path split into legs at pinned waypoints, so moving one waypoint only
+/// replans the legs it touches.
+#[derive(Clone, Debug)]
+pub struct PinnedRoute {
+    /// Waypoint positions between legs, `legs.len() - 1` of them.
+    pub pins: Vec<IVec2>,
+    /// Each leg starts at the last pose of the previous one.
+    pub legs: Vec<Vec<Cell>>,
+}
+
+impl PinnedRoute {
+    pub fn new(path: Vec<Cell>) -> Self {
+        Self {
+            pins: Vec::new(),
+            legs: vec![path],
+        }
+    }
+
+    /// The whole route, without repeating the poses legs share.
+    pub fn path(&self) -> Vec<Cell> {
+        let mut path = Vec::new();
+        for (i, leg) in self.legs.iter().enumerate() {
+            let skip = if i == 0 { 0 } else { 1 };
+            path.extend(leg.iter().skip(skip).cloned());
+        }
+        path
+    }
+
+    /// Leg holding the pose at `index` of [`PinnedRoute::path`].
+    pub fn leg_at(&self, index: usize) -> Option<usize> {
+        let mut end = 0;
+        for (i, leg) in self.legs.iter().enumerate() {
+            end += leg.len() - if i == 0 { 0 } else { 1 };
+            if index < end {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    fn leg_end(&self, leg: usize) -> IVec2 {
+        self.legs[leg].last().unwrap().position
+    }
+
+    /// Replans legs from `first` on, each from the end of the one before,
+    /// stopping once a leg arrives exactly as it did before.
+    fn replan_from(
+        &mut self,
+        first: usize,
+        grid: &Grid,
+        agent: &Agent,
+        neighbor_cache: &NeighborCacheRef,
+        config: &PlannerConfig,
+    ) -> bool {
+        for leg in first..self.legs.len() {
+            let start = if leg == 0 {
+                self.legs[0][0].clone()
+            } else {
+                self.legs[leg - 1].last().unwrap().clone()
+            };
+            let previous_arrival = self.legs[leg].last().unwrap().clone();
+            let goal = self.leg_end(leg);
+            let Ok(result) = planner::plan(grid, agent, neighbor_cache, start, goal, config) else {
+                return false;
+            };
+            self.legs[leg] = result.path;
+            if leg > first && *self.legs[leg].last().unwrap() == previous_arrival {
+                break;
+            }
+        }
+        true
+    }
+
+    /// Pins a waypoint at `position` on the leg holding path pose `index`,
+    /// splitting it in two and replanning. The route is left unchanged if
+    /// any affected leg can't be planned.
+    pub fn insert_pin(
+        &mut self,
+        index: usize,
+        position: IVec2,
+        grid: &Grid,
+        agent: &Agent,
+        neighbor_cache: &NeighborCacheRef,
+        config: &PlannerConfig,
+    ) -> bool {
+        let Some(leg) = self.leg_at(index) else {
+            return false;
+        };
+        let mut updated = self.clone();
+        let end = updated.legs[leg].last().unwrap().clone();
+        updated.legs[leg] = vec![updated.legs[leg][0].clone(), Cell::new(0, position)];
+        updated.legs.insert(leg + 1, vec![end]);
+        updated.pins.insert(leg, position);
+        if !updated.replan_from(leg, grid, agent, neighbor_cache, config) {
+            return false;
+        }
+        *self = updated;
+        true
+    }
+
+    /// Moves pin `pin` to `position`, replanning the legs on either side.
+    pub fn move_pin(
+        &mut self,
+        pin: usize,
+        position: IVec2,
+        grid: &Grid,
+        agent: &Agent,
+        neighbor_cache: &NeighborCacheRef,
+        config: &PlannerConfig,
+    ) -> bool {
+        if pin >= self.pins.len() {
+            return false;
+        }
+        let mut updated = self.clone();
+        updated.pins[pin] = position;
+        updated.legs[pin].last_mut().unwrap().position = position;
+        if !updated.replan_from(pin, grid, agent, neighbor_cache, config) {
+            return false;
+        }
+        *self = updated;
+        true
+    }
+
+    /// The pin closest to `position` within `radius` cells.
+    pub fn pin_near(&self, position: IVec2, radius: i32) -> Option<usize> {
+        self.pins
+            .iter()
+            .enumerate()
+            .filter(|(_, pin)| (**pin - position).abs().max_element() <= radius)
+            .min_by_key(|(_, pin)| (**pin - position).length_squared())
+            .map(|(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_pin_splits_and_moves() {
+        let grid = Grid::new(1.0, 12, 12);
+        let (agent, cache, config) = Fixture::new(12, 12).holonomic(1).build();
+        let start = Cell::new(0, IVec2::new(1, 1));
+        let path = planner::plan(&grid, &agent, &cache, start, IVec2::new(9, 1), &config)
+            .unwrap()
+            .path;
+        let mut route = PinnedRoute::new(path.clone());
+        assert_eq!(route.path(), path);
+
+        let pin = IVec2::new(5, 6);
+        assert!(route.insert_pin(3, pin, &grid, &agent, &cache, &config));
+        assert_eq!(route.legs.len(), 2);
+        assert_eq!(route.pins, vec![pin]);
+        let path = route.path();
+        assert!(path.iter().any(|pose| pose.position == pin));
+        assert_eq!(path.last().unwrap().position, IVec2::new(9, 1));
+        assert_eq!(route.leg_at(0), Some(0));
+        assert_eq!(route.leg_at(path.len() - 1), Some(1));
+        assert_eq!(route.pin_near(IVec2::new(6, 6), 1), Some(0));
+
+        let moved = IVec2::new(5, 9);
+        assert!(route.move_pin(0, moved, &grid, &agent, &cache, &config));
+        assert!(route.path().iter().any(|pose| pose.position == moved));
+        assert_eq!(route.pins, vec![moved]);
+
+        // Pinning into a wall keeps the route as it was.
+        let mut walled = Grid::new(1.0, 12, 12);
+        walled.set_cell(5, 9, true);
+        let before = route.path();
+        assert!(!route.move_pin(0, IVec2::new(5, 9), &walled, &agent, &cache, &config));
+        assert_eq!(route.path(), before);
+    }
+}
@@ -0,0 +1,96 @@
+use notan::math::IVec2;
+
+use crate::grid::Grid;
+
+/// A box of cells for editing groups of painted obstacles at once, like
+/// moving a whole shelving row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub min: IVec2,
+    pub max: IVec2,
+}
+
+impl Selection {
+    /// The box spanned by two opposite corners, both included.
+    pub fn new(corner: IVec2, opposite: IVec2) -> Self {
+        Self {
+            min: corner.min(opposite),
+            max: corner.max(opposite),
+        }
+    }
+
+    pub fn contains(&self, cell: IVec2) -> bool {
+        cell.cmpge(self.min).all() && cell.cmple(self.max).all()
+    }
+
+    /// Blocked cells of the grid inside the box, row by row.
+    pub fn blocked_cells(&self, grid: &Grid) -> Vec<IVec2> {
+        (self.min.y..=self.max.y)
+            .flat_map(|y| (self.min.x..=self.max.x).map(move |x| IVec2::new(x, y)))
+            .filter(|cell| grid.in_bounds(cell.x, cell.y) && grid.is_cell_blocked(cell.x, cell.y))
+            .collect()
+    }
+
+    /// Moves the selected obstacles by `offset`, or copies them if `copy`,
+    /// and moves the box along. Cells landing off the grid are dropped.
+    /// Returns how many obstacles were moved.
+    pub fn shift(&mut self, grid: &mut Grid, offset: IVec2, copy: bool) -> usize {
+        let cells = self.blocked_cells(grid);
+        if !copy {
+            for cell in &cells {
+                grid.set_cell(cell.x, cell.y, false);
+            }
+        }
+        for cell in &cells {
+            let moved = *cell + offset;
+            grid.set_cell(moved.x, moved.y, true);
+        }
+        self.min += offset;
+        self.max += offset;
+        cells.len()
+    }
+
+    /// Clears the selected obstacles, returning how many there were.
+    pub fn delete(&self, grid: &mut Grid) -> usize {
+        let cells = self.blocked_cells(grid);
+        for cell in &cells {
+            grid.set_cell(cell.x, cell.y, false);
+        }
+        cells.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_copy_delete() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        for x in 2..5 {
+            grid.set_cell(x, 3, true);
+        }
+        grid.set_cell(8, 8, true);
+        let mut selection = Selection::new(IVec2::new(5, 4), IVec2::new(1, 2));
+        assert_eq!(selection.min, IVec2::new(1, 2));
+        assert_eq!(selection.blocked_cells(&grid).len(), 3);
+
+        // Shift the row down, leaving the obstacle outside alone.
+        assert_eq!(selection.shift(&mut grid, IVec2::new(0, 2), false), 3);
+        assert!(!grid.is_cell_blocked(3, 3));
+        assert!(grid.is_cell_blocked(3, 5));
+        assert!(grid.is_cell_blocked(8, 8));
+        assert!(selection.contains(IVec2::new(3, 5)));
+
+        // Copy it, partly off the right edge.
+        assert_eq!(selection.shift(&mut grid, IVec2::new(6, 0), true), 3);
+        assert!(grid.is_cell_blocked(2, 5));
+        assert!(grid.is_cell_blocked(8, 5));
+        assert!(grid.is_cell_blocked(9, 5));
+        assert_eq!(selection.blocked_cells(&grid).len(), 2);
+
+        assert_eq!(selection.delete(&mut grid), 2);
+        assert!(!grid.is_cell_blocked(9, 5));
+        assert!(grid.is_cell_blocked(4, 5));
+    }
+}
@@ -0,0 +1,139 @@
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::Agent;
+use crate::bitarray::BitArray;
+use crate::cell::Cell;
+use crate::grid::{supercover_line, Grid};
+
+/// A 2D lidar casting `rays` evenly spaced rays up to `range` cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lidar {
+    pub range: f32,
+    pub rays: usize,
+}
+
+impl Lidar {
+    /// Cells seen by each ray from `origin`, up to and including the first
+    /// obstacle of `truth` it hits.
+    pub fn cast(&self, truth: &Grid, origin: IVec2) -> Vec<Vec<IVec2>> {
+        (0..self.rays)
+            .map(|ray| {
+                let angle = ray as f32 * 2.0 * std::f32::consts::PI / self.rays as f32;
+                let end = origin + (Vec2::from_angle(angle) * self.range).round().as_ivec2();
+                let mut seen = Vec::new();
+                for cell in supercover_line(origin, end) {
+                    if !truth.in_bounds(cell.x, cell.y) {
+                        break;
+                    }
+                    seen.push(cell);
+                    if truth.is_cell_blocked(cell.x, cell.y) {
+                        break;
+                    }
+                }
+                seen
+            })
+            .collect()
+    }
+}
+
+/// Hidden "true" map and the cells observed of it so far. Planning runs on
+/// a separate discovered grid that starts out empty, treating unknown cells
+/// as free, and gains obstacles as they are scanned.
+pub struct Discovery {
+    pub truth: Grid,
+    pub known: BitArray,
+}
+
+impl Discovery {
+    pub fn new(truth: Grid) -> Self {
+        let known = BitArray::new((truth.size.0 * truth.size.1) as usize);
+        Self { truth, known }
+    }
+
+    /// An empty grid matching the true map, to be filled in by scans.
+    pub fn blank_grid(&self) -> Grid {
+        let cell_size = self.truth.cell_size;
+        let mut grid = Grid::new(
+            cell_size,
+            (self.truth.size.0 as f32 * cell_size) as i32,
+            (self.truth.size.1 as f32 * cell_size) as i32,
+        );
+        grid.resolution = self.truth.resolution;
+        grid
+    }
+
+    pub fn is_known(&self, x: i32, y: i32) -> bool {
+        self.truth.in_bounds(x, y) && self.known.get_bool(self.truth.index(x, y))
+    }
+
+    pub fn known_count(&self) -> usize {
+        (0..self.known.len())
+            .filter(|&index| self.known.get_bool(index))
+            .count()
+    }
+
+    /// Scans from `origin`, marking seen cells known and copying their
+    /// obstacles into `discovered`. Returns the newly found obstacles.
+    pub fn scan(&mut self, discovered: &mut Grid, origin: IVec2, lidar: &Lidar) -> Vec<IVec2> {
+        let mut found = Vec::new();
+        for cell in lidar.cast(&self.truth, origin).into_iter().flatten() {
+            let index = self.truth.index(cell.x, cell.y);
+            if self.known.get_bool(index) {
+                continue;
+            }
+            self.known.set_bool(index, true);
+            if self.truth.is_cell_blocked(cell.x, cell.y) {
+                discovered.set_cell(cell.x, cell.y, true);
+                found.push(cell);
+            }
+        }
+        found
+    }
+}
+
+/// Checks whether any pose of `path` now overlaps obstacles, meaning it has
+/// to be replanned.
+pub fn is_path_invalidated(grid: &Grid, agent: &Agent, path: &[Cell]) -> bool {
+    path.iter().any(|pose| grid.is_pose_blocked(agent, pose))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::point_agent;
+
+    #[test]
+    fn test_scan_discovers_wall() {
+        let mut truth = Grid::new(1.0, 10, 10);
+        for y in 0..10 {
+            truth.set_cell(5, y, true);
+        }
+        let mut discovery = Discovery::new(truth);
+        let mut discovered = discovery.blank_grid();
+        assert_eq!(discovered.size, (10, 10));
+        let lidar = Lidar {
+            range: 8.0,
+            rays: 64,
+        };
+
+        let agent = point_agent(IVec2::new(0, 0));
+        let path: Vec<Cell> = (1..9).map(|x| Cell::new(0, IVec2::new(x, 2))).collect();
+        assert!(!is_path_invalidated(&discovered, &agent, &path));
+
+        let found = discovery.scan(&mut discovered, IVec2::new(2, 2), &lidar);
+        assert!(found.contains(&IVec2::new(5, 2)));
+        assert!(found.iter().all(|cell| cell.x == 5));
+        assert!(discovered.is_cell_blocked(5, 2));
+        // Nothing is seen behind the wall.
+        assert!(!discovery.is_known(6, 2));
+        assert!(discovery.is_known(4, 2));
+        assert!(is_path_invalidated(&discovered, &agent, &path));
+
+        // Scanning again from the same spot finds nothing new.
+        let known = discovery.known_count();
+        assert!(discovery
+            .scan(&mut discovered, IVec2::new(2, 2), &lidar)
+            .is_empty());
+        assert_eq!(discovery.known_count(), known);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use notan::math::IVec2;
+
+use crate::agent::Agent;
+use crate::cell::Cell;
+
+/// A vehicle driving along a planned path at a constant speed.
+#[derive(Clone)]
+pub struct SimAgent {
+    pub agent: Agent,
+    pub path: Vec<Cell>,
+    /// Path poses advanced per second.
+    pub speed: f32,
+    /// Fractional index into `path`.
+    pub progress: f32,
+}
+
+impl SimAgent {
+    pub fn pose(&self) -> Option<&Cell> {
+        let index = (self.progress as usize).min(self.path.len().saturating_sub(1));
+        self.path.get(index)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.progress as usize + 1 >= self.path.len()
+    }
+
+    fn cells(&self) -> HashSet<IVec2> {
+        let mut cells: HashSet<IVec2> = self.agent.current_footprint().into_iter().collect();
+        cells.insert(self.agent.position);
+        cells
+    }
+}
+
+/// Fixed-timestep playback of several agents' paths, reporting every pair
+/// of agents whose footprints overlap.
+pub struct Simulation {
+    pub agents: Vec<SimAgent>,
+    /// Seconds per step.
+    pub timestep: f32,
+    pub time: f32,
+    /// Pairs of agent indices colliding after the last step.
+    pub collisions: Vec<(usize, usize)>,
+    accumulator: f32,
+}
+
+impl Simulation {
+    pub fn new(timestep: f32) -> Self {
+        Self {
+            agents: Vec::new(),
+            timestep,
+            time: 0.0,
+            collisions: Vec::new(),
+            accumulator: 0.0,
+        }
+    }
+
+    /// Adds an agent at the start of `path`.
+    pub fn add_agent(&mut self, mut agent: Agent, path: Vec<Cell>, speed: f32) {
+        if let Some(start) = path.first() {
+            agent.position = start.position;
+            agent.rotation = start.rotation;
+        }
+        self.agents.push(SimAgent {
+            agent,
+            path,
+            speed,
+            progress: 0.0,
+        });
+    }
+
+    /// Runs as many fixed steps as fit into `delta` seconds, carrying the
+    /// remainder over. Returns the number of steps taken.
+    pub fn advance(&mut self, delta: f32) -> usize {
+        self.accumulator += delta;
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            self.accumulator -= self.timestep;
+            self.step();
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Moves every agent one timestep along its path and checks collisions.
+    pub fn step(&mut self) {
+        self.time += self.timestep;
+        for sim in &mut self.agents {
+            if !sim.is_finished() {
+                sim.progress += sim.speed * self.timestep;
+            }
+            if let Some(pose) = sim.pose().cloned() {
+                sim.agent.position = pose.position;
+                sim.agent.rotation = pose.rotation;
+            }
+        }
+
+        self.collisions.clear();
+        let cells: Vec<HashSet<IVec2>> = self.agents.iter().map(SimAgent::cells).collect();
+        for a in 0..cells.len() {
+            for b in a + 1..cells.len() {
+                if !cells[a].is_disjoint(&cells[b]) {
+                    self.collisions.push((a, b));
+                }
+            }
+        }
+    }
+
+    pub fn is_colliding(&self, index: usize) -> bool {
+        self.collisions
+            .iter()
+            .any(|&(a, b)| a == index || b == index)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.agents.iter().all(SimAgent::is_finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::point_agent;
+
+    fn row(y: i32, xs: impl Iterator<Item = i32>) -> Vec<Cell> {
+        xs.map(|x| Cell::new(0, IVec2::new(x, y))).collect()
+    }
+
+    #[test]
+    fn test_head_on_collision() {
+        let agent = point_agent(IVec2::new(0, 0));
+        let mut simulation = Simulation::new(0.25);
+        simulation.add_agent(agent.clone(), row(0, 0..=6), 4.0);
+        simulation.add_agent(agent.clone(), row(0, (0..=6).rev()), 4.0);
+        simulation.add_agent(agent, row(2, 0..=6), 4.0);
+
+        assert_eq!(simulation.advance(0.6), 2);
+        assert!(simulation.collisions.is_empty());
+        simulation.step();
+        assert_eq!(simulation.collisions, vec![(0, 1)]);
+        assert!(simulation.is_colliding(1));
+        assert!(!simulation.is_colliding(2));
+
+        while !simulation.is_finished() {
+            simulation.step();
+        }
+        assert_eq!(simulation.agents[0].agent.position, IVec2::new(6, 0));
+        assert_eq!(simulation.agents[1].agent.position, IVec2::new(0, 0));
+    }
+}
@@ -0,0 +1,565 @@
+use std::ops::Range;
+
+use geo::{Coord, LineString, SimplifyIdx};
+use notan::math::{IVec2, Vec2};
+use splines::{Interpolation, Key, Spline};
+
+use crate::agent::Agent;
+use crate::cell::Cell;
+use crate::grid::Grid;
+
+/// Samples per path pose along [`smooth_path`].
+pub const SAMPLES_PER_POSE: usize = 10;
+
+/// Indices of the poses Ramer-Douglas-Peucker keeps within `epsilon` cells.
+fn simplified_indices(path: &[Cell], epsilon: f32) -> Vec<usize> {
+    let line_string = LineString::new(
+        path.iter()
+            .map(|cell| Coord {
+                x: cell.position.x as f64,
+                y: cell.position.y as f64,
+            })
+            .collect(),
+    );
+    line_string.simplify_idx(&(epsilon as f64))
+}
+
+/// The key poses of `path`: the first and last, and those needed to stay
+/// within `epsilon` cells of every pose dropped, as waypoints for
+/// controllers and exporters. With `preserve_direction_changes`, every pose
+/// where the vehicle switches between driving forward and reversing is
+/// kept too, however close to the line it is.
+pub fn simplify_path(path: &[Cell], epsilon: f32, preserve_direction_changes: bool) -> Vec<Cell> {
+    let mut keys = simplified_indices(path, epsilon);
+    if preserve_direction_changes {
+        // A switch is where the motion turns back on itself; moves without
+        // a translation don't change the direction.
+        let mut last_motion = None;
+        for (i, pair) in path.windows(2).enumerate() {
+            let motion = pair[1].position - pair[0].position;
+            if motion == IVec2::ZERO {
+                continue;
+            }
+            if last_motion.is_some_and(|last: IVec2| last.dot(motion) < 0) {
+                keys.push(i);
+            }
+            last_motion = Some(motion);
+        }
+        keys.sort_unstable();
+        keys.dedup();
+    }
+    keys.into_iter().map(|i| path[i].clone()).collect()
+}
+
+/// World-space points along a Bezier spline through the key poses of
+/// `path`, for drawing and exporting. Direction switches keep sharp corners.
+pub fn smooth_path(path: &[Cell], cell_size: f32, max_increments: u16) -> Vec<Vec2> {
+    let (_, spline) = key_spline(path, cell_size, max_increments);
+    sample_spline(&spline, path.len())
+}
+
+/// The spline behind [`smooth_path`], with the index in `path` of each of
+/// its keys. Key `i` sits at parameter `i`.
+fn key_spline(
+    path: &[Cell],
+    cell_size: f32,
+    max_increments: u16,
+) -> (Vec<usize>, Spline<f32, Vec2>) {
+    let simplified_path = simplified_indices(path, 0.5);
+    // add back direction nodes, where reverse
+    // switches to the opposite direction and back
+    let simplified_path = path
+        .iter()
+        .enumerate()
+        .filter(|(i, cell)| {
+            let (i, cell) = (*i, *cell);
+            if i == 0 || i == path.len() - 1 {
+                return true;
+            }
+
+            let next = &path[i + 1];
+            if next.is_reverse_to(cell, max_increments as i16) {
+                return true;
+            }
+
+            simplified_path.contains(&i)
+        })
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    let spline = build_spline(path, &simplified_path, cell_size, max_increments, cell_size);
+    (simplified_path, spline)
+}
+
+/// A spline with a key at each of `indices` into `path`, leaving along the
+/// pose's heading with tangents `tangent_length` long, or in a straight line
+/// where the vehicle switches into reverse.
+fn build_spline(
+    path: &[Cell],
+    indices: &[usize],
+    cell_size: f32,
+    max_increments: u16,
+    tangent_length: f32,
+) -> Spline<f32, Vec2> {
+    let mut keys = Vec::with_capacity(indices.len());
+    for (i, cell_i) in indices.iter().enumerate() {
+        let cell = &path[*cell_i];
+        let x = (cell.position.x as f32 + 0.5) * cell_size;
+        let y = (cell.position.y as f32 + 0.5) * cell_size;
+        let xy = Vec2::new(x, y);
+
+        let angle = (cell.rotation as f32 / max_increments as f32) * std::f32::consts::PI * 2.0;
+        let angle_vector = Vec2::from_angle(angle);
+        let tangent = xy + angle_vector * tangent_length;
+        let reverse = *cell_i != 0
+            && *cell_i != path.len() - 1
+            && path[cell_i + 1].is_reverse_to(cell, max_increments as i16);
+        let interpolation = if reverse {
+            Interpolation::Linear
+        } else {
+            Interpolation::Bezier(tangent)
+        };
+        keys.push(Key::new(i as f32, xy, interpolation));
+    }
+    Spline::from_vec(keys)
+}
+
+fn sample_spline(spline: &Spline<f32, Vec2>, poses: usize) -> Vec<Vec2> {
+    // now sample the spline at a higher resolution
+    (0..poses * SAMPLES_PER_POSE)
+        .map(|i| {
+            let t = i as f32 / SAMPLES_PER_POSE as f32;
+            spline
+                .clamped_sample(t)
+                .unwrap_or_else(|| panic!("Failed to sample x at {} (len: {})", t, spline.len()))
+        })
+        .collect()
+}
+
+/// Signed curvature at each point of a polyline, positive turning toward
+/// +y: one over the radius of the circle through the point and its
+/// neighbors, in the points' inverse units. Repeated points are skipped
+/// over, and the ends, with no turn to measure, get zero.
+pub fn curvature(points: &[Vec2]) -> Vec<f32> {
+    // Runs of a repeated point, like the clamped samples past a spline's
+    // last key, share one curvature, measured against the runs either side.
+    let mut runs: Vec<(Vec2, usize)> = Vec::new();
+    for point in points {
+        match runs.last_mut() {
+            Some((last, count)) if last == point => *count += 1,
+            _ => runs.push((*point, 1)),
+        }
+    }
+    let mut curvatures = Vec::with_capacity(points.len());
+    for (i, &(point, count)) in runs.iter().enumerate() {
+        let neighbors = (i.checked_sub(1).map(|j| runs[j].0), runs.get(i + 1));
+        let curvature = match neighbors {
+            (Some(previous), Some(&(next, _))) => {
+                let (incoming, outgoing) = (point - previous, next - point);
+                let chord = next - previous;
+                if chord == Vec2::ZERO {
+                    0.0
+                } else {
+                    2.0 * incoming.perp_dot(outgoing)
+                        / (incoming.length() * outgoing.length() * chord.length())
+                }
+            }
+            _ => 0.0,
+        };
+        curvatures.extend(std::iter::repeat_n(curvature, count));
+    }
+    curvatures
+}
+
+/// A run of samples bending tighter than the vehicle can turn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurvatureViolation {
+    /// Indices of the offending samples.
+    pub samples: Range<usize>,
+    /// Largest curvature magnitude among them.
+    pub peak: f32,
+}
+
+/// Runs of `points` whose curvature magnitude exceeds `max_curvature`,
+/// such as [`crate::control::BicycleModel::max_curvature`]. Direction
+/// switches, where the vehicle stops and turns back, are allowed any
+/// corner.
+pub fn curvature_violations(points: &[Vec2], max_curvature: f32) -> Vec<CurvatureViolation> {
+    let curvatures = curvature(points);
+    let is_switch = |i: usize| {
+        i > 0
+            && i + 1 < points.len()
+            && (points[i] - points[i - 1]).dot(points[i + 1] - points[i]) < 0.0
+    };
+    let mut violations: Vec<CurvatureViolation> = Vec::new();
+    for (i, curvature) in curvatures.iter().enumerate() {
+        if curvature.abs() <= max_curvature || is_switch(i) {
+            continue;
+        }
+        match violations.last_mut() {
+            Some(last) if last.samples.end == i => {
+                last.samples.end = i + 1;
+                last.peak = last.peak.max(curvature.abs());
+            }
+            _ => violations.push(CurvatureViolation {
+                samples: i..i + 1,
+                peak: curvature.abs(),
+            }),
+        }
+    }
+    violations
+}
+
+/// Like [`smooth_path`], but between key poses where the spline bends
+/// tighter than `max_curvature` (per world unit) it follows the grid path
+/// instead, whose moves come from the vehicle's own motion primitives.
+/// Returns the points along with the violations found in the spline.
+pub fn smooth_path_within_curvature(
+    path: &[Cell],
+    cell_size: f32,
+    max_increments: u16,
+    max_curvature: f32,
+) -> (Vec<Vec2>, Vec<CurvatureViolation>) {
+    let (keys, spline) = key_spline(path, cell_size, max_increments);
+    let samples = sample_spline(&spline, path.len());
+    let violations = curvature_violations(&samples, max_curvature);
+    if violations.is_empty() || keys.len() < 2 {
+        return (samples, violations);
+    }
+    // Sample `i` lies between keys `i / SAMPLES_PER_POSE` and the next.
+    let last_segment = keys.len() - 2;
+    let mut rough = vec![false; keys.len() - 1];
+    for violation in &violations {
+        for sample in violation.samples.clone() {
+            rough[(sample / SAMPLES_PER_POSE).min(last_segment)] = true;
+        }
+    }
+    let center = |cell: &Cell| (cell.position.as_vec2() + Vec2::splat(0.5)) * cell_size;
+    let mut points = Vec::with_capacity(samples.len());
+    for (segment, pair) in keys.windows(2).enumerate() {
+        if rough[segment] {
+            points.extend(path[pair[0]..pair[1]].iter().map(center));
+        } else {
+            let start = segment * SAMPLES_PER_POSE;
+            points.extend_from_slice(&samples[start..start + SAMPLES_PER_POSE]);
+        }
+    }
+    points.push(center(&path[keys[keys.len() - 1]]));
+    (points, violations)
+}
+
+/// Tangent lengths, in cells, tried in turn when a stretch of spline hits
+/// something.
+const TIGHTENED_TANGENTS: [f32; 2] = [0.5, 0.25];
+
+/// Whether the footprint hits a blocked cell at any of `points`, which are
+/// in world units along a stretch of path leaving `pose`. The vehicle faces
+/// along the points, or against them where `pose` is reversing.
+fn hits_obstacle(grid: &Grid, agent: &Agent, pose: &Cell, points: &[Vec2]) -> bool {
+    let pose_heading = Cell::increment_to_heading(pose.rotation, agent.max_increments);
+    let facing = Vec2::from_angle(pose_heading);
+    (0..points.len()).any(|i| {
+        let motion = points[(i + 1).min(points.len() - 1)] - points[i.saturating_sub(1)];
+        let heading = if motion == Vec2::ZERO {
+            pose_heading
+        } else if motion.dot(facing) < 0.0 {
+            (-motion).y.atan2(-motion.x)
+        } else {
+            motion.y.atan2(motion.x)
+        };
+        agent
+            .footprint_at_point(points[i] / grid.cell_size, heading)
+            .iter()
+            .any(|cell| grid.is_cell_blocked(cell.x, cell.y))
+    })
+}
+
+/// Like [`smooth_path`], checked against `grid` with the agent's footprint
+/// at every sample. Between key poses where the spline cuts into an
+/// obstacle it's tightened: rebuilt through every pose of the path with
+/// ever shorter tangents, and as a last resort replaced by the straight
+/// moves between the planned poses themselves, so the result never leaves
+/// the space the planner checked.
+pub fn smooth_path_collision_free(grid: &Grid, agent: &Agent, path: &[Cell]) -> Vec<Vec2> {
+    let (cell_size, max_increments) = (grid.cell_size, agent.max_increments);
+    let (keys, spline) = key_spline(path, cell_size, max_increments);
+    let samples = sample_spline(&spline, path.len());
+    if keys.len() < 2 {
+        return samples;
+    }
+    let center = |cell: &Cell| (cell.position.as_vec2() + Vec2::splat(0.5)) * cell_size;
+    let mut points = Vec::with_capacity(samples.len());
+    for (segment, pair) in keys.windows(2).enumerate() {
+        let (from, to) = (pair[0], pair[1]);
+        let start = segment * SAMPLES_PER_POSE;
+        // Up to and including the next key, so the check covers the joint.
+        let stretch = &samples[start..=start + SAMPLES_PER_POSE];
+        if !hits_obstacle(grid, agent, &path[from], stretch) {
+            points.extend_from_slice(&stretch[..SAMPLES_PER_POSE]);
+            continue;
+        }
+        let indices: Vec<usize> = (from..=to).collect();
+        let tightened = TIGHTENED_TANGENTS.iter().find_map(|tangent| {
+            let spline = build_spline(
+                path,
+                &indices,
+                cell_size,
+                max_increments,
+                tangent * cell_size,
+            );
+            let stretch = sample_spline(&spline, indices.len());
+            let stretch = &stretch[..=(indices.len() - 1) * SAMPLES_PER_POSE];
+            (!hits_obstacle(grid, agent, &path[from], stretch)).then(|| stretch.to_vec())
+        });
+        match tightened {
+            Some(stretch) => points.extend_from_slice(&stretch[..stretch.len() - 1]),
+            None => points.extend(path[from..to].iter().map(center)),
+        }
+    }
+    points.push(center(&path[keys[keys.len() - 1]]));
+    points
+}
+
+/// A polyline such as [`smooth_path`]'s, measured along its length so it
+/// can be sampled by distance instead of by spline parameter, which
+/// bunches points up in sharp turns.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ArcLengthPath {
+    points: Vec<Vec2>,
+    /// Distance along the path to each point.
+    distances: Vec<f32>,
+}
+
+impl ArcLengthPath {
+    pub fn new(points: Vec<Vec2>) -> Self {
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                total += points[i - 1].distance(*point);
+            }
+            distances.push(total);
+        }
+        Self { points, distances }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// The point `distance` along the path, clamped to its ends. `None` for
+    /// an empty path.
+    pub fn sample_at_distance(&self, distance: f32) -> Option<Vec2> {
+        let distance = distance.clamp(0.0, self.length());
+        // First point at or past the distance; the one before starts the
+        // segment it lies on.
+        let next = self
+            .distances
+            .partition_point(|along| *along < distance)
+            .min(self.points.len().checked_sub(1)?);
+        if next == 0 {
+            return Some(self.points[0]);
+        }
+        let (start, end) = (self.distances[next - 1], self.distances[next]);
+        let t = if end > start {
+            (distance - start) / (end - start)
+        } else {
+            1.0
+        };
+        Some(self.points[next - 1].lerp(self.points[next], t))
+    }
+
+    /// Points evenly spaced along the path, both ends included, at most
+    /// `spacing` apart: the spacing is shrunk so the last point lands on the
+    /// end.
+    pub fn resample(&self, spacing: f32) -> Vec<Vec2> {
+        let Some(first) = self.points.first() else {
+            return Vec::new();
+        };
+        let length = self.length();
+        if length <= 0.0 || spacing <= 0.0 {
+            return vec![*first];
+        }
+        let segments = (length / spacing).ceil().max(1.0) as usize;
+        (0..=segments)
+            .filter_map(|i| self.sample_at_distance(length * i as f32 / segments as f32))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::point_agent;
+
+    fn positions(path: &[Cell]) -> Vec<IVec2> {
+        path.iter().map(|pose| pose.position).collect()
+    }
+
+    #[test]
+    fn test_simplify_path() {
+        // Forward along a row, up a diagonal, then backing up a little
+        // along it.
+        let mut path: Vec<Cell> = (0..=5).map(|x| Cell::new(0, IVec2::new(x, 0))).collect();
+        path.extend((1..=3).map(|i| Cell::new(1, IVec2::new(5 + i, i))));
+        path.push(Cell::new(1, IVec2::new(7, 2)));
+        path.push(Cell::new(1, IVec2::new(6, 1)));
+
+        let keys = simplify_path(&path, 0.5, false);
+        assert_eq!(
+            positions(&keys),
+            vec![
+                IVec2::new(0, 0),
+                IVec2::new(5, 0),
+                IVec2::new(8, 3),
+                IVec2::new(6, 1)
+            ]
+        );
+
+        // A loose tolerance drops the bend and, unless preserved, the
+        // switch into reverse.
+        assert_eq!(
+            positions(&simplify_path(&path, 10.0, false)),
+            vec![IVec2::new(0, 0), IVec2::new(6, 1)]
+        );
+        assert_eq!(
+            positions(&simplify_path(&path, 10.0, true)),
+            vec![IVec2::new(0, 0), IVec2::new(8, 3), IVec2::new(6, 1)]
+        );
+        assert_eq!(keys[2].rotation, 1);
+        assert!(simplify_path(&[], 0.5, true).is_empty());
+    }
+
+    #[test]
+    fn test_arc_length_sampling() {
+        let path = ArcLengthPath::new(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(3.0, 4.0),
+        ]);
+        assert_eq!(path.length(), 7.0);
+        assert_eq!(path.sample_at_distance(1.5), Some(Vec2::new(1.5, 0.0)));
+        assert_eq!(path.sample_at_distance(3.0), Some(Vec2::new(3.0, 0.0)));
+        assert_eq!(path.sample_at_distance(5.0), Some(Vec2::new(3.0, 2.0)));
+        assert_eq!(path.sample_at_distance(-1.0), Some(Vec2::new(0.0, 0.0)));
+        assert_eq!(path.sample_at_distance(9.0), Some(Vec2::new(3.0, 4.0)));
+        assert_eq!(ArcLengthPath::default().sample_at_distance(1.0), None);
+        let resampled = path.resample(2.0);
+        assert_eq!(resampled.len(), 5);
+        assert_eq!(resampled[2], Vec2::new(3.0, 0.5));
+        assert_eq!(resampled[4], Vec2::new(3.0, 4.0));
+
+        // The spline's own samples bunch up in a turn; resampled ones are
+        // evenly spaced along it.
+        let mut cells: Vec<Cell> = (0..=4).map(|x| Cell::new(0, IVec2::new(x, 0))).collect();
+        cells.extend((1..=4).map(|y| Cell::new(2, IVec2::new(5, y))));
+        let spline = smooth_path(&cells, 1.0, 8);
+        let gaps = |points: &[Vec2]| -> Vec<f32> {
+            points
+                .windows(2)
+                .map(|pair| pair[0].distance(pair[1]))
+                .filter(|gap| *gap > 0.0)
+                .collect()
+        };
+        let spread = |gaps: &[f32]| {
+            gaps.iter().copied().fold(0.0, f32::max)
+                / gaps.iter().copied().fold(f32::INFINITY, f32::min)
+        };
+        assert!(spread(&gaps(&spline)) > 2.0);
+        let even = ArcLengthPath::new(spline).resample(0.25);
+        let even_gaps = gaps(&even);
+        assert!(even_gaps.iter().all(|gap| *gap <= 0.25));
+        assert!(spread(&even_gaps) < 1.2);
+    }
+
+    #[test]
+    fn test_curvature_violations() {
+        // Points on a circle of radius 2, counter-clockwise in a y-down
+        // frame, so turning toward +y.
+        let circle: Vec<Vec2> = (0..=8)
+            .map(|i| Vec2::from_angle(i as f32 * 0.2) * 2.0)
+            .collect();
+        let curvatures = curvature(&circle);
+        assert_eq!((curvatures[0], curvatures[8]), (0.0, 0.0));
+        assert!(curvatures[1..8].iter().all(|k| (k - 0.5).abs() < 1e-3));
+        let reversed: Vec<Vec2> = circle.iter().rev().copied().collect();
+        assert!((curvature(&reversed)[4] + 0.5).abs() < 1e-3);
+        assert!(curvature_violations(&circle, 0.6).is_empty());
+        assert_eq!(
+            curvature_violations(&circle, 0.4),
+            vec![CurvatureViolation {
+                samples: 1..8,
+                peak: curvatures[1..8].iter().copied().fold(0.0, f32::max),
+            }]
+        );
+        // Repeats take the curvature of the turn they sit on.
+        let mut padded = vec![circle[0]; 3];
+        padded.extend(circle.iter().flat_map(|point| [*point; 2]));
+        padded.extend(vec![circle[8]; 50_000]);
+        let padded_curvatures = curvature(&padded);
+        assert_eq!(padded_curvatures.len(), padded.len());
+        assert_eq!(padded_curvatures[..5], [0.0; 5]);
+        assert_eq!(padded_curvatures[5..7], [curvatures[1]; 2]);
+        assert!(padded_curvatures[19..].iter().all(|k| *k == 0.0));
+
+        // Stopping and backing up is no violation.
+        let switch = [Vec2::ZERO, Vec2::X, Vec2::new(0.5, 0.1)];
+        assert!(curvature_violations(&switch, 0.1).is_empty());
+
+        // Turning a corner of the grid in a single key segment.
+        let mut cells: Vec<Cell> = (0..=4).map(|x| Cell::new(0, IVec2::new(x, 0))).collect();
+        cells.extend((1..=4).map(|y| Cell::new(2, IVec2::new(5, y))));
+        let spline = smooth_path(&cells, 1.0, 8);
+        let peak = curvature(&spline)
+            .iter()
+            .fold(0.0, |max: f32, k| max.max(k.abs()));
+        let (points, violations) = smooth_path_within_curvature(&cells, 1.0, 8, peak + 0.1);
+        assert!(violations.is_empty());
+        assert_eq!(points, spline);
+        let (points, violations) = smooth_path_within_curvature(&cells, 1.0, 8, peak / 2.0);
+        assert!(!violations.is_empty());
+        // The turn is the grid's diagonal move, the straights still curves.
+        let corner = points
+            .iter()
+            .position(|p| *p == Vec2::new(4.5, 0.5))
+            .unwrap();
+        assert_eq!(points[corner + 1], Vec2::new(5.5, 1.5));
+        assert!(points.len() > cells.len());
+        assert_eq!(points.last(), Some(&Vec2::new(5.5, 4.5)));
+    }
+
+    #[test]
+    fn test_collision_free_smoothing() {
+        let mut cells: Vec<Cell> = (0..=4).map(|x| Cell::new(0, IVec2::new(x, 1))).collect();
+        cells.extend((2..=5).map(|y| Cell::new(2, IVec2::new(5, y))));
+        let mut grid = Grid::new(1.0, 8, 8);
+        let agent = point_agent(IVec2::new(0, 1));
+        let spline = smooth_path(&cells, 1.0, 8);
+
+        // With nothing in the way the spline is kept as is.
+        let points = smooth_path_collision_free(&grid, &agent, &cells);
+        assert_eq!(points, spline[..points.len()]);
+        assert_eq!(points.last(), Some(&Vec2::new(5.5, 5.5)));
+
+        // The grid path turns the corner diagonally, but the spline rounds
+        // it through the cell beside it.
+        let cell_of = |point: &Vec2| point.floor().as_ivec2();
+        assert!(spline
+            .iter()
+            .any(|point| cell_of(point) == IVec2::new(5, 1)));
+        grid.set_cell(5, 1, true);
+        let points = smooth_path_collision_free(&grid, &agent, &cells);
+        assert!(points
+            .iter()
+            .all(|point| !grid.is_cell_blocked(cell_of(point).x, cell_of(point).y)));
+        assert_eq!(points.first(), Some(&Vec2::new(0.5, 1.5)));
+        assert_eq!(points.last(), Some(&Vec2::new(5.5, 5.5)));
+        // Only the corner changed.
+        assert_eq!(points[..10], spline[..10]);
+    }
+}
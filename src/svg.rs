@@ -0,0 +1,214 @@
+use std::fmt::Write;
+
+use geo::Polygon;
+use notan::math::Vec2;
+
+use crate::agent::Agent;
+use crate::cell::Cell;
+use crate::grid::Grid;
+use crate::smoothing;
+
+/// Colors and sizes for [`scene_svg`]. Colors are any SVG paint, like
+/// `"#333"` or `"none"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgStyle {
+    /// Size of a cell in the output, in SVG user units.
+    pub cell_size: f32,
+    pub background: String,
+    pub obstacle: String,
+    /// Grid lines are left out if `None`.
+    pub grid_lines: Option<String>,
+    pub footprint: String,
+    pub footprint_opacity: f32,
+    /// Also outline the footprint at every this many path poses, if set.
+    pub footprint_every: Option<usize>,
+    pub path: String,
+    pub path_width: f32,
+    /// Also draw the raw cell path under the smoothed one, if set.
+    pub raw_path: Option<String>,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        Self {
+            cell_size: 10.0,
+            background: "white".to_string(),
+            obstacle: "#333".to_string(),
+            grid_lines: None,
+            footprint: "#e6a700".to_string(),
+            footprint_opacity: 0.4,
+            footprint_every: None,
+            path: "#1a7f37".to_string(),
+            path_width: 2.0,
+            raw_path: None,
+        }
+    }
+}
+
+fn polygon_points(polygon: &Polygon<f64>, scale: f64) -> String {
+    polygon
+        .exterior()
+        .coords()
+        .map(|coord| format!("{:.2},{:.2}", coord.x * scale, coord.y * scale))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn polyline_points(points: &[Vec2]) -> String {
+    points
+        .iter()
+        .map(|point| format!("{:.2},{:.2}", point.x, point.y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The grid, its obstacles, the agent's footprint and the smoothed `path`
+/// as a standalone SVG document, for papers and documentation. Blocked
+/// cells are merged into one rectangle per run along each row.
+pub fn scene_svg(
+    grid: &Grid,
+    agent: &Agent,
+    path: &[Cell],
+    max_increments: u16,
+    style: &SvgStyle,
+) -> String {
+    let size = style.cell_size;
+    let (width, height) = (grid.size.0 as f32 * size, grid.size.1 as f32 * size);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    let _ = writeln!(
+        out,
+        r#"<rect width="{width}" height="{height}" fill="{}"/>"#,
+        style.background
+    );
+
+    let _ = writeln!(out, r#"<g fill="{}">"#, style.obstacle);
+    for y in 0..grid.size.1 {
+        let mut x = 0;
+        while x < grid.size.0 {
+            if !grid.is_cell_blocked(x, y) {
+                x += 1;
+                continue;
+            }
+            let run_start = x;
+            while x < grid.size.0 && grid.is_cell_blocked(x, y) {
+                x += 1;
+            }
+            let _ = writeln!(
+                out,
+                r#"<rect x="{}" y="{}" width="{}" height="{size}"/>"#,
+                run_start as f32 * size,
+                y as f32 * size,
+                (x - run_start) as f32 * size
+            );
+        }
+    }
+    // Obstacle outlines are in the demo's screen units.
+    let scale = (size / grid.cell_size) as f64;
+    for polygon in &grid.polygons {
+        let _ = writeln!(
+            out,
+            r#"<polygon points="{}"/>"#,
+            polygon_points(polygon, scale)
+        );
+    }
+    let _ = writeln!(out, "</g>");
+
+    if let Some(color) = &style.grid_lines {
+        let _ = writeln!(out, r#"<g stroke="{color}" stroke-width="0.5">"#);
+        for x in 0..=grid.size.0 {
+            let x = x as f32 * size;
+            let _ = writeln!(out, r#"<line x1="{x}" y1="0" x2="{x}" y2="{height}"/>"#);
+        }
+        for y in 0..=grid.size.1 {
+            let y = y as f32 * size;
+            let _ = writeln!(out, r#"<line x1="0" y1="{y}" x2="{width}" y2="{y}"/>"#);
+        }
+        let _ = writeln!(out, "</g>");
+    }
+
+    let _ = writeln!(
+        out,
+        r#"<g fill="{}" fill-opacity="{}" stroke="{}">"#,
+        style.footprint, style.footprint_opacity, style.footprint
+    );
+    let mut poses = vec![(agent.position, agent.rotation)];
+    if let Some(every) = style.footprint_every.filter(|&every| every > 0) {
+        poses.extend(
+            path.iter()
+                .step_by(every)
+                .map(|pose| (pose.position, pose.rotation)),
+        );
+    }
+    for (position, rotation) in poses {
+        let polygon = agent.footprint_polygon(position, rotation, size);
+        let _ = writeln!(
+            out,
+            r#"<polygon points="{}"/>"#,
+            polygon_points(&polygon, 1.0)
+        );
+    }
+    let _ = writeln!(out, "</g>");
+
+    if let Some(color) = &style.raw_path {
+        let centers: Vec<Vec2> = path
+            .iter()
+            .map(|pose| (pose.position.as_vec2() + Vec2::splat(0.5)) * size)
+            .collect();
+        let _ = writeln!(
+            out,
+            r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="{}" stroke-dasharray="2 2"/>"#,
+            polyline_points(&centers),
+            style.path_width / 2.0
+        );
+    }
+    if path.len() > 1 {
+        let points = smoothing::smooth_path(path, size, max_increments);
+        let _ = writeln!(
+            out,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linejoin="round"/>"#,
+            polyline_points(&points),
+            style.path,
+            style.path_width
+        );
+    }
+    let _ = writeln!(out, "</svg>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+
+    #[test]
+    fn test_scene_svg() {
+        let mut grid = Grid::new(1.0, 8, 6);
+        for x in 2..5 {
+            grid.set_cell(x, 1, true);
+        }
+        grid.set_cell(6, 1, true);
+        let agent = Agent::new(IVec2::new(1, 4), Vec2::new(2.0, 1.0), 0, 8);
+        let path: Vec<Cell> = (1..7).map(|x| Cell::new(0, IVec2::new(x, 4))).collect();
+        let style = SvgStyle {
+            footprint_every: Some(2),
+            raw_path: Some("gray".to_string()),
+            ..SvgStyle::default()
+        };
+
+        let svg = scene_svg(&grid, &agent, &path, 8, &style);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(r#"width="80" height="60""#));
+        // The wall is one run plus a lone cell.
+        assert!(svg.contains(r#"<rect x="20" y="10" width="30" height="10"/>"#));
+        assert!(svg.contains(r#"<rect x="60" y="10" width="10" height="10"/>"#));
+        // The agent plus every other pose of the path.
+        assert_eq!(svg.matches("<polygon").count(), 4);
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+}
@@ -0,0 +1,92 @@
+//! Fixtures shared by the planner tests: a point-sized car with eight
+//! headings, a neighbor cache turning one increment per move, and a config
+//! sized for the map.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use notan::math::{IVec2, Vec2};
+
+use crate::agent::{Agent, MotionModel};
+use crate::cell::{NeighborCache, NeighborCacheRef};
+use crate::planner::PlannerConfig;
+
+pub const MAX_INCREMENTS: u16 = 8;
+
+/// A car small enough to fit any free cell, at `position`.
+pub fn point_agent(position: IVec2) -> Agent {
+    Agent::new(position, Vec2::new(0.01, 0.01), 0, MAX_INCREMENTS)
+}
+
+pub fn neighbor_cache() -> NeighborCacheRef {
+    Rc::new(RefCell::new(NeighborCache::new_precomputed(
+        MAX_INCREMENTS,
+        1,
+    )))
+}
+
+/// Builds the agent, cache and config a test plans with on a `width` x
+/// `height` map.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    width: i32,
+    height: i32,
+    position: IVec2,
+    size: Vec2,
+    heading_weight: Option<u32>,
+    states_per_cell: usize,
+}
+
+impl Fixture {
+    pub fn new(width: i32, height: i32) -> Self {
+        Fixture {
+            width,
+            height,
+            position: IVec2::ZERO,
+            size: Vec2::new(0.01, 0.01),
+            heading_weight: None,
+            states_per_cell: MAX_INCREMENTS as usize,
+        }
+    }
+
+    pub fn at(mut self, position: IVec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Makes the agent holonomic, charging `heading_weight` per increment
+    /// turned.
+    pub fn holonomic(mut self, heading_weight: u32) -> Self {
+        self.heading_weight = Some(heading_weight);
+        self
+    }
+
+    /// Sizes `max_states` for this many states per cell, for searches that
+    /// revisit poses, like under time or a payload.
+    pub fn states_per_cell(mut self, states: usize) -> Self {
+        self.states_per_cell = states;
+        self
+    }
+
+    pub fn agent(&self) -> Agent {
+        let mut agent = Agent::new(self.position, self.size, 0, MAX_INCREMENTS);
+        if let Some(heading_weight) = self.heading_weight {
+            agent.motion = MotionModel::Holonomic { heading_weight };
+        }
+        agent
+    }
+
+    pub fn config(&self) -> PlannerConfig {
+        let cells = (self.width * self.height) as usize;
+        PlannerConfig::new(1, MAX_INCREMENTS, cells * self.states_per_cell)
+    }
+
+    pub fn build(&self) -> (Agent, NeighborCacheRef, PlannerConfig) {
+        (self.agent(), neighbor_cache(), self.config())
+    }
+}
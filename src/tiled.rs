@@ -0,0 +1,421 @@
+use geo::{LineString, Polygon};
+use notan::math::Vec2;
+
+use crate::annotations::{Annotation, AnnotationKind};
+use crate::cell::Cell;
+use crate::grid::Grid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TiledError {
+    /// The file isn't XML this importer can read, with what went wrong.
+    Malformed(String),
+    /// The map has no tile layer of this name.
+    MissingLayer(String),
+    /// Tile data in an encoding other than CSV or plain XML, like base64.
+    UnsupportedEncoding(String),
+    /// Infinite maps store their tiles in chunks, which aren't supported.
+    Infinite,
+}
+
+/// One XML tag, with the text up to the next tag.
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+    closing: bool,
+    text: &'a str,
+}
+
+impl Tag<'_> {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn number(&self, name: &str) -> Result<Option<f64>, TiledError> {
+        self.attribute(name)
+            .map(|value| {
+                value.trim().parse().map_err(|_| {
+                    TiledError::Malformed(format!("{} is not a number: {:?}", name, value))
+                })
+            })
+            .transpose()
+    }
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Splits `source` into its tags, skipping the declaration and comments.
+/// Just enough XML for Tiled's output.
+fn tags(source: &str) -> Result<Vec<Tag<'_>>, TiledError> {
+    let mut tags = Vec::new();
+    let mut rest = source;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment
+                .find("-->")
+                .ok_or_else(|| TiledError::Malformed("unterminated comment".to_string()))?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let end = rest
+            .find('>')
+            .ok_or_else(|| TiledError::Malformed("unterminated tag".to_string()))?;
+        let inner = &rest[1..end];
+        rest = &rest[end + 1..];
+        if inner.starts_with('?') || inner.starts_with('!') {
+            continue;
+        }
+        let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+        let closing = inner.starts_with('/');
+        let inner = inner.trim_start_matches('/').trim_end_matches('/');
+        let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+        let (name, mut attributes_source) = inner.split_at(name_end);
+        let mut attributes = Vec::new();
+        loop {
+            attributes_source = attributes_source.trim_start();
+            if attributes_source.is_empty() {
+                break;
+            }
+            let malformed = || TiledError::Malformed(format!("bad attributes in <{}>", name));
+            let equals = attributes_source.find('=').ok_or_else(malformed)?;
+            let key = attributes_source[..equals].trim();
+            let value_source = attributes_source[equals + 1..].trim_start();
+            let quote = value_source.chars().next().ok_or_else(malformed)?;
+            if quote != '"' && quote != '\'' {
+                return Err(malformed());
+            }
+            let value_end = value_source[1..].find(quote).ok_or_else(malformed)?;
+            attributes.push((key, unescape(&value_source[1..1 + value_end])));
+            attributes_source = &value_source[value_end + 2..];
+        }
+        tags.push(Tag {
+            name,
+            attributes,
+            closing,
+            text,
+        });
+    }
+    Ok(tags)
+}
+
+/// A Tiled object, in tile units.
+struct Object {
+    name: Option<String>,
+    id: Option<String>,
+    kind: String,
+    origin: Vec2,
+    rotation: Option<f32>,
+    /// Outline for rectangles, ellipses (by their bounds) and polygons,
+    /// empty for points.
+    outline: Vec<Vec2>,
+}
+
+impl Object {
+    fn center(&self) -> Vec2 {
+        if self.outline.is_empty() {
+            return self.origin;
+        }
+        let (min, max) = self.outline.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+        (min + max) / 2.0
+    }
+}
+
+fn object_start(tag: &Tag, tile_size: Vec2) -> Result<Object, TiledError> {
+    let origin = Vec2::new(
+        tag.number("x")?.unwrap_or(0.0) as f32,
+        tag.number("y")?.unwrap_or(0.0) as f32,
+    ) / tile_size;
+    let size = Vec2::new(
+        tag.number("width")?.unwrap_or(0.0) as f32,
+        tag.number("height")?.unwrap_or(0.0) as f32,
+    ) / tile_size;
+    let outline = if size.x > 0.0 && size.y > 0.0 {
+        vec![
+            origin,
+            origin + Vec2::new(size.x, 0.0),
+            origin + size,
+            origin + Vec2::new(0.0, size.y),
+        ]
+    } else {
+        Vec::new()
+    };
+    Ok(Object {
+        name: tag.attribute("name").map(str::to_string),
+        id: tag.attribute("id").map(str::to_string),
+        // Tiled 1.9 saved the object type as class.
+        kind: tag
+            .attribute("type")
+            .or_else(|| tag.attribute("class"))
+            .unwrap_or("")
+            .to_lowercase(),
+        origin,
+        rotation: tag.number("rotation")?.map(|degrees| degrees as f32),
+        outline,
+    })
+}
+
+fn polygon_points(points: &str, origin: Vec2, tile_size: Vec2) -> Result<Vec<Vec2>, TiledError> {
+    points
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point
+                .split_once(',')
+                .and_then(|(x, y)| Some((x.parse::<f32>().ok()?, y.parse::<f32>().ok()?)))
+                .ok_or_else(|| TiledError::Malformed(format!("bad polygon point {:?}", point)))?;
+            Ok(origin + Vec2::new(x, y) / tile_size)
+        })
+        .collect()
+}
+
+/// Adds `object` to the grid as a zone or an annotation, going by its type.
+fn place_object(grid: &mut Grid, mut object: Object, max_increments: u16) {
+    if let Some(degrees) = object.rotation {
+        // Tiled turns objects clockwise about their origin, which is the
+        // same sense as headings with y pointing down.
+        let turn = Vec2::from_angle(degrees.to_radians());
+        for point in &mut object.outline {
+            *point = object.origin + turn.rotate(*point - object.origin);
+        }
+    }
+    let to_polygon = |outline: &[Vec2]| {
+        let exterior = outline
+            .iter()
+            .map(|point| {
+                let world = *point * grid.cell_size;
+                (world.x as f64, world.y as f64)
+            })
+            .collect::<Vec<_>>();
+        Polygon::new(LineString::from(exterior), vec![])
+    };
+    let kind = match object.kind.as_str() {
+        "obstacle" | "keep_out" | "keep_in" if object.outline.len() >= 3 => {
+            let polygon = to_polygon(&object.outline);
+            match object.kind.as_str() {
+                "obstacle" => grid.add_polygon(polygon),
+                "keep_out" => grid.keep_out.push(polygon),
+                _ => grid.keep_in.push(polygon),
+            }
+            return;
+        }
+        "dock" => AnnotationKind::Dock,
+        "charger" => AnnotationKind::Charger,
+        "spawn" => AnnotationKind::Spawn,
+        "passing_bay" => AnnotationKind::PassingBay,
+        "label" | "" => AnnotationKind::Label,
+        _ => return,
+    };
+    let position = object.center().floor().as_ivec2();
+    let name = match (object.name.filter(|name| !name.is_empty()), object.id) {
+        (Some(name), _) => name,
+        (None, Some(id)) => format!("object_{}", id),
+        (None, None) => return,
+    };
+    grid.annotations.insert(Annotation {
+        name,
+        kind,
+        position,
+        heading: object
+            .rotation
+            .map(|degrees| Cell::heading_to_increment(degrees.to_radians(), max_increments)),
+    });
+}
+
+/// Imports a map saved by the Tiled editor as `.tmx`. Every non-empty tile
+/// of the tile layer named `obstacle_layer` blocks its cell, one cell per
+/// tile. Objects become zones or annotations by their type (class):
+/// `obstacle`, `keep_out` and `keep_in` shapes become obstacle polygons
+/// and geofences; `dock`, `charger`, `spawn` and `passing_bay` objects,
+/// and untyped or `label` ones, become annotations at their center named after the
+/// object, facing its rotation if it has one. Other objects are skipped.
+pub fn import_tmx(
+    source: &str,
+    obstacle_layer: &str,
+    cell_size: f32,
+    max_increments: u16,
+) -> Result<Grid, TiledError> {
+    let tags = tags(source)?;
+    let map = tags
+        .iter()
+        .find(|tag| tag.name == "map" && !tag.closing)
+        .ok_or_else(|| TiledError::Malformed("no <map>".to_string()))?;
+    if map.attribute("infinite") == Some("1") {
+        return Err(TiledError::Infinite);
+    }
+    let dimension = |name| {
+        map.number(name)?
+            .filter(|value| *value > 0.0)
+            .ok_or_else(|| TiledError::Malformed(format!("<map> has no {}", name)))
+    };
+    let (width, height) = (dimension("width")? as i32, dimension("height")? as i32);
+    let tile_size = Vec2::new(
+        dimension("tilewidth")? as f32,
+        dimension("tileheight")? as f32,
+    );
+    let mut grid = Grid::new(
+        cell_size,
+        (width as f32 * cell_size) as i32,
+        (height as f32 * cell_size) as i32,
+    );
+
+    let mut found_layer = false;
+    let mut in_layer = false;
+    let mut object: Option<Object> = None;
+    let mut tile = 0;
+    let block = |grid: &mut Grid, tile: &mut i32, gid: &str| -> Result<(), TiledError> {
+        let gid: u32 = gid
+            .trim()
+            .parse()
+            .map_err(|_| TiledError::Malformed(format!("bad tile {:?}", gid)))?;
+        if gid != 0 {
+            grid.set_cell(*tile % width, *tile / width, true);
+        }
+        *tile += 1;
+        Ok(())
+    };
+    for tag in &tags {
+        match (tag.name, tag.closing) {
+            ("layer", false) => {
+                in_layer = tag.attribute("name") == Some(obstacle_layer);
+                found_layer |= in_layer;
+                tile = 0;
+            }
+            ("layer", true) => in_layer = false,
+            ("data", false) if in_layer => match tag.attribute("encoding") {
+                Some("csv") => {
+                    for gid in tag.text.split(',').filter(|gid| !gid.trim().is_empty()) {
+                        block(&mut grid, &mut tile, gid)?;
+                    }
+                }
+                None => {}
+                Some(encoding) => {
+                    return Err(TiledError::UnsupportedEncoding(encoding.to_string()))
+                }
+            },
+            ("tile", false) if in_layer => {
+                block(&mut grid, &mut tile, tag.attribute("gid").unwrap_or("0"))?;
+            }
+            ("chunk", false) => return Err(TiledError::Infinite),
+            ("object", false) => {
+                if let Some(done) = object.take() {
+                    place_object(&mut grid, done, max_increments);
+                }
+                object = Some(object_start(tag, tile_size)?);
+            }
+            ("object", true) | ("objectgroup", true) => {
+                if let Some(done) = object.take() {
+                    place_object(&mut grid, done, max_increments);
+                }
+            }
+            ("polygon", false) => {
+                if let Some(object) = &mut object {
+                    object.outline = polygon_points(
+                        tag.attribute("points").unwrap_or(""),
+                        object.origin,
+                        tile_size,
+                    )?;
+                }
+            }
+            ("point", false) => {
+                if let Some(object) = &mut object {
+                    object.outline.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(done) = object.take() {
+        place_object(&mut grid, done, max_increments);
+    }
+    if !found_layer {
+        return Err(TiledError::MissingLayer(obstacle_layer.to_string()));
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+    use crate::goal::Goal;
+
+    const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="6" height="4" tilewidth="32" tileheight="32" infinite="0">
+ <tileset firstgid="1" source="warehouse.tsx"/>
+ <layer id="1" name="floor" width="6" height="4">
+  <data encoding="csv">
+1,1,1,1,1,1,
+1,1,1,1,1,1,
+1,1,1,1,1,1,
+1,1,1,1,1,1
+</data>
+ </layer>
+ <layer id="2" name="walls" width="6" height="4">
+  <data encoding="csv">
+0,0,0,0,0,0,
+0,3,3,0,0,0,
+0,0,0,0,0,2147483651,
+0,0,0,0,0,0
+</data>
+ </layer>
+ <!-- docks and zones -->
+ <objectgroup id="3" name="places">
+  <object id="1" name="dock_3" type="dock" x="144" y="16" rotation="90">
+   <point/>
+  </object>
+  <object id="2" class="spawn" x="16" y="112">
+   <point/>
+  </object>
+  <object id="3" name="no &amp; go" type="keep_out" x="128" y="96" width="64" height="32"/>
+  <object id="4" type="decoration" x="0" y="0" width="32" height="32"/>
+ </objectgroup>
+</map>
+"#;
+
+    #[test]
+    fn test_import_tmx() {
+        let grid = import_tmx(MAP, "walls", 1.0, 8).unwrap();
+        assert_eq!(grid.size, (6, 4));
+        let blocked: Vec<(i32, i32)> = (0..4)
+            .flat_map(|y| (0..6).map(move |x| (x, y)))
+            .filter(|&(x, y)| grid.is_cell_blocked(x, y))
+            .collect();
+        // Flipped tiles keep their flags in the high bits.
+        assert_eq!(blocked, vec![(1, 1), (2, 1), (5, 2)]);
+
+        let dock = grid.annotations.get("dock_3").unwrap();
+        assert_eq!(dock.kind, AnnotationKind::Dock);
+        assert_eq!(dock.position, IVec2::new(4, 0));
+        assert_eq!(dock.heading, Some(2));
+        assert_eq!(
+            grid.annotations.goal("object_2"),
+            Some(Goal::Cell(IVec2::new(0, 3)))
+        );
+        assert_eq!(grid.annotations.entries.len(), 2);
+        assert_eq!(grid.keep_out.len(), 1);
+        assert!(grid.keep_in.is_empty());
+
+        assert_eq!(
+            import_tmx(MAP, "roof", 1.0, 8).err(),
+            Some(TiledError::MissingLayer("roof".to_string()))
+        );
+        let base64 = MAP.replace(r#"encoding="csv""#, r#"encoding="base64""#);
+        assert_eq!(
+            import_tmx(&base64, "walls", 1.0, 8).err(),
+            Some(TiledError::UnsupportedEncoding("base64".to_string()))
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use notan::math::Vec2;
+
+use crate::agent::Agent;
+use crate::cell::Cell;
+use crate::grid::Grid;
+use crate::planner::PlanResult;
+
+/// Cost of a single straight move, the unit plan costs are counted in.
+pub const COST_PER_CELL: f32 = 1000.0;
+
+/// How fast a vehicle drives and turns, used to time a path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VelocityLimits {
+    /// Top forward speed, in meters per second. A speed profile on the
+    /// agent scales it down for sideways and backward moves.
+    pub max_speed: f32,
+    /// Top turn rate, in radians per second.
+    pub max_turn_rate: f32,
+}
+
+/// A path pose in meters and seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetricPose {
+    /// Cell center, in meters from the grid origin.
+    pub position: Vec2,
+    /// Heading in radians.
+    pub heading: f32,
+    /// Seconds from the start of the path.
+    pub time: f32,
+    /// Speed of the move arriving here, in meters per second. Zero at the
+    /// start and after turns in place.
+    pub speed: f32,
+}
+
+/// A plan converted to world units.
+#[derive(Clone, Debug)]
+pub struct MetricPath {
+    pub poses: Vec<MetricPose>,
+    /// Meters driven.
+    pub length: f32,
+    /// Plan cost, in meters of straight driving.
+    pub cost: f32,
+    /// Seconds to drive the whole path.
+    pub duration: f32,
+}
+
+/// Distance driven along `path`, in cells.
+pub fn path_length(path: &[Cell]) -> f32 {
+    path.windows(2)
+        .map(|pair| {
+            pair[0]
+                .position
+                .as_vec2()
+                .distance(pair[1].position.as_vec2())
+        })
+        .sum()
+}
+
+/// Converts a plan cost to the meters of straight driving it's worth.
+pub fn cost_to_meters(grid: &Grid, cost: u32) -> f32 {
+    grid.to_meters(cost as f32 / COST_PER_CELL)
+}
+
+/// Converts `result` to meters and seconds, timing every move by whichever
+/// of driving and turning takes longer. Moves never drive faster than the
+/// grid's speed limit under the footprint at either end.
+pub fn to_metric(
+    grid: &Grid,
+    agent: &Agent,
+    result: &PlanResult,
+    limits: VelocityLimits,
+) -> MetricPath {
+    let max_increments = agent.max_increments;
+    let mut time = 0.0;
+    let mut poses = Vec::with_capacity(result.path.len());
+    for (index, pose) in result.path.iter().enumerate() {
+        let mut speed = 0.0;
+        if let Some(previous) = index.checked_sub(1).map(|index| &result.path[index]) {
+            let motion = (pose.position - previous.position).as_vec2();
+            let distance = grid.to_meters(motion.length());
+            let turn = pose.rotation_to(previous.rotation, max_increments as i16);
+            let turn_time = Cell::increment_to_heading(turn, max_increments) / limits.max_turn_rate;
+            let mut drive_time = 0.0;
+            if distance > 0.0 {
+                let factor = agent.speed_profile.map_or(1.0, |profile| {
+                    let heading = Cell::increment_to_heading(previous.rotation, max_increments);
+                    profile.cost_factor(heading, motion)
+                });
+                let limit = grid
+                    .pose_speed_limit(agent, previous)
+                    .min(grid.pose_speed_limit(agent, pose));
+                drive_time = (distance * factor / limits.max_speed).max(distance / limit);
+            }
+            let step = drive_time.max(turn_time);
+            if distance > 0.0 && step > 0.0 {
+                speed = distance / step;
+            }
+            time += step;
+        }
+        poses.push(MetricPose {
+            position: grid.cell_to_meters(pose.position),
+            heading: Cell::increment_to_heading(pose.rotation, max_increments),
+            time,
+            speed,
+        });
+    }
+    MetricPath {
+        poses,
+        length: grid.to_meters(path_length(&result.path)),
+        cost: cost_to_meters(grid, result.cost),
+        duration: time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+    use std::f32::consts::PI;
+
+    use super::*;
+    use geo::{LineString, Polygon};
+
+    use crate::agent::SpeedProfile;
+    use crate::test_support::point_agent;
+
+    fn result(path: Vec<Cell>, cost: u32) -> PlanResult {
+        PlanResult {
+            path,
+            cost,
+            start_adjustment: None,
+            suboptimality_bound: None,
+            expanded: 0,
+        }
+    }
+
+    #[test]
+    fn test_straight_path_in_meters() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        grid.resolution = 0.5;
+        let agent = point_agent(IVec2::ZERO);
+        let path = (0..4).map(|x| Cell::new(0, IVec2::new(x, 0))).collect();
+        let limits = VelocityLimits {
+            max_speed: 2.0,
+            max_turn_rate: PI,
+        };
+        let metric = to_metric(&grid, &agent, &result(path, 3000), limits);
+        assert_eq!(metric.length, 1.5);
+        assert_eq!(metric.cost, 1.5);
+        assert_eq!(metric.duration, 0.75);
+        assert_eq!(metric.poses[0].speed, 0.0);
+        assert_eq!(metric.poses[3].position, Vec2::new(1.75, 0.25));
+        assert_eq!(metric.poses[3].speed, 2.0);
+    }
+
+    #[test]
+    fn test_turns_and_speed_profile() {
+        let grid = Grid::new(1.0, 10, 10);
+        let mut agent = point_agent(IVec2::ZERO);
+        agent.speed_profile = Some(SpeedProfile {
+            forward: 1.0,
+            sideways: 0.5,
+            backward: 0.25,
+        });
+        let path = vec![
+            Cell::new(0, IVec2::new(0, 0)),
+            Cell::new(2, IVec2::new(0, 0)),
+            Cell::new(2, IVec2::new(1, 0)),
+        ];
+        let limits = VelocityLimits {
+            max_speed: 1.0,
+            max_turn_rate: PI / 2.0,
+        };
+        let metric = to_metric(&grid, &agent, &result(path, 0), limits);
+        // A quarter turn in place takes a second, then the sideways move two.
+        assert_eq!(metric.poses[1].time, 1.0);
+        assert_eq!(metric.poses[1].speed, 0.0);
+        assert!((metric.duration - 3.0).abs() < 1e-5);
+        assert!((metric.poses[2].speed - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_speed_zone() {
+        let mut grid = Grid::new(1.0, 10, 10);
+        let zone = Polygon::new(
+            LineString::from(vec![(3.2, 0.2), (4.8, 0.2), (4.8, 0.8), (3.2, 0.8)]),
+            vec![],
+        );
+        grid.add_speed_zone(&zone, 0.5);
+        grid.set_speed_limit(4, 0, 0.25);
+        grid.add_speed_zone(&zone, 1.0);
+        assert_eq!(grid.speed_limit_at(3, 0), 0.5);
+        assert_eq!(grid.speed_limit_at(4, 0), 0.25);
+        assert_eq!(grid.speed_limit_at(5, 0), f32::INFINITY);
+
+        let agent = point_agent(IVec2::ZERO);
+        let path = (0..7).map(|x| Cell::new(0, IVec2::new(x, 0))).collect();
+        let limits = VelocityLimits {
+            max_speed: 2.0,
+            max_turn_rate: PI,
+        };
+        let metric = to_metric(&grid, &agent, &result(path, 6000), limits);
+        let speeds: Vec<f32> = metric.poses.iter().map(|pose| pose.speed).collect();
+        assert_eq!(speeds, vec![0.0, 2.0, 2.0, 0.5, 0.25, 0.25, 2.0]);
+        assert_eq!(metric.duration, 0.5 * 3.0 + 2.0 + 4.0 * 2.0);
+    }
+}
@@ -0,0 +1,186 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::agent::Agent;
+use crate::cell::{Cell, NeighborCacheRef};
+use crate::grid::Grid;
+use crate::planner::{self, Candidates, MoveRejection, PlannerConfig};
+
+/// What [`validate_path`] found out about a valid path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathReport {
+    /// Cost of the path recomputed move by move.
+    pub cost: u32,
+    pub moves: usize,
+    pub reverse_moves: usize,
+    /// Moves through closed doors, charged the configured door cost.
+    pub door_moves: usize,
+}
+
+/// The first thing wrong with a path. Indices are of the offending pose,
+/// i.e. the end of the offending move.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathViolation {
+    Empty,
+    /// The footprint collides at the first pose.
+    StartBlocked,
+    /// The move into this pose isn't one the agent can make from the pose
+    /// before it.
+    NotAPrimitive(usize),
+    /// The move into this pose passes through an obstacle.
+    Blocked(usize),
+    /// The move into this pose leaves the geofences.
+    OutsideGeofence(usize),
+    /// The move into this pose is steeper than the agent can climb.
+    TooSteep(usize),
+    /// The moves up to this pose cost more than a `u32` holds, so no plan
+    /// could have reported their cost.
+    CostOverflow(usize),
+    CostMismatch {
+        reported: u32,
+        recomputed: u32,
+    },
+}
+
+/// Checks a path against the same rules the planner follows: every move is
+/// one of the agent's motion primitives under `config`, every pose a move
+/// passes through is free and inside the geofences, and `cost` is what the
+/// moves add up to with terrain, doors and soft costs included.
+///
+/// Meant for paths that come from elsewhere, like over the network or out
+/// of tests. Extra costs of wrapping planners, like
+/// [`crate::risk::plan_chance_constrained`], aren't recomputed, and paths
+/// that escape from a blocked start are rejected.
+pub fn validate_path(
+    path: &[Cell],
+    cost: u32,
+    grid: &Grid,
+    agent: &Agent,
+    neighbor_cache: &NeighborCacheRef,
+    config: &PlannerConfig,
+) -> Result<PathReport, PathViolation> {
+    let start = path.first().ok_or(PathViolation::Empty)?;
+    if grid.is_pose_blocked(agent, start) {
+        return Err(PathViolation::StartBlocked);
+    }
+    let filtered_cache;
+    let neighbor_cache = if config.allow_forward && config.allow_reverse {
+        neighbor_cache
+    } else {
+        filtered_cache = Rc::new(RefCell::new(
+            neighbor_cache
+                .borrow()
+                .filtered(config.allow_forward, config.allow_reverse),
+        ));
+        &filtered_cache
+    };
+    let costs = planner::cost_cache(neighbor_cache, config);
+
+    let mut report = PathReport {
+        cost: 0,
+        moves: 0,
+        reverse_moves: 0,
+        door_moves: 0,
+    };
+    let mut candidates = Candidates::new();
+    for (index, pair) in path.windows(2).enumerate() {
+        let (from, to) = (&pair[0], &pair[1]);
+        let index = index + 1;
+        candidates.clear();
+        planner::motion_candidates(agent, neighbor_cache, &costs, from, config, &mut candidates);
+        let move_cost = candidates
+            .iter()
+            .filter(|(candidate, _)| candidate == to)
+            .map(|(_, cost)| *cost)
+            .min()
+            .ok_or(PathViolation::NotAPrimitive(index))?;
+
+        let checked =
+            planner::check_move(grid, agent, from, to, move_cost, config).map_err(|rejection| {
+                match rejection {
+                    MoveRejection::OutsideGeofence => PathViolation::OutsideGeofence(index),
+                    MoveRejection::Blocked => PathViolation::Blocked(index),
+                    MoveRejection::TooSteep => PathViolation::TooSteep(index),
+                }
+            })?;
+        let move_cost = checked.cost;
+        if checked.through_doors {
+            report.door_moves += 1;
+        }
+
+        report.cost = report
+            .cost
+            .checked_add(move_cost)
+            .ok_or(PathViolation::CostOverflow(index))?;
+        report.moves += 1;
+        if from.position != to.position && to.is_reverse_to(from, config.max_increments as i16) {
+            report.reverse_moves += 1;
+        }
+    }
+    if report.cost != cost {
+        return Err(PathViolation::CostMismatch {
+            reported: cost,
+            recomputed: report.cost,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use notan::math::IVec2;
+
+    use super::*;
+    use crate::test_support::Fixture;
+
+    #[test]
+    fn test_validate_planned_path() {
+        let mut grid = Grid::new(1.0, 12, 12);
+        for y in 0..8 {
+            grid.set_cell(6, y, true);
+        }
+        grid.set_soft_cost(6, 8, 500);
+        let (agent, cache, config) = Fixture::new(12, 12).at(IVec2::new(2, 2)).build();
+        let start = Cell::new(0, agent.position);
+        let result =
+            planner::plan(&grid, &agent, &cache, start, IVec2::new(10, 2), &config).unwrap();
+
+        let validate =
+            |path: &[Cell], cost| validate_path(path, cost, &grid, &agent, &cache, &config);
+        let report = validate(&result.path, result.cost).unwrap();
+        assert_eq!(report.cost, result.cost);
+        assert_eq!(report.moves, result.path.len() - 1);
+
+        assert_eq!(
+            validate(&result.path, result.cost + 1),
+            Err(PathViolation::CostMismatch {
+                reported: result.cost + 1,
+                recomputed: result.cost,
+            })
+        );
+        assert_eq!(validate(&[], 0), Err(PathViolation::Empty));
+
+        // Jumping straight across the wall isn't a move the agent can make,
+        // and stepping into it is blocked.
+        let jump = [
+            Cell::new(0, IVec2::new(5, 2)),
+            Cell::new(0, IVec2::new(7, 2)),
+        ];
+        assert_eq!(validate(&jump, 0), Err(PathViolation::NotAPrimitive(1)));
+        let into_wall = [
+            Cell::new(0, IVec2::new(5, 2)),
+            Cell::new(0, IVec2::new(6, 2)),
+        ];
+        assert_eq!(validate(&into_wall, 0), Err(PathViolation::Blocked(1)));
+
+        // Each move fits a u32, but not both together.
+        let mut costly = Grid::new(1.0, 12, 12);
+        costly.set_soft_cost(1, 0, 3_000_000_000);
+        costly.set_soft_cost(2, 0, 3_000_000_000);
+        let path: Vec<Cell> = (0..3).map(|x| Cell::new(0, IVec2::new(x, 0))).collect();
+        assert_eq!(
+            validate_path(&path, 0, &costly, &agent, &cache, &config),
+            Err(PathViolation::CostOverflow(2))
+        );
+    }
+}